@@ -0,0 +1,100 @@
+// 按变化上报: 平时按 --adaptive 间隔上报, 指标突变时立即上报并在 BURST_SECS 内恢复每秒上报
+// 服务端指定了上报间隔时以服务端为准
+use std::time::{Duration, Instant};
+
+use stat_common::server_status::StatRequest;
+
+use crate::Args;
+
+const BURST_SECS: u64 = 30;
+// 内存使用率变化(%)
+const MEM_DELTA: f64 = 10.0;
+// 硬盘使用率变化(%)
+const HDD_DELTA: f64 = 1.0;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Snapshot {
+    cpu: f64,
+    mem: f64,
+    hdd: f64,
+    net_active: bool,
+}
+
+impl Snapshot {
+    fn from_stat(stat: &StatRequest) -> Self {
+        let percent = |used: u64, total: u64| {
+            if total > 0 {
+                100.0 * used as f64 / total as f64
+            } else {
+                0.0
+            }
+        };
+        Self {
+            cpu: stat.cpu,
+            mem: percent(stat.memory_used, stat.memory_total),
+            hdd: percent(stat.hdd_used, stat.hdd_total),
+            net_active: stat.network_rx > 0 || stat.network_tx > 0,
+        }
+    }
+}
+
+pub struct Adaptive {
+    baseline: u64,
+    cpu_delta: f64,
+    last: Option<Snapshot>,
+    last_sent: Instant,
+    burst_until: Option<Instant>,
+}
+
+impl Adaptive {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            baseline: args.adaptive,
+            cpu_delta: args.adaptive_cpu,
+            last: None,
+            last_sent: Instant::now(),
+            burst_until: None,
+        }
+    }
+
+    fn changed(&self, cur: &Snapshot) -> bool {
+        match self.last {
+            Some(last) => {
+                (cur.cpu - last.cpu).abs() >= self.cpu_delta
+                    || (cur.mem - last.mem).abs() >= MEM_DELTA
+                    || (cur.hdd - last.hdd).abs() >= HDD_DELTA
+                    || cur.net_active != last.net_active
+            }
+            None => true,
+        }
+    }
+
+    pub fn should_report(&mut self, stat: &StatRequest) -> bool {
+        let now = Instant::now();
+        let forced = crate::server_report_interval();
+        if forced > 1 {
+            // 每秒 tick 有抖动, 预留 500ms
+            if now.duration_since(self.last_sent) + Duration::from_millis(500) < Duration::from_secs(forced) {
+                return false;
+            }
+            self.last_sent = now;
+            return true;
+        }
+        if self.baseline == 0 {
+            return true;
+        }
+
+        let cur = Snapshot::from_stat(stat);
+        let burst = self.burst_until.map(|t| now < t).unwrap_or(false);
+        if self.changed(&cur) {
+            debug!("adaptive report, metrics changed => {:?}", cur);
+            self.burst_until = Some(now + Duration::from_secs(BURST_SECS));
+        } else if !burst && now.duration_since(self.last_sent).as_secs() < self.baseline {
+            return false;
+        }
+
+        self.last = Some(cur);
+        self.last_sent = now;
+        true
+    }
+}
@@ -0,0 +1,146 @@
+// 离线缓存: 上报失败期间每 BUFFER_SECS 缓存一份完整数据, 恢复后按原时间戳补发(replay=true), 服务端只写入历史
+// 指定 --buffer-file 时追加写入文件(length-delimited protobuf), 重启后继续补发
+use once_cell::sync::Lazy;
+use prost::Message;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::sync::Mutex;
+
+use stat_common::server_status::StatRequest;
+
+const BUFFER_SECS: u64 = 10;
+// 每次补发条数
+const REPLAY_BATCH: usize = 10;
+
+#[derive(Default)]
+struct Buffer {
+    cap: usize,
+    path: String,
+    queue: VecDeque<StatRequest>,
+    // 最近一次上报失败
+    offline: bool,
+    last_ts: u64,
+    // 文件中的记录数
+    file_records: usize,
+    replaying: bool,
+}
+
+impl Buffer {
+    fn save(&mut self) {
+        if self.path.is_empty() {
+            return;
+        }
+        let data = self
+            .queue
+            .iter()
+            .flat_map(|o| o.encode_length_delimited_to_vec())
+            .collect::<Vec<_>>();
+        match fs::write(&self.path, data) {
+            Ok(_) => self.file_records = self.queue.len(),
+            Err(err) => warn!("save buffer file {} error => {:?}", self.path, err),
+        }
+    }
+
+    // 追加队尾一条
+    fn append(&mut self) {
+        let data = match self.queue.back() {
+            Some(stat) if !self.path.is_empty() => stat.encode_length_delimited_to_vec(),
+            _ => return,
+        };
+        // 队列溢出丢弃旧数据后文件偏大, 超过 2 倍时重写
+        if self.file_records >= self.cap * 2 {
+            return self.save();
+        }
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(&data));
+        match result {
+            Ok(_) => self.file_records += 1,
+            Err(err) => warn!("append buffer file {} error => {:?}", self.path, err),
+        }
+    }
+}
+
+static G_BUFFER: Lazy<Mutex<Buffer>> = Lazy::new(Default::default);
+
+pub fn init(cap: usize, path: &str) {
+    let mut o = G_BUFFER.lock().unwrap();
+    o.cap = cap;
+    if cap == 0 || path.is_empty() {
+        return;
+    }
+    o.path = path.to_string();
+    match fs::read(path) {
+        Ok(data) => {
+            let mut buf = data.as_slice();
+            while !buf.is_empty() {
+                match StatRequest::decode_length_delimited(&mut buf) {
+                    Ok(stat) => o.queue.push_back(stat),
+                    Err(err) => {
+                        warn!("buffer file {} corrupted => {:?}", path, err);
+                        break;
+                    }
+                }
+            }
+            o.file_records = o.queue.len();
+            while o.queue.len() > cap {
+                o.queue.pop_front();
+            }
+            if !o.queue.is_empty() {
+                eprintln!("load {} buffered reports from {}", o.queue.len(), path);
+            }
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => warn!("read buffer file {} error => {:?}", path, err),
+    }
+}
+
+pub fn set_online(ok: bool) {
+    if let Ok(mut o) = G_BUFFER.lock() {
+        o.offline = !ok;
+    }
+}
+
+// 离线且距上次缓存超过 BUFFER_SECS
+pub fn due(ts: u64) -> bool {
+    let o = G_BUFFER.lock().unwrap();
+    o.cap > 0 && o.offline && ts >= o.last_ts + BUFFER_SECS
+}
+
+pub fn push(mut stat: StatRequest) {
+    stat.replay = true;
+    let mut o = G_BUFFER.lock().unwrap();
+    o.last_ts = stat.latest_ts;
+    o.queue.push_back(stat);
+    while o.queue.len() > o.cap {
+        o.queue.pop_front();
+    }
+    o.append();
+}
+
+// 在线时取出一批待补发, 同一时间只有一批在补发
+pub fn take_batch() -> Vec<StatRequest> {
+    let mut o = G_BUFFER.lock().unwrap();
+    if o.offline || o.replaying || o.queue.is_empty() {
+        return Vec::new();
+    }
+    o.replaying = true;
+    let n = o.queue.len().min(REPLAY_BATCH);
+    o.queue.drain(..n).collect()
+}
+
+// failed 为未发送成功的, 放回队首
+pub fn replay_done(failed: Vec<StatRequest>) {
+    let mut o = G_BUFFER.lock().unwrap();
+    o.replaying = false;
+    if !failed.is_empty() {
+        o.offline = true;
+        for stat in failed.into_iter().rev() {
+            o.queue.push_front(stat);
+        }
+    }
+    o.save();
+}
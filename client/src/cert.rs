@@ -0,0 +1,104 @@
+// tls 证书到期检查, 握手时不校验证书链, 过期/自签证书也能取到到期时间
+use lazy_static::lazy_static;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::{self, Certificate, ClientConfig, ClientConnection, ServerName};
+
+use crate::supervisor;
+use crate::Result;
+use stat_common::server_status::CertInfo;
+
+const SAMPLE_PERIOD: u64 = 3_600_000; //ms
+const TIMEOUT_MS: u64 = 5000;
+
+lazy_static! {
+    pub static ref G_CERTS: Arc<Mutex<Vec<CertInfo>>> = Arc::new(Default::default());
+}
+
+struct NoVerify;
+
+impl ServerCertVerifier for NoVerify {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+// example.com:443, 省略端口为 443
+fn split_target(target: &str) -> (&str, u16) {
+    match target.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') => match port.parse::<u16>() {
+            Ok(port) => (host.trim_start_matches('[').trim_end_matches(']'), port),
+            Err(_) => (target, 443),
+        },
+        _ => (target, 443),
+    }
+}
+
+// 返回证书到期时间 unix ts
+fn query_expire(config: &Arc<ClientConfig>, target: &str) -> Result<i64> {
+    let (host, port) = split_target(target);
+    let addr = (host, port).to_socket_addrs()?.next().ok_or("resolve host fail")?;
+    let server_name = ServerName::try_from(host).map_err(|_| "invalid domain")?;
+    let mut conn = ClientConnection::new(config.clone(), server_name)?;
+    let mut sock = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS))?;
+    sock.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS)))?;
+    sock.set_write_timeout(Some(Duration::from_millis(TIMEOUT_MS)))?;
+    while conn.is_handshaking() {
+        conn.complete_io(&mut sock)?;
+    }
+    let cert = conn
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or("no peer certificate")?;
+    let (_, x509) = x509_parser::parse_x509_certificate(&cert.0).map_err(|err| format!("{:?}", err))?;
+    Ok(x509.validity().not_after.timestamp())
+}
+
+pub fn start_cert_collect_t(targets: &[String]) {
+    let targets = targets.to_vec();
+    supervisor::spawn("cert", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let targets = targets.clone();
+        let config = Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(Arc::new(NoVerify))
+                .with_no_client_auth(),
+        );
+        Box::new(move || {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+            let certs = targets
+                .iter()
+                .map(|target| match query_expire(&config, target) {
+                    Ok(expire_at) => CertInfo {
+                        target: target.to_string(),
+                        expire_at,
+                        days: (expire_at - now).div_euclid(86400),
+                        ..Default::default()
+                    },
+                    Err(err) => {
+                        warn!("check cert {} error => {:?}", target, err);
+                        CertInfo {
+                            target: target.to_string(),
+                            error: err.to_string(),
+                            ..Default::default()
+                        }
+                    }
+                })
+                .collect();
+            if let Ok(mut o) = G_CERTS.lock() {
+                *o = certs;
+            }
+        })
+    });
+}
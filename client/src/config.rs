@@ -0,0 +1,101 @@
+// 客户端配置文件, 支持 toml/yaml, 键名同长参数(- 或 _ 均可), 优先级: 命令行 > 环境变量 > 配置文件 > 默认值
+// 列表参数可写为数组, name=value 形式的参数(如 probe)可写为表
+use clap::{CommandFactory, Parser, ValueSource};
+use serde_json::Value;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use crate::Args;
+use crate::Result;
+
+fn parse_file(path: &str) -> Result<Value> {
+    let content = fs::read_to_string(path)?;
+    match Path::new(path).extension().and_then(|s| s.to_str()) {
+        Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&content)?),
+        _ => Ok(toml::from_str(&content)?),
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// 转为命令行参数, 命令行或环境变量已指定的跳过
+fn file_args(path: &str, cli: &clap::ArgMatches) -> Result<Vec<OsString>> {
+    let table = match parse_file(path)? {
+        Value::Object(o) => o,
+        // 空 yaml
+        Value::Null => Default::default(),
+        _ => return Err(format!("{}: expect a table", path).into()),
+    };
+    let cmd = Args::command();
+    let mut argv: Vec<OsString> = Vec::new();
+    for (key, value) in table.iter() {
+        let long = key.replace('_', "-");
+        let arg = cmd
+            .get_arguments()
+            .find(|a| a.get_long() == Some(long.as_str()))
+            .ok_or_else(|| format!("{}: unknown key `{}`", path, key))?;
+        if long.eq("config")
+            || matches!(
+                cli.value_source(arg.get_id()),
+                Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+            )
+        {
+            continue;
+        }
+
+        let invalid = || format!("{}: invalid value of `{}`", path, key);
+        if !arg.is_takes_value_set() {
+            if value.as_bool().ok_or_else(invalid)? {
+                argv.push(format!("--{}", long).into());
+            }
+            continue;
+        }
+        let values = match value {
+            Value::Array(list) => list.iter().map(scalar).collect::<Option<Vec<_>>>(),
+            Value::Object(o) => o
+                .iter()
+                .map(|(k, v)| scalar(v).map(|v| format!("{}={}", k, v)))
+                .collect(),
+            _ => scalar(value).map(|v| vec![v]),
+        }
+        .ok_or_else(invalid)?;
+        for v in values {
+            argv.push(format!("--{}={}", long, v).into());
+        }
+    }
+    Ok(argv)
+}
+
+fn merge(mut argv: Vec<OsString>, cli: &clap::ArgMatches) -> Result<Args> {
+    if let Some(path) = cli.get_one::<String>("config") {
+        // 放在子命令之前
+        argv.splice(1..1, file_args(path, cli)?);
+    }
+    Ok(Args::try_parse_from(argv)?)
+}
+
+pub fn load_args() -> Args {
+    let argv = env::args_os().collect::<Vec<_>>();
+    let cli = Args::command().get_matches_from(argv.clone());
+    merge(argv, &cli).unwrap_or_else(|err| {
+        eprintln!("load config error => {}", err);
+        process::exit(1);
+    })
+}
+
+// 重新读取配置文件, 命令行及环境变量不变
+pub fn reload_args() -> Result<Args> {
+    let argv = env::args_os().collect::<Vec<_>>();
+    let cli = Args::command().try_get_matches_from(argv.clone())?;
+    merge(argv, &cli)
+}
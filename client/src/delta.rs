@@ -0,0 +1,52 @@
+// --delta N: 每 N 次上报发送一次完整数据, 其余只发送相对该完整数据变化的字段, 需服务端支持
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use stat_common::capability;
+use stat_common::server_status::StatRequest;
+
+use crate::{server_support, Sampler};
+
+// 上报失败时服务端可能没有最新的完整数据, 下一次改为完整上报
+static G_FAILED: AtomicBool = AtomicBool::new(false);
+
+pub struct Delta {
+    every: u32,
+    count: u32,
+    full: Option<StatRequest>,
+}
+
+impl Delta {
+    pub fn new(every: u32) -> Self {
+        Self {
+            every,
+            count: 0,
+            full: None,
+        }
+    }
+
+    // 返回增量, None 时发送完整数据
+    pub fn diff(&mut self, sampler: &Sampler) -> Option<StatRequest> {
+        let enabled = self.every > 1 && server_support(capability::DELTA);
+        if G_FAILED.swap(false, Ordering::Relaxed) || !enabled {
+            self.full = None;
+        }
+        if !enabled {
+            return None;
+        }
+        let stat = sampler.to_stat();
+        self.count += 1;
+        if let Some(full) = self.full.as_ref().filter(|_| self.count < self.every) {
+            match stat_common::delta::diff(full, &stat) {
+                Ok(delta) => return Some(delta),
+                Err(err) => error!("delta diff error => {:?}", err),
+            }
+        }
+        self.count = 0;
+        self.full = Some(stat);
+        None
+    }
+}
+
+pub fn report_failed() {
+    G_FAILED.store(true, Ordering::Relaxed);
+}
@@ -0,0 +1,114 @@
+// docker 容器统计, 通过 /var/run/docker.sock 调用 Engine API, 无需 docker cli
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use crate::Result;
+use stat_common::server_status::{Container, DockerInfo};
+
+const DOCKER_SOCK: &str = "/var/run/docker.sock";
+const SAMPLE_PERIOD: u64 = 10000; //ms
+const TIMEOUT_MS: u64 = 5000;
+
+lazy_static! {
+    pub static ref G_DOCKER: Arc<Mutex<Option<DockerInfo>>> = Arc::new(Default::default());
+}
+
+// HTTP/1.0 短连接, 响应不会分块
+fn get(path: &str) -> Result<Value> {
+    let mut stream = UnixStream::connect(DOCKER_SOCK)?;
+    stream.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(TIMEOUT_MS)))?;
+    write!(stream, "GET {} HTTP/1.0\r\nHost: docker\r\n\r\n", path)?;
+    let mut resp = Vec::new();
+    stream.read_to_end(&mut resp)?;
+    let resp = String::from_utf8_lossy(&resp);
+    let (head, body) = resp.split_once("\r\n\r\n").ok_or("invalid response")?;
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        return Err(format!("GET {} => {}", path, status).into());
+    }
+    Ok(serde_json::from_str(body)?)
+}
+
+// 上次的 (容器 cpu 累计, 系统 cpu 累计), 用于计算使用率
+type CpuSnapshot = HashMap<String, (u64, u64)>;
+
+fn container_stats(o: &mut Container, prev: &mut CpuSnapshot) {
+    let stats = match get(&format!("/containers/{}/stats?stream=false&one-shot=true", o.id)) {
+        Ok(v) => v,
+        Err(err) => {
+            warn!("docker stats {} error => {:?}", o.name, err);
+            return;
+        }
+    };
+    let cpu_total = stats["cpu_stats"]["cpu_usage"]["total_usage"].as_u64().unwrap_or(0);
+    let system_total = stats["cpu_stats"]["system_cpu_usage"].as_u64().unwrap_or(0);
+    let online_cpus = stats["cpu_stats"]["online_cpus"].as_u64().unwrap_or(1);
+    if let Some((cpu_prev, system_prev)) = prev.insert(o.id.to_string(), (cpu_total, system_total)) {
+        if system_total > system_prev && cpu_total >= cpu_prev {
+            o.cpu = (cpu_total - cpu_prev) as f64 / (system_total - system_prev) as f64 * online_cpus as f64 * 100.0;
+        }
+    }
+    // 同 docker stats, 扣除 page cache (cgroup v1: cache, v2: inactive_file)
+    let usage = stats["memory_stats"]["usage"].as_u64().unwrap_or(0);
+    let cache = stats["memory_stats"]["stats"]["inactive_file"]
+        .as_u64()
+        .or_else(|| stats["memory_stats"]["stats"]["cache"].as_u64())
+        .unwrap_or(0);
+    o.memory = usage.saturating_sub(cache);
+    o.memory_limit = stats["memory_stats"]["limit"].as_u64().unwrap_or(0);
+}
+
+fn collect(with_stats: bool, prev: &mut CpuSnapshot) -> Result<DockerInfo> {
+    let list = get("/containers/json?all=1")?;
+    let mut info = DockerInfo::default();
+    for c in list.as_array().map(|o| o.as_slice()).unwrap_or_default() {
+        let state = c["State"].as_str().unwrap_or_default();
+        info.total += 1;
+        if state.eq("running") {
+            info.running += 1;
+        }
+        if !with_stats {
+            continue;
+        }
+        let mut o = Container {
+            id: c["Id"].as_str().unwrap_or_default().chars().take(12).collect(),
+            name: c["Names"][0]
+                .as_str()
+                .unwrap_or_default()
+                .trim_start_matches('/')
+                .to_string(),
+            image: c["Image"].as_str().unwrap_or_default().to_string(),
+            state: state.to_string(),
+            ..Default::default()
+        };
+        if state.eq("running") {
+            container_stats(&mut o, prev);
+        }
+        info.containers.push(o);
+    }
+    // 已删除的容器
+    prev.retain(|id, _| info.containers.iter().any(|o| o.id.eq(id)));
+    Ok(info)
+}
+
+pub fn start_docker_collect_t(with_stats: bool) {
+    supervisor::spawn("docker", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let mut prev = CpuSnapshot::new();
+        Box::new(move || {
+            let info = collect(with_stats, &mut prev)
+                .map_err(|err| warn!("docker collect error => {:?}", err))
+                .ok();
+            if let Ok(mut o) = G_DOCKER.lock() {
+                *o = info;
+            }
+        })
+    });
+}
@@ -0,0 +1,124 @@
+// 自定义指标脚本, 输出单个数值或 json 对象(取其中的数值字段), 结果上报到 metrics
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::supervisor;
+use crate::Result;
+
+const TIMEOUT_MS: u64 = 10000;
+// json 嵌套层数上限
+const MAX_DEPTH: usize = 3;
+const MAX_OUTPUT: u64 = 64 * 1024;
+
+lazy_static! {
+    pub static ref G_EXEC_METRICS: Arc<Mutex<HashMap<String, f64>>> = Arc::new(Default::default());
+}
+
+// name:/path/to/script arg1 arg2
+pub fn parse(specs: &[String]) -> Vec<(String, Vec<String>)> {
+    specs
+        .iter()
+        .filter_map(|spec| {
+            let (name, cmd) = spec.split_once(':')?;
+            let argv = cmd.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>();
+            if name.trim().is_empty() || argv.is_empty() {
+                return None;
+            }
+            Some((name.trim().to_string(), argv))
+        })
+        .collect()
+}
+
+fn run(argv: &[String]) -> Result<String> {
+    let mut child = Command::new(&argv[0])
+        .args(&argv[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // 边运行边读取, 输出超过管道缓冲时脚本不会阻塞到超时
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            let mut output = String::new();
+            let r = stdout.take(MAX_OUTPUT).read_to_string(&mut output).map(|_| output);
+            tx.send(r).ok();
+        });
+    }
+    let deadline = Instant::now() + Duration::from_millis(TIMEOUT_MS);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().ok();
+            child.wait().ok();
+            return Err("timeout".into());
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    if !status.success() {
+        return Err(format!("exit {:?}", status.code()).into());
+    }
+    // 脚本退出后其子进程仍可能持有 stdout, 同样受超时限制
+    let remain = deadline.saturating_duration_since(Instant::now());
+    match rx.recv_timeout(remain) {
+        Ok(r) => Ok(r?),
+        Err(_) => Err("timeout".into()),
+    }
+}
+
+fn flatten(prefix: &str, v: &Value, depth: usize, out: &mut HashMap<String, f64>) {
+    match v {
+        Value::Number(n) => {
+            if let Some(f) = n.as_f64() {
+                out.insert(prefix.to_string(), f);
+            }
+        }
+        Value::Bool(b) => {
+            out.insert(prefix.to_string(), if *b { 1.0 } else { 0.0 });
+        }
+        Value::Object(map) if depth < MAX_DEPTH => {
+            for (k, v) in map {
+                flatten(&format!("{}.{}", prefix, k), v, depth + 1, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_output(name: &str, output: &str, out: &mut HashMap<String, f64>) -> Result<()> {
+    let s = output.trim();
+    if let Ok(v) = s.parse::<f64>() {
+        out.insert(name.to_string(), v);
+        return Ok(());
+    }
+    let v = serde_json::from_str::<Value>(s)?;
+    flatten(name, &v, 0, out);
+    Ok(())
+}
+
+pub fn start_exec_metric_collect_t(scripts: Vec<(String, Vec<String>)>, interval: u64) {
+    supervisor::spawn("exec_metric", Duration::from_secs(interval), move || {
+        let scripts = scripts.clone();
+        Box::new(move || {
+            let mut metrics = HashMap::new();
+            for (name, argv) in scripts.iter() {
+                if let Err(err) = run(argv).and_then(|output| parse_output(name, &output, &mut metrics)) {
+                    warn!("exec metric `{}` error => {:?}", name, err);
+                }
+            }
+            if let Ok(mut o) = G_EXEC_METRICS.lock() {
+                *o = metrics;
+            }
+        })
+    });
+}
@@ -0,0 +1,109 @@
+// --addr 多地址故障转移: 当前地址连续失败 FAIL_THRESHOLD 次切换到下一个,
+// 不在主地址时每 PRIMARY_CHECK_SECS 探测主地址 tcp 连通性, 恢复后切回
+use once_cell::sync::Lazy;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::dns;
+use crate::supervisor;
+
+const FAIL_THRESHOLD: u32 = 3;
+const PRIMARY_CHECK_SECS: u64 = 60;
+const CONNECT_TIMEOUT_MS: u64 = 3000;
+
+#[derive(Debug, Default)]
+struct Failover {
+    addrs: Vec<String>,
+    cur: usize,
+    failures: u32,
+}
+
+static G_FAILOVER: Lazy<Mutex<Failover>> = Lazy::new(Default::default);
+
+// 返回主地址, 与主地址协议(http/grpc/mqtt)不同的忽略
+pub fn init(addr: &str) -> String {
+    let mut addrs = addr
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let primary = match addrs.first() {
+        Some(o) => o.to_string(),
+        None => return addr.to_string(),
+    };
+    let scheme = |o: &str| {
+        ["grpc", "mqtt"]
+            .into_iter()
+            .find(|s| o.starts_with(s))
+            .unwrap_or("http")
+    };
+    addrs.retain(|o| {
+        let same = scheme(o) == scheme(&primary);
+        if !same {
+            eprintln!("⚠️ addr `{}` ignored, scheme differs from `{}`", o, primary);
+        }
+        same
+    });
+    let mut o = G_FAILOVER.lock().unwrap();
+    o.addrs = addrs;
+    o.cur = 0;
+    primary
+}
+
+pub fn current() -> String {
+    let o = G_FAILOVER.lock().unwrap();
+    o.addrs.get(o.cur).cloned().unwrap_or_default()
+}
+
+// 只统计当前地址的结果, 切换前发出的请求忽略
+pub fn report(addr: &str, ok: bool) {
+    let mut o = G_FAILOVER.lock().unwrap();
+    if o.addrs.len() < 2 || !o.addrs[o.cur].eq(addr) {
+        return;
+    }
+    if ok {
+        o.failures = 0;
+        return;
+    }
+    o.failures += 1;
+    if o.failures >= FAIL_THRESHOLD {
+        o.cur = (o.cur + 1) % o.addrs.len();
+        o.failures = 0;
+        warn!(
+            "{} failed {} times, fail over to {}",
+            addr, FAIL_THRESHOLD, o.addrs[o.cur]
+        );
+    }
+}
+
+fn reachable(addr: &str) -> bool {
+    let timeout = Duration::from_millis(CONNECT_TIMEOUT_MS);
+    match dns::host_port(addr).to_socket_addrs() {
+        Ok(mut addrs) => addrs.any(|sa| TcpStream::connect_timeout(&sa, timeout).is_ok()),
+        Err(_) => false,
+    }
+}
+
+pub fn start_primary_check_t() {
+    if G_FAILOVER.lock().unwrap().addrs.len() < 2 {
+        return;
+    }
+    supervisor::spawn("failover", Duration::from_secs(PRIMARY_CHECK_SECS), || {
+        Box::new(|| {
+            let primary = {
+                let o = G_FAILOVER.lock().unwrap();
+                if o.cur == 0 {
+                    return;
+                }
+                o.addrs[0].to_string()
+            };
+            if reachable(&primary) {
+                let mut o = G_FAILOVER.lock().unwrap();
+                info!("primary {} is back, switch from {}", primary, o.addrs[o.cur]);
+                o.cur = 0;
+                o.failures = 0;
+            }
+        })
+    });
+}
@@ -0,0 +1,100 @@
+// GPU 采集, NVIDIA 通过 NVML(需安装驱动), AMD 读取 sysfs(amdgpu)
+use lazy_static::lazy_static;
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
+use nvml_wrapper::Nvml;
+use std::fs;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use stat_common::server_status::Gpu;
+
+const SAMPLE_PERIOD: u64 = 2000; //ms
+
+lazy_static! {
+    pub static ref G_GPUS: Arc<Mutex<Vec<Gpu>>> = Arc::new(Default::default());
+}
+
+fn nvidia_gpus(nvml: &Nvml) -> Vec<Gpu> {
+    let count = nvml.device_count().unwrap_or(0);
+    (0..count)
+        .filter_map(|i| {
+            let device = nvml.device_by_index(i).ok()?;
+            let mut gpu = Gpu {
+                index: i,
+                name: device.name().unwrap_or_default(),
+                vendor: "nvidia".to_string(),
+                ..Default::default()
+            };
+            if let Ok(o) = device.utilization_rates() {
+                gpu.utilization = o.gpu as f64;
+            }
+            if let Ok(o) = device.memory_info() {
+                gpu.memory_total = o.total / 1024 / 1024;
+                gpu.memory_used = o.used / 1024 / 1024;
+            }
+            if let Ok(o) = device.temperature(TemperatureSensor::Gpu) {
+                gpu.temperature = o as f64;
+            }
+            Some(gpu)
+        })
+        .collect()
+}
+
+fn read_u64(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse::<u64>().ok()
+}
+
+// /sys/class/drm/cardN/device, 仅 amdgpu 驱动有 gpu_busy_percent
+fn amd_gpus() -> Vec<Gpu> {
+    let mut cards = fs::read_dir("/sys/class/drm")
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with("card") && !name.contains('-'))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    cards.sort();
+    cards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, card)| {
+            let dir = format!("/sys/class/drm/{}/device", card);
+            let busy = read_u64(&format!("{}/gpu_busy_percent", dir))?;
+            let temperature = fs::read_dir(format!("{}/hwmon", dir))
+                .ok()
+                .and_then(|mut rd| rd.next())
+                .and_then(|e| e.ok())
+                .and_then(|e| read_u64(&format!("{}/temp1_input", e.path().to_string_lossy())))
+                .map(|v| v as f64 / 1000.0)
+                .unwrap_or(0.0);
+            Some(Gpu {
+                index: i as u32,
+                name: fs::read_to_string(format!("{}/product_name", dir))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(|_| card.to_string()),
+                vendor: "amd".to_string(),
+                utilization: busy as f64,
+                memory_total: read_u64(&format!("{}/mem_info_vram_total", dir)).unwrap_or(0) / 1024 / 1024,
+                memory_used: read_u64(&format!("{}/mem_info_vram_used", dir)).unwrap_or(0) / 1024 / 1024,
+                temperature,
+            })
+        })
+        .collect()
+}
+
+pub fn start_gpu_collect_t() {
+    supervisor::spawn("gpu", Duration::from_millis(SAMPLE_PERIOD), || {
+        // 未安装 NVIDIA 驱动时只采集 AMD
+        let nvml = Nvml::init().map_err(|err| info!("nvml unavailable => {:?}", err)).ok();
+        Box::new(move || {
+            let mut gpus = nvml.as_ref().map(nvidia_gpus).unwrap_or_default();
+            gpus.extend(amd_gpus());
+            if let Ok(mut o) = G_GPUS.lock() {
+                *o = gpus;
+            }
+        })
+    });
+}
@@ -1,19 +1,39 @@
 // #![allow(unused)]
 use std::net::ToSocketAddrs;
-use std::thread;
 use std::time::Duration;
-use tonic::transport::Channel;
+use tokio::time;
+use tonic::codec::CompressionEncoding;
 use tonic::{metadata::MetadataValue, Request};
 use tower::timeout::Timeout;
 
+use stat_common::auth;
 use stat_common::server_status::server_status_client::ServerStatusClient;
 use stat_common::server_status::StatRequest;
 
-use crate::sample_all;
+use crate::adaptive::Adaptive;
+use crate::buffer;
+use crate::delta::{self, Delta};
+use crate::failover;
+use crate::proxy;
+use crate::tls;
+use crate::trace;
 use crate::Args;
-use crate::INTERVAL_MS;
+use crate::{
+    auth_pass, client_capabilities, compression, jitter, report_interval, set_new_pass, set_report_interval,
+    set_server_caps, Sampler,
+};
 
-// TODO TLS
+const BACKOFF_MIN_MS: u64 = 1000;
+const BACKOFF_MAX_MS: u64 = 60_000;
+const MAX_FAILURES: u32 = 3;
+
+// 断线期间同样缓存, 间隔受重连退避影响
+fn buffer_sample(sampler: &mut Sampler, args: &Args) {
+    sampler.refresh(args);
+    if buffer::due(sampler.gauges().latest_ts) {
+        buffer::push(sampler.to_stat());
+    }
+}
 
 pub async fn report(args: &Args, stat_base: &mut StatRequest) -> anyhow::Result<()> {
     if !vec![stat_base.online4, stat_base.online6].iter().any(|&x| x) {
@@ -30,44 +50,134 @@ pub async fn report(args: &Args, stat_base: &mut StatRequest) -> anyhow::Result<
         );
     }
 
-    let auth_user: String;
-    let ssr_auth: &[u8];
-    if args.gid.is_empty() {
-        auth_user = args.user.to_string();
-        ssr_auth = b"single";
-    } else {
-        auth_user = args.gid.to_string();
-        ssr_auth = b"group";
-    }
-    let token = MetadataValue::try_from(format!("{}@_@{}", auth_user, args.pass))?;
+    let (auth_user, ssr_auth) = auth::auth_user(&args.user, &args.gid);
 
-    let channel = Channel::from_shared(args.addr.to_string())?.connect().await?;
-    let timeout_channel = Timeout::new(channel, Duration::from_millis(3000));
+    let mut sampler = Sampler::new(stat_base.clone());
+    let mut adaptive = Adaptive::new(args);
+    let mut delta = Delta::new(args.delta);
+    let mut backoff = BACKOFF_MIN_MS;
+    loop {
+        // 长连接, keepalive 探测断线, 断线后指数退避 + 抖动重连, 多地址时按 failover 当前地址连接
+        let addr = failover::current();
+        let endpoint = tls::grpc_endpoint(&addr, args)?
+            .connect_timeout(Duration::from_secs(5))
+            .tcp_keepalive(Some(Duration::from_secs(60)))
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .keep_alive_timeout(Duration::from_secs(10))
+            .keep_alive_while_idle(true);
+        let channel = match proxy::grpc_connect(endpoint, args).await {
+            Ok(channel) => {
+                backoff = BACKOFF_MIN_MS;
+                channel
+            }
+            Err(err) => {
+                failover::report(&addr, false);
+                buffer::set_online(false);
+                buffer_sample(&mut sampler, args);
+                if failover::current() != addr {
+                    continue;
+                }
+                let delay = backoff + jitter(backoff / 2);
+                error!("grpc connect error => {:?}, retry in {}ms", err, delay);
+                time::sleep(Duration::from_millis(delay)).await;
+                backoff = (backoff * 2).min(BACKOFF_MAX_MS);
+                continue;
+            }
+        };
+        eprintln!("grpc connected => {}", addr);
 
-    let grpc_client = ServerStatusClient::with_interceptor(timeout_channel, move |mut req: Request<()>| {
-        req.metadata_mut().insert("authorization", token.clone());
-        req.metadata_mut()
-            .insert("ssr-auth", MetadataValue::try_from(ssr_auth).unwrap());
+        let timeout_channel = Timeout::new(channel, Duration::from_millis(3000));
+        // 每次请求取当前密码, 支持服务端密码轮换
+        let mut grpc_client = ServerStatusClient::with_interceptor(timeout_channel, move |mut req: Request<()>| {
+            let token = MetadataValue::try_from(auth::grpc_token(auth_user, &auth_pass(args)))
+                .map_err(|_| tonic::Status::unauthenticated("invalid token"))?;
+            req.metadata_mut().insert("authorization", token);
+            req.metadata_mut()
+                .insert(auth::SSR_AUTH, MetadataValue::try_from(ssr_auth).unwrap());
 
-        Ok(req)
-    });
+            Ok(req)
+        });
 
-    loop {
-        let stat_rt = sample_all(args, stat_base);
-        let mut client = grpc_client.clone();
-        tokio::spawn(async move {
-            let request = tonic::Request::new(stat_rt);
+        // handshake, 旧版本服务端返回 Unimplemented 则按旧协议上报
+        match grpc_client.handshake(Request::new(client_capabilities())).await {
+            Ok(resp) => set_server_caps(resp.into_inner()),
+            Err(status) if status.code() == tonic::Code::Unimplemented => {
+                info!("handshake unsupported by server");
+            }
+            Err(status) => error!("grpc handshake status => {:?}", status),
+        }
+        // grpc 仅支持 gzip
+        if compression(args).is_some() {
+            grpc_client = grpc_client.send_compressed(CompressionEncoding::Gzip);
+        }
+        // 新连接可能是重启后的服务端, 先完整上报
+        delta::report_failed();
+
+        // 串行上报, 连续失败 MAX_FAILURES 次后重建连接
+        let mut failures = 0;
+        let mut interval = report_interval(&stat_base.name);
+        while failures < MAX_FAILURES {
+            interval.tick().await;
+            // 已切换地址(如主地址恢复)
+            if failover::current() != addr {
+                break;
+            }
 
-            match client.report(request).await {
+            buffer_sample(&mut sampler, args);
+            let batch = buffer::take_batch();
+            if !batch.is_empty() {
+                // 补发离线缓存, 失败时放回队列
+                let mut failed = Vec::new();
+                for stat in batch {
+                    if !failed.is_empty() {
+                        failed.push(stat);
+                        continue;
+                    }
+                    if let Err(status) = grpc_client.report(Request::new(stat.clone())).await {
+                        error!("grpc replay status => {:?}", status);
+                        if matches!(status.code(), tonic::Code::Unavailable | tonic::Code::DeadlineExceeded) {
+                            failed.push(stat);
+                        }
+                    }
+                }
+                buffer::replay_done(failed);
+            }
+            if !adaptive.should_report(sampler.gauges()) {
+                continue;
+            }
+            let traces = sampler.gauges().traces.clone();
+            let stat = delta.diff(&sampler).unwrap_or_else(|| sampler.to_stat());
+            match grpc_client.report(Request::new(stat)).await {
                 Ok(resp) => {
+                    failures = 0;
+                    trace::delivered(&traces);
+                    failover::report(&addr, true);
+                    buffer::set_online(true);
                     info!("grpc report resp => {:?}", resp);
+                    if resp.get_ref().need_full {
+                        delta::report_failed();
+                    }
+                    set_new_pass(&resp.get_ref().new_password);
+                    set_report_interval(resp.get_ref().report_interval);
                 }
                 Err(status) => {
+                    failures += 1;
+                    failover::report(&addr, false);
+                    delta::report_failed();
+                    buffer::set_online(false);
                     error!("grpc report status => {:?}", status);
                 }
             }
-        });
+        }
 
-        thread::sleep(Duration::from_millis(INTERVAL_MS));
+        if failover::current() != addr {
+            info!("grpc switch to {}", failover::current());
+            continue;
+        }
+        buffer_sample(&mut sampler, args);
+        let delay = backoff + jitter(backoff / 2);
+        warn!("grpc connection lost, reconnect in {}ms", delay);
+        time::sleep(Duration::from_millis(delay)).await;
+        backoff = (backoff * 2).min(BACKOFF_MAX_MS);
     }
 }
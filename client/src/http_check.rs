@@ -0,0 +1,62 @@
+// http(s) 接口健康检查, GET 返回 2xx(跟随重定向后)为通过
+use lazy_static::lazy_static;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use stat_common::server_status::HttpCheck;
+
+const SAMPLE_PERIOD: u64 = 30; //s
+const TIMEOUT: u64 = 10; //s
+
+lazy_static! {
+    pub static ref G_HTTP_CHECKS: Arc<Mutex<Vec<HttpCheck>>> = Arc::new(Default::default());
+}
+
+async fn check(client: &reqwest::Client, url: &str) -> HttpCheck {
+    let instant = Instant::now();
+    let mut result = HttpCheck {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    match client.get(url).send().await {
+        Ok(resp) => {
+            result.status = resp.status().as_u16() as u32;
+            result.ok = resp.status().is_success();
+        }
+        Err(err) => {
+            warn!("http check {} error => {:?}", url, err);
+            // 错误信息中的 url 可能带 token, 面板只展示 scheme://host
+            result.error = err.without_url().to_string();
+        }
+    }
+    result.latency = instant.elapsed().as_millis() as u32;
+    result
+}
+
+pub async fn http_check_loop(urls: Vec<String>) {
+    let http_client = match reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(TIMEOUT))
+        .user_agent(concat!("stat_client/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            error!("http check client error => {:?}", err);
+            return;
+        }
+    };
+    let mut interval = time::interval(time::Duration::from_secs(SAMPLE_PERIOD));
+    loop {
+        interval.tick().await;
+        let mut results = Vec::with_capacity(urls.len());
+        for url in urls.iter() {
+            results.push(check(&http_client, url).await);
+        }
+        if let Ok(mut o) = G_HTTP_CHECKS.lock() {
+            *o = results;
+        }
+    }
+}
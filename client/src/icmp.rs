@@ -0,0 +1,78 @@
+// icmp echo, 优先使用免 root 的 ping socket(net.ipv4.ping_group_range), 其次 raw socket(需 root/CAP_NET_RAW)
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+// raw socket 会收到本机所有 icmp 回复, 每次请求使用不同的 id 区分并发探测的线程
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+fn checksum(buf: &[u8]) -> u16 {
+    let mut sum = buf
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// (socket, 是否 raw)
+fn open(v6: bool) -> io::Result<(Socket, bool)> {
+    let (domain, protocol) = if v6 {
+        (Domain::IPV6, Protocol::ICMPV6)
+    } else {
+        (Domain::IPV4, Protocol::ICMPV4)
+    };
+    Socket::new(domain, Type::DGRAM, Some(protocol))
+        .map(|s| (s, false))
+        .or_else(|_| Socket::new(domain, Type::RAW, Some(protocol)).map(|s| (s, true)))
+}
+
+pub fn available(v6: bool) -> bool {
+    open(v6).is_ok()
+}
+
+pub fn ping(ip: IpAddr, seq: u16, timeout: Duration) -> io::Result<Duration> {
+    let v6 = ip.is_ipv6();
+    let (mut socket, raw) = open(v6)?;
+    let id = (std::process::id() as u16).wrapping_add(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut packet = [0_u8; 24];
+    packet[0] = if v6 { 128 } else { 8 };
+    packet[4..6].copy_from_slice(&id.to_be_bytes());
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    // icmpv6 校验和由内核计算
+    if !v6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    // connect 后内核只投递来自目标地址的包
+    socket.connect(&SockAddr::from(SocketAddr::new(ip, 0)))?;
+    let start = Instant::now();
+    socket.send(&packet)?;
+    let mut buf = [0_u8; 1500];
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        socket.set_read_timeout(Some(timeout - elapsed))?;
+        let n = socket.read(&mut buf)?;
+        // raw ipv4 socket 收到的数据包含 ip 头
+        let offset = if raw && !v6 { (buf[0] & 0x0f) as usize * 4 } else { 0 };
+        if n < offset + 8 {
+            continue;
+        }
+        let reply = &buf[offset..n];
+        // ping socket 的 id 由内核替换, 只比较 seq
+        if reply[0] == (if v6 { 129 } else { 0 })
+            && reply[6..8] == seq.to_be_bytes()
+            && (!raw || reply[4..6] == id.to_be_bytes())
+        {
+            return Ok(start.elapsed());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// ipmi 传感器(风扇/电源/机箱等), 解析 ipmitool sdr elist 输出, 需加载 ipmi_devintf 并以 root 运行
+use lazy_static::lazy_static;
+use std::process::Command;
+use std::str;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use crate::Result;
+use stat_common::server_status::IpmiSensor;
+
+const SAMPLE_PERIOD: u64 = 60000; //ms
+
+lazy_static! {
+    pub static ref G_IPMI: Arc<Mutex<Vec<IpmiSensor>>> = Arc::new(Default::default());
+}
+
+// FAN1             | 30h | ok  |  29.1 | 4200 RPM
+// PS1 Status       | c8h | ok  |  10.1 | Presence detected
+fn parse_line(line: &str) -> Option<IpmiSensor> {
+    let v: Vec<&str> = line.split('|').map(|s| s.trim()).collect();
+    if v.len() < 5 || v[2].eq("ns") {
+        return None;
+    }
+    let reading = v[4].to_string();
+    let (value, unit) = match reading.split_once(' ') {
+        Some((value, unit)) => match value.parse::<f64>() {
+            Ok(value) => (value, unit.to_string()),
+            Err(_) => (0.0, String::new()),
+        },
+        None => (0.0, String::new()),
+    };
+    Some(IpmiSensor {
+        name: v[0].to_string(),
+        status: v[2].to_string(),
+        reading,
+        value,
+        unit,
+    })
+}
+
+fn collect() -> Result<Vec<IpmiSensor>> {
+    let output = Command::new("ipmitool").args(["sdr", "elist"]).output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    Ok(str::from_utf8(&output.stdout)?.lines().filter_map(parse_line).collect())
+}
+
+pub fn start_ipmi_collect_t() {
+    supervisor::spawn("ipmi", Duration::from_millis(SAMPLE_PERIOD), || {
+        Box::new(|| {
+            let sensors = collect()
+                .map_err(|err| warn!("ipmitool error => {:?}", err))
+                .unwrap_or_default();
+            if let Ok(mut o) = G_IPMI.lock() {
+                *o = sensors;
+            }
+        })
+    });
+}
@@ -4,26 +4,61 @@ extern crate log;
 extern crate pretty_env_logger;
 use clap::Parser;
 use hyper::header;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use prost::Message;
-use std::net::ToSocketAddrs;
+use std::collections::VecDeque;
+use std::fs;
 use std::process;
 use std::sync::Mutex;
-use std::thread;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 use sysinfo::{System, SystemExt};
 use tokio::time;
 
-use stat_common::server_status::{IpInfo, StatRequest, SysInfo};
+use stat_common::codec::{self, Encoding};
+use stat_common::server_status::{Capabilities, IpInfo, StatRequest, SysInfo};
+use stat_common::{auth, capability, PROTO_VERSION};
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
 type Result<T> = std::result::Result<T, GenericError>;
+mod adaptive;
+mod buffer;
+#[cfg(feature = "rustls")]
+mod cert;
+mod config;
+mod delta;
+mod diag;
+mod dns;
+#[cfg(unix)]
+mod docker;
+mod exec_metric;
+mod failover;
+#[cfg(feature = "gpu")]
+mod gpu;
 mod grpc;
+mod http_check;
+mod icmp;
 mod ip_api;
+#[cfg(feature = "ipmi")]
+mod ipmi;
+mod mqtt;
+mod ntp;
+mod nvme;
+mod power;
+mod procs;
+mod proxy;
+mod reload;
 mod status;
+mod supervisor;
 mod sys_info;
+mod systemd;
+mod tls;
+mod trace;
+mod udp;
+mod zfs;
 
 const INTERVAL_MS: u64 = 1000;
+// 最多同时进行中的上报请求, 超出时取消最旧的
+const MAX_INFLIGHT: usize = 3;
 static CU: &str = "cu.tz.cloudcpp.com:80";
 static CT: &str = "ct.tz.cloudcpp.com:80";
 static CM: &str = "cm.tz.cloudcpp.com:80";
@@ -32,10 +67,124 @@ static CM: &str = "cm.tz.cloudcpp.com:80";
 pub struct ClientConfig {
     ip_info: Option<IpInfo>,
     sys_info: Option<SysInfo>,
+    // 服务端协商结果, 旧版本服务端为 None
+    server_caps: Option<Capabilities>,
+    // ip_info/sys_info 变更计数
+    info_gen: u64,
+    // 服务端密码轮换下发的新密码
+    new_pass: Option<String>,
+    // --pass-file, 新密码先写入再切换
+    pass_file: String,
+    // 服务端指定的上报间隔(s)
+    report_interval: u64,
+    // 热重载后的参数, 用于更新展示信息
+    reloaded: Option<Args>,
 }
 
 pub static G_CONFIG: Lazy<Mutex<ClientConfig>> = Lazy::new(|| Mutex::new(ClientConfig::default()));
 
+pub fn client_capabilities() -> Capabilities {
+    Capabilities {
+        proto_version: PROTO_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: vec![capability::JSON.to_string(), capability::EXTRA.to_string()],
+    }
+}
+
+pub fn auth_pass(args: &Args) -> String {
+    G_CONFIG
+        .lock()
+        .unwrap()
+        .new_pass
+        .clone()
+        .unwrap_or_else(|| args.pass.to_string())
+}
+
+// 启动时优先使用 --pass-file 中保存的轮换后密码
+pub fn load_pass_file(args: &mut Args) {
+    if args.pass_file.is_empty() {
+        return;
+    }
+    G_CONFIG.lock().unwrap().pass_file = args.pass_file.to_string();
+    match fs::read_to_string(&args.pass_file) {
+        Ok(s) if !s.trim().is_empty() => args.pass = s.trim().to_string(),
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => eprintln!("⚠️ read --pass-file {} error => {:?}", args.pass_file, err),
+    }
+}
+
+// 新密码先持久化到 --pass-file 再切换, 写入失败继续使用旧密码, 服务端下次上报会再次下发
+// 未指定 --pass-file 时仅保存在内存中, 重启前需更新 --pass
+pub fn set_new_pass(pass: &str) {
+    if pass.is_empty() {
+        return;
+    }
+    let mut o = G_CONFIG.lock().unwrap();
+    if o.new_pass.as_deref().eq(&Some(pass)) {
+        return;
+    }
+    if o.pass_file.is_empty() {
+        warn!("server rotated the password, switch to the new one, please update --pass before restart");
+    } else {
+        let tmp = format!("{}.tmp", o.pass_file);
+        if let Err(err) = fs::write(&tmp, pass).and_then(|_| fs::rename(&tmp, &o.pass_file)) {
+            error!("save rotated password to {} error => {:?}", o.pass_file, err);
+            return;
+        }
+        warn!("server rotated the password, saved to {}", o.pass_file);
+    }
+    o.new_pass = Some(pass.to_string());
+}
+
+pub fn set_reloaded_args(args: &Args) {
+    let mut o = G_CONFIG.lock().unwrap();
+    o.reloaded = Some(args.clone());
+    o.info_gen += 1;
+}
+
+pub fn set_report_interval(secs: u64) {
+    let mut o = G_CONFIG.lock().unwrap();
+    if o.report_interval != secs {
+        info!("server set report interval => {}s", secs);
+        o.report_interval = secs;
+    }
+}
+
+pub fn server_report_interval() -> u64 {
+    G_CONFIG.lock().map(|o| o.report_interval).unwrap_or(0)
+}
+
+pub fn set_server_caps(caps: Capabilities) {
+    eprintln!(
+        "server v{} proto {} caps {:?}",
+        caps.version, caps.proto_version, caps.capabilities
+    );
+    if let Ok(mut o) = G_CONFIG.lock() {
+        o.server_caps = Some(caps);
+    }
+}
+
+// --compress, 服务端握手声明支持时才压缩
+pub fn compression(args: &Args) -> Option<codec::Compression> {
+    if !server_support(capability::COMPRESSION) {
+        return None;
+    }
+    codec::Compression::from_content_encoding(&args.compress)
+}
+
+pub fn server_support(cap: &str) -> bool {
+    G_CONFIG
+        .lock()
+        .map(|o| {
+            o.server_caps
+                .as_ref()
+                .map(|caps| caps.capabilities.iter().any(|c| c.eq(cap)))
+                .unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
 // https://docs.rs/clap/latest/clap/_derive/index.html#command-attributes
 #[derive(Parser, Debug, Clone)]
 #[clap(author, version = env!("APP_VERSION"), about, long_about = None)]
@@ -45,13 +194,54 @@ pub struct Args {
         long,
         value_parser,
         env = "SSR_ADDR",
-        default_value = "http://127.0.0.1:8080/report"
+        default_value = "http://127.0.0.1:8080/report",
+        help = "server addr (http/grpc/mqtt), a comma separated list fails over in order"
     )]
     addr: String,
     #[clap(short, long, value_parser, env = "SSR_USER", default_value = "h1", help = "username")]
     user: String,
     #[clap(short, long, value_parser, env = "SSR_PASS", default_value = "p1", help = "password")]
     pass: String,
+    #[clap(
+        long = "pass-file",
+        value_parser,
+        env = "SSR_PASS_FILE",
+        default_value = "",
+        help = "persist the rotated password to this file, preferred over --pass on start"
+    )]
+    pass_file: String,
+    #[clap(
+        long = "tls-cert",
+        value_parser,
+        env = "SSR_TLS_CERT",
+        default_value = "",
+        help = "client certificate(pem) for mTLS, CN is the host name or gid"
+    )]
+    tls_cert: String,
+    #[clap(
+        long = "tls-key",
+        value_parser,
+        env = "SSR_TLS_KEY",
+        default_value = "",
+        help = "client private key(pem) for mTLS"
+    )]
+    tls_key: String,
+    #[clap(
+        long = "tls-ca",
+        value_parser,
+        env = "SSR_TLS_CA",
+        default_value = "",
+        help = "ca certificate(pem) to verify the server"
+    )]
+    tls_ca: String,
+    #[clap(
+        long = "proxy",
+        value_parser,
+        env = "SSR_PROXY",
+        default_value = "",
+        help = "report via proxy, eg: socks5://127.0.0.1:1080, default: HTTPS_PROXY/ALL_PROXY"
+    )]
+    proxy: String,
     #[clap(
         short = 'n',
         long,
@@ -60,6 +250,14 @@ pub struct Args {
         help = "enable vnstat, default:false"
     )]
     vnstat: bool,
+    #[clap(
+        long = "traffic-reset-day",
+        value_parser = clap::value_parser!(u32).range(0..=31),
+        env = "SSR_TRAFFIC_RESET_DAY",
+        default_value = "0",
+        help = "monthly traffic reset day 1-31, 0 to follow server monthstart"
+    )]
+    traffic_reset_day: u32,
     #[clap(
         long = "disable-tupd",
         value_parser,
@@ -74,6 +272,125 @@ pub struct Args {
         help = "disable ping, default:false"
     )]
     disable_ping: bool,
+    #[clap(
+        long = "enable-temps",
+        value_parser,
+        env = "SSR_ENABLE_TEMPS",
+        help = "report hardware temperatures, default:false"
+    )]
+    enable_temps: bool,
+    #[clap(
+        long = "power",
+        value_parser,
+        env = "SSR_POWER",
+        help = "report cpu package power via rapl, linux only, default:false"
+    )]
+    power: bool,
+    #[clap(
+        long = "gpu",
+        value_parser,
+        env = "SSR_GPU",
+        help = "report gpu metrics, default:false"
+    )]
+    gpu: bool,
+    #[clap(
+        long = "ipmi",
+        value_parser,
+        env = "SSR_IPMI",
+        help = "report ipmi sensors via ipmitool, default:false"
+    )]
+    ipmi: bool,
+    #[clap(
+        long = "per-core",
+        value_parser,
+        env = "SSR_PER_CORE",
+        help = "report per-core cpu usage, default:false"
+    )]
+    per_core: bool,
+    #[clap(
+        long = "top-procs",
+        value_parser,
+        env = "SSR_TOP_PROCS",
+        default_value = "0",
+        help = "report top N processes by cpu and memory, 0 to disable"
+    )]
+    top_procs: usize,
+    #[clap(
+        long = "docker",
+        value_parser,
+        env = "SSR_DOCKER",
+        help = "report docker containers, default:false"
+    )]
+    docker: bool,
+    #[clap(
+        long = "docker-stats",
+        value_parser,
+        env = "SSR_DOCKER_STATS",
+        help = "report per-container cpu/mem, implies --docker, default:false"
+    )]
+    docker_stats: bool,
+    #[clap(
+        long = "zfs",
+        value_parser,
+        env = "SSR_ZFS",
+        help = "report zfs pools, default:false"
+    )]
+    zfs: bool,
+    #[clap(
+        long = "nvme",
+        value_parser,
+        env = "SSR_NVME",
+        help = "report nvme temperature and wear, linux only, default:false"
+    )]
+    nvme: bool,
+    #[clap(
+        long = "ntp",
+        value_parser,
+        env = "SSR_NTP",
+        default_value = "",
+        help = "ntp server to measure clock offset, eg: pool.ntp.org"
+    )]
+    ntp: String,
+    #[clap(
+        long = "check-cert",
+        value_parser,
+        env = "SSR_CHECK_CERT",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "check tls cert expiry, repeatable, eg: example.com:443"
+    )]
+    check_cert: Vec<String>,
+    #[clap(
+        long = "check-port",
+        value_parser,
+        env = "SSR_CHECK_PORT",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "check tcp port liveness, eg: 5432,6379,10.0.0.2:80"
+    )]
+    check_port: Vec<String>,
+    #[clap(
+        long = "http-check",
+        value_parser,
+        env = "SSR_HTTP_CHECK",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "http(s) health check url, repeatable, eg: https://api.example.com/health"
+    )]
+    http_check: Vec<String>,
+    #[clap(
+        long = "dns-probe",
+        value_parser,
+        env = "SSR_DNS_PROBE",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "dns resolution probe, repeatable, eg: example.com@8.8.8.8"
+    )]
+    dns_probe: Vec<String>,
     #[clap(
         long = "disable-extra",
         value_parser,
@@ -81,6 +398,55 @@ pub struct Args {
         help = "disable extra info report, default:false"
     )]
     disable_extra: bool,
+    #[clap(
+        long = "probe",
+        value_parser,
+        env = "SSR_PROBE",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "custom tcp ping probe, repeatable, eg: hk=1.1.1.1:443, hk=icmp:1.1.1.1, hk=udp:1.1.1.1:7"
+    )]
+    probe: Vec<String>,
+    #[clap(
+        long = "icmp",
+        value_parser,
+        env = "SSR_ICMP",
+        help = "use icmp ping for probes, fallback to tcp without permission, default:false"
+    )]
+    icmp: bool,
+    #[clap(
+        long = "probe-interval",
+        value_parser,
+        env = "SSR_PROBE_INTERVAL",
+        default_value = "1",
+        help = "probe interval in seconds"
+    )]
+    probe_interval: u64,
+    #[clap(
+        long = "probe-window",
+        value_parser,
+        env = "SSR_PROBE_WINDOW",
+        default_value = "100",
+        help = "probe sliding window size for loss/jitter, min 10"
+    )]
+    probe_window: u32,
+    #[clap(
+        long = "trace-loss",
+        value_parser,
+        env = "SSR_TRACE_LOSS",
+        default_value = "0",
+        help = "run traceroute when probe loss >= N%, 0 to disable"
+    )]
+    trace_loss: u32,
+    #[clap(
+        long = "trace-rtt",
+        value_parser,
+        env = "SSR_TRACE_RTT",
+        default_value = "0",
+        help = "run traceroute when probe rtt >= N ms, 0 to disable"
+    )]
+    trace_rtt: u32,
     #[clap(long = "ct", value_parser, env = "SSR_CT_ADDR", default_value = CT, help = "China Telecom probe addr")]
     ct_addr: String,
     #[clap(long = "cm", value_parser, env = "SSR_CM_ADDR", default_value = CM, help = "China Mobile probe addr")]
@@ -91,6 +457,29 @@ pub struct Args {
     ip_info: bool,
     #[clap(long = "json", value_parser, help = "use json protocol, default:false")]
     json: bool,
+    #[clap(
+        long = "compress",
+        value_parser = clap::builder::PossibleValuesParser::new(["none", "gzip", "zstd"]),
+        env = "SSR_COMPRESS",
+        default_value = "none",
+        help = "compress report body if the server supports it, grpc always uses gzip"
+    )]
+    compress: String,
+    #[clap(
+        long = "delta",
+        value_parser,
+        env = "SSR_DELTA",
+        default_value = "0",
+        help = "send a full report every N reports and only changed fields in between, 0 to disable"
+    )]
+    delta: u32,
+    #[clap(
+        long = "sign",
+        value_parser,
+        env = "SSR_SIGN",
+        help = "sign http reports with hmac-sha256 instead of sending the password, default:false"
+    )]
+    sign: bool,
     #[clap(short = '6', value_parser, long = "ipv6", help = "ipv6 only, default:false")]
     ipv6: bool,
     // for group
@@ -144,6 +533,16 @@ pub struct Args {
         help = "iface list, eg: eth0,eth1"
     )]
     iface: Vec<String>,
+    #[clap(
+        long = "watch-unit",
+        value_parser,
+        env = "SSR_WATCH_UNIT",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "systemd unit list, eg: nginx.service,postgresql.service"
+    )]
+    watch_unit: Vec<String>,
     #[clap(
         short = 'e',
         long = "exclude-iface",
@@ -154,6 +553,191 @@ pub struct Args {
         help = "exclude iface"
     )]
     exclude_iface: Vec<String>,
+    #[clap(
+        long = "disk-include",
+        value_parser,
+        env = "SSR_DISK_INCLUDE",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "mountpoint list, eg: /,/var"
+    )]
+    disk_include: Vec<String>,
+    #[clap(
+        long = "disk-exclude",
+        value_parser,
+        env = "SSR_DISK_EXCLUDE",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "exclude mountpoint prefix, eg: /boot,/mnt/backup"
+    )]
+    disk_exclude: Vec<String>,
+    #[clap(
+        long = "extra",
+        value_parser,
+        env = "SSR_EXTRA",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "extra labels, eg: env=prod,owner=ops"
+    )]
+    extra: Vec<String>,
+    #[clap(
+        long = "exec-metric",
+        value_parser,
+        env = "SSR_EXEC_METRIC",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        require_delimiter = true,
+        help = "custom metric script, repeatable, eg: qps:/opt/qps.sh"
+    )]
+    exec_metric: Vec<String>,
+    #[clap(
+        long = "exec-interval",
+        value_parser,
+        env = "SSR_EXEC_INTERVAL",
+        default_value = "60",
+        help = "custom metric script interval in seconds"
+    )]
+    exec_interval: u64,
+    #[clap(
+        long = "collector",
+        value_parser = clap::builder::PossibleValuesParser::new(["auto", "native", "sysinfo"]),
+        env = "SSR_COLLECTOR",
+        default_value = "auto",
+        help = "metrics collector, native is linux only"
+    )]
+    collector: String,
+    #[clap(
+        long = "splay",
+        value_parser,
+        env = "SSR_SPLAY",
+        default_value = "0",
+        help = "random start delay in seconds, default:0"
+    )]
+    splay: u64,
+    #[clap(
+        long = "adaptive",
+        value_parser,
+        env = "SSR_ADAPTIVE",
+        default_value = "0",
+        help = "report every N seconds unless metrics change, 0 to disable"
+    )]
+    adaptive: u64,
+    #[clap(
+        long = "adaptive-cpu",
+        value_parser,
+        env = "SSR_ADAPTIVE_CPU",
+        default_value = "20",
+        help = "cpu change(%) that triggers an immediate report"
+    )]
+    adaptive_cpu: f64,
+    #[clap(
+        long = "buffer-size",
+        value_parser,
+        env = "SSR_BUFFER_SIZE",
+        default_value = "360",
+        help = "reports buffered while the server is unreachable, one per 10s, 0 to disable"
+    )]
+    buffer_size: usize,
+    #[clap(
+        long = "buffer-file",
+        value_parser,
+        env = "SSR_BUFFER_FILE",
+        default_value = "",
+        help = "persist buffered reports to file, replayed after restart"
+    )]
+    buffer_file: String,
+    #[clap(
+        long = "config",
+        value_parser,
+        env = "SSR_CONFIG",
+        help = "config file(toml/yaml), keys are long flag names, overridden by flags and env"
+    )]
+    config: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// collect version, flags (secrets redacted), errors, sample output and environment into a bundle
+    Diag {
+        #[clap(long, value_parser, help = "upload the bundle to the server, default:false")]
+        upload: bool,
+        #[clap(short, long, value_parser, help = "save the bundle to file instead of stdout")]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collector {
+    // 直接读取 /proc, 仅 linux
+    Native,
+    Sysinfo,
+}
+
+static G_COLLECTOR: OnceCell<Collector> = OnceCell::new();
+
+// 逻辑核数, 用于归一化负载
+static G_CORES: Lazy<f64> = Lazy::new(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f64);
+
+pub fn collector() -> Collector {
+    *G_COLLECTOR.get().unwrap_or(&Collector::Native)
+}
+
+// auto: linux 优先 native, 其他平台 sysinfo; 未编译的采集器回退到另一个
+fn select_collector(name: &str) -> Collector {
+    let native = cfg!(feature = "native") && "linux".eq(std::env::consts::OS);
+    let sysinfo = cfg!(feature = "sysinfo");
+    match name {
+        "native" if native => Collector::Native,
+        "sysinfo" if sysinfo => Collector::Sysinfo,
+        "auto" | "native" | "sysinfo" => {
+            if !name.eq("auto") {
+                eprintln!("collector `{}` unavailable, fallback", name);
+            }
+            if native || !sysinfo {
+                Collector::Native
+            } else {
+                Collector::Sysinfo
+            }
+        }
+        _ => {
+            eprintln!("invalid collector `{}`, use auto", name);
+            select_collector("auto")
+        }
+    }
+}
+
+// [0, max) 随机抖动, 避免大量客户端同时请求
+pub fn jitter(max: u64) -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos() as u64;
+    nanos % max.max(1)
+}
+
+// 按主机名固定相位上报, 同时启动的客户端均匀分布在一个周期内
+pub fn report_interval(name: &str) -> time::Interval {
+    let digest = md5::compute(name.as_bytes());
+    let phase = u64::from_le_bytes(digest.0[..8].try_into().unwrap()) % INTERVAL_MS;
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let delay = (phase + INTERVAL_MS - now_ms % INTERVAL_MS) % INTERVAL_MS;
+    let mut interval = time::interval_at(
+        time::Instant::now() + Duration::from_millis(delay),
+        Duration::from_millis(INTERVAL_MS),
+    );
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+    interval
+}
+
+// http://host/report => http://host/<path>
+pub fn api_url(addr: &str, path: &str) -> String {
+    let start = addr.find("://").map(|i| i + 3).unwrap_or(0);
+    match addr[start..].rfind('/') {
+        Some(idx) => format!("{}/{}", &addr[..start + idx], path),
+        None => format!("{}/{}", addr, path),
+    }
 }
 
 pub fn skip_iface(name: &str, args: &Args) -> bool {
@@ -169,107 +753,417 @@ pub fn skip_iface(name: &str, args: &Args) -> bool {
     false
 }
 
-fn sample_all(args: &Args, stat_base: &StatRequest) -> StatRequest {
-    // dbg!(&stat_base);
-    let mut stat_rt = stat_base.clone();
+pub fn skip_disk(mount: &str, args: &Args) -> bool {
+    if !args.disk_include.is_empty() {
+        return !args.disk_include.iter().any(|o| mount.eq(o));
+    }
+    args.disk_exclude.iter().any(|o| mount.starts_with(o))
+}
 
-    #[cfg(all(feature = "native", not(feature = "sysinfo")))]
-    status::sample(args, &mut stat_rt);
-    #[cfg(all(feature = "sysinfo", not(feature = "native")))]
-    sys_info::sample(args, &mut stat_rt);
+pub fn normalize_args(args: &mut Args) {
+    args.iface.retain(|e| !e.trim().is_empty());
+    args.exclude_iface.retain(|e| !e.trim().is_empty());
+    args.watch_unit.retain(|e| !e.trim().is_empty());
+    args.check_cert.retain(|e| !e.trim().is_empty());
+    args.check_port.retain(|e| !e.trim().is_empty());
+    args.http_check.retain(|e| !e.trim().is_empty());
+    args.dns_probe.retain(|e| !e.trim().is_empty());
+    args.probe.retain(|e| !e.trim().is_empty());
+    args.docker |= args.docker_stats;
+    args.disk_include.retain(|e| !e.trim().is_empty());
+    args.disk_exclude.retain(|e| !e.trim().is_empty());
+}
 
-    stat_rt.latest_ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+// 展示信息, 启动及热重载时设置
+fn apply_labels(stat_base: &mut StatRequest, args: &Args) {
+    stat_base.alias = if !args.gid.is_empty() && !args.alias.eq("unknown") {
+        args.alias.to_owned()
+    } else {
+        String::new()
+    };
+    stat_base.weight = args.weight;
+    stat_base.notify = !args.disable_notify;
+    stat_base.r#type = args.host_type.to_owned();
+    stat_base.location = args.location.to_owned();
+    stat_base.extra.clear();
+    for kv in args.extra.iter() {
+        if let Some((k, v)) = kv.split_once('=') {
+            stat_base.extra.insert(k.trim().to_string(), v.trim().to_string());
+        } else {
+            eprintln!("invalid extra `{}, ignore", kv);
+        }
+    }
+}
 
-    if !args.disable_extra {
+// 静态字段(name/alias/sys_info/ip_info 等)仅在变化时编码一次, 每次只采集动态指标
+pub struct Sampler {
+    stat_base: StatRequest,
+    base_pb: Vec<u8>,
+    info_gen: u64,
+    gauges: StatRequest,
+}
+
+impl Sampler {
+    pub fn new(stat_base: StatRequest) -> Self {
+        let base_pb = stat_base.encode_to_vec();
+        Self {
+            stat_base,
+            base_pb,
+            info_gen: 0,
+            gauges: StatRequest::default(),
+        }
+    }
+
+    pub fn set_network(&mut self, ipv4: bool, ipv6: bool) {
+        if self.stat_base.online4 != ipv4 || self.stat_base.online6 != ipv6 {
+            self.stat_base.online4 = ipv4;
+            self.stat_base.online6 = ipv6;
+            self.base_pb = self.stat_base.encode_to_vec();
+        }
+    }
+
+    pub fn refresh(&mut self, args: &Args) {
         if let Ok(o) = G_CONFIG.lock() {
-            if let Some(ip_info) = o.ip_info.as_ref() {
-                stat_rt.ip_info = Some(ip_info.clone());
+            if o.info_gen != self.info_gen {
+                self.info_gen = o.info_gen;
+                if !args.disable_extra {
+                    self.stat_base.ip_info = o.ip_info.clone();
+                    self.stat_base.sys_info = o.sys_info.clone();
+                }
+                if let Some(reloaded) = &o.reloaded {
+                    apply_labels(&mut self.stat_base, reloaded);
+                }
+                self.base_pb = self.stat_base.encode_to_vec();
             }
-            if let Some(sys_info) = o.sys_info.as_ref() {
-                stat_rt.sys_info = Some(sys_info.clone());
+        }
+
+        match collector() {
+            Collector::Native => status::sample(args, &mut self.gauges),
+            Collector::Sysinfo => sys_info::sample(args, &mut self.gauges),
+        }
+
+        if !args.watch_unit.is_empty() {
+            if let Ok(o) = systemd::G_UNITS.lock() {
+                self.gauges.units = o.clone();
             }
         }
-    }
 
-    stat_rt
-}
+        if !args.exec_metric.is_empty() {
+            if let Ok(o) = exec_metric::G_EXEC_METRICS.lock() {
+                self.gauges.metrics = o.clone();
+            }
+        }
 
-fn http_report(args: &Args, stat_base: &mut StatRequest) -> Result<()> {
-    let mut domain = args.addr.split('/').collect::<Vec<&str>>()[2].to_owned();
-    if !domain.contains(':') {
-        if args.addr.contains("https") {
-            domain = format!("{}:443", domain);
-        } else {
-            domain = format!("{}:80", domain);
+        if "linux".eq(std::env::consts::OS) {
+            if let Ok(o) = status::G_IP_TRAFFIC.lock() {
+                (self.gauges.net_in_v4, self.gauges.net_out_v4) = (o.in_v4, o.out_v4);
+                (self.gauges.net_in_v6, self.gauges.net_out_v6) = (o.in_v6, o.out_v6);
+                (self.gauges.net_rx_v4, self.gauges.net_tx_v4) = (o.rx_v4, o.tx_v4);
+                (self.gauges.net_rx_v6, self.gauges.net_tx_v6) = (o.rx_v6, o.tx_v6);
+            }
+            if let Ok(o) = status::G_REBOOT.lock() {
+                self.gauges.kernel = o.kernel.clone();
+                self.gauges.reboot_required = o.required;
+                self.gauges.reboot_reason = o.reason.clone();
+            }
+        }
+
+        if args.power {
+            if let Ok(o) = power::G_POWER.lock() {
+                self.gauges.power = *o;
+            }
+        }
+
+        // 重载后可能新增
+        self.gauges.probes = status::get_probes();
+        // 上报成功后由 trace::delivered 清除, 失败时随下次上报重发
+        self.gauges.traces = trace::pending();
+        if !args.disable_ping {
+            self.gauges.carrier_probes = status::get_carrier_probes();
+        }
+
+        if !args.dns_probe.is_empty() {
+            if let Ok(o) = dns::G_DNS_PROBES.lock() {
+                self.gauges.dns_probes = o.clone();
+            }
+        }
+
+        if !args.http_check.is_empty() {
+            if let Ok(o) = http_check::G_HTTP_CHECKS.lock() {
+                self.gauges.http_checks = o.clone();
+            }
+        }
+
+        if !args.check_port.is_empty() {
+            if let Ok(o) = status::G_PORTS.lock() {
+                self.gauges.ports = o.clone();
+            }
+        }
+
+        #[cfg(feature = "rustls")]
+        if !args.check_cert.is_empty() {
+            if let Ok(o) = cert::G_CERTS.lock() {
+                self.gauges.certs = o.clone();
+            }
+        }
+
+        if !args.ntp.is_empty() {
+            if let Ok(o) = ntp::G_NTP_OFFSET.lock() {
+                self.gauges.ntp_offset = *o;
+            }
         }
+
+        if args.nvme {
+            if let Ok(o) = nvme::G_NVME.lock() {
+                self.gauges.nvme = o.clone();
+            }
+        }
+
+        if args.zfs {
+            if let Ok(o) = zfs::G_ZFS_POOLS.lock() {
+                self.gauges.zfs_pools = o.clone();
+            }
+        }
+
+        if args.top_procs > 0 {
+            if let Ok(o) = procs::G_TOP_PROCS.lock() {
+                self.gauges.top_cpu = o.cpu.clone();
+                self.gauges.top_memory = o.memory.clone();
+            }
+        }
+
+        #[cfg(unix)]
+        if args.docker {
+            if let Ok(o) = docker::G_DOCKER.lock() {
+                self.gauges.docker = o.clone();
+            }
+        }
+
+        #[cfg(feature = "gpu")]
+        if args.gpu {
+            if let Ok(o) = gpu::G_GPUS.lock() {
+                self.gauges.gpus = o.clone();
+            }
+        }
+
+        #[cfg(feature = "ipmi")]
+        if args.ipmi {
+            if let Ok(o) = ipmi::G_IPMI.lock() {
+                self.gauges.ipmi = o.clone();
+            }
+        }
+
+        self.gauges.load_per_core = (self.gauges.load_1 / *G_CORES * 100.0).round() / 100.0;
+
+        self.gauges.collector_restarts = supervisor::health();
+
+        self.gauges.latest_ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    }
+
+    pub fn gauges(&self) -> &StatRequest {
+        &self.gauges
     }
-    let tcp_addr = domain.to_socket_addrs()?.next().unwrap();
-    let (ipv4, ipv6) = (tcp_addr.is_ipv4(), tcp_addr.is_ipv6());
-    if ipv4 {
-        stat_base.online4 = ipv4;
+
+    // proto3 重复出现的字段后者覆盖, 拼接 静态 + 动态 编码等价于完整消息
+    pub fn encode_pb(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.base_pb.len() + self.gauges.encoded_len());
+        buf.extend_from_slice(&self.base_pb);
+        self.gauges.encode_raw(&mut buf);
+        buf
+    }
+
+    // json / grpc 需要完整消息
+    pub fn to_stat(&self) -> StatRequest {
+        let mut stat_rt = self.stat_base.clone();
+        stat_rt.merge(self.gauges.encode_to_vec().as_slice()).unwrap();
+        stat_rt
     }
-    if ipv6 {
-        stat_base.online6 = ipv6;
+}
+
+// --sign 时不发送密码, 以 hmac 签名代替
+fn http_auth(req: reqwest::RequestBuilder, user: &str, pass: &str, body: &[u8], sign: bool) -> reqwest::RequestBuilder {
+    if !sign {
+        return req.basic_auth(user, Some(pass));
     }
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string();
+    req.basic_auth(user, None::<&str>)
+        .header(auth::SSR_TIMESTAMP, &ts)
+        .header(auth::SSR_SIGNATURE, auth::sign(pass, &ts, body))
+}
 
-    let http_client = reqwest::Client::builder()
+// 补发离线缓存, 连接失败或 5xx 时放回队列
+async fn http_replay(client: reqwest::Client, args: Args, batch: Vec<StatRequest>, encoding: Encoding) {
+    let url = failover::current();
+    let (auth_user, ssr_auth) = auth::auth_user(&args.user, &args.gid);
+    let mut failed = Vec::new();
+    for stat in batch {
+        if !failed.is_empty() {
+            failed.push(stat);
+            continue;
+        }
+        let compression = compression(&args);
+        let body = codec::encode(&stat, encoding).and_then(|body| match compression {
+            Some(c) => codec::compress(&body, c),
+            None => Ok(body),
+        });
+        let body = match body {
+            Ok(body) => body,
+            Err(err) => {
+                error!("replay encode error => {:?}", err);
+                continue;
+            }
+        };
+        let req = client.post(&url);
+        let mut req = http_auth(req, auth_user, &auth_pass(&args), &body, args.sign)
+            .timeout(Duration::from_secs(3))
+            .header(header::CONTENT_TYPE, encoding.content_type())
+            .header(auth::SSR_AUTH, ssr_auth)
+            .body(body);
+        if let Some(c) = compression {
+            req = req.header(header::CONTENT_ENCODING, c.content_encoding());
+        }
+        let result = req.send().await;
+        match result {
+            // 非 2xx(如鉴权失败/限流)时保留, 稍后重发
+            Ok(resp) if resp.status().is_success() => info!("replay {} => {:?}", stat.latest_ts, resp.status()),
+            Ok(resp) => {
+                error!("replay resp => {:?}", resp.status());
+                failed.push(stat);
+            }
+            Err(err) => {
+                error!("replay error => {:?}", err);
+                failed.push(stat);
+            }
+        }
+    }
+    buffer::replay_done(failed);
+}
+
+async fn http_report(args: &Args, stat_base: &StatRequest) -> Result<()> {
+    dns::start_resolve_t(dns::host_port(&args.addr));
+    let (base4, base6) = (stat_base.online4, stat_base.online6);
+
+    let http_client = proxy::http_builder(tls::http_builder(reqwest::Client::builder(), args)?, args)?
         .pool_max_idle_per_host(1)
         .connect_timeout(Duration::from_secs(5))
         .user_agent(format!("{}/{}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION")))
         .build()?;
+
+    // handshake, 旧版本服务端 404 则按旧协议上报
+    let caps_url = api_url(&args.addr, "api/capabilities");
+    let client = http_client.clone();
+    tokio::spawn(async move {
+        match client.get(&caps_url).timeout(Duration::from_secs(3)).send().await {
+            Ok(resp) if resp.status().is_success() => match resp.json::<Capabilities>().await {
+                Ok(caps) => set_server_caps(caps),
+                Err(err) => error!("handshake error => {:?}", err),
+            },
+            Ok(resp) => info!("handshake unsupported => {:?}", resp.status()),
+            Err(err) => error!("handshake error => {:?}", err),
+        }
+    });
+
+    let mut sampler = Sampler::new(stat_base.clone());
+    let mut adaptive = adaptive::Adaptive::new(args);
+    let mut delta = delta::Delta::new(args.delta);
+    let mut inflight: VecDeque<tokio::task::JoinHandle<()>> = VecDeque::new();
+    let mut interval = report_interval(&stat_base.name);
     loop {
-        let stat_rt = sample_all(args, stat_base);
-
-        let body_data: Option<Vec<u8>>;
-        let mut content_type = "application/octet-stream";
-        if args.json {
-            let data = serde_json::to_string(&stat_rt)?;
-            trace!("json_str => {:?}", serde_json::to_string(&data)?);
-            body_data = Some(data.into());
-            content_type = "application/json";
-        } else {
-            let buf = stat_rt.encode_to_vec();
-            body_data = Some(buf);
-            // content_type = "application/octet-stream";
+        interval.tick().await;
+
+        // 增量依赖上一次完整数据, 需按序送达, 只保留一个进行中的请求
+        let max_inflight = if args.delta > 1 { 1 } else { MAX_INFLIGHT };
+        inflight.retain(|h| !h.is_finished());
+        while inflight.len() >= max_inflight {
+            if let Some(h) = inflight.pop_front() {
+                warn!("report too slow, cancel stale request");
+                h.abort();
+                // 被取消的可能是完整数据, 下一次改为完整上报
+                delta::report_failed();
+            }
+        }
+
+        if let Some((ipv4, ipv6)) = dns::resolved_network() {
+            sampler.set_network(base4 || ipv4, base6 || ipv6);
+        }
+
+        sampler.refresh(args);
+        if buffer::due(sampler.gauges().latest_ts) {
+            buffer::push(sampler.to_stat());
+        }
+        let encoding = if args.json { Encoding::Json } else { Encoding::Protobuf };
+        let batch = buffer::take_batch();
+        if !batch.is_empty() {
+            tokio::spawn(http_replay(http_client.clone(), args.clone(), batch, encoding));
+        }
+        if !adaptive.should_report(sampler.gauges()) {
+            continue;
         }
+
+        let mut body_data = match (encoding, delta.diff(&sampler)) {
+            (_, Some(stat)) => Some(codec::encode(&stat, encoding)?),
+            (Encoding::Protobuf, None) => Some(sampler.encode_pb()),
+            (Encoding::Json, None) => Some(codec::encode(&sampler.to_stat(), encoding)?),
+        };
+        let compression = compression(args);
+        if let Some(c) = compression {
+            body_data = Some(codec::compress(&body_data.unwrap(), c)?);
+        }
+        let content_type = encoding.content_type();
         // byte 581, json str 1281
         // dbg!(&body_data.as_ref().unwrap().len());
 
         let client = http_client.clone();
-        let url = args.addr.to_string();
-        let auth_pass = args.pass.to_string();
-        let auth_user: String;
-        let ssr_auth: &str;
-        if args.gid.is_empty() {
-            auth_user = args.user.to_string();
-            ssr_auth = "single";
-        } else {
-            auth_user = args.gid.to_string();
-            ssr_auth = "group";
-        }
+        let url = failover::current();
+        let auth_pass = auth_pass(args);
+        let (auth_user, ssr_auth) = auth::auth_user(&args.user, &args.gid);
+        let auth_user = auth_user.to_string();
+        let sign = args.sign;
+        let traces = sampler.gauges().traces.clone();
 
         // http
-        tokio::spawn(async move {
-            match client
-                .post(&url)
-                .basic_auth(auth_user, Some(auth_pass))
+        inflight.push_back(tokio::spawn(async move {
+            let body_data = body_data.unwrap();
+            let req = client.post(&url);
+            let mut req = http_auth(req, &auth_user, &auth_pass, &body_data, sign)
                 .timeout(Duration::from_secs(3))
                 .header(header::CONTENT_TYPE, content_type)
-                .header("ssr-auth", ssr_auth)
-                .body(body_data.unwrap())
-                .send()
-                .await
-            {
+                .header(auth::SSR_AUTH, ssr_auth)
+                .body(body_data);
+            if let Some(c) = compression {
+                req = req.header(header::CONTENT_ENCODING, c.content_encoding());
+            }
+            match req.send().await {
                 Ok(resp) => {
                     info!("report resp => {:?}", resp);
+                    failover::report(&url, !resp.status().is_server_error());
+                    buffer::set_online(!resp.status().is_server_error());
+                    if resp.status().is_success() {
+                        trace::delivered(&traces);
+                    } else {
+                        delta::report_failed();
+                    }
+                    if let Ok(o) = resp.json::<serde_json::Value>().await {
+                        // 服务端缺少完整数据(如重启后), 增量被丢弃
+                        if o["need_full"].as_bool().unwrap_or(false) {
+                            delta::report_failed();
+                        }
+                        set_new_pass(o["new_password"].as_str().unwrap_or_default());
+                        set_report_interval(o["report_interval"].as_u64().unwrap_or(0));
+                    }
                 }
                 Err(err) => {
                     error!("report error => {:?}", err);
+                    failover::report(&url, false);
+                    buffer::set_online(false);
+                    delta::report_failed();
                 }
             }
-        });
-
-        thread::sleep(Duration::from_millis(INTERVAL_MS));
+        }));
     }
 }
 
@@ -283,6 +1177,7 @@ async fn refresh_ip_info(args: &Args) {
                 info!("refresh_ip_info succ => {:?}", ip_info);
                 if let Ok(mut o) = G_CONFIG.lock() {
                     o.ip_info = Some(ip_info);
+                    o.info_gen += 1;
                 }
             }
             Err(err) => {
@@ -296,10 +1191,11 @@ async fn refresh_ip_info(args: &Args) {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    pretty_env_logger::init();
-    let mut args = Args::parse();
-    args.iface.retain(|e| !e.trim().is_empty());
-    args.exclude_iface.retain(|e| !e.trim().is_empty());
+    let mut args = config::load_args();
+    diag::init_logger();
+    normalize_args(&mut args);
+    load_pass_file(&mut args);
+    args.addr = failover::init(&args.addr);
     if args.debug {
         dbg!(&args);
     }
@@ -323,25 +1219,98 @@ async fn main() -> Result<()> {
 
     if let Ok(mut o) = G_CONFIG.lock() {
         o.sys_info = Some(sys_info);
+        o.info_gen += 1;
     }
 
-    // use native
-    #[cfg(all(feature = "native", not(feature = "sysinfo")))]
-    {
-        eprintln!("enable feature native");
-        status::start_cpu_percent_collect_t();
-        status::start_net_speed_collect_t(&args);
+    let collector = select_collector(&args.collector);
+    eprintln!("use collector {:?}", collector);
+    match collector {
+        Collector::Native => {
+            status::start_cpu_percent_collect_t();
+            status::start_net_speed_collect_t(&args);
+            status::start_disk_io_collect_t();
+        }
+        Collector::Sysinfo => {
+            sys_info::start_cpu_percent_collect_t();
+            sys_info::start_net_speed_collect_t(&args);
+            sys_info::start_disk_io_collect_t();
+        }
     }
+    G_COLLECTOR.set(collector).ok();
 
-    // use sysinfo
-    #[cfg(all(feature = "sysinfo", not(feature = "native")))]
-    {
-        eprintln!("enable feature sysinfo");
-        sys_info::start_cpu_percent_collect_t();
-        sys_info::start_net_speed_collect_t();
+    if status::parse_probes(&args.probe).len() < args.probe.len() {
+        eprintln!("⚠️ invalid --probe ignored, eg: hk=1.1.1.1:443");
     }
-
     status::start_all_ping_collect_t(&args);
+    if "linux".eq(std::env::consts::OS) {
+        status::start_reboot_check_t();
+        status::start_ip_traffic_collect_t();
+    }
+    if args.gpu {
+        #[cfg(feature = "gpu")]
+        gpu::start_gpu_collect_t();
+        #[cfg(not(feature = "gpu"))]
+        eprintln!("⚠️ --gpu ignored, build with `--features gpu` to enable");
+    }
+    if args.ipmi {
+        #[cfg(feature = "ipmi")]
+        ipmi::start_ipmi_collect_t();
+        #[cfg(not(feature = "ipmi"))]
+        eprintln!("⚠️ --ipmi ignored, build with `--features ipmi` to enable");
+    }
+    if args.top_procs > 0 {
+        procs::start_top_procs_collect_t(args.top_procs);
+    }
+    if !args.watch_unit.is_empty() {
+        systemd::start_unit_collect_t(&args.watch_unit);
+    }
+    if args.zfs {
+        zfs::start_zfs_collect_t();
+    }
+    if args.nvme {
+        nvme::start_nvme_collect_t();
+    }
+    if !args.ntp.is_empty() {
+        ntp::start_ntp_collect_t(&args.ntp);
+    }
+    if args.power {
+        if "linux".eq(std::env::consts::OS) {
+            power::start_power_collect_t();
+        } else {
+            eprintln!("⚠️ --power ignored, linux only");
+            args.power = false;
+        }
+    }
+    if !args.check_port.is_empty() {
+        status::start_port_check_t(&args.check_port);
+    }
+    if !args.dns_probe.is_empty() {
+        dns::start_dns_probe_t(dns::parse_probes(&args.dns_probe));
+    }
+    if !args.http_check.is_empty() {
+        tokio::spawn(http_check::http_check_loop(args.http_check.clone()));
+    }
+    if !args.check_cert.is_empty() {
+        #[cfg(feature = "rustls")]
+        cert::start_cert_collect_t(&args.check_cert);
+        #[cfg(not(feature = "rustls"))]
+        eprintln!("⚠️ --check-cert ignored, build with `--features rustls` to enable");
+    }
+    let scripts = exec_metric::parse(&args.exec_metric);
+    if scripts.len() < args.exec_metric.len() {
+        eprintln!("⚠️ invalid --exec-metric ignored, eg: qps:/opt/qps.sh");
+    }
+    if !scripts.is_empty() {
+        exec_metric::start_exec_metric_collect_t(scripts, args.exec_interval.max(1));
+    }
+    if args.docker {
+        #[cfg(unix)]
+        docker::start_docker_collect_t(args.docker_stats);
+        #[cfg(not(unix))]
+        eprintln!("⚠️ --docker ignored, unix only");
+    }
+    failover::start_primary_check_t();
+    supervisor::start_supervisor_t();
     let (ipv4, ipv6) = status::get_network();
     eprintln!("get_network (ipv4, ipv6) => ({}, {})", ipv4, ipv6);
 
@@ -357,11 +1326,12 @@ async fn main() -> Result<()> {
         online4: ipv4,
         online6: ipv6,
         vnstat: args.vnstat,
-        weight: args.weight,
-        notify: true,
+        traffic_reset_day: args.traffic_reset_day,
         version: env!("CARGO_PKG_VERSION").to_string(),
+        proto_version: PROTO_VERSION,
         ..Default::default()
     };
+    apply_labels(&mut stat_base, &args);
     if !args.gid.is_empty() {
         stat_base.gid = args.gid.to_owned();
         if stat_base.name.eq("h1") {
@@ -369,27 +1339,35 @@ async fn main() -> Result<()> {
         }
         if args.alias.eq("unknown") {
             args.alias = stat_base.name.to_owned();
-        } else {
-            stat_base.alias = args.alias.to_owned();
         }
     }
-    if args.disable_notify {
-        stat_base.notify = false;
+    // dbg!(&stat_base);
+
+    if let Some(Command::Diag { upload, output }) = &args.command {
+        return diag::run(&args, &stat_base, *upload, output).await;
     }
-    if !args.host_type.is_empty() {
-        stat_base.r#type = args.host_type.to_owned();
+    diag::start_error_dump_t(&args);
+
+    if args.config.is_some() {
+        tokio::spawn(reload::watch(args.clone()));
     }
-    if !args.location.is_empty() {
-        stat_base.location = args.location.to_owned();
+    buffer::init(args.buffer_size, &args.buffer_file);
+
+    if args.splay > 0 {
+        let delay = jitter(args.splay * 1000);
+        eprintln!("splay {}ms before report", delay);
+        time::sleep(Duration::from_millis(delay)).await;
     }
-    // dbg!(&stat_base);
 
     if args.addr.starts_with("http") {
-        let result = http_report(&args, &mut stat_base);
+        let result = http_report(&args, &stat_base).await;
         dbg!(&result);
     } else if args.addr.starts_with("grpc") {
         let result = grpc::report(&args, &mut stat_base).await;
         dbg!(&result);
+    } else if args.addr.starts_with("mqtt") {
+        let result = mqtt::report(&args, &stat_base).await;
+        dbg!(&result);
     } else {
         eprint!("invalid addr scheme!");
     }
@@ -0,0 +1,170 @@
+// mqtt 上报: mqtt://[user:pass@]host[:port][/topic], 经由 broker 转发, 服务端订阅 [mqtt] report_topic
+// broker 账号在地址中, 服务端鉴权信息在消息体前缀(见 stat_common::auth)
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time;
+
+use stat_common::auth;
+use stat_common::codec::{self, Encoding};
+use stat_common::server_status::StatRequest;
+
+use crate::adaptive::Adaptive;
+use crate::buffer;
+use crate::failover;
+use crate::trace;
+use crate::Args;
+use crate::{auth_pass, jitter, report_interval, Sampler};
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_TOPIC_PREFIX: &str = "serverstatus/report";
+const BACKOFF_MIN_MS: u64 = 1000;
+const BACKOFF_MAX_MS: u64 = 60_000;
+
+#[derive(Debug, Default)]
+struct Target {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+    topic: String,
+}
+
+fn parse_addr(addr: &str, auth_user: &str) -> anyhow::Result<Target> {
+    let rest = addr
+        .strip_prefix("mqtt://")
+        .ok_or_else(|| anyhow::anyhow!("invalid mqtt addr `{}`", addr))?;
+    let (authority, topic) = rest.split_once('/').unwrap_or((rest, ""));
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (userinfo, host_port),
+        None => ("", authority),
+    };
+    let (username, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !host_port.ends_with(']') => (host, port.parse()?),
+        _ => (host_port, DEFAULT_PORT),
+    };
+    if host.is_empty() {
+        anyhow::bail!("invalid mqtt addr `{}`", addr);
+    }
+
+    Ok(Target {
+        host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+        port,
+        username: username.to_string(),
+        password: password.to_string(),
+        topic: match topic.trim_matches('/') {
+            "" => format!("{}/{}", DEFAULT_TOPIC_PREFIX, auth_user),
+            o => o.to_string(),
+        },
+    })
+}
+
+// 仅放入发送队列, 队列满视为失败
+fn publish(client: &AsyncClient, topic: &str, body: &[u8], args: &Args) -> anyhow::Result<()> {
+    let (auth_user, ssr_auth) = auth::auth_user(&args.user, &args.gid);
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let payload = auth::mqtt_payload(ssr_auth, auth_user, &auth_pass(args), ts, body);
+    client.try_publish(topic, QoS::AtLeastOnce, false, payload)?;
+    Ok(())
+}
+
+pub async fn report(args: &Args, stat_base: &StatRequest) -> anyhow::Result<()> {
+    let (auth_user, _) = auth::auth_user(&args.user, &args.gid);
+    let encoding = if args.json { Encoding::Json } else { Encoding::Protobuf };
+
+    let mut sampler = Sampler::new(stat_base.clone());
+    let mut adaptive = Adaptive::new(args);
+    loop {
+        let addr = failover::current();
+        let target = parse_addr(&addr, auth_user)?;
+        let mut opts = MqttOptions::new(format!("stat_client_{}", stat_base.name), &target.host, target.port);
+        opts.set_keep_alive(Duration::from_secs(30));
+        if !target.username.is_empty() {
+            opts.set_credentials(&target.username, &target.password);
+        }
+        let (client, mut eventloop) = AsyncClient::new(opts, 64);
+        eprintln!("mqtt publish => {}:{}/{}", target.host, target.port, target.topic);
+
+        // eventloop, 断线后退避重连, 连接状态用于缓存/故障转移
+        let online = Arc::new(AtomicBool::new(false));
+        let poll_online = online.clone();
+        let poll_addr = addr.to_string();
+        let poll_t = tokio::spawn(async move {
+            let mut backoff = BACKOFF_MIN_MS;
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("mqtt connected => {}", poll_addr);
+                        backoff = BACKOFF_MIN_MS;
+                        poll_online.store(true, Ordering::Relaxed);
+                        failover::report(&poll_addr, true);
+                        buffer::set_online(true);
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        poll_online.store(false, Ordering::Relaxed);
+                        failover::report(&poll_addr, false);
+                        buffer::set_online(false);
+                        let delay = backoff + jitter(backoff / 2);
+                        error!("mqtt eventloop error => {:?}, retry in {}ms", err, delay);
+                        time::sleep(Duration::from_millis(delay)).await;
+                        backoff = (backoff * 2).min(BACKOFF_MAX_MS);
+                    }
+                }
+            }
+        });
+
+        let mut interval = report_interval(&stat_base.name);
+        loop {
+            interval.tick().await;
+            // 已切换地址(如主地址恢复)
+            if failover::current() != addr {
+                break;
+            }
+
+            sampler.refresh(args);
+            if buffer::due(sampler.gauges().latest_ts) {
+                buffer::push(sampler.to_stat());
+            }
+            if !online.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let batch = buffer::take_batch();
+            if !batch.is_empty() {
+                // 补发离线缓存, 失败时放回队列
+                let mut failed = Vec::new();
+                for stat in batch {
+                    if !failed.is_empty() {
+                        failed.push(stat);
+                        continue;
+                    }
+                    let result =
+                        codec::encode(&stat, encoding).and_then(|body| publish(&client, &target.topic, &body, args));
+                    if let Err(err) = result {
+                        error!("mqtt replay error => {:?}", err);
+                        failed.push(stat);
+                    }
+                }
+                buffer::replay_done(failed);
+            }
+            if !adaptive.should_report(sampler.gauges()) {
+                continue;
+            }
+
+            let body = match encoding {
+                Encoding::Protobuf => sampler.encode_pb(),
+                Encoding::Json => codec::encode(&sampler.to_stat(), encoding)?,
+            };
+            match publish(&client, &target.topic, &body, args) {
+                Ok(_) => trace::delivered(&sampler.gauges().traces),
+                Err(err) => error!("mqtt publish error => {:?}", err),
+            }
+        }
+
+        poll_t.abort();
+        info!("mqtt switch to {}", failover::current());
+    }
+}
@@ -0,0 +1,74 @@
+// 轻量 SNTP 查询, 计算本机时钟与 NTP 服务器的偏差
+use lazy_static::lazy_static;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::supervisor;
+use crate::Result;
+
+const SAMPLE_PERIOD: u64 = 600_000; //ms
+const TIMEOUT_MS: u64 = 3000;
+// 1900-01-01 到 1970-01-01 的秒数
+const NTP_EPOCH_OFFSET: f64 = 2_208_988_800.0;
+
+lazy_static! {
+    // 本机时钟偏差 ms, 正数为本机慢于 NTP 服务器
+    pub static ref G_NTP_OFFSET: Arc<Mutex<Option<i64>>> = Arc::new(Default::default());
+}
+
+fn now_secs() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()
+}
+
+fn read_ts(buf: &[u8]) -> f64 {
+    let secs = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64;
+    let frac = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as f64 / 4_294_967_296.0;
+    secs + frac - NTP_EPOCH_OFFSET
+}
+
+pub fn query_offset(server: &str) -> Result<i64> {
+    let addr = if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{}:123", server)
+    };
+    let addr = addr.to_socket_addrs()?.next().ok_or("resolve ntp server fail")?;
+    let socket = UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" })?;
+    socket.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS)))?;
+    socket.connect(addr)?;
+
+    // LI = 0, VN = 3, Mode = 3 (client)
+    let mut buf = [0_u8; 48];
+    buf[0] = 0x1b;
+    let t1 = now_secs();
+    socket.send(&buf)?;
+    let n = socket.recv(&mut buf)?;
+    let t4 = now_secs();
+    if n < 48 {
+        return Err("invalid ntp response".into());
+    }
+    // receive / transmit timestamp
+    let t2 = read_ts(&buf[32..40]);
+    let t3 = read_ts(&buf[40..48]);
+    if t3 <= 0.0 {
+        return Err("ntp server not synchronized".into());
+    }
+    Ok((((t2 - t1) + (t3 - t4)) / 2.0 * 1000.0).round() as i64)
+}
+
+pub fn start_ntp_collect_t(server: &str) {
+    let server = server.to_string();
+    supervisor::spawn("ntp", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let server = server.clone();
+        Box::new(move || {
+            let offset = query_offset(&server)
+                .map_err(|err| warn!("ntp query {} error => {:?}", server, err))
+                .ok();
+            if let Ok(mut o) = G_NTP_OFFSET.lock() {
+                *o = offset;
+            }
+        })
+    });
+}
@@ -0,0 +1,84 @@
+// nvme 盘温度/寿命, 温度读取 sysfs hwmon, 寿命及温控降速时间需要 nvme-cli
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use crate::Result;
+use stat_common::server_status::NvmeDrive;
+
+const SAMPLE_PERIOD: u64 = 60000; //ms
+
+lazy_static! {
+    pub static ref G_NVME: Arc<Mutex<Vec<NvmeDrive>>> = Arc::new(Default::default());
+}
+
+// 内核 >= 5.5 的 nvme 控制器带 hwmon, temp1 为综合温度
+fn hwmon_temp(dir: &Path) -> Option<f64> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("hwmon"))
+        .find_map(|e| fs::read_to_string(e.path().join("temp1_input")).ok())
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .map(|v| v / 1000.0)
+}
+
+fn smart_log(name: &str) -> Result<Value> {
+    let output = Command::new("nvme")
+        .args(["smart-log", &format!("/dev/{}", name), "-o", "json"])
+        .output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn collect() -> Vec<NvmeDrive> {
+    let mut dirs = fs::read_dir("/sys/class/nvme")
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs.iter()
+        .map(|dir| {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let model = fs::read_to_string(dir.join("model")).unwrap_or_default();
+            let mut drive = NvmeDrive {
+                model: model.trim().to_string(),
+                temperature: hwmon_temp(dir).unwrap_or(0.0),
+                ..Default::default()
+            };
+            match smart_log(&name) {
+                Ok(log) => {
+                    let field = |key: &str| log[key].as_u64().unwrap_or(0);
+                    // smart-log 温度单位为开尔文
+                    if drive.temperature == 0.0 && field("temperature") > 0 {
+                        drive.temperature = field("temperature") as f64 - 273.0;
+                    }
+                    drive.percent_used = field("percent_used") as u32;
+                    drive.critical_warning = field("critical_warning") as u32;
+                    drive.throttle_time = field("thm_temp1_total_time") + field("thm_temp2_total_time");
+                }
+                Err(err) => debug!("nvme smart-log {} error => {:?}", name, err),
+            }
+            drive.name = name;
+            drive
+        })
+        .collect()
+}
+
+pub fn start_nvme_collect_t() {
+    supervisor::spawn("nvme", Duration::from_millis(SAMPLE_PERIOD), || {
+        Box::new(|| {
+            let drives = collect();
+            if let Ok(mut o) = G_NVME.lock() {
+                *o = drives;
+            }
+        })
+    });
+}
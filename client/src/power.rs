@@ -0,0 +1,107 @@
+// cpu 封装功耗, 读取 powercap(intel-rapl, 新内核的 amd 也使用)或 amd_energy 的累计能量计数
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::supervisor;
+
+const SAMPLE_PERIOD: u64 = 5000; //ms
+
+lazy_static! {
+    // 所有封装合计 W, 无法读取或尚无两次采样时为 None, 不上报
+    pub static ref G_POWER: Arc<Mutex<Option<f64>>> = Arc::new(Default::default());
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse::<u64>().ok()
+}
+
+// (计数文件 uJ, 回绕上限 uJ)
+fn energy_counters() -> Vec<(PathBuf, u64)> {
+    // intel-rapl:0, intel-rapl:1 为各封装, intel-rapl:0:0 等子域已包含在内
+    let mut counters = fs::read_dir("/sys/class/powercap")
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    let name = p.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    name.starts_with("intel-rapl:") && name.matches(':').count() == 1
+                })
+                .filter_map(|p| Some((p.join("energy_uj"), read_u64(&p.join("max_energy_range_uj"))?)))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !counters.is_empty() {
+        return counters;
+    }
+    // amd_energy 驱动, Esocket* 为封装计数, 64 位不回绕
+    let hwmons = fs::read_dir("/sys/class/hwmon")
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for dir in hwmons {
+        if fs::read_to_string(dir.join("name")).unwrap_or_default().trim() != "amd_energy" {
+            continue;
+        }
+        for idx in 1.. {
+            let label = match fs::read_to_string(dir.join(format!("energy{}_label", idx))) {
+                Ok(label) => label,
+                Err(_) => break,
+            };
+            if label.starts_with("Esocket") {
+                counters.push((dir.join(format!("energy{}_input", idx)), u64::MAX));
+            }
+        }
+    }
+    counters
+}
+
+pub fn start_power_collect_t() {
+    supervisor::spawn("power", Duration::from_millis(SAMPLE_PERIOD), || {
+        let counters = energy_counters();
+        if counters.is_empty() {
+            warn!("no rapl/amd_energy counter found, need root to read energy_uj");
+        }
+        let mut last: Option<(Instant, Vec<u64>)> = None;
+        Box::new(move || {
+            // 任一计数不可读(如权限变化)时整体不上报, 避免少算的功耗
+            let values = counters
+                .iter()
+                .map(|(path, _)| read_u64(path))
+                .collect::<Option<Vec<_>>>();
+            let values = match values {
+                Some(values) if !values.is_empty() => values,
+                _ => {
+                    last = None;
+                    if let Ok(mut o) = G_POWER.lock() {
+                        *o = None;
+                    }
+                    return;
+                }
+            };
+            let now = Instant::now();
+            if let Some((ts, prev)) = last.as_ref() {
+                let secs = now.duration_since(*ts).as_secs_f64();
+                let uj = counters
+                    .iter()
+                    .zip(values.iter().zip(prev.iter()))
+                    .map(|((_, range), (&cur, &prev))| {
+                        if cur >= prev {
+                            cur - prev
+                        } else {
+                            range.saturating_sub(prev) + cur
+                        }
+                    })
+                    .sum::<u64>();
+                if secs > 0.0 {
+                    if let Ok(mut o) = G_POWER.lock() {
+                        *o = Some((uj as f64 / 1e6 / secs * 10.0).round() / 10.0);
+                    }
+                }
+            }
+            last = Some((now, values));
+        })
+    });
+}
@@ -0,0 +1,51 @@
+// 按 cpu/内存排序的前 N 个进程, 两种采集器共用 sysinfo 实现
+use lazy_static::lazy_static;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use sysinfo::{PidExt, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+use crate::supervisor;
+use stat_common::server_status::Process;
+
+// cpu 使用率为两次刷新间的平均值
+const SAMPLE_PERIOD: u64 = 5000; //ms
+
+#[derive(Debug, Default)]
+pub struct TopProcs {
+    pub cpu: Vec<Process>,
+    pub memory: Vec<Process>,
+}
+
+lazy_static! {
+    pub static ref G_TOP_PROCS: Arc<Mutex<TopProcs>> = Arc::new(Default::default());
+}
+
+pub fn start_top_procs_collect_t(n: usize) {
+    supervisor::spawn("procs", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu());
+        Box::new(move || {
+            sys.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu());
+            let mut procs = sys
+                .processes()
+                .values()
+                .map(|p| Process {
+                    pid: p.pid().as_u32(),
+                    name: p.name().to_string(),
+                    cpu: (p.cpu_usage() as f64 * 10.0).round() / 10.0,
+                    // KB -> KiB
+                    memory: p.memory() * 1000 / 1024,
+                })
+                .collect::<Vec<_>>();
+            procs.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+            let cpu = procs.iter().take(n).cloned().collect();
+            procs.sort_by(|a, b| b.memory.cmp(&a.memory));
+            procs.truncate(n);
+            if let Ok(mut o) = G_TOP_PROCS.lock() {
+                o.cpu = cpu;
+                o.memory = procs;
+            }
+        })
+    });
+}
@@ -0,0 +1,118 @@
+// 上报代理, --proxy 或环境变量 HTTPS_PROXY/ALL_PROXY, 支持 http:// 与 socks5:// (socks5h:// 由代理解析域名)
+// http 上报交给 reqwest, grpc 上报通过自定义 connector 建立隧道(http CONNECT / socks5)
+use anyhow::Result;
+use std::env;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use tonic::transport::{Channel, Endpoint, Uri};
+
+use crate::Args;
+
+const ENV_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+// CONNECT 响应头上限
+const MAX_RESP_HEADER: usize = 8192;
+
+fn proxy_url(args: &Args) -> Option<String> {
+    if !args.proxy.is_empty() {
+        return Some(args.proxy.to_string());
+    }
+    ENV_VARS
+        .iter()
+        .filter_map(|o| env::var(o).ok())
+        .find(|o| !o.trim().is_empty())
+}
+
+// reqwest 默认已读取环境变量
+pub fn http_builder(builder: reqwest::ClientBuilder, args: &Args) -> Result<reqwest::ClientBuilder> {
+    if args.proxy.is_empty() {
+        return Ok(builder);
+    }
+    Ok(builder.proxy(reqwest::Proxy::all(&args.proxy)?))
+}
+
+async fn http_connect(proxy: &reqwest::Url, host: &str, port: u16) -> Result<TcpStream> {
+    let proxy_host = proxy.host_str().unwrap_or_default();
+    let mut stream = TcpStream::connect((proxy_host, proxy.port_or_known_default().unwrap_or(80))).await?;
+    let mut req = format!("CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n", host, port);
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or_default());
+        req.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::encode(credentials)
+        ));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await?;
+
+    // 逐字节读到空行, 不多读隧道数据
+    let mut resp = Vec::new();
+    while !resp.ends_with(b"\r\n\r\n") {
+        if resp.len() >= MAX_RESP_HEADER {
+            anyhow::bail!("proxy response header too large");
+        }
+        let mut byte = [0_u8; 1];
+        if stream.read(&mut byte).await? == 0 {
+            anyhow::bail!("proxy closed connection");
+        }
+        resp.push(byte[0]);
+    }
+    let status_line = String::from_utf8_lossy(&resp);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        anyhow::bail!("proxy CONNECT {}:{} => {}", host, port, status_line);
+    }
+    Ok(stream)
+}
+
+async fn socks5_connect(proxy: &reqwest::Url, host: &str, port: u16) -> Result<TcpStream> {
+    let proxy_addr = (proxy.host_str().unwrap_or_default(), proxy.port().unwrap_or(1080));
+    // socks5:// 本地解析, socks5h:// 由代理解析
+    let target = if proxy.scheme() == "socks5h" {
+        (host.to_string(), port)
+    } else {
+        let addr = tokio::net::lookup_host((host, port))
+            .await?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("resolve {} failed", host))?;
+        (addr.ip().to_string(), port)
+    };
+    let target = (target.0.as_str(), target.1);
+    let stream = if proxy.username().is_empty() {
+        Socks5Stream::connect(proxy_addr, target).await?
+    } else {
+        let password = proxy.password().unwrap_or_default();
+        Socks5Stream::connect_with_password(proxy_addr, target, proxy.username(), password).await?
+    };
+    Ok(stream.into_inner())
+}
+
+async fn connect(proxy: reqwest::Url, uri: Uri) -> Result<TcpStream> {
+    let host = uri
+        .host()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+    match proxy.scheme() {
+        "http" | "https" => http_connect(&proxy, host, port).await,
+        "socks5" | "socks5h" => socks5_connect(&proxy, host, port).await,
+        scheme => anyhow::bail!("unsupported proxy scheme `{}`", scheme),
+    }
+}
+
+pub async fn grpc_connect(endpoint: Endpoint, args: &Args) -> Result<Channel> {
+    let proxy = match proxy_url(args) {
+        Some(o) => reqwest::Url::parse(&o)?,
+        None => return Ok(endpoint.connect().await?),
+    };
+    info!(
+        "grpc connect via proxy {}://{}",
+        proxy.scheme(),
+        proxy.host_str().unwrap_or_default()
+    );
+    let connector = tower::service_fn(move |uri: Uri| connect(proxy.clone(), uri));
+    Ok(endpoint.connect_with_connector(connector).await?)
+}
@@ -0,0 +1,77 @@
+// 配置热重载: unix 收到 SIGHUP, windows 命名事件 Global\stat_client_reload 被置位时重新读取 --config
+// 仅探测目标及参数(--probe/--cu/--ct/--cm/--icmp/--probe-interval/--probe-window)
+// 和展示信息(--alias/--location/--type/--weight/--extra/--disable-notify)即时生效, 其他参数需重启
+use crate::{config, status, Args};
+
+fn reload(cur: &mut Args) {
+    let mut args = match config::reload_args() {
+        Ok(args) => args,
+        Err(err) => {
+            error!("reload config error => {}", err);
+            return;
+        }
+    };
+    crate::normalize_args(&mut args);
+    if args.disable_ping != cur.disable_ping {
+        warn!("--disable-ping changed, restart to take effect");
+        args.disable_ping = cur.disable_ping;
+    }
+    info!("reload config => {}", args.config.as_deref().unwrap_or_default());
+    status::reload_ping_collect_t(cur, &args);
+    crate::set_reloaded_args(&args);
+    *cur = args;
+}
+
+#[cfg(unix)]
+pub async fn watch(mut args: Args) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hup = match signal(SignalKind::hangup()) {
+        Ok(hup) => hup,
+        Err(err) => {
+            error!("listen SIGHUP error => {:?}", err);
+            return;
+        }
+    };
+    while hup.recv().await.is_some() {
+        reload(&mut args);
+    }
+}
+
+#[cfg(windows)]
+pub async fn watch(mut args: Args) {
+    use windows_sys::Win32::Foundation::WAIT_OBJECT_0;
+    use windows_sys::Win32::System::Threading::{CreateEventW, WaitForSingleObject};
+
+    // 自动复位事件, Global 需要 SeCreateGlobalPrivilege(服务默认具备), 失败时使用会话内名字
+    let event = ["Global\\stat_client_reload", "stat_client_reload"]
+        .iter()
+        .map(|name| {
+            let name = name.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+            unsafe { CreateEventW(std::ptr::null(), 0, 0, name.as_ptr()) }
+        })
+        .find(|&event| event != 0);
+    let event = match event {
+        Some(event) => event,
+        None => {
+            error!("create reload event error => {:?}", std::io::Error::last_os_error());
+            return;
+        }
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    std::thread::spawn(move || {
+        // INFINITE
+        while unsafe { WaitForSingleObject(event, u32::MAX) } == WAIT_OBJECT_0 {
+            if tx.blocking_send(()).is_err() {
+                break;
+            }
+        }
+    });
+    while rx.recv().await.is_some() {
+        reload(&mut args);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn watch(_args: Args) {}
@@ -1,17 +1,18 @@
 // #![allow(unused)]
-use chrono::{Datelike, Local};
+use chrono::{Datelike, Local, NaiveDate};
 use lazy_static::lazy_static;
 use once_cell::sync::OnceCell;
 use regex::Regex;
 use std::collections::HashMap;
 use std::collections::LinkedList;
+use std::collections::VecDeque;
 use std::fs;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::ErrorKind::ConnectionRefused;
 use std::net::TcpStream;
-use std::net::{Shutdown, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, ToSocketAddrs, UdpSocket};
 use std::process::Command;
 use std::str;
 use std::sync::Arc;
@@ -19,9 +20,16 @@ use std::sync::Mutex;
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::skip_iface;
+use crate::icmp;
+use crate::supervisor;
+use crate::trace;
+use crate::udp;
 use crate::Args;
-use stat_common::server_status::StatRequest;
+use crate::{skip_disk, skip_iface};
+use stat_common::server_status::{
+    DiskUsage, MemoryDetail, PortState, Pressure, ProbeResult, RaidArray, StatRequest, Temperature,
+};
+use stat_common::RTT_HISTOGRAM_BOUNDS;
 
 const SAMPLE_PERIOD: u64 = 1000; //ms
 const TIMEOUT_MS: u64 = 1000;
@@ -61,7 +69,7 @@ static MEMORY_REGEX: &str = r#"^(?P<key>\S*):\s*(?P<value>\d*)\s*kB"#;
 lazy_static! {
     static ref MEMORY_REGEX_RE: Regex = Regex::new(MEMORY_REGEX).unwrap();
 }
-pub fn get_memory() -> (u64, u64, u64, u64) {
+fn read_meminfo() -> HashMap<String, u64> {
     let file = File::open("/proc/meminfo").unwrap();
     let buf_reader = BufReader::new(file);
     let mut res_dict = HashMap::new();
@@ -71,6 +79,29 @@ pub fn get_memory() -> (u64, u64, u64, u64) {
             res_dict.insert(caps["key"].to_string(), caps["value"].parse::<u64>().unwrap());
         };
     }
+    res_dict
+}
+
+// HugePages_* 为页数, 其余为 KiB
+fn memory_detail(res_dict: &HashMap<String, u64>) -> MemoryDetail {
+    let get = |k: &str| res_dict.get(k).copied().unwrap_or(0);
+    MemoryDetail {
+        available: get("MemAvailable"),
+        cached: get("Cached") + get("SReclaimable"),
+        buffers: get("Buffers"),
+        shared: get("Shmem"),
+        hugepages_total: get("HugePages_Total"),
+        hugepages_free: get("HugePages_Free"),
+        hugepage_size: get("Hugepagesize"),
+    }
+}
+
+pub fn get_memory_detail() -> MemoryDetail {
+    memory_detail(&read_meminfo())
+}
+
+pub fn get_memory() -> (u64, u64, u64, u64, MemoryDetail) {
+    let res_dict = read_meminfo();
 
     let mem_total = res_dict["MemTotal"];
     let swap_total = res_dict["SwapTotal"];
@@ -79,7 +110,7 @@ pub fn get_memory() -> (u64, u64, u64, u64) {
     let mem_used =
         mem_total - res_dict["MemFree"] - res_dict["Buffers"] - res_dict["Cached"] - res_dict["SReclaimable"];
 
-    (mem_total, mem_used, swap_total, swap_free)
+    (mem_total, mem_used, swap_total, swap_free, memory_detail(&res_dict))
 }
 
 macro_rules! exec_shell_cmd_fetch_u32 {
@@ -102,11 +133,214 @@ pub fn tupd() -> (u32, u32, u32, u32) {
     (t, u, p, d)
 }
 
+// 系统 fd (已分配, 上限), /proc/sys/fs/file-nr: allocated unused max
+pub fn get_file_nr() -> (u64, u64) {
+    let contents = fs::read_to_string("/proc/sys/fs/file-nr").unwrap_or_default();
+    let v: Vec<u64> = contents
+        .split_whitespace()
+        .map(|s| s.parse::<u64>().unwrap_or(0))
+        .collect();
+    match v[..] {
+        [allocated, unused, max] => (allocated.saturating_sub(unused), max),
+        _ => (0, 0),
+    }
+}
+
+// 客户端自身 fd (已打开, soft limit)
+pub fn get_self_fds() -> (u64, u64) {
+    let open = fs::read_dir("/proc/self/fd").map(|rd| rd.count() as u64).unwrap_or(0);
+    // Max open files            1024                 524288               files
+    let limit = fs::read_to_string("/proc/self/limits")
+        .unwrap_or_default()
+        .lines()
+        .find(|l| l.starts_with("Max open files"))
+        .and_then(|l| {
+            l.trim_start_matches("Max open files")
+                .split_whitespace()
+                .next()?
+                .parse::<u64>()
+                .ok()
+        })
+        .unwrap_or(0);
+    (open, limit)
+}
+
+// (当前, 上限), 未加载 nf_conntrack 模块时为 0
+pub fn get_conntrack() -> (u64, u64) {
+    let read = |name: &str| {
+        fs::read_to_string(format!("/proc/sys/net/netfilter/{}", name))
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    (read("nf_conntrack_count"), read("nf_conntrack_max"))
+}
+
+// /proc/net/snmp 的 Tcp 段为表头行+数值行
+fn snmp_value(contents: &str, section: &str, key: &str) -> u64 {
+    let mut rows = contents.lines().filter(|l| l.starts_with(section));
+    let (head, values) = match (rows.next(), rows.next()) {
+        (Some(head), Some(values)) => (head, values),
+        _ => return 0,
+    };
+    head.split_whitespace()
+        .zip(values.split_whitespace())
+        .find(|(k, _)| k.eq(&key))
+        .and_then(|(_, v)| v.parse::<u64>().ok())
+        .unwrap_or(0)
+}
+
+// /proc/net/snmp6 每行一个 key value
+fn snmp6_value(contents: &str, key: &str) -> u64 {
+    contents
+        .lines()
+        .find_map(|l| {
+            let mut iter = l.split_whitespace();
+            if iter.next()? != key {
+                return None;
+            }
+            iter.next()?.parse::<u64>().ok()
+        })
+        .unwrap_or(0)
+}
+
+// /proc/net/sockstat 形如 TCP: inuse 5 orphan 0 tw 2 alloc 8 mem 1
+fn sockstat_value(contents: &str, section: &str, key: &str) -> u32 {
+    contents
+        .lines()
+        .find(|l| l.starts_with(section))
+        .and_then(|l| {
+            let v: Vec<&str> = l.split_whitespace().collect();
+            let idx = v.iter().position(|o| o.eq(&key))?;
+            v.get(idx + 1)?.parse::<u32>().ok()
+        })
+        .unwrap_or(0)
+}
+
+// /proc/pressure/cpu 形如 some avg10=0.00 avg60=0.00 avg300=0.00 total=0, 内核 >= 4.20
+pub fn get_pressure(resource: &str) -> Option<Pressure> {
+    let contents = fs::read_to_string(format!("/proc/pressure/{}", resource)).ok()?;
+    let avg = |kind: &str, key: &str| {
+        contents
+            .lines()
+            .find(|l| l.starts_with(kind))
+            .and_then(|l| l.split_whitespace().find_map(|kv| kv.strip_prefix(key)))
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(0.0)
+    };
+    Some(Pressure {
+        some_avg10: avg("some", "avg10="),
+        some_avg60: avg("some", "avg60="),
+        full_avg10: avg("full", "avg10="),
+        full_avg60: avg("full", "avg60="),
+    })
+}
+
+// (tcp established, tcp time_wait, udp sockets), 读取内核计数, 连接数很多时也不需要遍历
+pub fn get_sockets() -> (u32, u32, u32) {
+    let snmp = fs::read_to_string("/proc/net/snmp").unwrap_or_default();
+    let sockstat = fs::read_to_string("/proc/net/sockstat").unwrap_or_default();
+    let sockstat6 = fs::read_to_string("/proc/net/sockstat6").unwrap_or_default();
+    (
+        snmp_value(&snmp, "Tcp:", "CurrEstab") as u32,
+        sockstat_value(&sockstat, "TCP:", "tw"),
+        sockstat_value(&sockstat, "UDP:", "inuse") + sockstat_value(&sockstat6, "UDP6:", "inuse"),
+    )
+}
+
+// (v4 in, v4 out, v6 in, v6 out) ip 层字节数, 内核只有全局计数, 扣除 lo 后为所有网卡合计
+pub fn get_ip_octets() -> (u64, u64, u64, u64) {
+    let netstat = fs::read_to_string("/proc/net/netstat").unwrap_or_default();
+    let snmp6 = fs::read_to_string("/proc/net/snmp6").unwrap_or_default();
+    let lo6 = fs::read_to_string("/proc/net/dev_snmp6/lo").unwrap_or_default();
+    let (lo6_in, lo6_out) = (snmp6_value(&lo6, "Ip6InOctets"), snmp6_value(&lo6, "Ip6OutOctets"));
+    // lo 的字节数含 14 字节以太网头
+    let (mut lo_in, mut lo_out) = (0, 0);
+    if let Ok(dev) = fs::read_to_string("/proc/net/dev") {
+        if let Some(v) = dev.lines().find_map(|l| l.trim_start().strip_prefix("lo:")) {
+            let v: Vec<u64> = v.split_whitespace().map(|o| o.parse::<u64>().unwrap_or(0)).collect();
+            if v.len() > 9 {
+                lo_in = v[0].saturating_sub(v[1] * 14);
+                lo_out = v[8].saturating_sub(v[9] * 14);
+            }
+        }
+    }
+    (
+        snmp_value(&netstat, "IpExt:", "InOctets").saturating_sub(lo_in.saturating_sub(lo6_in)),
+        snmp_value(&netstat, "IpExt:", "OutOctets").saturating_sub(lo_out.saturating_sub(lo6_out)),
+        snmp6_value(&snmp6, "Ip6InOctets").saturating_sub(lo6_in),
+        snmp6_value(&snmp6, "Ip6OutOctets").saturating_sub(lo6_out),
+    )
+}
+
+#[derive(Debug, Default)]
+pub struct IpTraffic {
+    pub clock: f64,
+    // 累计字节
+    pub in_v4: u64,
+    pub out_v4: u64,
+    pub in_v6: u64,
+    pub out_v6: u64,
+    // 字节/秒
+    pub rx_v4: u64,
+    pub tx_v4: u64,
+    pub rx_v6: u64,
+    pub tx_v6: u64,
+}
+
+lazy_static! {
+    pub static ref G_IP_TRAFFIC: Arc<Mutex<IpTraffic>> = Arc::new(Default::default());
+}
+
+pub fn start_ip_traffic_collect_t() {
+    supervisor::spawn("ip_traffic", Duration::from_millis(SAMPLE_PERIOD), || {
+        Box::new(|| {
+            let (in_v4, out_v4, in_v6, out_v6) = get_ip_octets();
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+            if let Ok(mut t) = G_IP_TRAFFIC.lock() {
+                let diff = now - t.clock;
+                if t.clock > 0.0 && diff > 0.0 {
+                    t.rx_v4 = (in_v4.saturating_sub(t.in_v4) as f64 / diff) as u64;
+                    t.tx_v4 = (out_v4.saturating_sub(t.out_v4) as f64 / diff) as u64;
+                    t.rx_v6 = (in_v6.saturating_sub(t.in_v6) as f64 / diff) as u64;
+                    t.tx_v6 = (out_v6.saturating_sub(t.out_v6) as f64 / diff) as u64;
+                }
+                t.clock = now;
+                (t.in_v4, t.out_v4, t.in_v6, t.out_v6) = (in_v4, out_v4, in_v6, out_v6);
+            }
+        })
+    });
+}
+
+// 本计费周期的起始日, 当月无该日(如 31 号)时取月末
+fn billing_start(today: NaiveDate, reset_day: u32) -> NaiveDate {
+    let reset_date = |year: i32, month: u32| {
+        (1..=reset_day)
+            .rev()
+            .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .unwrap()
+    };
+    let (year, month) = (today.year(), today.month());
+    let start = reset_date(year, month);
+    if today >= start {
+        return start;
+    }
+    if month == 1 {
+        reset_date(year - 1, 12)
+    } else {
+        reset_date(year, month - 1)
+    }
+}
+
 pub fn get_vnstat_traffic(args: &Args) -> (u64, u64, u64, u64) {
     let local_now = Local::now();
     let (mut network_in, mut network_out, mut m_network_in, mut m_network_out) = (0, 0, 0, 0);
+    // 非 1 号重置时按天累加本周期流量
+    let by_day = args.traffic_reset_day > 1;
+    let mode = if by_day { "day" } else { "month" };
+    let start = billing_start(local_now.date_naive(), args.traffic_reset_day.max(1));
     let a = Command::new("/usr/bin/vnstat")
-        .args(&["--json", "m"])
+        .args(&["--json", &mode[..1]])
         .output()
         .expect("failed to execute vnstat")
         .stdout;
@@ -121,14 +355,19 @@ pub fn get_vnstat_traffic(args: &Args) -> (u64, u64, u64, u64) {
         }
 
         let total_o = iface["traffic"]["total"].as_object().unwrap();
-        let month_v = iface["traffic"]["month"].as_array().unwrap();
+        let month_v = iface["traffic"][mode].as_array().unwrap();
         network_in += total_o["rx"].as_u64().unwrap();
         network_out += total_o["tx"].as_u64().unwrap();
 
         for data in month_v {
             let year = data["date"]["year"].as_i64().unwrap() as i32;
             let month = data["date"]["month"].as_i64().unwrap() as u32;
-            if local_now.year() != year || local_now.month() != month {
+            if by_day {
+                let day = data["date"]["day"].as_i64().unwrap() as u32;
+                if NaiveDate::from_ymd_opt(year, month, day).map_or(true, |d| d < start) {
+                    continue;
+                }
+            } else if local_now.year() != year || local_now.month() != month {
                 continue;
             }
 
@@ -173,25 +412,60 @@ pub fn get_sys_traffic(args: &Args) -> (u64, u64) {
     (network_in, network_out)
 }
 
-static DF_CMD:&str = "df -Tlm --total -t ext4 -t ext3 -t ext2 -t reiserfs -t jfs -t ntfs -t fat32 -t btrfs -t fuseblk -t zfs -t simfs -t xfs";
-pub fn get_hdd() -> (u64, u64) {
-    let (mut hdd_total, mut hdd_used) = (0, 0);
+static DF_TYPES: &str =
+    "-t ext4 -t ext3 -t ext2 -t reiserfs -t jfs -t ntfs -t fat32 -t btrfs -t fuseblk -t zfs -t simfs -t xfs";
+
+// 去掉表头, 挂载点可能含空格, 从第 n 列起合并
+fn df_rows(opts: &str, n: usize) -> Vec<(Vec<String>, String)> {
+    let cmd = format!("df {} {}", opts, DF_TYPES);
     let a = &Command::new("/bin/sh")
-        .args(&["-c", DF_CMD])
+        .args(&["-c", cmd.as_str()])
         .output()
         .expect("failed to execute df")
         .stdout;
-    let _ = str::from_utf8(a).map(|s| {
-        s.trim().split('\n').last().map(|s| {
+    str::from_utf8(a)
+        .unwrap_or_default()
+        .lines()
+        .skip(1)
+        .filter_map(|s| {
             let vec: Vec<&str> = s.split_whitespace().collect();
-            // dbg!(&vec);
-            hdd_total = vec[2].parse::<u64>().unwrap();
-            hdd_used = vec[3].parse::<u64>().unwrap();
-            Some(())
-        });
-    });
+            if vec.len() <= n {
+                return None;
+            }
+            Some((vec[..n].iter().map(|o| o.to_string()).collect(), vec[n..].join(" ")))
+        })
+        .collect()
+}
+
+// 挂载点 => (inode 总数, 已用)
+pub fn get_inodes() -> HashMap<String, (u64, u64)> {
+    df_rows("-il", 5)
+        .into_iter()
+        .map(|(cols, mount)| {
+            let total = cols[1].parse::<u64>().unwrap_or(0);
+            let used = cols[2].parse::<u64>().unwrap_or(0);
+            (mount, (total, used))
+        })
+        .collect()
+}
 
-    (hdd_total, hdd_used)
+pub fn get_disks(args: &Args) -> Vec<DiskUsage> {
+    let inodes = get_inodes();
+    df_rows("-Tlm", 6)
+        .into_iter()
+        .filter(|(_, mount)| !skip_disk(mount, args))
+        .map(|(cols, mount)| {
+            let (inodes_total, inodes_used) = inodes.get(&mount).copied().unwrap_or((0, 0));
+            DiskUsage {
+                fs_type: cols[1].to_string(),
+                total: cols[2].parse::<u64>().unwrap_or(0),
+                used: cols[3].parse::<u64>().unwrap_or(0),
+                inodes_total,
+                inodes_used,
+                mount,
+            }
+        })
+        .collect()
 }
 
 #[derive(Debug, Default)]
@@ -211,62 +485,333 @@ lazy_static! {
 #[allow(unused)]
 pub fn start_net_speed_collect_t(args: &Args) {
     let args_1 = args.clone();
-    thread::spawn(move || loop {
-        let _ = File::open("/proc/net/dev").map(|file| {
-            let buf_reader = BufReader::new(file);
-            let (mut avgrx, mut avgtx) = (0, 0);
-            for line in buf_reader.lines() {
-                let l = line.unwrap();
-                let v: Vec<&str> = l.split(':').collect();
-                if v.len() < 2 {
-                    continue;
+    supervisor::spawn("net", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let args_1 = args_1.clone();
+        Box::new(move || {
+            let _ = File::open("/proc/net/dev").map(|file| {
+                let buf_reader = BufReader::new(file);
+                let (mut avgrx, mut avgtx) = (0, 0);
+                for line in buf_reader.lines() {
+                    let l = line.unwrap();
+                    let v: Vec<&str> = l.split(':').collect();
+                    if v.len() < 2 {
+                        continue;
+                    }
+
+                    // spec iface
+                    if skip_iface(v[0], &args_1) {
+                        continue;
+                    }
+
+                    let v1: Vec<&str> = v[1].split_whitespace().collect();
+                    avgrx += v1[0].parse::<u64>().unwrap();
+                    avgtx += v1[8].parse::<u64>().unwrap();
                 }
 
-                // spec iface
-                if skip_iface(v[0], &args_1) {
-                    continue;
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as f64;
+
+                if let Ok(mut t) = G_NET_SPEED.lock() {
+                    t.diff = now - t.clock;
+                    t.clock = now;
+                    t.netrx = ((avgrx - t.avgrx) as f64 / t.diff) as u64;
+                    t.nettx = ((avgtx - t.avgtx) as f64 / t.diff) as u64;
+                    t.avgrx = avgrx;
+                    t.avgtx = avgtx;
+
+                    // dbg!(&t);
                 }
+            });
+        })
+    });
+}
+
+// hwmon 单位为千分之一度
+fn read_milli_celsius(path: &str) -> f64 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .map(|v| v / 1000.0)
+        .unwrap_or(0.0)
+}
+
+pub fn get_temps() -> Vec<Temperature> {
+    let mut temps = Vec::new();
+    let mut dirs = fs::read_dir("/sys/class/hwmon")
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    dirs.sort();
+    for dir in dirs {
+        let dir = dir.to_string_lossy();
+        let chip = fs::read_to_string(format!("{}/name", dir)).unwrap_or_default();
+        let mut inputs = fs::read_dir(dir.as_ref())
+            .map(|rd| {
+                rd.filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| name.starts_with("temp") && name.ends_with("_input"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        inputs.sort();
+        for input in inputs {
+            let prefix = input.trim_end_matches("_input");
+            let label = fs::read_to_string(format!("{}/{}_label", dir, prefix))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|_| prefix.to_string());
+            temps.push(Temperature {
+                label: format!("{} {}", chip.trim(), label),
+                current: read_milli_celsius(&format!("{}/{}", dir, input)),
+                max: read_milli_celsius(&format!("{}/{}_max", dir, prefix)),
+                critical: read_milli_celsius(&format!("{}/{}_crit", dir, prefix)),
+            });
+        }
+    }
+    temps
+}
 
-                let v1: Vec<&str> = v[1].split_whitespace().collect();
-                avgrx += v1[0].parse::<u64>().unwrap();
-                avgtx += v1[8].parse::<u64>().unwrap();
+// md0 : active raid1 sdb1[1] sda1[0]
+//       1046528 blocks super 1.2 [2/1] [U_]
+//       [==>..................]  recovery = 12.5% (131072/1046528) finish=0.1min speed=...
+pub fn get_raid_arrays() -> Vec<RaidArray> {
+    let contents = fs::read_to_string("/proc/mdstat").unwrap_or_default();
+    let mut arrays: Vec<RaidArray> = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with("md") && line.contains(" : ") {
+            let v: Vec<&str> = line.split_whitespace().collect();
+            arrays.push(RaidArray {
+                name: v[0].to_string(),
+                state: v.get(2).unwrap_or(&"").to_string(),
+                level: v.get(3).filter(|o| o.starts_with("raid")).unwrap_or(&"").to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let o = match arrays.last_mut() {
+            Some(o) if line.starts_with(' ') => o,
+            _ => continue,
+        };
+        for word in line.split_whitespace() {
+            // [2/1]
+            if let Some((n, m)) = word
+                .strip_prefix('[')
+                .and_then(|w| w.strip_suffix(']'))
+                .and_then(|w| w.split_once('/'))
+            {
+                o.devices = n.parse::<u32>().unwrap_or(0);
+                o.active = m.parse::<u32>().unwrap_or(0);
+                o.degraded = o.active < o.devices;
+            }
+        }
+        for action in ["resync", "recovery", "reshape", "check"] {
+            if let Some(rest) = line.split_once(&format!("{} =", action)).map(|(_, r)| r) {
+                o.action = action.to_string();
+                o.progress = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|s| s.trim_end_matches('%').parse::<f64>().ok())
+                    .unwrap_or(0.0);
             }
+        }
+        // 等待执行
+        if line.contains("=DELAYED") || line.contains("=PENDING") {
+            o.action = line.split('=').next().unwrap_or_default().trim().to_string();
+        }
+    }
+    arrays
+}
 
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as f64;
+#[derive(Debug, Default)]
+pub struct RebootState {
+    pub kernel: String,
+    pub required: bool,
+    pub reason: String,
+}
 
-            if let Ok(mut t) = G_NET_SPEED.lock() {
-                t.diff = now - t.clock;
-                t.clock = now;
-                t.netrx = ((avgrx - t.avgrx) as f64 / t.diff) as u64;
-                t.nettx = ((avgtx - t.avgtx) as f64 / t.diff) as u64;
-                t.avgrx = avgrx;
-                t.avgtx = avgtx;
+lazy_static! {
+    pub static ref G_REBOOT: Arc<Mutex<RebootState>> = Arc::new(Default::default());
+}
+
+// debian/ubuntu 升级后写入 reboot-required, 其他发行版比较运行中与已安装的内核版本
+fn check_reboot() -> RebootState {
+    let running = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+    let running = running.trim().to_string();
+    if fs::metadata("/var/run/reboot-required").is_ok() {
+        let pkgs = fs::read_to_string("/var/run/reboot-required.pkgs").unwrap_or_default();
+        let pkgs = pkgs.split_whitespace().collect::<Vec<_>>().join(",");
+        return RebootState {
+            kernel: running,
+            required: true,
+            reason: if pkgs.is_empty() {
+                "reboot-required".to_string()
+            } else {
+                format!("reboot-required: {}", pkgs)
+            },
+        };
+    }
+    if running.is_empty() {
+        return Default::default();
+    }
+    // running 即 uname -r, 已安装更高版本的内核时需要重启
+    // 按版本号比较, 目录 mtime 会被 depmod/重装等刷新, 不可靠
+    let latest = fs::read_dir("/lib/modules").ok().and_then(|rd| {
+        rd.filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .max_by_key(|name| kernel_version(name))
+    });
+    match latest {
+        Some(latest) if kernel_version(latest) > kernel_version(&running) => RebootState {
+            reason: format!("kernel {} installed, running {}", latest, running),
+            kernel: running,
+            required: true,
+        },
+        _ => RebootState {
+            kernel: running,
+            ..Default::default()
+        },
+    }
+}
+
+// 5.15.0-91-generic => [5, 15, 0, 91]
+fn kernel_version(release: &str) -> Vec<u64> {
+    release
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse::<u64>().ok())
+        .collect()
+}
 
-                // dbg!(&t);
+pub fn start_reboot_check_t() {
+    supervisor::spawn("reboot", Duration::from_secs(300), || {
+        Box::new(|| {
+            let state = check_reboot();
+            if let Ok(mut o) = G_REBOOT.lock() {
+                *o = state;
             }
-        });
-        thread::sleep(Duration::from_millis(SAMPLE_PERIOD));
+        })
+    });
+}
+
+#[derive(Debug, Default)]
+pub struct DiskIo {
+    pub clock: f64,
+    pub read: u64,
+    pub write: u64,
+    pub total_read: u64,
+    pub total_write: u64,
+}
+
+lazy_static! {
+    pub static ref G_DISK_IO: Arc<Mutex<DiskIo>> = Arc::new(Default::default());
+}
+
+// 只统计整块盘, 分区/loop/device-mapper/软 raid 会重复计算
+const DISKSTATS_SKIP: [&str; 7] = ["loop", "ram", "zram", "dm-", "md", "sr", "fd"];
+const SECTOR_SIZE: u64 = 512;
+
+#[allow(unused)]
+pub fn start_disk_io_collect_t() {
+    supervisor::spawn("disk_io", Duration::from_millis(SAMPLE_PERIOD), || {
+        Box::new(|| {
+            let _ = fs::read_to_string("/proc/diskstats").map(|contents| {
+                let (mut total_read, mut total_write) = (0, 0);
+                for line in contents.lines() {
+                    let v: Vec<&str> = line.split_whitespace().collect();
+                    if v.len() < 10 {
+                        continue;
+                    }
+                    let name = v[2];
+                    if DISKSTATS_SKIP.iter().any(|o| name.starts_with(o))
+                        || fs::metadata(format!("/sys/block/{}", name)).is_err()
+                    {
+                        continue;
+                    }
+                    total_read += v[5].parse::<u64>().unwrap_or(0) * SECTOR_SIZE;
+                    total_write += v[9].parse::<u64>().unwrap_or(0) * SECTOR_SIZE;
+                }
+
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+
+                if let Ok(mut t) = G_DISK_IO.lock() {
+                    let diff = now - t.clock;
+                    // 首次采样或计数器回绕
+                    if t.clock > 0.0 && diff > 0.0 && total_read >= t.total_read && total_write >= t.total_write {
+                        t.read = ((total_read - t.total_read) as f64 / diff) as u64;
+                        t.write = ((total_write - t.total_write) as f64 / diff) as u64;
+                    }
+                    t.clock = now;
+                    t.total_read = total_read;
+                    t.total_write = total_write;
+                }
+            });
+        })
     });
 }
 
 lazy_static! {
     pub static ref G_CPU_PERCENT: Arc<Mutex<f64>> = Arc::new(Default::default());
+    pub static ref G_CPU_CORES: Arc<Mutex<Vec<f64>>> = Arc::new(Default::default());
+    pub static ref G_CPU_STEAL: Arc<Mutex<f64>> = Arc::new(Default::default());
 }
+
+// (steal, 全部 jiffies), 字段 user nice system idle iowait irq softirq steal
+fn cpu_steal_jiffies(line: &str) -> (u64, u64) {
+    let v = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .map(|e| e.parse::<u64>().unwrap_or(0))
+        .collect::<Vec<_>>();
+    (v.get(7).copied().unwrap_or(0), v.iter().sum())
+}
+
+// user/nice/system/idle
+fn cpu_jiffies(line: &str) -> Vec<u64> {
+    line.split_whitespace()
+        .skip(1)
+        .take(4)
+        .map(|e| e.parse::<u64>().unwrap_or(0))
+        .collect()
+}
+
 #[allow(unused)]
 pub fn start_cpu_percent_collect_t() {
-    let mut pre_cpu: Vec<u64> = vec![0, 0, 0, 0];
-    thread::spawn(move || loop {
-        let _ = File::open("/proc/stat").map(|file| {
-            let mut buf_reader = BufReader::new(file);
-            let mut buf = String::new();
-            let _ = buf_reader.read_line(&mut buf).map(|_| {
-                let cur_cpu = buf
-                    .split_whitespace()
+    supervisor::spawn("cpu", Duration::from_millis(SAMPLE_PERIOD), || {
+        let mut pre_cpu: Vec<u64> = vec![0, 0, 0, 0];
+        let mut pre_cores: Vec<Vec<u64>> = Vec::new();
+        let mut pre_steal = (0, 0);
+        Box::new(move || {
+            let _ = fs::read_to_string("/proc/stat").map(|contents| {
+                // cpu0 cpu1 ...
+                let cur_cores = contents
+                    .lines()
+                    .filter(|l| l.starts_with("cpu") && l.as_bytes().get(3).map_or(false, u8::is_ascii_digit))
+                    .map(cpu_jiffies)
+                    .collect::<Vec<_>>();
+                let cores = cur_cores
+                    .iter()
                     .enumerate()
-                    .filter(|&(idx, _)| idx > 0 && idx < 5)
-                    .map(|(_, e)| e.parse::<u64>().unwrap())
+                    .map(|(i, cur)| {
+                        let pre = pre_cores.get(i).cloned().unwrap_or_else(|| vec![0; 4]);
+                        let st = cur.iter().sum::<u64>().saturating_sub(pre.iter().sum()).max(1);
+                        let idle = cur[3].saturating_sub(pre[3]);
+                        (100.0 - 100.0 * idle as f64 / st as f64).round()
+                    })
                     .collect::<Vec<_>>();
+                pre_cores = cur_cores;
+                if let Ok(mut o) = G_CPU_CORES.lock() {
+                    *o = cores;
+                }
+
+                let line = contents.lines().next().unwrap_or_default();
+                // 虚拟机被宿主机占用的时间, 超售时偏高
+                let cur_steal = cpu_steal_jiffies(line);
+                let total = cur_steal.1.saturating_sub(pre_steal.1).max(1);
+                let steal = 100.0 * cur_steal.0.saturating_sub(pre_steal.0) as f64 / total as f64;
+                pre_steal = cur_steal;
+                if let Ok(mut o) = G_CPU_STEAL.lock() {
+                    *o = (steal * 10.0).round() / 10.0;
+                }
 
+                let cur_cpu = cpu_jiffies(line);
                 let pre: u64 = pre_cpu.iter().sum();
                 let cur: u64 = cur_cpu.iter().sum();
                 let mut st = cur - pre;
@@ -276,19 +821,13 @@ pub fn start_cpu_percent_collect_t() {
 
                 let res = 100.0 - (100.0 * (cur_cpu[3] - pre_cpu[3]) as f64 / st as f64);
 
-                // dbg!(&pre_cpu);
-                // dbg!(&cur_cpu);
-
                 pre_cpu = cur_cpu;
 
                 if let Ok(mut cpu_percent) = G_CPU_PERCENT.lock() {
                     *cpu_percent = res.round();
-                    // dbg!(cpu_percent);
                 }
             });
-        });
-
-        thread::sleep(Duration::from_millis(SAMPLE_PERIOD));
+        })
     });
 }
 
@@ -316,95 +855,403 @@ pub fn get_network() -> (bool, bool) {
 #[derive(Debug, Default)]
 pub struct PingData {
     pub probe_uri: String,
+    // 展示名
+    pub label: String,
+    // 使用 icmp 探测, 无权限时回退 tcp
+    pub icmp: bool,
     pub lost_rate: u32,
     pub ping_time: u32,
+    // 窗口内成功探测的 rtt 标准差及分位数 ms
+    pub jitter: f64,
+    pub p50: u32,
+    pub p95: u32,
+    pub p99: u32,
+    // 累计 rtt 分布, 桶上界见 RTT_HISTOGRAM_BOUNDS
+    pub histogram: Vec<u64>,
 }
 
-fn start_ping_collect_t(data: &Arc<Mutex<PingData>>) {
-    let mut package_list: LinkedList<i32> = LinkedList::new();
-    let mut package_lost: u32 = 0;
-    let pt = &*data.lock().unwrap();
-    let addr = pt
-        .probe_uri
-        .to_socket_addrs()
-        .unwrap()
-        .next()
-        .expect("can't get addr info");
-    info!("{} => {:?}", pt.probe_uri, addr);
+fn update_histogram(o: &mut PingData, rtt: u32) {
+    if o.histogram.is_empty() {
+        o.histogram = vec![0; RTT_HISTOGRAM_BOUNDS.len() + 1];
+    }
+    let idx = RTT_HISTOGRAM_BOUNDS
+        .iter()
+        .position(|&bound| rtt < bound)
+        .unwrap_or(RTT_HISTOGRAM_BOUNDS.len());
+    o.histogram[idx] += 1;
+}
+
+fn update_rtt_stats(o: &mut PingData, rtts: &VecDeque<u32>) {
+    if rtts.is_empty() {
+        return;
+    }
+    let n = rtts.len() as f64;
+    let mean = rtts.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let variance = rtts.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    o.jitter = (variance.sqrt() * 10.0).round() / 10.0;
 
+    let mut sorted = rtts.iter().copied().collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    (o.p50, o.p95, o.p99) = (percentile(0.5), percentile(0.95), percentile(0.99));
+}
+
+// window 为丢包率/抖动的统计窗口(次)
+fn start_ping_collect_t(name: &'static str, data: &Arc<Mutex<PingData>>, interval: Duration, window: usize) {
     let ping_data = data.clone();
-    thread::spawn(move || loop {
-        if package_list.len() > 100 && package_list.pop_front().unwrap() == 0 {
-            package_lost -= 1;
+    supervisor::spawn(name, interval, move || {
+        let mut package_list: LinkedList<i32> = LinkedList::new();
+        let mut package_lost: u32 = 0;
+        let mut rtts: VecDeque<u32> = VecDeque::with_capacity(window);
+        let mut seq: u16 = 0;
+        // (目标, icmp, udp)
+        let (probe_uri, icmp, udp) = {
+            let o = ping_data.lock().unwrap();
+            if let Some(uri) = o.probe_uri.strip_prefix("icmp:") {
+                (uri.to_string(), true, false)
+            } else if let Some(uri) = o.probe_uri.strip_prefix("udp:") {
+                (uri.to_string(), false, true)
+            } else {
+                (o.probe_uri.to_string(), o.icmp, false)
+            }
+        };
+        // icmp/udp 目标可省略端口
+        let mut addr = probe_uri
+            .to_socket_addrs()
+            .or_else(|_| (probe_uri.as_str(), 0).to_socket_addrs())
+            .unwrap()
+            .next()
+            .expect("can't get addr info");
+        let icmp = icmp && icmp::available(addr.is_ipv6());
+        if udp && addr.port() == 0 {
+            addr.set_port(udp::DEFAULT_PORT);
+        } else if !icmp && !udp && addr.port() == 0 {
+            warn!("{} icmp unavailable, fallback to tcp:80", probe_uri);
+            addr.set_port(80);
         }
+        info!("{} => {:?}, icmp: {}, udp: {}", probe_uri, addr, icmp, udp);
+
+        let ping_data = ping_data.clone();
+        Box::new(move || {
+            if package_list.len() > window && package_list.pop_front().unwrap() == 0 {
+                package_lost -= 1;
+            }
 
-        let instant = Instant::now();
-        match TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-            Ok(s) => {
-                let _ = s.shutdown(Shutdown::Both);
+            let instant = Instant::now();
+            let ok = if icmp {
+                seq = seq.wrapping_add(1);
+                icmp::ping(addr.ip(), seq, Duration::from_millis(TIMEOUT_MS)).is_ok()
+            } else if udp {
+                seq = seq.wrapping_add(1);
+                match udp::ping(addr, seq, Duration::from_millis(TIMEOUT_MS)) {
+                    Ok(_) => true,
+                    // 端口不可达同样说明主机可达, 与 tcp 一致
+                    Err(e) => e.kind() == ConnectionRefused,
+                }
+            } else {
+                match TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
+                    Ok(s) => {
+                        let _ = s.shutdown(Shutdown::Both);
+                        true
+                    }
+                    Err(e) => e.kind() == ConnectionRefused,
+                }
+            };
+            if ok {
                 package_list.push_back(1);
+            } else {
+                package_lost += 1;
+                package_list.push_back(0);
             }
-            Err(e) => {
-                // error!("{:?}", e);
-                if e.kind() == ConnectionRefused {
-                    package_list.push_back(1);
-                } else {
-                    package_lost += 1;
-                    package_list.push_back(0);
+            let time_cost_ms = instant.elapsed().as_millis();
+            if ok {
+                if rtts.len() >= window {
+                    rtts.pop_front();
                 }
+                rtts.push_back(time_cost_ms as u32);
             }
-        }
-        let time_cost_ms = instant.elapsed().as_millis();
 
-        if let Ok(mut o) = ping_data.lock() {
-            o.ping_time = time_cost_ms as u32;
-            if package_list.len() > 30 {
-                o.lost_rate = package_lost * 100 / package_list.len() as u32;
+            if let Ok(mut o) = ping_data.lock() {
+                update_rtt_stats(&mut o, &rtts);
+                if ok {
+                    update_histogram(&mut o, time_cost_ms as u32);
+                }
+                o.ping_time = time_cost_ms as u32;
+                // 样本过少时丢包率波动大
+                if package_list.len() > window * 3 / 10 {
+                    o.lost_rate = package_lost * 100 / package_list.len() as u32;
+                }
+                if let Some(reason) = trace::check(o.lost_rate, ok.then_some(o.ping_time)) {
+                    trace::spawn_trace(&o.probe_uri, addr.ip(), reason);
+                }
             }
-        }
-
-        thread::sleep(Duration::from_millis(SAMPLE_PERIOD));
+        })
     });
 }
 
-pub static G_PING_10010: OnceCell<Arc<Mutex<PingData>>> = OnceCell::new();
-pub static G_PING_189: OnceCell<Arc<Mutex<PingData>>> = OnceCell::new();
-pub static G_PING_10086: OnceCell<Arc<Mutex<PingData>>> = OnceCell::new();
+type PingCell = OnceCell<Arc<Mutex<PingData>>>;
+
+pub static G_PING_10010: PingCell = OnceCell::new();
+pub static G_PING_189: PingCell = OnceCell::new();
+pub static G_PING_10086: PingCell = OnceCell::new();
+
+type Probes = Vec<(String, Arc<Mutex<PingData>>)>;
+
+lazy_static! {
+    // --probe 自定义探测目标, (name, data), 重载时替换
+    pub static ref G_PROBES: Mutex<Probes> = Default::default();
+}
+
+// name=host:port 或 name/label=host:port, 返回 (name, label, target)
+pub fn parse_probes(specs: &[String]) -> Vec<(String, String, String)> {
+    specs
+        .iter()
+        .filter_map(|spec| {
+            let (name, target) = spec.split_once('=')?;
+            let (name, label) = name.split_once('/').unwrap_or((name, name));
+            let (name, label) = (name.trim(), label.trim());
+            if name.is_empty() || !target.contains(':') {
+                return None;
+            }
+            Some((name.to_string(), label.to_string(), target.trim().to_string()))
+        })
+        .collect()
+}
+
+// 三网探测地址可带展示名, 如 --cu "香港=1.1.1.1:80"
+fn carrier_ping_data(addr: &str, label: &str, icmp: bool) -> PingData {
+    let (label, addr) = addr.split_once('=').unwrap_or((label, addr));
+    PingData {
+        probe_uri: addr.trim().to_string(),
+        label: label.trim().to_string(),
+        icmp,
+        ..Default::default()
+    }
+}
+
+// (worker 名, 全局数据, 按参数生成的探测配置)
+fn carriers(args: &Args) -> Vec<(&'static str, &'static PingCell, PingData)> {
+    [
+        ("ping_cu", &G_PING_10010, &args.cu_addr, "联通"),
+        ("ping_ct", &G_PING_189, &args.ct_addr, "电信"),
+        ("ping_cm", &G_PING_10086, &args.cm_addr, "移动"),
+    ]
+    .into_iter()
+    .map(|(name, cell, addr, label)| (name, cell, carrier_ping_data(addr, label, args.icmp)))
+    .collect()
+}
+
+fn probe_result(name: &str, data: &Arc<Mutex<PingData>>) -> ProbeResult {
+    let o = data.lock().unwrap();
+    ProbeResult {
+        name: name.to_string(),
+        label: o.label.to_string(),
+        target: o.probe_uri.to_string(),
+        lost_rate: o.lost_rate,
+        ping_time: o.ping_time,
+        jitter: o.jitter,
+        p50: o.p50,
+        p95: o.p95,
+        p99: o.p99,
+        histogram: o.histogram.clone(),
+    }
+}
+
+pub fn get_probes() -> Vec<ProbeResult> {
+    G_PROBES
+        .lock()
+        .map(|probes| probes.iter().map(|(name, data)| probe_result(name, data)).collect())
+        .unwrap_or_default()
+}
+
+// 三网探测的抖动/分位数, 延迟及丢包率仍使用 time_/ping_ 字段
+pub fn get_carrier_probes() -> Vec<ProbeResult> {
+    [("cu", &G_PING_10010), ("ct", &G_PING_189), ("cm", &G_PING_10086)]
+        .iter()
+        .filter_map(|(name, cell)| Some(probe_result(name, cell.get()?)))
+        .collect()
+}
+
+// (探测间隔, 统计窗口)
+fn probe_opts(args: &Args) -> (Duration, usize) {
+    (
+        Duration::from_millis(args.probe_interval.max(1) * SAMPLE_PERIOD),
+        (args.probe_window as usize).max(10),
+    )
+}
+
+// worker 名需 'static, 仅在启动及重载时泄漏
+fn probe_worker(name: &str) -> &'static str {
+    Box::leak(format!("probe_{}", name).into_boxed_str())
+}
 
 pub fn start_all_ping_collect_t(args: &Args) {
-    G_PING_10010
-        .set(Arc::new(Mutex::new(PingData {
-            probe_uri: args.cu_addr.to_owned(),
-            lost_rate: 0,
-            ping_time: 0,
-        })))
-        .unwrap();
-    G_PING_189
-        .set(Arc::new(Mutex::new(PingData {
-            probe_uri: args.ct_addr.to_owned(),
-            lost_rate: 0,
-            ping_time: 0,
-        })))
-        .unwrap();
-    G_PING_10086
-        .set(Arc::new(Mutex::new(PingData {
-            probe_uri: args.cm_addr.to_owned(),
-            lost_rate: 0,
-            ping_time: 0,
-        })))
-        .unwrap();
+    for (_, cell, data) in carriers(args) {
+        cell.set(Arc::new(Mutex::new(data))).unwrap();
+    }
+
+    if args.trace_loss > 0 || args.trace_rtt > 0 {
+        trace::G_TRACE_THRESHOLD.set((args.trace_loss, args.trace_rtt)).ok();
+    }
+    let probes = parse_probes(&args.probe)
+        .into_iter()
+        .map(|(name, label, target)| {
+            let data = PingData {
+                probe_uri: target,
+                label,
+                icmp: args.icmp,
+                ..Default::default()
+            };
+            (name, Arc::new(Mutex::new(data)))
+        })
+        .collect::<Probes>();
 
     if !args.disable_ping {
-        start_ping_collect_t(G_PING_10010.get().unwrap());
-        start_ping_collect_t(G_PING_189.get().unwrap());
-        start_ping_collect_t(G_PING_10086.get().unwrap());
+        let (interval, window) = probe_opts(args);
+        for (name, cell, _) in carriers(args) {
+            start_ping_collect_t(name, cell.get().unwrap(), interval, window);
+        }
+        for (name, data) in probes.iter() {
+            start_ping_collect_t(probe_worker(name), data, interval, window);
+        }
     }
+    *G_PROBES.lock().unwrap() = probes;
 }
 
-pub fn sample(args: &Args, stat: &mut StatRequest) {
-    stat.version = env!("CARGO_PKG_VERSION").to_string();
-    stat.vnstat = args.vnstat;
+// 重载配置: 展示名原地更新, 目标变化的探测清空统计后重启, 探测参数变化时全部重启并保留统计, 已删除的停止
+pub fn reload_ping_collect_t(old: &Args, new: &Args) {
+    let restart_all = probe_opts(old) != probe_opts(new) || old.icmp != new.icmp;
+    let (interval, window) = probe_opts(new);
+
+    for (name, cell, fresh) in carriers(new) {
+        let data = match cell.get() {
+            Some(data) => data,
+            None => continue,
+        };
+        let changed = {
+            let mut o = data.lock().unwrap();
+            let changed = o.probe_uri != fresh.probe_uri;
+            if changed {
+                *o = fresh;
+            } else {
+                (o.label, o.icmp) = (fresh.label, fresh.icmp);
+            }
+            changed
+        };
+        if (changed || restart_all) && !new.disable_ping {
+            start_ping_collect_t(name, data, interval, window);
+        }
+    }
 
+    let mut probes = G_PROBES.lock().unwrap();
+    let mut next: Probes = Vec::new();
+    for (name, label, target) in parse_probes(&new.probe) {
+        let fresh = PingData {
+            probe_uri: target,
+            label,
+            icmp: new.icmp,
+            ..Default::default()
+        };
+        let (data, changed) = match probes.iter().find(|(o, _)| o.eq(&name)) {
+            Some((_, data)) => {
+                let mut o = data.lock().unwrap();
+                let changed = o.probe_uri != fresh.probe_uri;
+                if changed {
+                    *o = fresh;
+                } else {
+                    (o.label, o.icmp) = (fresh.label, fresh.icmp);
+                }
+                (data.clone(), changed)
+            }
+            None => (Arc::new(Mutex::new(fresh)), true),
+        };
+        if (changed || restart_all) && !new.disable_ping {
+            start_ping_collect_t(probe_worker(&name), &data, interval, window);
+        }
+        next.push((name, data));
+    }
+    for (name, _) in probes.iter() {
+        if !next.iter().any(|(o, _)| o.eq(name)) {
+            supervisor::stop(&format!("probe_{}", name));
+        }
+    }
+    *probes = next;
+}
+
+lazy_static! {
+    pub static ref G_PORTS: Arc<Mutex<Vec<PortState>>> = Arc::new(Default::default());
+}
+
+const PORT_PERIOD: u64 = 5000; //ms
+                               // 连续失败次数达到阈值才判定为 down, 避免偶发超时告警
+const PORT_FAIL_THRESHOLD: u32 = 3;
+
+// 默认路由对应的本机地址, udp connect 不发送数据
+fn lan_addr() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:53").ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+// 本机端口可能只监听在 127.0.0.1/::1/局域网地址之一, 任一可连即为 up
+fn port_addrs(target: &str) -> Vec<SocketAddr> {
+    match target.parse::<u16>() {
+        Ok(port) => {
+            let mut ips = vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)];
+            ips.extend(lan_addr().filter(|ip| !ip.is_loopback()));
+            ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect()
+        }
+        Err(_) => target.to_socket_addrs().map(|iter| iter.collect()).unwrap_or_default(),
+    }
+}
+
+fn port_up(target: &str) -> bool {
+    port_addrs(target).iter().any(|addr| {
+        TcpStream::connect_timeout(addr, Duration::from_millis(TIMEOUT_MS))
+            .map(|s| s.shutdown(Shutdown::Both))
+            .is_ok()
+    })
+}
+
+// 5432 为本机端口, 也可为 host:port
+pub fn start_port_check_t(ports: &[String]) {
+    let targets = ports.to_vec();
+    supervisor::spawn("port", Duration::from_millis(PORT_PERIOD), move || {
+        let targets = targets.clone();
+        let mut fails = vec![0_u32; targets.len()];
+        Box::new(move || {
+            // 并发检查, 总耗时不随端口数增长
+            let handles = targets
+                .iter()
+                .map(|target| {
+                    let target = target.to_string();
+                    thread::spawn(move || port_up(&target))
+                })
+                .collect::<Vec<_>>();
+            let states = handles
+                .into_iter()
+                .zip(targets.iter())
+                .zip(fails.iter_mut())
+                .map(|((handle, target), fail)| {
+                    if handle.join().unwrap_or(false) {
+                        *fail = 0;
+                    } else {
+                        *fail = fail.saturating_add(1);
+                    }
+                    PortState {
+                        target: target.to_string(),
+                        up: *fail < PORT_FAIL_THRESHOLD,
+                    }
+                })
+                .collect();
+            if let Ok(mut o) = G_PORTS.lock() {
+                *o = states;
+            }
+        })
+    });
+}
+
+pub fn sample(args: &Args, stat: &mut StatRequest) {
     stat.uptime = get_uptime();
 
     let (load_1, load_5, load_15) = get_loadavg();
@@ -412,21 +1259,36 @@ pub fn sample(args: &Args, stat: &mut StatRequest) {
     stat.load_5 = load_5;
     stat.load_15 = load_15;
 
-    let (mem_total, mem_used, swap_total, swap_free) = get_memory();
+    let (mem_total, mem_used, swap_total, swap_free, mem_detail) = get_memory();
     stat.memory_total = mem_total;
     stat.memory_used = mem_used;
     stat.swap_total = swap_total;
-    stat.swap_used = swap_total - swap_free;
+    stat.swap_used = swap_total.saturating_sub(swap_free);
+    stat.memory_detail = Some(mem_detail);
 
-    let (hdd_total, hdd_used) = get_hdd();
-    stat.hdd_total = hdd_total;
-    stat.hdd_used = hdd_used;
+    stat.disks = get_disks(args);
+    stat.hdd_total = stat.disks.iter().map(|o| o.total).sum();
+    stat.hdd_used = stat.disks.iter().map(|o| o.used).sum();
+
+    if args.enable_temps {
+        stat.temps = get_temps();
+    }
+    stat.raid = get_raid_arrays();
 
     let (t, u, p, d) = if args.disable_tupd { (0, 0, 0, 0) } else { tupd() };
     stat.tcp = t;
     stat.udp = u;
     stat.process = p;
     stat.thread = d;
+    if !args.disable_tupd {
+        (stat.tcp_established, stat.tcp_time_wait, stat.udp_sockets) = get_sockets();
+    }
+    (stat.conntrack_count, stat.conntrack_max) = get_conntrack();
+    stat.psi_cpu = get_pressure("cpu");
+    stat.psi_memory = get_pressure("memory");
+    stat.psi_io = get_pressure("io");
+    (stat.fd_used, stat.fd_max) = get_file_nr();
+    (stat.client_fds, stat.client_fd_limit) = get_self_fds();
 
     if args.vnstat {
         let (network_in, network_out, m_network_in, m_network_out) = get_vnstat_traffic(args);
@@ -443,11 +1305,24 @@ pub fn sample(args: &Args, stat: &mut StatRequest) {
     if let Ok(o) = G_CPU_PERCENT.lock() {
         stat.cpu = *o;
     }
+    if let Ok(o) = G_CPU_STEAL.lock() {
+        stat.cpu_steal = *o;
+    }
+    if args.per_core {
+        if let Ok(o) = G_CPU_CORES.lock() {
+            stat.cpu_cores = o.clone();
+        }
+    }
 
     if let Ok(o) = G_NET_SPEED.lock() {
         stat.network_rx = o.netrx;
         stat.network_tx = o.nettx;
     }
+
+    if let Ok(o) = G_DISK_IO.lock() {
+        stat.disk_read = o.read;
+        stat.disk_write = o.write;
+    }
     {
         let o = &*G_PING_10010.get().unwrap().lock().unwrap();
         stat.ping_10010 = o.lost_rate.into();
@@ -0,0 +1,123 @@
+// 采集线程守护: panic 或长时间无心跳时重启, 健康状态通过 collector_restarts 上报
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 超过 STALL_SECS 无心跳判定为卡死
+const STALL_SECS: u64 = 30;
+const CHECK_SECS: u64 = 5;
+
+type Step = Box<dyn FnMut() + Send>;
+type Factory = Arc<dyn Fn() -> Step + Send + Sync>;
+
+struct Worker {
+    period: Duration,
+    factory: Factory,
+    // 每次(重)启动分配新值, 旧线程发现代数不一致后退出
+    gen: u64,
+    last_beat: Instant,
+    restarts: u32,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+static G_WORKERS: Lazy<Mutex<HashMap<&'static str, Worker>>> = Lazy::new(Default::default);
+// 全局递增, 停止后同名重建的采集器不会与旧线程代数相同
+static G_GEN: AtomicU64 = AtomicU64::new(0);
+
+fn next_gen() -> u64 {
+    G_GEN.fetch_add(1, Ordering::Relaxed)
+}
+
+fn run(name: &'static str, gen: u64, period: Duration, factory: Factory) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut step = factory();
+            loop {
+                step();
+                match G_WORKERS.lock() {
+                    Ok(mut workers) => match workers.get_mut(name) {
+                        Some(w) if w.gen == gen => w.last_beat = Instant::now(),
+                        _ => return,
+                    },
+                    Err(_) => return,
+                }
+                thread::sleep(period);
+            }
+        }));
+        if let Err(err) = result {
+            error!("collector `{}` panicked => {:?}", name, err);
+        }
+    })
+}
+
+// factory 每次(重)启动时调用, 返回带初始状态的单次采集函数; 同名采集器已存在时替换
+pub fn spawn<F>(name: &'static str, period: Duration, factory: F)
+where
+    F: Fn() -> Step + Send + Sync + 'static,
+{
+    let factory: Factory = Arc::new(factory);
+    let mut workers = G_WORKERS.lock().unwrap();
+    let gen = next_gen();
+    let handle = run(name, gen, period, factory.clone());
+    workers.insert(
+        name,
+        Worker {
+            period,
+            factory,
+            gen,
+            last_beat: Instant::now(),
+            restarts: 0,
+            handle: Some(handle),
+        },
+    );
+}
+
+// 线程在下次心跳时退出
+pub fn stop(name: &str) {
+    if let Ok(mut workers) = G_WORKERS.lock() {
+        workers.remove(name);
+    }
+}
+
+pub fn start_supervisor_t() {
+    thread::spawn(|| loop {
+        thread::sleep(Duration::from_secs(CHECK_SECS));
+        if let Ok(mut workers) = G_WORKERS.lock() {
+            for (name, w) in workers.iter_mut() {
+                let dead = w.handle.as_ref().map(|h| h.is_finished()).unwrap_or(true);
+                let stalled = w.last_beat.elapsed() > w.period + Duration::from_secs(STALL_SECS);
+                if !dead && !stalled {
+                    continue;
+                }
+                warn!(
+                    "collector `{}` {}, restart",
+                    name,
+                    if dead { "dead" } else { "stalled" }
+                );
+                w.gen = next_gen();
+                w.restarts += 1;
+                w.last_beat = Instant::now();
+                w.handle = Some(run(name, w.gen, w.period, w.factory.clone()));
+            }
+        }
+    });
+}
+
+// 有过重启的采集器, eg: cpu:2,ping_ct:1
+pub fn health() -> String {
+    let workers = match G_WORKERS.lock() {
+        Ok(workers) => workers,
+        Err(_) => return String::new(),
+    };
+    let mut list = workers
+        .iter()
+        .filter(|(_, w)| w.restarts > 0)
+        .map(|(name, w)| format!("{}:{}", name, w.restarts))
+        .collect::<Vec<_>>();
+    list.sort();
+    list.join(",")
+}
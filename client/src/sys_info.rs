@@ -3,15 +3,17 @@
 use lazy_static::lazy_static;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::thread;
 use std::time::Duration;
-use sysinfo::{CpuExt, DiskExt, NetworkExt, RefreshKind, System, SystemExt};
+use sysinfo::{
+    ComponentExt, CpuExt, DiskExt, NetworkExt, ProcessExt, ProcessRefreshKind, RefreshKind, System, SystemExt,
+};
 
-use crate::skip_iface;
 use crate::status;
 use crate::status::get_vnstat_traffic;
+use crate::supervisor;
 use crate::Args;
-use stat_common::server_status::{StatRequest, SysInfo};
+use crate::{skip_disk, skip_iface};
+use stat_common::server_status::{DiskUsage, MemoryDetail, StatRequest, SysInfo, Temperature};
 
 const SAMPLE_PERIOD: u64 = 1000; //ms
 
@@ -36,18 +38,23 @@ lazy_static! {
     ]
     .to_vec();
     pub static ref G_CPU_PERCENT: Arc<Mutex<f64>> = Arc::new(Default::default());
+    pub static ref G_CPU_CORES: Arc<Mutex<Vec<f64>>> = Arc::new(Default::default());
 }
 pub fn start_cpu_percent_collect_t() {
-    let mut sys = System::new_all();
-    sys.refresh_cpu();
-    thread::spawn(move || loop {
-        let global_cpu = sys.global_cpu_info();
-        if let Ok(mut cpu_percent) = G_CPU_PERCENT.lock() {
-            *cpu_percent = global_cpu.cpu_usage().round() as f64;
-        }
-
+    supervisor::spawn("cpu", Duration::from_millis(SAMPLE_PERIOD), || {
+        let mut sys = System::new_all();
         sys.refresh_cpu();
-        thread::sleep(Duration::from_millis(SAMPLE_PERIOD));
+        Box::new(move || {
+            let global_cpu = sys.global_cpu_info();
+            if let Ok(mut cpu_percent) = G_CPU_PERCENT.lock() {
+                *cpu_percent = global_cpu.cpu_usage().round() as f64;
+            }
+            if let Ok(mut o) = G_CPU_CORES.lock() {
+                *o = sys.cpus().iter().map(|c| c.cpu_usage().round() as f64).collect();
+            }
+
+            sys.refresh_cpu();
+        })
     });
 }
 
@@ -62,34 +69,67 @@ lazy_static! {
 }
 
 pub fn start_net_speed_collect_t(args: &Args) {
-    let mut sys = System::new_all();
-    sys.refresh_all();
     let args_1 = args.clone();
-    thread::spawn(move || loop {
-        let (mut net_rx, mut net_tx) = (0_u64, 0_u64);
-        for (name, data) in sys.networks() {
-            // spec iface
-            if skip_iface(name, &args_1) {
-                continue;
+    supervisor::spawn("net", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let mut sys = System::new_all();
+        sys.refresh_all();
+        let args_1 = args_1.clone();
+        Box::new(move || {
+            let (mut net_rx, mut net_tx) = (0_u64, 0_u64);
+            for (name, data) in sys.networks() {
+                // spec iface
+                if skip_iface(name, &args_1) {
+                    continue;
+                }
+                net_rx += data.received();
+                net_tx += data.transmitted();
+            }
+            if let Ok(mut t) = G_NET_SPEED.lock() {
+                t.net_rx = net_rx;
+                t.net_tx = net_tx;
             }
-            net_rx += data.received();
-            net_tx += data.transmitted();
-        }
-        if let Ok(mut t) = G_NET_SPEED.lock() {
-            t.net_rx = net_rx;
-            t.net_tx = net_tx;
-        }
 
-        sys.refresh_networks();
-        thread::sleep(Duration::from_millis(SAMPLE_PERIOD));
+            sys.refresh_networks();
+        })
+    });
+}
+
+#[derive(Debug, Default)]
+pub struct DiskIo {
+    pub read: u64,
+    pub write: u64,
+}
+
+lazy_static! {
+    pub static ref G_DISK_IO: Arc<Mutex<DiskIo>> = Arc::new(Default::default());
+}
+
+// sysinfo 无整盘统计, 按进程读写量合计, 不含已退出的进程
+// 遍历全部进程开销较大, 只刷新读写量并放宽周期
+const DISK_IO_PERIOD: u64 = 5000; //ms
+
+pub fn start_disk_io_collect_t() {
+    supervisor::spawn("disk_io", Duration::from_millis(DISK_IO_PERIOD), || {
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(ProcessRefreshKind::new().with_disk_usage());
+        Box::new(move || {
+            sys.refresh_processes_specifics(ProcessRefreshKind::new().with_disk_usage());
+            let (mut read, mut write) = (0_u64, 0_u64);
+            for process in sys.processes().values() {
+                let usage = process.disk_usage();
+                read += usage.read_bytes;
+                write += usage.written_bytes;
+            }
+            if let Ok(mut t) = G_DISK_IO.lock() {
+                t.read = read * 1000 / DISK_IO_PERIOD;
+                t.write = write * 1000 / DISK_IO_PERIOD;
+            }
+        })
     });
 }
 
 // TODO
 pub fn sample(args: &Args, stat: &mut StatRequest) {
-    stat.version = env!("CARGO_PKG_VERSION").to_string();
-    stat.vnstat = args.vnstat;
-
     // 注意：sysinfo 统一使用 KB, 非KiB，需要转换一下
     let mut sys = System::new_with_specifics(RefreshKind::new().with_disks_list().with_memory());
 
@@ -117,19 +157,61 @@ pub fn sample(args: &Args, stat: &mut StatRequest) {
     stat.memory_total = mem_total;
     stat.memory_used = mem_used;
     stat.swap_total = swap_total;
-    stat.swap_used = swap_total - swap_free;
-
-    // hdd  KB -> KiB
-    let (mut hdd_total, mut hdd_avail) = (0_u64, 0_u64);
-    for disk in sys.disks() {
-        let fs = String::from_utf8_lossy(disk.file_system()).to_lowercase();
-        if G_EXPECT_FS.iter().any(|&k| fs.contains(k)) {
-            hdd_total += disk.total_space();
-            hdd_avail += disk.available_space();
-        }
+    stat.swap_used = swap_total.saturating_sub(swap_free);
+    // linux 下其余明细读取 /proc/meminfo
+    let mut mem_detail = if "linux".eq(std::env::consts::OS) {
+        status::get_memory_detail()
+    } else {
+        MemoryDetail::default()
+    };
+    mem_detail.available = sys.available_memory() * 1000 / 1024;
+    stat.memory_detail = Some(mem_detail);
+
+    // hdd  B -> MiB, linux 下 inode 读取 df -i
+    let inodes = if "linux".eq(std::env::consts::OS) {
+        status::get_inodes()
+    } else {
+        Default::default()
+    };
+    stat.disks = sys
+        .disks()
+        .iter()
+        .filter_map(|disk| {
+            let fs = String::from_utf8_lossy(disk.file_system()).to_lowercase();
+            let mount = disk.mount_point().to_string_lossy().to_string();
+            if !G_EXPECT_FS.iter().any(|&k| fs.contains(k)) || skip_disk(&mount, args) {
+                return None;
+            }
+            let (inodes_total, inodes_used) = inodes.get(&mount).copied().unwrap_or((0, 0));
+            Some(DiskUsage {
+                mount,
+                fs_type: fs,
+                total: disk.total_space() / 1024 / 1024,
+                used: disk.total_space().saturating_sub(disk.available_space()) / 1024 / 1024,
+                inodes_total,
+                inodes_used,
+            })
+        })
+        .collect();
+    stat.hdd_total = stat.disks.iter().map(|o| o.total).sum();
+    stat.hdd_used = stat.disks.iter().map(|o| o.used).sum();
+    if "linux".eq(std::env::consts::OS) {
+        stat.raid = status::get_raid_arrays();
+    }
+
+    if args.enable_temps {
+        let sys = System::new_with_specifics(RefreshKind::new().with_components_list());
+        stat.temps = sys
+            .components()
+            .iter()
+            .map(|o| Temperature {
+                label: o.label().to_string(),
+                current: o.temperature() as f64,
+                max: o.max() as f64,
+                critical: o.critical().unwrap_or(0.0) as f64,
+            })
+            .collect();
     }
-    stat.hdd_total = hdd_total / 1024 / 1024;
-    stat.hdd_used = (hdd_total - hdd_avail) / 1024 / 1024;
 
     // t/u/p/d TODO
     let (t, u, p, d) = if args.disable_tupd {
@@ -143,6 +225,17 @@ pub fn sample(args: &Args, stat: &mut StatRequest) {
     stat.udp = u;
     stat.process = p;
     stat.thread = d;
+    if !args.disable_tupd && "linux".eq(std::env::consts::OS) {
+        (stat.tcp_established, stat.tcp_time_wait, stat.udp_sockets) = status::get_sockets();
+    }
+    if "linux".eq(std::env::consts::OS) {
+        (stat.conntrack_count, stat.conntrack_max) = status::get_conntrack();
+        stat.psi_cpu = status::get_pressure("cpu");
+        stat.psi_memory = status::get_pressure("memory");
+        stat.psi_io = status::get_pressure("io");
+        (stat.fd_used, stat.fd_max) = status::get_file_nr();
+        (stat.client_fds, stat.client_fd_limit) = status::get_self_fds();
+    }
 
     // traffic
     if args.vnstat {
@@ -169,10 +262,19 @@ pub fn sample(args: &Args, stat: &mut StatRequest) {
     if let Ok(o) = G_CPU_PERCENT.lock() {
         stat.cpu = *o;
     }
+    if args.per_core {
+        if let Ok(o) = G_CPU_CORES.lock() {
+            stat.cpu_cores = o.clone();
+        }
+    }
     if let Ok(o) = G_NET_SPEED.lock() {
         stat.network_rx = o.net_rx;
         stat.network_tx = o.net_tx;
     }
+    if let Ok(o) = G_DISK_IO.lock() {
+        stat.disk_read = o.read;
+        stat.disk_write = o.write;
+    }
     {
         let o = &*status::G_PING_10010.get().unwrap().lock().unwrap();
         stat.ping_10010 = o.lost_rate.into();
@@ -0,0 +1,49 @@
+// systemd 服务状态, 通过 systemctl is-active 查询
+use lazy_static::lazy_static;
+use std::process::Command;
+use std::str;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use stat_common::server_status::UnitState;
+
+const SAMPLE_PERIOD: u64 = 10000; //ms
+
+lazy_static! {
+    pub static ref G_UNITS: Arc<Mutex<Vec<UnitState>>> = Arc::new(Default::default());
+}
+
+// 按参数顺序每行输出一个状态, 不存在的服务为 inactive
+fn query(units: &[String]) -> Vec<UnitState> {
+    let output = Command::new("systemctl").arg("is-active").args(units).output();
+    let stdout = match &output {
+        Ok(o) => str::from_utf8(&o.stdout).unwrap_or_default(),
+        Err(err) => {
+            warn!("systemctl error => {:?}", err);
+            ""
+        }
+    };
+    let mut states = stdout.lines();
+    units
+        .iter()
+        .map(|name| UnitState {
+            name: name.to_string(),
+            state: states.next().unwrap_or("unknown").trim().to_string(),
+        })
+        .collect()
+}
+
+pub fn start_unit_collect_t(units: &[String]) {
+    let units = units.to_vec();
+    supervisor::spawn("systemd", Duration::from_millis(SAMPLE_PERIOD), move || {
+        let units = units.clone();
+        Box::new(move || {
+            let states = query(&units);
+            if let Ok(mut o) = G_UNITS.lock() {
+                *o = states;
+            }
+        })
+    });
+}
@@ -0,0 +1,66 @@
+// mTLS 客户端证书, http(https://) 与 grpc 上报共用, 服务端按证书 CN 鉴权
+use anyhow::{Context, Result};
+use std::fs;
+use tonic::transport::Endpoint;
+
+use crate::Args;
+
+fn enabled(args: &Args) -> bool {
+    !args.tls_cert.is_empty() || !args.tls_ca.is_empty()
+}
+
+fn read(path: &str) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("read {} error", path))
+}
+
+#[cfg(feature = "rustls")]
+pub fn http_builder(builder: reqwest::ClientBuilder, args: &Args) -> Result<reqwest::ClientBuilder> {
+    let mut builder = builder;
+    if !args.tls_ca.is_empty() {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&read(&args.tls_ca)?)?);
+    }
+    if !args.tls_cert.is_empty() {
+        // rustls 需要证书与私钥在同一 pem 中
+        let mut pem = read(&args.tls_cert)?;
+        pem.push(b'\n');
+        pem.extend(read(&args.tls_key)?);
+        builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+    }
+    Ok(builder)
+}
+
+#[cfg(not(feature = "rustls"))]
+pub fn http_builder(builder: reqwest::ClientBuilder, args: &Args) -> Result<reqwest::ClientBuilder> {
+    if enabled(args) {
+        anyhow::bail!("--tls-* requires feature `rustls`");
+    }
+    Ok(builder)
+}
+
+// grpc://host:port => https://host:port
+#[cfg(feature = "rustls")]
+pub fn grpc_endpoint(addr: &str, args: &Args) -> Result<Endpoint> {
+    if !enabled(args) {
+        return Ok(Endpoint::from_shared(addr.to_string())?);
+    }
+    let mut tls = tonic::transport::ClientTlsConfig::new();
+    if !args.tls_ca.is_empty() {
+        tls = tls.ca_certificate(tonic::transport::Certificate::from_pem(read(&args.tls_ca)?));
+    }
+    if !args.tls_cert.is_empty() {
+        tls = tls.identity(tonic::transport::Identity::from_pem(
+            read(&args.tls_cert)?,
+            read(&args.tls_key)?,
+        ));
+    }
+    let endpoint = Endpoint::from_shared(addr.replacen("grpc://", "https://", 1))?;
+    Ok(endpoint.tls_config(tls)?)
+}
+
+#[cfg(not(feature = "rustls"))]
+pub fn grpc_endpoint(addr: &str, args: &Args) -> Result<Endpoint> {
+    if enabled(args) {
+        anyhow::bail!("--tls-* requires feature `rustls`");
+    }
+    Ok(Endpoint::from_shared(addr.to_string())?)
+}
@@ -0,0 +1,122 @@
+// 探测丢包/延迟超过阈值时抓取路由跟踪, 优先 mtr, 其次 traceroute, 同一目标限频, 结果随上报发送直到成功
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::Result;
+use stat_common::server_status::TraceReport;
+
+// 同一目标两次跟踪的最小间隔
+const MIN_INTERVAL: u64 = 1800; //s
+const TIMEOUT: u64 = 60; //s
+const MAX_OUTPUT: usize = 4096;
+const MAX_PENDING: usize = 10;
+
+// (丢包率 %, 延迟 ms), 0 为不触发
+pub static G_TRACE_THRESHOLD: OnceCell<(u32, u32)> = OnceCell::new();
+// 待上报
+pub static G_TRACES: Lazy<Mutex<Vec<TraceReport>>> = Lazy::new(Default::default);
+static G_LAST_TRACE: Lazy<Mutex<HashMap<IpAddr, Instant>>> = Lazy::new(Default::default);
+
+pub fn pending() -> Vec<TraceReport> {
+    G_TRACES.lock().map(|o| o.clone()).unwrap_or_default()
+}
+
+// 上报成功后移除已送达的, 期间新增的保留
+pub fn delivered(sent: &[TraceReport]) {
+    if sent.is_empty() {
+        return;
+    }
+    if let Ok(mut o) = G_TRACES.lock() {
+        o.retain(|t| !sent.iter().any(|s| s.target == t.target && s.ts == t.ts));
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > Duration::from_secs(TIMEOUT) {
+            child.kill().ok();
+            child.wait().ok();
+            return Err("timeout".into());
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_string(&mut output)?;
+    }
+    if !status.success() {
+        return Err(format!("exit {:?}", status.code()).into());
+    }
+    Ok(output)
+}
+
+fn trace(ip: IpAddr) -> Result<String> {
+    let ip = ip.to_string();
+    run("mtr", &["-r", "-n", "-c", "5", "-m", "30", &ip])
+        .or_else(|_| run("traceroute", &["-n", "-q", "1", "-w", "1", "-m", "30", &ip]))
+}
+
+// 超过阈值时返回原因
+pub fn check(lost_rate: u32, rtt: Option<u32>) -> Option<String> {
+    let &(max_loss, max_rtt) = G_TRACE_THRESHOLD.get()?;
+    if max_loss > 0 && lost_rate >= max_loss {
+        return Some(format!("lost {}%", lost_rate));
+    }
+    match rtt {
+        Some(rtt) if max_rtt > 0 && rtt >= max_rtt => Some(format!("rtt {}ms", rtt)),
+        _ => None,
+    }
+}
+
+pub fn spawn_trace(target: &str, ip: IpAddr, reason: String) {
+    if let Ok(mut o) = G_LAST_TRACE.lock() {
+        let recent = o.get(&ip).map_or(false, |ts| ts.elapsed().as_secs() < MIN_INTERVAL);
+        if recent {
+            return;
+        }
+        o.insert(ip, Instant::now());
+    }
+    let target = target.to_string();
+    thread::spawn(move || {
+        info!("trace {} ({}) => {}", target, ip, reason);
+        let mut report = trace(ip).unwrap_or_else(|err| {
+            warn!("trace {} error => {:?}", target, err);
+            format!("trace error: {}", err)
+        });
+        if report.len() > MAX_OUTPUT {
+            let mut end = MAX_OUTPUT;
+            while !report.is_char_boundary(end) {
+                end -= 1;
+            }
+            report.truncate(end);
+        }
+        if let Ok(mut o) = G_TRACES.lock() {
+            o.push(TraceReport {
+                target,
+                reason,
+                report,
+                ts: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            });
+            // 长时间无法上报时只保留最近的
+            if o.len() > MAX_PENDING {
+                o.remove(0);
+            }
+        }
+    });
+}
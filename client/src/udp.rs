@@ -0,0 +1,38 @@
+// udp echo 探测(RFC 862 echo 或自建回显服务), 发送 seq + 时间戳, 收到原样回显为成功
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_PORT: u16 = 7;
+
+pub fn ping(addr: SocketAddr, seq: u16, timeout: Duration) -> io::Result<Duration> {
+    let bind: SocketAddr = if addr.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(addr)?;
+
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+    let mut packet = [0_u8; 16];
+    packet[..4].copy_from_slice(b"SSR\0");
+    packet[4..6].copy_from_slice(&seq.to_be_bytes());
+    packet[8..16].copy_from_slice(&ts.to_be_bytes());
+
+    let start = Instant::now();
+    socket.send(&packet)?;
+    let mut buf = [0_u8; 1500];
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        socket.set_read_timeout(Some(timeout - elapsed))?;
+        let n = socket.recv(&mut buf)?;
+        // 忽略超时后迟到的旧回显
+        if n >= packet.len() && buf[..packet.len()] == packet {
+            return Ok(start.elapsed());
+        }
+    }
+}
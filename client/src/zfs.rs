@@ -0,0 +1,72 @@
+// zfs 存储池状态, 解析 zpool list/status 输出
+use lazy_static::lazy_static;
+use std::process::Command;
+use std::str;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::supervisor;
+use crate::Result;
+use stat_common::server_status::ZfsPool;
+
+const SAMPLE_PERIOD: u64 = 30000; //ms
+
+lazy_static! {
+    pub static ref G_ZFS_POOLS: Arc<Mutex<Vec<ZfsPool>>> = Arc::new(Default::default());
+}
+
+fn zpool(args: &[&str]) -> Result<String> {
+    let output = Command::new("zpool").args(args).output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    Ok(str::from_utf8(&output.stdout)?.to_string())
+}
+
+// scan: scrub repaired 0B in 00:00:01 with 0 errors on Sun Oct 11 00:24:02 2026
+fn scan_status(pool: &str) -> String {
+    zpool(&["status", pool])
+        .ok()
+        .and_then(|s| {
+            s.lines()
+                .map(|l| l.trim())
+                .find(|l| l.starts_with("scan:"))
+                .map(|l| l.trim_start_matches("scan:").trim().to_string())
+        })
+        .unwrap_or_default()
+}
+
+fn collect() -> Result<Vec<ZfsPool>> {
+    // -H 无表头, tab 分隔; -p 精确字节数
+    let pools = zpool(&["list", "-Hp", "-o", "name,size,alloc,health"])?
+        .lines()
+        .filter_map(|l| {
+            let v: Vec<&str> = l.split('\t').collect();
+            if v.len() < 4 {
+                return None;
+            }
+            Some(ZfsPool {
+                name: v[0].to_string(),
+                size: v[1].parse::<u64>().unwrap_or(0),
+                alloc: v[2].parse::<u64>().unwrap_or(0),
+                health: v[3].to_string(),
+                scan: scan_status(v[0]),
+            })
+        })
+        .collect();
+    Ok(pools)
+}
+
+pub fn start_zfs_collect_t() {
+    supervisor::spawn("zfs", Duration::from_millis(SAMPLE_PERIOD), || {
+        Box::new(|| {
+            let pools = collect()
+                .map_err(|err| warn!("zpool error => {:?}", err))
+                .unwrap_or_default();
+            if let Ok(mut o) = G_ZFS_POOLS.lock() {
+                *o = pools;
+            }
+        })
+    });
+}
@@ -36,7 +36,11 @@ fn main() {
     println!("cargo:rustc-env=APP_VERSION={}", app_version);
 
     std::env::set_var("PROTOC", protobuf_src::protoc());
+    // for grpc reflection
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let descriptor_path = out_dir.join("server_status_descriptor.bin");
     tonic_build::configure()
+        .file_descriptor_set_path(descriptor_path)
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .compile(&["proto/server_status.proto"], &["proto"])
         .unwrap();
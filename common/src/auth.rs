@@ -0,0 +1,138 @@
+//! 鉴权约定
+//!
+//! - http: Basic Auth `user:pass`, 组模式 user 为 gid, 并带 `ssr-auth: group` 头
+//! - grpc: metadata `authorization: user@_@pass`, `ssr-auth: single|group`
+//! - mqtt: 消息体前缀 `single|group\nuser\nts\nsignature\n`, 之后为 protobuf 或 json, 签名同 http, 不含密码
+//! - http 签名: Basic Auth 不带密码, `ssr-timestamp: unix 秒`, `ssr-signature: hex(hmac-sha256(pass, "{ts}\n{body}"))`
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const SSR_AUTH: &str = "ssr-auth";
+pub const SSR_TIMESTAMP: &str = "ssr-timestamp";
+pub const SSR_SIGNATURE: &str = "ssr-signature";
+pub const SINGLE: &str = "single";
+pub const GROUP: &str = "group";
+
+const TOKEN_SEP: &str = "@_@";
+
+/// 单机模式返回 (user, "single"), 组模式返回 (gid, "group")
+pub fn auth_user<'a>(user: &'a str, gid: &'a str) -> (&'a str, &'static str) {
+    if gid.is_empty() {
+        (user, SINGLE)
+    } else {
+        (gid, GROUP)
+    }
+}
+
+/// grpc authorization token
+pub fn grpc_token(user: &str, pass: &str) -> String {
+    format!("{}{}{}", user, TOKEN_SEP, pass)
+}
+
+/// 解析 grpc authorization token => (user, pass)
+pub fn parse_grpc_token(token: &str) -> Option<(&str, &str)> {
+    let tuple = token.split(TOKEN_SEP).collect::<Vec<_>>();
+    if tuple.len() == 2 {
+        return Some((tuple[0], tuple[1]));
+    }
+    None
+}
+
+/// mqtt 消息体, 无 header 可用, 鉴权信息放在前四行; broker 及其他订阅者可见, 只带签名
+pub fn mqtt_payload(ssr_auth: &str, user: &str, pass: &str, ts: u64, body: &[u8]) -> Vec<u8> {
+    let ts = ts.to_string();
+    let mut buf = format!("{}\n{}\n{}\n{}\n", ssr_auth, user, ts, sign(pass, &ts, body)).into_bytes();
+    buf.extend_from_slice(body);
+    buf
+}
+
+/// 解析 mqtt 消息体 => (ssr_auth, user, ts, signature, body)
+pub fn parse_mqtt_payload(buf: &[u8]) -> Option<(&str, &str, &str, &str, &[u8])> {
+    let mut parts = buf.splitn(5, |&b| b == b'\n');
+    let mut line = || std::str::from_utf8(parts.next()?).ok();
+    let (ssr_auth, user, ts, sig) = (line()?, line()?, line()?, line()?);
+    let body = parts.next()?;
+    Some((ssr_auth, user, ts, sig, body))
+}
+
+/// 常量时间比较, 用于令牌等, 长度不同直接返回 false
+pub fn ct_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn hmac(pass: &str, ts: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(pass.as_bytes()).expect("hmac accepts any key size");
+    mac.update(ts.as_bytes());
+    mac.update(b"\n");
+    mac.update(body);
+    mac
+}
+
+/// http 上报签名, body 为实际发送的内容(压缩后)
+pub fn sign(pass: &str, ts: &str, body: &[u8]) -> String {
+    hmac(pass, ts, body)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 校验签名, 常量时间比较
+pub fn verify_sign(pass: &str, ts: &str, body: &[u8], sig: &str) -> bool {
+    // from_str_radix 接受 '+' 前缀, 先限定为 hex 字符
+    if sig.len() % 2 != 0 || !sig.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return false;
+    }
+    let sig = (0..sig.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&sig[i..i + 2], 16).ok())
+        .collect::<Option<Vec<_>>>();
+    match sig {
+        Some(sig) => hmac(pass, ts, body).verify_slice(&sig).is_ok(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_verify() {
+        let sig = sign("p1", "1700000000", b"body");
+        assert_eq!(sig.len(), 64);
+        assert!(verify_sign("p1", "1700000000", b"body", &sig));
+        assert!(verify_sign("p1", "1700000000", b"body", &sig.to_uppercase()));
+        assert!(!verify_sign("p2", "1700000000", b"body", &sig));
+        assert!(!verify_sign("p1", "1700000001", b"body", &sig));
+        assert!(!verify_sign("p1", "1700000000", b"body2", &sig));
+    }
+
+    #[test]
+    fn test_verify_invalid_sign() {
+        let sig = sign("p1", "1", b"");
+        // 奇数长度
+        assert!(!verify_sign("p1", "1", b"", &sig[..63]));
+        assert!(!verify_sign("p1", "1", b"", &format!("{}0", sig)));
+        // 非 hex
+        assert!(!verify_sign("p1", "1", b"", &format!("zz{}", &sig[2..])));
+        assert!(!verify_sign("p1", "1", b"", &format!("+1{}", &sig[2..])));
+        // 非 ascii, 不能按字节切分
+        assert!(!verify_sign("p1", "1", b"", &format!("é{}", &sig[2..])));
+        assert!(!verify_sign("p1", "1", b"", ""));
+        // 截断的签名
+        assert!(!verify_sign("p1", "1", b"", &sig[..32]));
+    }
+
+    #[test]
+    fn test_mqtt_payload() {
+        let payload = mqtt_payload(GROUP, "g1", "p1", 1700000000, b"a\nb");
+        let (ssr_auth, user, ts, sig, body) = parse_mqtt_payload(&payload).unwrap();
+        assert_eq!((ssr_auth, user, ts, body), (GROUP, "g1", "1700000000", &b"a\nb"[..]));
+        assert!(verify_sign("p1", ts, body, sig));
+        assert!(!payload.windows(2).any(|w| w == b"p1"));
+    }
+}
@@ -0,0 +1,270 @@
+//! 上报数据构造
+
+use crate::server_status::{DiskUsage, DockerInfo, Gpu, IpInfo, MemoryDetail, StatRequest, SysInfo, Temperature};
+use crate::PROTO_VERSION;
+
+/// [`StatRequest`] 构造器, 未设置的字段为默认值
+#[derive(Debug, Clone)]
+pub struct StatRequestBuilder {
+    inner: StatRequest,
+}
+
+impl StatRequestBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            inner: StatRequest {
+                name: name.to_string(),
+                frame: "data".to_string(),
+                notify: true,
+                proto_version: PROTO_VERSION,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// agent 版本
+    pub fn version(mut self, version: &str) -> Self {
+        self.inner.version = version.to_string();
+        self
+    }
+
+    /// 组模式, 配合 gid 鉴权
+    pub fn group(mut self, gid: &str, alias: &str) -> Self {
+        self.inner.gid = gid.to_string();
+        self.inner.alias = alias.to_string();
+        self
+    }
+
+    pub fn host_type(mut self, host_type: &str) -> Self {
+        self.inner.r#type = host_type.to_string();
+        self
+    }
+
+    pub fn location(mut self, location: &str) -> Self {
+        self.inner.location = location.to_string();
+        self
+    }
+
+    pub fn notify(mut self, notify: bool) -> Self {
+        self.inner.notify = notify;
+        self
+    }
+
+    pub fn weight(mut self, weight: u64) -> Self {
+        self.inner.weight = weight;
+        self
+    }
+
+    pub fn online(mut self, online4: bool, online6: bool) -> Self {
+        self.inner.online4 = online4;
+        self.inner.online6 = online6;
+        self
+    }
+
+    /// 秒
+    pub fn uptime(mut self, uptime: u64) -> Self {
+        self.inner.uptime = uptime;
+        self
+    }
+
+    pub fn load(mut self, load_1: f64, load_5: f64, load_15: f64) -> Self {
+        self.inner.load_1 = load_1;
+        self.inner.load_5 = load_5;
+        self.inner.load_15 = load_15;
+        self
+    }
+
+    /// 百分比 0-100
+    pub fn cpu(mut self, cpu: f64) -> Self {
+        self.inner.cpu = cpu;
+        self
+    }
+
+    /// KiB
+    pub fn memory(mut self, total: u64, used: u64) -> Self {
+        self.inner.memory_total = total;
+        self.inner.memory_used = used;
+        self
+    }
+
+    /// 内存明细, KiB
+    pub fn memory_detail(mut self, detail: MemoryDetail) -> Self {
+        self.inner.memory_detail = Some(detail);
+        self
+    }
+
+    /// KiB
+    pub fn swap(mut self, total: u64, used: u64) -> Self {
+        self.inner.swap_total = total;
+        self.inner.swap_used = used;
+        self
+    }
+
+    /// MiB
+    pub fn hdd(mut self, total: u64, used: u64) -> Self {
+        self.inner.hdd_total = total;
+        self.inner.hdd_used = used;
+        self
+    }
+
+    /// 挂载点用量, MiB, 同时更新 hdd 合计
+    pub fn disk(mut self, disk: DiskUsage) -> Self {
+        self.inner.hdd_total += disk.total;
+        self.inner.hdd_used += disk.used;
+        self.inner.disks.push(disk);
+        self
+    }
+
+    /// 温度 ℃
+    pub fn temperature(mut self, temp: Temperature) -> Self {
+        self.inner.temps.push(temp);
+        self
+    }
+
+    /// GPU, 显存 MiB
+    pub fn gpu(mut self, gpu: Gpu) -> Self {
+        self.inner.gpus.push(gpu);
+        self
+    }
+
+    /// docker 容器
+    pub fn docker(mut self, docker: DockerInfo) -> Self {
+        self.inner.docker = Some(docker);
+        self
+    }
+
+    /// 各核心使用率 %
+    pub fn cpu_cores(mut self, cores: Vec<f64>) -> Self {
+        self.inner.cpu_cores = cores;
+        self
+    }
+
+    /// 连接数
+    pub fn sockets(mut self, tcp_established: u32, tcp_time_wait: u32, udp_sockets: u32) -> Self {
+        self.inner.tcp_established = tcp_established;
+        self.inner.tcp_time_wait = tcp_time_wait;
+        self.inner.udp_sockets = udp_sockets;
+        self
+    }
+
+    /// 连接跟踪表
+    pub fn conntrack(mut self, count: u64, max: u64) -> Self {
+        self.inner.conntrack_count = count;
+        self.inner.conntrack_max = max;
+        self
+    }
+
+    /// 自定义指标
+    pub fn metric(mut self, name: &str, value: f64) -> Self {
+        self.inner.metrics.insert(name.to_string(), value);
+        self
+    }
+
+    /// 硬盘读写速度 bytes/s
+    pub fn disk_io(mut self, read: u64, write: u64) -> Self {
+        self.inner.disk_read = read;
+        self.inner.disk_write = write;
+        self
+    }
+
+    /// 网速 bytes/s
+    pub fn network_speed(mut self, rx: u64, tx: u64) -> Self {
+        self.inner.network_rx = rx;
+        self.inner.network_tx = tx;
+        self
+    }
+
+    /// 总流量 bytes
+    pub fn network_traffic(mut self, total_in: u64, total_out: u64) -> Self {
+        self.inner.network_in = total_in;
+        self.inner.network_out = total_out;
+        self
+    }
+
+    /// tcp/udp/进程/线程数
+    pub fn tupd(mut self, tcp: u32, udp: u32, process: u32, thread: u32) -> Self {
+        self.inner.tcp = tcp;
+        self.inner.udp = udp;
+        self.inner.process = process;
+        self.inner.thread = thread;
+        self
+    }
+
+    pub fn custom(mut self, custom: &str) -> Self {
+        self.inner.custom = Some(custom.to_string());
+        self
+    }
+
+    pub fn extra(mut self, key: &str, value: &str) -> Self {
+        self.inner.extra.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn sys_info(mut self, sys_info: SysInfo) -> Self {
+        self.inner.sys_info = Some(sys_info);
+        self
+    }
+
+    pub fn ip_info(mut self, ip_info: IpInfo) -> Self {
+        self.inner.ip_info = Some(ip_info);
+        self
+    }
+
+    /// 上报时间戳, 秒
+    pub fn latest_ts(mut self, ts: u64) -> Self {
+        self.inner.latest_ts = ts;
+        self
+    }
+
+    pub fn build(self) -> StatRequest {
+        self.inner
+    }
+}
+
+/// [`SysInfo`] 构造器
+#[derive(Debug, Clone, Default)]
+pub struct SysInfoBuilder {
+    inner: SysInfo,
+}
+
+impl SysInfoBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// agent 名称及版本
+    pub fn agent(mut self, name: &str, version: &str) -> Self {
+        self.inner.name = name.to_string();
+        self.inner.version = version.to_string();
+        self
+    }
+
+    pub fn os(mut self, os_name: &str, os_arch: &str, os_family: &str, os_release: &str) -> Self {
+        self.inner.os_name = os_name.to_string();
+        self.inner.os_arch = os_arch.to_string();
+        self.inner.os_family = os_family.to_string();
+        self.inner.os_release = os_release.to_string();
+        self
+    }
+
+    pub fn kernel_version(mut self, kernel_version: &str) -> Self {
+        self.inner.kernel_version = kernel_version.to_string();
+        self
+    }
+
+    pub fn cpu(mut self, cpu_num: u32, cpu_brand: &str, cpu_vender_id: &str) -> Self {
+        self.inner.cpu_num = cpu_num;
+        self.inner.cpu_brand = cpu_brand.to_string();
+        self.inner.cpu_vender_id = cpu_vender_id.to_string();
+        self
+    }
+
+    pub fn host_name(mut self, host_name: &str) -> Self {
+        self.inner.host_name = host_name.to_string();
+        self
+    }
+
+    pub fn build(self) -> SysInfo {
+        self.inner
+    }
+}
@@ -0,0 +1,104 @@
+//! 上报数据编解码, 对应 http `Content-Type`, 压缩对应 `Content-Encoding`
+
+use prost::Message;
+use std::io::{Read, Write};
+
+use crate::server_status::StatRequest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Protobuf,
+    Json,
+}
+
+impl Encoding {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Protobuf => "application/octet-stream",
+            Encoding::Json => "application/json",
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "application/octet-stream" => Some(Encoding::Protobuf),
+            "application/json" => Some(Encoding::Json),
+            _ => None,
+        }
+    }
+}
+
+// 无 content type 时(如 mqtt)按内容判断, protobuf 首字节不会是 `{`
+pub fn sniff(buf: &[u8]) -> Encoding {
+    match buf.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => Encoding::Json,
+        _ => Encoding::Protobuf,
+    }
+}
+
+pub fn encode(stat: &StatRequest, encoding: Encoding) -> anyhow::Result<Vec<u8>> {
+    Ok(match encoding {
+        Encoding::Protobuf => stat.encode_to_vec(),
+        Encoding::Json => serde_json::to_vec(stat)?,
+    })
+}
+
+pub fn decode(buf: &[u8], encoding: Encoding) -> anyhow::Result<StatRequest> {
+    Ok(match encoding {
+        Encoding::Protobuf => StatRequest::decode(buf)?,
+        Encoding::Json => serde_json::from_slice(buf)?,
+    })
+}
+
+// 解压后上限, 防止压缩炸弹
+const MAX_DECOMPRESSED: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_content_encoding(content_encoding: &str) -> Option<Self> {
+        match content_encoding {
+            "gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+pub fn compress(buf: &[u8], compression: Compression) -> anyhow::Result<Vec<u8>> {
+    Ok(match compression {
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(buf)?;
+            encoder.finish()?
+        }
+        Compression::Zstd => zstd::encode_all(buf, 0)?,
+    })
+}
+
+pub fn decompress(buf: &[u8], compression: Compression) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match compression {
+        Compression::Gzip => flate2::read::GzDecoder::new(buf)
+            .take(MAX_DECOMPRESSED + 1)
+            .read_to_end(&mut out)?,
+        Compression::Zstd => zstd::Decoder::new(buf)?
+            .take(MAX_DECOMPRESSED + 1)
+            .read_to_end(&mut out)?,
+    };
+    if out.len() as u64 > MAX_DECOMPRESSED {
+        anyhow::bail!("decompressed body exceeds {} bytes", MAX_DECOMPRESSED);
+    }
+    Ok(out)
+}
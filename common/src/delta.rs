@@ -0,0 +1,57 @@
+//! 增量上报
+//!
+//! 客户端定期发送完整数据, 其间只发送相对该完整数据变化的字段(`delta = true`),
+//! 未变化的字段为默认值, protobuf 编码时不占空间; 变为默认值的字段名列在 `delta_reset` 中.
+//! 服务端保存每台主机最近一次完整数据, 将增量合并到其副本上, 丢失的增量不影响后续合并.
+
+use serde_json::Value;
+
+use crate::server_status::StatRequest;
+
+// 用于服务端定位主机, 增量中始终携带
+const IDENTITY: [&str; 2] = ["name", "gid"];
+const DELTA_FIELDS: [&str; 2] = ["delta", "delta_reset"];
+
+/// 由最近一次完整数据及本次数据生成增量
+pub fn diff(full: &StatRequest, cur: &StatRequest) -> anyhow::Result<StatRequest> {
+    let full = serde_json::to_value(full)?;
+    let mut out = serde_json::to_value(StatRequest::default())?;
+    let mut reset = Vec::new();
+    if let Value::Object(cur) = serde_json::to_value(cur)? {
+        for (k, v) in cur {
+            if DELTA_FIELDS.contains(&k.as_str()) || (!IDENTITY.contains(&k.as_str()) && full.get(&k) == Some(&v)) {
+                continue;
+            }
+            if out.get(&k) == Some(&v) {
+                reset.push(k.to_string());
+            }
+            out[k] = v;
+        }
+    }
+    let mut delta: StatRequest = serde_json::from_value(out)?;
+    delta.delta = true;
+    delta.delta_reset = reset;
+    Ok(delta)
+}
+
+/// 合并增量(json)到完整数据, 增量中为默认值且不在 delta_reset 中的字段视为未变化
+pub fn merge(full: &mut Value, delta: &Value) -> anyhow::Result<()> {
+    let defaults = serde_json::to_value(StatRequest::default())?;
+    let reset = delta["delta_reset"]
+        .as_array()
+        .map(|o| o.iter().filter_map(|k| k.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let delta = match delta.as_object() {
+        Some(o) => o,
+        None => anyhow::bail!("invalid delta"),
+    };
+    for (k, v) in delta {
+        if DELTA_FIELDS.contains(&k.as_str()) {
+            continue;
+        }
+        if reset.contains(&k.as_str()) || defaults.get(k) != Some(v) {
+            full[k] = v.clone();
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,47 @@
+//! ServerStatus-Rust 上报协议
+//!
+//! 包含 protobuf 生成的 [`server_status`] 类型, [`builder`] 构造上报数据,
+//! [`codec`] 编解码, [`delta`] 增量上报及 [`auth`] 鉴权约定, 可用于编写自定义 agent.
+//!
+//! ```no_run
+//! use stat_common::builder::StatRequestBuilder;
+//! use stat_common::codec;
+//!
+//! let stat = StatRequestBuilder::new("h1")
+//!     .online(true, false)
+//!     .uptime(3600)
+//!     .cpu(12.5)
+//!     .memory(1024, 512)
+//!     .extra("env", "prod")
+//!     .build();
+//! let body = codec::encode(&stat, codec::Encoding::Protobuf).unwrap();
+//! // POST body => http://127.0.0.1:8080/report, Content-Type: codec::Encoding::Protobuf.content_type()
+//! ```
+
+pub mod auth;
+pub mod builder;
+pub mod codec;
+pub mod delta;
+
 pub mod server_status {
     tonic::include_proto!("server_status");
+
+    /// 用于 grpc reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("server_status_descriptor");
+}
+
+// 协议版本, StatRequest 或协商能力变化时递增
+pub const PROTO_VERSION: u32 = 2;
+
+// 探测延迟直方图桶上界 ms, 末尾另有一个 >= 500ms 的桶
+pub const RTT_HISTOGRAM_BOUNDS: [u32; 7] = [5, 10, 20, 50, 100, 200, 500];
+
+// 可选能力, 通过 grpc Handshake 或 GET /api/capabilities 协商
+pub mod capability {
+    pub const JSON: &str = "json";
+    pub const EXTRA: &str = "extra";
+    pub const MSGPACK: &str = "msgpack";
+    pub const COMPRESSION: &str = "compression";
+    pub const DELTA: &str = "delta";
+    pub const PER_IFACE: &str = "per_iface";
 }
@@ -0,0 +1,205 @@
+#![deny(warnings)]
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::incident::G_INCIDENT_MGR;
+
+const ACKS_FILE: &str = "acks.json";
+
+pub static G_ALERT_MGR: Lazy<Mutex<AlertMgr>> = Lazy::new(Default::default);
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ack {
+    pub host: String,
+    pub event: String,
+    pub by: String,
+    pub ts: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct AlertMgr {
+    ack_expire: u64,
+    acks: HashMap<String, Ack>,
+}
+
+impl AlertMgr {
+    pub fn init(&mut self, ack_expire: u64) {
+        self.ack_expire = ack_expire;
+        let contents = fs::read_to_string(ACKS_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<HashMap<String, Ack>>(&contents) {
+            Ok(acks) => {
+                self.acks = acks;
+                trace!("load {} succ!", ACKS_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", ACKS_FILE, err);
+            }
+        }
+    }
+
+    fn save(&self) {
+        // 单元测试不落盘
+        if cfg!(test) {
+            return;
+        }
+        match serde_json::to_string(&self.acks) {
+            Ok(s) => {
+                if let Err(err) = fs::write(ACKS_FILE, s) {
+                    error!("save {} fail => {:?}", ACKS_FILE, err);
+                }
+            }
+            Err(err) => error!("save {} fail => {:?}", ACKS_FILE, err),
+        }
+    }
+
+    fn key(host: &str, event: &str) -> String {
+        format!("{}/{}", host, event)
+    }
+
+    pub fn ack(&mut self, host: &str, event: &str, by: &str) -> Ack {
+        let o = Ack {
+            host: host.to_string(),
+            event: event.to_string(),
+            by: by.to_string(),
+            ts: now_ts(),
+        };
+        info!("{} {} acked by {}", host, event, by);
+        G_INCIDENT_MGR
+            .lock()
+            .unwrap()
+            .record(host, "Ack", &format!("{} acked by {}", event, by));
+        self.acks.insert(Self::key(host, event), o.clone());
+        self.save();
+        o
+    }
+
+    pub fn unack(&mut self, host: &str, event: &str) -> Option<Ack> {
+        let o = self.acks.remove(&Self::key(host, event));
+        if o.is_some() {
+            self.save();
+        }
+        o
+    }
+
+    // 清除主机全部确认
+    pub fn unack_all(&mut self, host: &str) -> Vec<Ack> {
+        let keys: Vec<String> = self
+            .acks
+            .iter()
+            .filter(|(_, o)| o.host.eq(host))
+            .map(|(k, _)| k.to_string())
+            .collect();
+        let acks: Vec<Ack> = keys.iter().filter_map(|k| self.acks.remove(k)).collect();
+        if !acks.is_empty() {
+            self.save();
+        }
+        acks
+    }
+
+    pub fn get_ack(&self, host: &str, event: &str) -> Option<&Ack> {
+        self.acks
+            .get(&Self::key(host, event))
+            .filter(|o| self.ack_expire == 0 || o.ts + self.ack_expire >= now_ts())
+    }
+
+    pub fn is_acked(&self, host: &str, event: &str) -> bool {
+        self.get_ack(host, event).is_some()
+    }
+
+    // 用于面板展示
+    pub fn acked_by(&self, host: &str) -> Option<&Ack> {
+        let now = now_ts();
+        self.acks
+            .values()
+            .find(|o| o.host.eq(host) && (self.ack_expire == 0 || o.ts + self.ack_expire >= now))
+    }
+
+    pub fn list_acks(&mut self) -> Vec<Ack> {
+        let (expire, now) = (self.ack_expire, now_ts());
+        let len = self.acks.len();
+        self.acks.retain(|_, o| expire == 0 || o.ts + expire >= now);
+        if len != self.acks.len() {
+            self.save();
+        }
+        self.acks.values().cloned().collect()
+    }
+}
+
+// 持续性告警(服务/端口/证书等)的状态即 G_INCIDENT_MGR 中未结束的事件, 重启后延续, 不重复告警也不丢失恢复
+// 返回 Some(true) 为新触发, Some(false) 为已恢复
+pub fn edge(host: &str, kind: &str, firing: bool, detail: &str) -> Option<bool> {
+    let mut incident_mgr = G_INCIDENT_MGR.lock().unwrap();
+    match (firing, incident_mgr.is_open(host, kind)) {
+        (true, false) => {
+            incident_mgr.open(host, kind, detail);
+            Some(true)
+        }
+        (false, true) => {
+            incident_mgr.close(host, kind);
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+// 关闭以 prefix 开头但本次未出现的告警(监控项已移除), 返回其 kind
+pub fn sweep(host: &str, prefix: &str, seen: &HashSet<String>) -> Vec<String> {
+    let mut incident_mgr = G_INCIDENT_MGR.lock().unwrap();
+    let gone = incident_mgr
+        .open_kinds(host, prefix)
+        .into_iter()
+        .filter(|kind| !seen.contains(kind))
+        .collect::<Vec<_>>();
+    for kind in gone.iter() {
+        incident_mgr.close(host, kind);
+    }
+    gone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ack_until_node_up() {
+        let mut mgr = AlertMgr::default();
+        mgr.ack("h1", "NodeDown", "admin");
+        assert!(mgr.is_acked("h1", "NodeDown"));
+        assert!(!mgr.is_acked("h1", "Flapping"));
+        assert!(!mgr.is_acked("h2", "NodeDown"));
+        assert_eq!(mgr.acked_by("h1").map(|o| o.by.as_str()), Some("admin"));
+
+        // NodeUp 时清除确认, 再次下线需重新告警
+        assert!(mgr.unack("h1", "NodeDown").is_some());
+        assert!(!mgr.is_acked("h1", "NodeDown"));
+        assert!(mgr.acked_by("h1").is_none());
+        assert!(mgr.unack("h1", "NodeDown").is_none());
+    }
+
+    #[test]
+    fn test_ack_expire() {
+        let mut mgr = AlertMgr {
+            ack_expire: 60,
+            ..Default::default()
+        };
+        mgr.ack("h1", "NodeDown", "admin");
+        mgr.ack("h1", "Flapping", "admin");
+        mgr.acks.get_mut(&AlertMgr::key("h1", "NodeDown")).unwrap().ts -= 61;
+        assert!(!mgr.is_acked("h1", "NodeDown"));
+        assert!(mgr.is_acked("h1", "Flapping"));
+        assert_eq!(mgr.list_acks().len(), 1);
+        assert_eq!(mgr.unack_all("h1").len(), 1);
+        assert!(mgr.acks.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+#![deny(warnings)]
+// 备份/恢复, 配置及持久化状态打包为单个 json 文件, 用于迁移
+// 内存中的数据(最近一小时指标, ping 历史)不包含在内
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const FORMAT_VERSION: u32 = 1;
+const CONFIG_KEY: &str = "config.toml";
+// 工作目录下的状态文件
+const STATE_FILES: [&str; 8] = [
+    "stats.json",
+    "incidents.json",
+    "acks.json",
+    "paging.json",
+    "rotations.json",
+    "notify_queue.json",
+    "maintenance.json",
+    "disk_history.json",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub server_version: String,
+    pub created: String,
+    // 文件名 => 内容
+    pub files: BTreeMap<String, String>,
+}
+
+pub fn default_name() -> String {
+    format!("ssr-backup-{}.json", Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+pub fn create(config: &str) -> Result<Archive> {
+    let mut files = BTreeMap::new();
+    if let Ok(contents) = fs::read_to_string(config) {
+        files.insert(CONFIG_KEY.to_string(), contents);
+    }
+    for name in STATE_FILES {
+        if let Ok(contents) = fs::read_to_string(name) {
+            files.insert(name.to_string(), contents);
+        }
+    }
+    Ok(Archive {
+        version: FORMAT_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: Local::now().to_rfc3339(),
+        files,
+    })
+}
+
+// 恢复前需停止服务, 否则运行中的服务会覆盖状态文件; force 时已存在的文件重命名为 .<时间>.bak
+pub fn restore(archive: &Archive, config: &str, force: bool) -> Result<Vec<String>> {
+    if archive.version > FORMAT_VERSION {
+        return Err(anyhow!("unsupported backup version {}", archive.version));
+    }
+    let targets = archive
+        .files
+        .iter()
+        .filter_map(|(name, contents)| {
+            let path = if name.eq(CONFIG_KEY) {
+                config
+            } else {
+                STATE_FILES.iter().find(|o| name.eq(*o)).copied()?
+            };
+            Some((path, contents))
+        })
+        .collect::<Vec<_>>();
+
+    let exists = targets
+        .iter()
+        .filter(|(path, _)| Path::new(path).exists())
+        .map(|(path, _)| path.to_string())
+        .collect::<Vec<_>>();
+    if !force && !exists.is_empty() {
+        return Err(anyhow!(
+            "{} already exists, use --force to overwrite",
+            exists.join(", ")
+        ));
+    }
+
+    let bak_ts = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut restored = Vec::new();
+    for (path, contents) in targets {
+        if Path::new(path).exists() {
+            fs::rename(path, format!("{}.{}.bak", path, bak_ts))?;
+        }
+        fs::write(path, contents)?;
+        restored.push(path.to_string());
+    }
+    Ok(restored)
+}
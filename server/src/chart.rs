@@ -0,0 +1,95 @@
+#![deny(warnings)]
+// 告警附带的指标趋势图, cpu 红 / 内存 蓝 / 硬盘 绿, 不依赖字体
+// 仅 tgbot 支持, 自定义告警只画最近占用最高的指标, 掉线告警画全部
+use anyhow::Result;
+use plotters::prelude::*;
+
+use crate::history::{self, Point};
+
+const WIDTH: u32 = 480;
+const HEIGHT: u32 = 200;
+// 至少 3 个点才出图
+const MIN_POINTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Cpu,
+    Memory,
+    Hdd,
+}
+
+impl Metric {
+    fn value(self, o: &Point) -> f64 {
+        match self {
+            Metric::Cpu => o.cpu,
+            Metric::Memory => o.memory,
+            Metric::Hdd => o.hdd,
+        }
+        .min(100.0)
+    }
+
+    fn color(self) -> RGBColor {
+        match self {
+            Metric::Cpu => RED,
+            Metric::Memory => BLUE,
+            Metric::Hdd => GREEN,
+        }
+    }
+}
+
+const METRICS: [Metric; 3] = [Metric::Hdd, Metric::Memory, Metric::Cpu];
+
+// 最近一个点占用率最高的指标
+fn offending(o: &Point) -> Metric {
+    METRICS
+        .iter()
+        .copied()
+        .max_by(|a, b| a.value(o).total_cmp(&b.value(o)))
+        .unwrap_or(Metric::Cpu)
+}
+
+// all 为 false 时只画告警指标
+pub fn render_png(name: &str, all: bool) -> Result<Option<Vec<u8>>> {
+    let points = history::get(name);
+    if points.len() < MIN_POINTS {
+        return Ok(None);
+    }
+    let (start, end) = (points[0].ts, points[points.len() - 1].ts);
+    let metrics = if all {
+        METRICS.to_vec()
+    } else {
+        vec![offending(&points[points.len() - 1])]
+    };
+
+    let mut rgb = vec![0_u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut rgb, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(8)
+            .build_cartesian_2d(start..end, 0_f64..100_f64)?;
+
+        let grid = RGBColor(220, 220, 220);
+        for y in [25.0, 50.0, 75.0] {
+            chart.draw_series(LineSeries::new(vec![(start, y), (end, y)], &grid))?;
+        }
+        for m in metrics {
+            let width = if m == Metric::Cpu || !all { 2 } else { 1 };
+            chart.draw_series(LineSeries::new(
+                points.iter().map(|o| (o.ts, m.value(o))),
+                m.color().stroke_width(width),
+            ))?;
+        }
+        root.present()?;
+    }
+
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, WIDTH, HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&rgb)?;
+    }
+    Ok(Some(png_data))
+}
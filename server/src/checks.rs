@@ -0,0 +1,156 @@
+#![deny(warnings)]
+// 定时任务监控, 类似 healthchecks.io
+// 任务成功后请求 /ping/<token>, 开始时 /ping/<token>/start, 失败时 /ping/<token>/fail
+// 超过 interval + grace 未收到成功 ping 则告警
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub static G_CHECK_MGR: Lazy<Mutex<CheckMgr>> = Lazy::new(Default::default);
+
+fn default_grace() -> u64 {
+    300
+}
+fn default_as_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub token: String,
+    // 预期间隔(s)
+    pub interval: u64,
+    // 宽限时间(s)
+    #[serde(default = "default_grace")]
+    pub grace: u64,
+    #[serde(default = "default_as_true")]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Ping {
+    Success,
+    Start,
+    Fail,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckState {
+    pub name: String,
+    pub interval: u64,
+    pub grace: u64,
+    // new / up / started / down
+    pub status: String,
+    pub last_ping: u64,
+    pub last_start: u64,
+    // 最近一次 start => success 耗时(s)
+    pub last_duration: u64,
+    #[serde(skip)]
+    notify: bool,
+    #[serde(skip)]
+    failed: bool,
+    #[serde(skip)]
+    alerted: bool,
+}
+
+impl CheckState {
+    fn is_down(&self, now: u64) -> bool {
+        self.failed || (self.last_ping > 0 && self.last_ping + self.interval + self.grace < now)
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckEvent {
+    pub name: String,
+    pub down: bool,
+    pub notify: bool,
+    pub last_ping: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct CheckMgr {
+    token_map: HashMap<String, usize>,
+    states: Vec<CheckState>,
+}
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl CheckMgr {
+    pub fn init(&mut self, checks: &[Check]) {
+        for (idx, o) in checks.iter().enumerate() {
+            self.token_map.insert(o.token.to_string(), idx);
+            self.states.push(CheckState {
+                name: o.name.to_string(),
+                interval: o.interval,
+                grace: o.grace,
+                status: "new".to_string(),
+                notify: o.notify,
+                ..Default::default()
+            });
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.states.is_empty()
+    }
+
+    pub fn ping(&mut self, token: &str, ping: Ping) -> Option<String> {
+        let now = now_ts();
+        let o = self.states.get_mut(*self.token_map.get(token)?)?;
+        match ping {
+            Ping::Success => {
+                if o.last_start > 0 {
+                    o.last_duration = now.saturating_sub(o.last_start);
+                    o.last_start = 0;
+                }
+                o.last_ping = now;
+                o.failed = false;
+                o.status = "up".to_string();
+            }
+            Ping::Start => {
+                o.last_start = now;
+                if !o.alerted {
+                    o.status = "started".to_string();
+                }
+            }
+            Ping::Fail => {
+                o.last_ping = now;
+                o.last_start = 0;
+                o.failed = true;
+                o.status = "down".to_string();
+            }
+        }
+        Some(o.name.to_string())
+    }
+
+    // 返回状态变化
+    pub fn check(&mut self) -> Vec<CheckEvent> {
+        let now = now_ts();
+        let mut events = Vec::new();
+        for o in self.states.iter_mut() {
+            let down = o.is_down(now);
+            if down {
+                o.status = "down".to_string();
+            }
+            if down != o.alerted {
+                o.alerted = down;
+                events.push(CheckEvent {
+                    name: o.name.to_string(),
+                    down,
+                    notify: o.notify,
+                    last_ping: o.last_ping,
+                });
+            }
+        }
+        events
+    }
+
+    pub fn list(&self) -> Vec<CheckState> {
+        self.states.clone()
+    }
+}
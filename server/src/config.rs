@@ -1,12 +1,94 @@
 #![deny(warnings)]
 use anyhow::Result;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::sync::Mutex;
 use uuid::Uuid;
 
 use crate::notifier;
+use crate::rotation::G_ROTATION_MGR;
+
+// argon2 hash => 已验证密码的 hmac 摘要(进程内随机 key), 避免每次上报都计算 hash, 且不在内存中保留明文
+static G_VERIFIED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(Default::default);
+static G_VERIFY_KEY: Lazy<String> = Lazy::new(|| Uuid::new_v4().to_string());
+// argon2 hash => (窗口开始时间, 失败次数), 限制暴力尝试消耗 cpu
+static G_VERIFY_FAILS: Lazy<Mutex<HashMap<String, (u64, u32)>>> = Lazy::new(Default::default);
+const VERIFY_FAIL_WINDOW: u64 = 60;
+const VERIFY_FAIL_MAX: u32 = 5;
+
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+pub fn hash_pass(pass: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pass.as_bytes(), &salt)
+        .map(|o| o.to_string())
+        .map_err(|err| anyhow::anyhow!("{}", err))
+}
+
+// 明文密码, argon2 hash 无法还原
+pub fn plain_pass(stored: &str) -> Option<String> {
+    if is_hashed(stored) {
+        return None;
+    }
+    Some(stored.to_string())
+}
+
+fn verify_digest(stored: &str, pass: &str) -> String {
+    stat_common::auth::sign(&G_VERIFY_KEY, stored, pass.as_bytes())
+}
+
+fn now_ts() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// 兼容明文及 argon2 hash (ssr-server hash-password 生成)
+pub fn verify_pass(stored: &str, pass: &str) -> bool {
+    if !is_hashed(stored) {
+        return stat_common::auth::ct_eq(pass, stored);
+    }
+    let digest = verify_digest(stored, pass);
+    if let Some(o) = G_VERIFIED.lock().unwrap().get(stored) {
+        if stat_common::auth::ct_eq(&digest, o) {
+            return true;
+        }
+    }
+
+    let now = now_ts();
+    {
+        let mut fails = G_VERIFY_FAILS.lock().unwrap();
+        fails.retain(|_, (start, _)| *start + VERIFY_FAIL_WINDOW > now);
+        if fails.get(stored).map(|(_, n)| *n >= VERIFY_FAIL_MAX).unwrap_or(false) {
+            warn!("too many password failures, skip verify for {}s", VERIFY_FAIL_WINDOW);
+            return false;
+        }
+    }
+
+    let ok = PasswordHash::new(stored)
+        .map(|hash| Argon2::default().verify_password(pass.as_bytes(), &hash).is_ok())
+        .unwrap_or(false);
+    if ok {
+        G_VERIFIED.lock().unwrap().insert(stored.to_string(), digest);
+    } else {
+        G_VERIFY_FAILS
+            .lock()
+            .unwrap()
+            .entry(stored.to_string())
+            .or_insert((now, 0))
+            .1 += 1;
+    }
+    ok
+}
 
 fn default_as_true() -> bool {
     true
@@ -17,6 +99,12 @@ fn default_grpc_addr() -> String {
 fn default_http_addr() -> String {
     "0.0.0.0:8080".to_string()
 }
+fn default_notify_retry() -> u32 {
+    8
+}
+fn default_cert_notify_days() -> Vec<i64> {
+    vec![30, 7, 1]
+}
 fn default_workspace() -> String {
     "/opt/ServerStatus".to_string()
 }
@@ -35,6 +123,24 @@ pub struct Host {
     pub notify: bool,
     #[serde(default = "bool::default")]
     pub disabled: bool,
+    // 到期日 YYYY-MM-DD
+    #[serde(default = "Default::default")]
+    pub expire: String,
+    // 月流量配额 GiB, 0 不限
+    #[serde(default = "Default::default")]
+    pub traffic_quota: u64,
+    // push 模式, GET/POST /api/push/<push_token>, 兼容 Uptime Kuma
+    #[serde(default = "Default::default")]
+    pub push_token: String,
+    // push 间隔(s), 超过 2 倍间隔未收到判定下线
+    #[serde(default = "Default::default")]
+    pub push_interval: u64,
+    // 上报间隔(s), 通过上报响应下发给客户端, 0 为客户端默认(1s)
+    #[serde(default = "Default::default")]
+    pub report_interval: u64,
+    // 所属租户 id, 空为全局
+    #[serde(default = "Default::default")]
+    pub tenant: String,
 
     #[serde(skip_deserializing)]
     pub last_network_in: u64,
@@ -65,6 +171,13 @@ pub struct HostGroup {
     pub pos: usize,
     #[serde(default = "Default::default", skip_serializing)]
     pub weight: u64,
+    // /i/<gid>?token=xxx 安装脚本令牌, 空为仅管理员可用
+    #[serde(default = "Default::default", skip_serializing)]
+    pub install_token: String,
+    #[serde(default = "Default::default")]
+    pub report_interval: u64,
+    #[serde(default = "Default::default")]
+    pub tenant: String,
 }
 
 impl HostGroup {
@@ -79,6 +192,8 @@ impl HostGroup {
             notify: self.notify,
             pos: self.pos,
             weight: self.weight,
+            report_interval: self.report_interval,
+            tenant: self.tenant.to_owned(),
             ..Default::default()
         }
     }
@@ -90,10 +205,75 @@ pub struct Config {
     pub http_addr: String,
     #[serde(default = "default_grpc_addr")]
     pub grpc_addr: String,
+    // 兼容原版 ServerStatus 客户端, 空为关闭
+    #[serde(default = "Default::default")]
+    pub legacy_addr: String,
     #[serde(default = "Default::default")]
     pub notify_interval: u64,
     #[serde(default = "Default::default")]
     pub offline_threshold: u64,
+    // flapping
+    #[serde(default = "Default::default")]
+    pub flap_window: u64,
+    #[serde(default = "Default::default")]
+    pub flap_count: u32,
+    // ack
+    #[serde(default = "Default::default")]
+    pub ack_expire: u64,
+    // incident
+    #[serde(default = "Default::default")]
+    pub max_incidents: usize,
+    // digest, HH:MM, 空为关闭
+    #[serde(default = "Default::default")]
+    pub digest_time: String,
+    #[serde(skip)]
+    pub digest_at: Option<chrono::NaiveTime>,
+    #[serde(default = "crate::digest::default_digest_tpl")]
+    pub digest_tpl: String,
+    #[serde(default = "Default::default")]
+    pub digest_top: usize,
+    // 客户端最低版本, 默认为服务端版本
+    #[serde(default = "Default::default")]
+    pub min_client_version: String,
+    // 定时(小时)通知版本过旧的主机, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub outdated_notify_interval: u64,
+    // 通知发送失败重试次数, 退避 30s 起翻倍, 0 为关闭
+    #[serde(default = "default_notify_retry")]
+    pub notify_retry: u32,
+    // 死信列表最大条数
+    #[serde(default = "Default::default")]
+    pub max_dead_letters: usize,
+    // 通知出站代理 http/https/socks5/socks5h, 各通知方式可单独配置 proxy 覆盖
+    #[serde(default = "Default::default")]
+    pub notify_proxy: String,
+    // 面板默认排序 weight/group/name/expire/traffic
+    #[serde(default = "Default::default")]
+    pub sort_by: String,
+    // 面板迷你趋势图时长(分钟), 0 为关闭
+    #[serde(default = "Default::default")]
+    pub sparkline_minutes: u64,
+    // 按硬盘用量增长率预计 N 天内写满时告警, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub disk_full_days: u64,
+    // 客户端与服务端时钟偏差超过 N 秒时标记并告警, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub clock_skew_threshold: u64,
+    // 交换分区使用率超过 N% 时标记并告警, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub swap_threshold: u64,
+    // 任一挂载点 inode 使用率超过 N% 时告警, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub inode_threshold: u64,
+    // 有 ipv6 流量的主机连续 N 分钟未收到 ipv6 数据而 ipv4 正常时告警, 0 为关闭
+    #[serde(default = "Default::default")]
+    pub ipv6_idle_minutes: u64,
+    // 客户端 --check-cert 证书剩余天数依次低于各阈值时告警, 空为关闭
+    #[serde(default = "default_cert_notify_days")]
+    pub cert_notify_days: Vec<i64>,
+    // 维护窗口 webhook 令牌, Authorization: Bearer <token> 或 ?token=, 空为仅管理员可用
+    #[serde(default = "Default::default", skip_serializing)]
+    pub maintenance_token: String,
     // admin user & pass
     pub admin_user: Option<String>,
     pub admin_pass: Option<String>,
@@ -108,12 +288,25 @@ pub struct Config {
     pub log: notifier::log::Config,
     #[serde(default = "Default::default")]
     pub webhook: notifier::webhook::Config,
+    #[serde(default = "Default::default")]
+    pub pagerduty: notifier::pagerduty::Config,
+    #[serde(default = "Default::default")]
+    pub opsgenie: notifier::opsgenie::Config,
+    #[serde(default = "Default::default")]
+    pub mqtt: crate::mqtt::Config,
+    #[serde(default = "Default::default")]
+    pub tls: crate::tls::Config,
 
     #[serde(default = "Default::default")]
     pub hosts: Vec<Host>,
     #[serde(default = "Default::default")]
     pub hosts_group: Vec<HostGroup>,
     #[serde(default = "Default::default")]
+    pub tenants: Vec<crate::tenant::Tenant>,
+    // cron job checks
+    #[serde(default = "Default::default")]
+    pub checks: Vec<crate::checks::Check>,
+    #[serde(default = "Default::default")]
     pub group_gc: u64,
 
     // deploy
@@ -121,6 +314,9 @@ pub struct Config {
     pub server_url: String,
     #[serde(default = "default_workspace")]
     pub workspace: String,
+    // 托管客户端目录, 非空时安装脚本从 /dl/ 下载
+    #[serde(default = "Default::default")]
+    pub client_dir: String,
 
     #[serde(skip_deserializing)]
     pub hosts_map: HashMap<String, Host>,
@@ -132,22 +328,66 @@ pub struct Config {
 impl Config {
     pub fn auth(&self, user: &str, pass: &str) -> bool {
         if let Some(o) = self.hosts_map.get(user) {
-            return pass.eq(o.password.as_str());
+            return G_ROTATION_MGR.lock().unwrap().verify(user, false, &o.password, pass);
         }
         false
     }
     pub fn group_auth(&self, gid: &str, pass: &str) -> bool {
         if let Some(o) = self.hosts_group_map.get(gid) {
-            return pass.eq(o.password.as_str());
+            return G_ROTATION_MGR.lock().unwrap().verify(gid, true, &o.password, pass);
         }
         false
     }
+    // mTLS 客户端证书 CN
+    pub fn cert_auth(&self, cn: &str, group_auth: bool) -> bool {
+        if group_auth {
+            self.hosts_group_map.contains_key(cn)
+        } else {
+            self.hosts_map.contains_key(cn)
+        }
+    }
     pub fn admin_auth(&self, user: &str, pass: &str) -> bool {
         if let (Some(u), Some(p)) = (self.admin_user.as_ref(), self.admin_pass.as_ref()) {
-            return user.eq(u.as_str()) && pass.eq(p.as_str());
+            return user.eq(u.as_str()) && verify_pass(p, pass);
         }
         false
     }
+    pub fn get_tenant(&self, id: &str) -> Option<&crate::tenant::Tenant> {
+        self.tenants.iter().find(|o| o.id.eq(id))
+    }
+    pub fn get_push_host(&self, token: &str) -> Option<&Host> {
+        if token.is_empty() {
+            return None;
+        }
+        self.hosts_map.values().find(|o| o.push_token.eq(token))
+    }
+    // 下发给客户端的上报间隔
+    pub fn report_interval(&self, user: &str, group_auth: bool) -> u64 {
+        if group_auth {
+            self.hosts_group_map.get(user).map(|o| o.report_interval).unwrap_or(0)
+        } else {
+            self.hosts_map.get(user).map(|o| o.report_interval).unwrap_or(0)
+        }
+    }
+    // 非 x.y.z 格式(telegraf/legacy 等第三方上报)不判断
+    pub fn is_outdated(&self, version: &str) -> bool {
+        let parse = |v: &str| {
+            v.trim_start_matches('v')
+                .split('.')
+                .map(|n| n.parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>()
+        };
+        match (parse(version), parse(&self.min_client_version)) {
+            // 补齐长度, 1.2 与 1.2.0 相同
+            (Some(mut cur), Some(mut min)) => {
+                let len = cur.len().max(min.len());
+                cur.resize(len, 0);
+                min.resize(len, 0);
+                cur < min
+            }
+            _ => false,
+        }
+    }
     // pub fn get_host(&self, name: &str) -> Option<&Host> {
     //     self.hosts_map.get(name)
     // }
@@ -157,7 +397,13 @@ impl Config {
 }
 
 pub fn from_str(content: &str) -> Option<Config> {
-    let mut o = toml::from_str::<Config>(content).unwrap();
+    let mut value = toml::from_str::<toml::Value>(content).unwrap();
+    if let Err(err) = crate::secret::resolve(&mut value) {
+        eprintln!("❌ resolve secret error => {:?}", err);
+        return None;
+    }
+    crate::tenant::merge_notify_config(&mut value);
+    let mut o = value.try_into::<Config>().unwrap();
     o.hosts_map = HashMap::new();
 
     for (idx, host) in o.hosts.iter_mut().enumerate() {
@@ -178,6 +424,43 @@ pub fn from_str(content: &str) -> Option<Config> {
         o.hosts_group_map.insert(group.gid.to_owned(), group.clone());
     }
 
+    // 签名上报需要服务端持有明文密码
+    let hashed = o
+        .hosts
+        .iter()
+        .filter(|h| is_hashed(&h.password))
+        .map(|h| h.name.as_str())
+        .chain(
+            o.hosts_group
+                .iter()
+                .filter(|g| is_hashed(&g.password))
+                .map(|g| g.gid.as_str()),
+        )
+        .collect::<Vec<_>>();
+    if !hashed.is_empty() {
+        eprintln!(
+            "⚠️ argon2 password for {}, --sign and mqtt reports from them will be rejected",
+            hashed.join(",")
+        );
+    }
+
+    for t in o.tenants.iter() {
+        if t.users.is_empty() && t.token.is_empty() {
+            eprintln!("❌ tenant `{}` requires users or token", t.id);
+            return None;
+        }
+    }
+    for (kind, id, tenant) in o
+        .hosts
+        .iter()
+        .map(|h| ("host", &h.name, &h.tenant))
+        .chain(o.hosts_group.iter().map(|g| ("group", &g.gid, &g.tenant)))
+    {
+        if !tenant.is_empty() && !o.tenants.iter().any(|t| t.id.eq(tenant)) {
+            eprintln!("⚠️ {} `{}` unknown tenant `{}`", kind, id, tenant);
+        }
+    }
+
     if o.offline_threshold < 30 {
         o.offline_threshold = 30;
     }
@@ -187,6 +470,56 @@ pub fn from_str(content: &str) -> Option<Config> {
     if o.group_gc < 30 {
         o.group_gc = 30;
     }
+    if o.flap_window == 0 {
+        o.flap_window = 600;
+    }
+    if o.ack_expire == 0 {
+        o.ack_expire = 86400;
+    }
+    if o.max_incidents == 0 {
+        o.max_incidents = 1000;
+    }
+    if o.max_dead_letters == 0 {
+        o.max_dead_letters = 100;
+    }
+    if o.digest_top == 0 {
+        o.digest_top = 3;
+    }
+    if !o.digest_time.trim().is_empty() {
+        match chrono::NaiveTime::parse_from_str(o.digest_time.trim(), "%H:%M") {
+            Ok(t) => o.digest_at = Some(t),
+            Err(err) => eprintln!("⚠️ invalid digest_time `{}` => {}, digest disabled", o.digest_time, err),
+        }
+    }
+    if !crate::stats::SORT_MODES.contains(&o.sort_by.as_str()) {
+        if !o.sort_by.is_empty() {
+            eprintln!("⚠️ unknown sort_by `{}`, use weight", o.sort_by);
+        }
+        o.sort_by = "weight".to_string();
+    }
+    let max_minutes = crate::history::WINDOW_SECS / 60;
+    if o.sparkline_minutes > max_minutes {
+        eprintln!(
+            "⚠️ sparkline_minutes `{}` too large, use {}",
+            o.sparkline_minutes, max_minutes
+        );
+        o.sparkline_minutes = max_minutes;
+    }
+    if o.swap_threshold > 100 {
+        eprintln!("⚠️ swap_threshold `{}` too large, use 100", o.swap_threshold);
+        o.swap_threshold = 100;
+    }
+    if o.inode_threshold > 100 {
+        eprintln!("⚠️ inode_threshold `{}` too large, use 100", o.inode_threshold);
+        o.inode_threshold = 100;
+    }
+    // 从大到小, 依次触发
+    o.cert_notify_days.retain(|&d| d >= 0);
+    o.cert_notify_days.sort_unstable_by(|a, b| b.cmp(a));
+    o.cert_notify_days.dedup();
+    if o.min_client_version.is_empty() {
+        o.min_client_version = env!("CARGO_PKG_VERSION").to_string();
+    }
 
     if o.admin_user.is_none() || o.admin_user.as_ref()?.is_empty() {
         o.admin_user = Some("admin".to_string());
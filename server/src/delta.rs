@@ -0,0 +1,48 @@
+#![deny(warnings)]
+// 增量上报, 保存每台主机最近一次完整数据, 增量合并到其副本上再走正常上报流程
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+// gid/name => 最近一次完整数据
+static G_FULL: Lazy<Mutex<HashMap<String, Value>>> = Lazy::new(Default::default);
+
+pub fn key(gid: &str, name: &str) -> String {
+    format!("{}/{}", gid, name)
+}
+
+// 无完整数据(如服务端重启后)时丢弃并返回 None, 由调用方通知客户端下一次完整上报
+pub fn apply(data: Value) -> Option<Value> {
+    if data["replay"].as_bool().unwrap_or(false) {
+        return Some(data);
+    }
+    let key = key(
+        data["gid"].as_str().unwrap_or_default(),
+        data["name"].as_str().unwrap_or_default(),
+    );
+    if !data["delta"].as_bool().unwrap_or(false) {
+        G_FULL.lock().unwrap().insert(key, data.clone());
+        return Some(data);
+    }
+
+    let mut full = match G_FULL.lock().unwrap().get(&key) {
+        Some(o) => o.clone(),
+        None => {
+            debug!("no full report for {}, drop delta", key);
+            return None;
+        }
+    };
+    match stat_common::delta::merge(&mut full, &data) {
+        Ok(_) => Some(full),
+        Err(err) => {
+            error!("merge delta {} error => {:?}", key, err);
+            None
+        }
+    }
+}
+
+// 清除已移除主机(如分组主机被回收)的完整数据, keys 为 gid/name
+pub fn prune(keys: &HashSet<String>) {
+    G_FULL.lock().unwrap().retain(|k, _| keys.contains(k));
+}
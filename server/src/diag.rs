@@ -0,0 +1,43 @@
+#![deny(warnings)]
+// 客户端诊断包 (stat_client diag --upload), 仅保存在内存中, 每个主机保留最近一份
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_SIZE: usize = 1 << 20;
+
+static G_DIAG: Lazy<Mutex<HashMap<String, Entry>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub host: String,
+    pub time: u64,
+    pub size: usize,
+    #[serde(skip_serializing)]
+    pub bundle: serde_json::Value,
+}
+
+pub fn put(host: &str, size: usize, bundle: serde_json::Value) {
+    info!("recv diag bundle from {}, {} bytes", host, size);
+    G_DIAG.lock().unwrap().insert(
+        host.to_string(),
+        Entry {
+            host: host.to_string(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            size,
+            bundle,
+        },
+    );
+}
+
+pub fn list() -> Vec<Entry> {
+    let mut list = G_DIAG.lock().unwrap().values().cloned().collect::<Vec<_>>();
+    list.sort_by(|a, b| b.time.cmp(&a.time));
+    list
+}
+
+pub fn get(host: &str) -> Option<serde_json::Value> {
+    G_DIAG.lock().unwrap().get(host).map(|o| o.bundle.clone())
+}
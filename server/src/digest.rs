@@ -0,0 +1,118 @@
+#![deny(warnings)]
+use chrono::{Local, NaiveDate};
+use minijinja::context;
+use serde::Serialize;
+
+use crate::jinja::{add_template, render_template};
+use crate::payload::{HostStat, StatsResp};
+
+const KIND: &str = "digest";
+const EXPIRE_DAYS: i64 = 30;
+
+pub fn default_digest_tpl() -> String {
+    r#"📊 ServerStatus 每日汇总 {{ date }}
+🟢 在线 {{ online }}/{{ total }}
+{% if offline %}🔴 离线: {{ offline | join(", ") }}
+{% endif %}🔥 CPU: {% for o in top_cpu %}{{ o.name }} {{ o.value }}%  {% endfor %}
+📶 月流量: {% for o in top_traffic %}{{ o.name }} {{ o.value }}  {% endfor %}
+{% if quota %}📦 流量配额: {% for o in quota %}{{ o.name }} {{ o.value }}  {% endfor %}
+{% endif %}{% if expiries %}⏰ 即将到期: {% for o in expiries %}{{ o.name }} {{ o.value }}  {% endfor %}
+{% endif %}"#
+        .to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct Item {
+    name: String,
+    value: String,
+}
+
+fn gib(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0 / 1024.0
+}
+
+pub fn month_traffic(o: &HostStat) -> u64 {
+    o.network_in.saturating_sub(o.last_network_in) + o.network_out.saturating_sub(o.last_network_out)
+}
+
+// 剩余天数, 未配置或格式错误返回 None
+pub fn expire_days(o: &HostStat) -> Option<i64> {
+    NaiveDate::parse_from_str(&o.expire, "%Y-%m-%d")
+        .ok()
+        .map(|d| (d - Local::now().date_naive()).num_days())
+}
+
+pub fn init(tpl: &str) {
+    add_template(KIND, "tpl", tpl.to_string());
+}
+
+pub fn render(resp: &StatsResp, top: usize) -> anyhow::Result<String> {
+    let servers = resp.servers.iter().collect::<Vec<_>>();
+    let offline = servers
+        .iter()
+        .filter(|o| !o.online4 && !o.online6)
+        .map(|o| o.alias.to_string())
+        .collect::<Vec<_>>();
+
+    let mut by_cpu = servers.clone();
+    by_cpu.sort_by(|a, b| b.cpu.partial_cmp(&a.cpu).unwrap_or(std::cmp::Ordering::Equal));
+    let top_cpu = by_cpu
+        .iter()
+        .take(top)
+        .map(|o| Item {
+            name: o.alias.to_string(),
+            value: format!("{:.0}", o.cpu),
+        })
+        .collect::<Vec<_>>();
+
+    let mut by_traffic = servers.clone();
+    by_traffic.sort_by_key(|o| std::cmp::Reverse(month_traffic(o)));
+    let top_traffic = by_traffic
+        .iter()
+        .take(top)
+        .map(|o| Item {
+            name: o.alias.to_string(),
+            value: format!("{:.2}G", gib(month_traffic(o))),
+        })
+        .collect::<Vec<_>>();
+
+    let quota = servers
+        .iter()
+        .filter(|o| o.traffic_quota > 0)
+        .map(|o| Item {
+            name: o.alias.to_string(),
+            value: format!(
+                "{:.0}% ({:.2}G/{}G)",
+                100.0 * gib(month_traffic(o)) / o.traffic_quota as f64,
+                gib(month_traffic(o)),
+                o.traffic_quota
+            ),
+        })
+        .collect::<Vec<_>>();
+
+    let expiries = servers
+        .iter()
+        .filter_map(|o| expire_days(o).map(|days| (o, days)))
+        .filter(|(_, days)| *days <= EXPIRE_DAYS)
+        .map(|(o, days)| Item {
+            name: o.alias.to_string(),
+            value: format!("{} ({}天)", o.expire, days),
+        })
+        .collect::<Vec<_>>();
+
+    render_template(
+        KIND,
+        "tpl",
+        context!(
+            date => Local::now().format("%Y-%m-%d").to_string(),
+            total => servers.len(),
+            online => servers.len() - offline.len(),
+            offline => offline,
+            top_cpu => top_cpu,
+            top_traffic => top_traffic,
+            quota => quota,
+            expiries => expiries,
+        ),
+        false,
+    )
+}
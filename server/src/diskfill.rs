@@ -0,0 +1,147 @@
+#![deny(warnings)]
+// 硬盘写满预测, 定时记录各挂载点用量, 按线性增长率估算写满天数, 用于发现日志缓慢增长等绝对阈值发现不了的问题
+// 告警状态由 alert::edge 维护
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::sync::Mutex;
+
+use crate::payload::HostStat;
+
+const DISK_HISTORY_FILE: &str = "disk_history.json";
+pub const SAMPLE_SECS: u64 = 600;
+const WINDOW_SECS: u64 = 7 * 86400;
+// 数据不足一天不预测
+const MIN_SPAN_SECS: u64 = 86400;
+
+pub static G_DISKFILL_MGR: Lazy<Mutex<DiskFillMgr>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Sample {
+    ts: u64,
+    // MB
+    used: u64,
+    total: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Forecast {
+    pub days: f64,
+    // MB/天
+    pub per_day: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DiskFillMgr {
+    // 主机名|挂载点, 旧版本客户端无挂载点明细, 为主机名
+    samples: HashMap<String, VecDeque<Sample>>,
+}
+
+fn sample_key(name: &str, mount: &str) -> String {
+    if mount.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}|{}", name, mount)
+    }
+}
+
+// (挂载点, 已用, 总量) MB, 旧版本客户端挂载点为空
+fn disk_usage(stat: &HostStat) -> Vec<(String, u64, u64)> {
+    if stat.disks.is_empty() {
+        return vec![(String::new(), stat.hdd_used, stat.hdd_total)];
+    }
+    stat.disks
+        .iter()
+        .filter(|o| o.total > 0)
+        .map(|o| (o.mount.to_string(), o.used, o.total))
+        .collect()
+}
+
+impl DiskFillMgr {
+    pub fn init(&mut self) {
+        let contents = fs::read_to_string(DISK_HISTORY_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<DiskFillMgr>(&contents) {
+            Ok(o) => {
+                *self = o;
+                trace!("load {} succ!", DISK_HISTORY_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", DISK_HISTORY_FILE, err);
+            }
+        }
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(self) {
+            Ok(s) => {
+                if let Err(err) = fs::write(DISK_HISTORY_FILE, s) {
+                    error!("save {} fail => {:?}", DISK_HISTORY_FILE, err);
+                }
+            }
+            Err(err) => error!("save {} fail => {:?}", DISK_HISTORY_FILE, err),
+        }
+    }
+
+    fn record(&mut self, key: &str, used: u64, total: u64, now: u64) {
+        let samples = self.samples.entry(key.to_string()).or_default();
+        // 扩容/换盘后重新计算
+        if samples.back().map(|o| o.total != total).unwrap_or(false) {
+            samples.clear();
+        }
+        samples.push_back(Sample { ts: now, used, total });
+        while samples.front().map(|o| o.ts + WINDOW_SECS < now).unwrap_or(false) {
+            samples.pop_front();
+        }
+    }
+
+    // 最小二乘拟合增长率, 不增长时为 None
+    fn forecast(&self, key: &str) -> Option<Forecast> {
+        let samples = self.samples.get(key)?;
+        let (first, last) = (samples.front()?, samples.back()?);
+        if last.ts < first.ts + MIN_SPAN_SECS {
+            return None;
+        }
+        let n = samples.len() as f64;
+        let mean_x = samples.iter().map(|o| (o.ts - first.ts) as f64).sum::<f64>() / n;
+        let mean_y = samples.iter().map(|o| o.used as f64).sum::<f64>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for o in samples {
+            let dx = (o.ts - first.ts) as f64 - mean_x;
+            cov += dx * (o.used as f64 - mean_y);
+            var += dx * dx;
+        }
+        if var <= 0.0 {
+            return None;
+        }
+        let per_day = cov / var * 86400.0;
+        if per_day <= 0.0 {
+            return None;
+        }
+        Some(Forecast {
+            days: last.total.saturating_sub(last.used) as f64 / per_day,
+            per_day,
+        })
+    }
+
+    // 记录本次用量, 返回各挂载点 (主机, 挂载点, 预计 days 天内写满时为 Some)
+    pub fn update(&mut self, servers: &[HostStat], days: u64, now: u64) -> Vec<(HostStat, String, Option<Forecast>)> {
+        let mut result = Vec::new();
+        for stat in servers {
+            for (mount, used, total) in disk_usage(stat) {
+                let key = sample_key(&stat.name, &mount);
+                self.record(&key, used, total, now);
+                let forecast = self.forecast(&key).filter(|o| o.days <= days as f64);
+                result.push((stat.clone(), mount, forecast));
+            }
+        }
+        // 已删除的主机及挂载点
+        self.samples
+            .retain(|_, o| o.back().map(|s| s.ts + WINDOW_SECS >= now).unwrap_or(false));
+        self.save();
+        result
+    }
+}
@@ -0,0 +1,148 @@
+#![deny(warnings)]
+use std::collections::{HashMap, VecDeque};
+
+use crate::notifier::Event;
+use crate::payload::HostStat;
+
+#[derive(Debug, Default)]
+struct FlapState {
+    transitions: VecDeque<u64>,
+    flapping: bool,
+    // 抖动期间被压制的最后一次上下线事件，恢复稳定后补发
+    last: Option<(Event, HostStat)>,
+}
+
+pub enum FlapAction {
+    Pass,
+    Suppress,
+    Start(u32),
+}
+
+pub struct FlapDetector {
+    window: u64,
+    count: usize,
+    hosts: HashMap<String, FlapState>,
+}
+
+impl FlapDetector {
+    pub fn new(window: u64, count: u32) -> Self {
+        Self {
+            window,
+            count: count as usize,
+            hosts: HashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.count > 0
+    }
+
+    pub fn is_flapping(&self, name: &str) -> bool {
+        self.hosts.get(name).map(|o| o.flapping).unwrap_or(false)
+    }
+
+    // 记录一次上下线状态变化
+    pub fn on_transition(&mut self, e: &Event, stat: &HostStat, now: u64) -> FlapAction {
+        if !self.enabled() {
+            return FlapAction::Pass;
+        }
+
+        let window = self.window;
+        let st = self.hosts.entry(stat.name.to_string()).or_default();
+        st.transitions.push_back(now);
+        st.transitions.retain(|&ts| ts + window >= now);
+
+        if st.flapping {
+            st.last = Some((e.clone(), stat.clone()));
+            return FlapAction::Suppress;
+        }
+        if st.transitions.len() >= self.count {
+            st.flapping = true;
+            st.last = Some((e.clone(), stat.clone()));
+            return FlapAction::Start(st.transitions.len() as u32);
+        }
+        FlapAction::Pass
+    }
+
+    // window 内无状态变化则判定恢复稳定，返回需补发的最后状态
+    pub fn check_stable(&mut self, now: u64) -> Vec<(Event, HostStat)> {
+        let window = self.window;
+        let mut resumed = Vec::new();
+        for (name, st) in self.hosts.iter_mut() {
+            st.transitions.retain(|&ts| ts + window >= now);
+            if st.flapping && st.transitions.is_empty() {
+                info!("{} flapping end", name);
+                st.flapping = false;
+                if let Some(last) = st.last.take() {
+                    resumed.push(last);
+                }
+            }
+        }
+        self.hosts.retain(|_, st| st.flapping || !st.transitions.is_empty());
+        resumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(name: &str) -> HostStat {
+        HostStat {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flap_start_stable() {
+        let mut detector = FlapDetector::new(60, 3);
+        let h1 = stat("h1");
+        assert!(matches!(
+            detector.on_transition(&Event::NodeDown, &h1, 100),
+            FlapAction::Pass
+        ));
+        assert!(matches!(
+            detector.on_transition(&Event::NodeUp, &h1, 110),
+            FlapAction::Pass
+        ));
+        assert!(matches!(
+            detector.on_transition(&Event::NodeDown, &h1, 120),
+            FlapAction::Start(3)
+        ));
+        assert!(detector.is_flapping("h1"));
+        assert!(!detector.is_flapping("h2"));
+        assert!(matches!(
+            detector.on_transition(&Event::NodeUp, &h1, 130),
+            FlapAction::Suppress
+        ));
+
+        // window 内仍有状态变化
+        assert!(detector.check_stable(180).is_empty());
+        assert!(detector.is_flapping("h1"));
+
+        // 恢复稳定, 补发最后一次状态
+        let resumed = detector.check_stable(191);
+        assert_eq!(resumed.len(), 1);
+        assert!(matches!(resumed[0].0, Event::NodeUp));
+        assert_eq!(resumed[0].1.name, "h1");
+        assert!(!detector.is_flapping("h1"));
+        assert!(detector.hosts.is_empty());
+        assert!(matches!(
+            detector.on_transition(&Event::NodeDown, &h1, 200),
+            FlapAction::Pass
+        ));
+    }
+
+    #[test]
+    fn test_flap_disabled() {
+        let mut detector = FlapDetector::new(60, 0);
+        for ts in 0..10 {
+            assert!(matches!(
+                detector.on_transition(&Event::NodeDown, &stat("h1"), ts),
+                FlapAction::Pass
+            ));
+        }
+        assert!(detector.check_stable(10).is_empty());
+    }
+}
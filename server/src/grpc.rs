@@ -1,23 +1,85 @@
 // #![allow(unused)]
+use tonic::codec::CompressionEncoding;
+use tonic::service::interceptor::InterceptedService;
 use tonic::{transport::Server, Request, Response, Status};
 
 use stat_common::server_status;
 use stat_common::server_status::server_status_server::{ServerStatus, ServerStatusServer};
-use stat_common::server_status::StatRequest;
+use stat_common::server_status::{Capabilities, StatRequest};
+use stat_common::{auth, capability, PROTO_VERSION};
 
+use crate::rotation::G_ROTATION_MGR;
+use crate::tls;
 use crate::G_CONFIG;
 use crate::G_STATS_MGR;
 
 #[derive(Default)]
 pub struct ServerStatusSrv {}
 
+pub fn server_capabilities() -> Capabilities {
+    Capabilities {
+        proto_version: PROTO_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: vec![
+            capability::JSON.to_string(),
+            capability::EXTRA.to_string(),
+            capability::MSGPACK.to_string(),
+            capability::COMPRESSION.to_string(),
+            capability::DELTA.to_string(),
+        ],
+    }
+}
+
 #[tonic::async_trait]
 impl ServerStatus for ServerStatusSrv {
     async fn report(&self, request: Request<StatRequest>) -> Result<Response<server_status::Response>, Status> {
+        let group_auth = request
+            .metadata()
+            .get(auth::SSR_AUTH)
+            .map(|v| v.to_str().unwrap_or_default().eq(auth::GROUP))
+            .unwrap_or(false);
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|token| token.to_str().ok())
+            .unwrap_or_default();
+        let cert_user = cert_user(&request, group_auth);
+        // 证书鉴权时不允许冒用其他主机/组上报
+        if let Some(cn) = cert_user.as_ref() {
+            let stat = request.get_ref();
+            if !tls::stat_matches_cn(cn, group_auth, &stat.name, &stat.gid) {
+                warn!("report {}/{} mismatch client cert `{}`", stat.gid, stat.name, cn);
+                return Err(Status::permission_denied("name/gid mismatch client certificate"));
+            }
+        }
+        let (new_password, report_interval) = match auth::parse_grpc_token(token) {
+            // 证书鉴权无密码轮换
+            _ if cert_user.is_some() => (
+                String::new(),
+                G_CONFIG
+                    .get()
+                    .map(|cfg| cfg.report_interval(cert_user.as_deref().unwrap_or_default(), group_auth))
+                    .unwrap_or(0),
+            ),
+            Some((user, pass)) => (
+                G_ROTATION_MGR
+                    .lock()
+                    .unwrap()
+                    .hint(user, group_auth, pass)
+                    .unwrap_or_default(),
+                G_CONFIG
+                    .get()
+                    .map(|cfg| cfg.report_interval(user, group_auth))
+                    .unwrap_or(0),
+            ),
+            None => Default::default(),
+        };
+
+        let mut need_full = false;
         if let Some(mgr) = G_STATS_MGR.get() {
             match serde_json::to_value(request.get_ref()) {
                 Ok(v) => {
-                    let _ = mgr.report(v);
+                    need_full = !mgr.report(v).unwrap_or(true);
                 }
                 Err(err) => {
                     error!("serde_json::to_value err => {:?}", err);
@@ -28,29 +90,58 @@ impl ServerStatus for ServerStatusSrv {
         Ok(Response::new(server_status::Response {
             code: 0,
             message: "ok".to_string(),
+            new_password,
+            report_interval,
+            need_full,
         }))
     }
+
+    async fn handshake(&self, request: Request<Capabilities>) -> Result<Response<Capabilities>, Status> {
+        let client = request.get_ref();
+        info!(
+            "handshake client v{} proto {} caps {:?}",
+            client.version, client.proto_version, client.capabilities
+        );
+        Ok(Response::new(server_capabilities()))
+    }
+}
+
+// mTLS, 证书 CN 已配置为主机/组时返回 CN
+fn cert_user<T>(req: &Request<T>, group_auth: bool) -> Option<String> {
+    // tonic 中 peer 证书为 DER
+    let certs = req.peer_certs()?;
+    let cn = tls::peer_cn(certs.first()?.get_ref())?;
+    let cfg = G_CONFIG.get()?;
+    if cfg.cert_auth(&cn, group_auth) {
+        return Some(cn);
+    }
+    None
 }
 
 fn check_auth(req: Request<()>) -> Result<Request<()>, Status> {
     let mut group_auth = false;
-    req.metadata().get("ssr-auth").map(|v| {
+    req.metadata().get(auth::SSR_AUTH).map(|v| {
         v.to_str().map(|s| {
-            group_auth = s.eq("group");
+            group_auth = s.eq(auth::GROUP);
         })
     });
 
+    if cert_user(&req, group_auth).is_some() {
+        return Ok(req);
+    }
+    if G_CONFIG.get().map(|cfg| cfg.tls.require_client_cert).unwrap_or(false) {
+        return Err(Status::unauthenticated("client certificate required"));
+    }
+
     match req.metadata().get("authorization") {
         Some(token) => {
-            let tuple = token.to_str().unwrap_or("").split("@_@").collect::<Vec<_>>();
-
-            if tuple.len() == 2 {
+            if let Some((user, pass)) = auth::parse_grpc_token(token.to_str().unwrap_or("")) {
                 if let Some(cfg) = G_CONFIG.get() {
                     if group_auth {
-                        if cfg.group_auth(tuple[0], tuple[1]) {
+                        if cfg.group_auth(user, pass) {
                             return Ok(req);
                         }
-                    } else if cfg.auth(tuple[0], tuple[1]) {
+                    } else if cfg.auth(user, pass) {
                         return Ok(req);
                     }
                 }
@@ -67,8 +158,32 @@ pub async fn serv_grpc(addr: &str) -> anyhow::Result<()> {
     let sock_addr = addr.parse().unwrap();
     let sss = ServerStatusSrv::default();
     eprintln!("🚀 listening on grpc://{}", sock_addr);
-    let svc = ServerStatusServer::with_interceptor(sss, check_auth);
-    Server::builder()
+    // 客户端 --compress 时 grpc 使用 gzip
+    let svc = InterceptedService::new(
+        ServerStatusServer::new(sss).accept_compressed(CompressionEncoding::Gzip),
+        check_auth,
+    );
+
+    // grpc.health.v1, 兼容 grpc-health-probe, 无需鉴权
+    let (mut health_reporter, health_svc) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<ServerStatusServer<ServerStatusSrv>>()
+        .await;
+
+    // reflection, 供 grpcurl 等工具使用
+    let reflection_svc = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(server_status::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::proto::GRPC_HEALTH_V1_FILE_DESCRIPTOR_SET)
+        .build()?;
+
+    let mut builder = Server::builder();
+    if let Some(cfg) = G_CONFIG.get().map(|cfg| &cfg.tls).filter(|o| o.enabled()) {
+        eprintln!("🔒 grpc tls enabled");
+        builder = builder.tls_config(tls::grpc_config(cfg)?)?;
+    }
+    builder
+        .add_service(health_svc)
+        .add_service(reflection_svc)
         .add_service(svc)
         .serve(sock_addr)
         .await
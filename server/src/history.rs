@@ -0,0 +1,356 @@
+#![deny(warnings)]
+// 最近一小时的主机指标, 用于告警图表及面板迷你趋势图; 最近一天的 ping 延迟分布, 用于 smokeping 图及热力图
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::payload::HostStat;
+
+const SAMPLE_SECS: u64 = 10;
+// 补发数据允许的时钟超前
+const MAX_REPLAY_SKEW: u64 = 60;
+pub const WINDOW_SECS: u64 = 3600;
+pub const PING_BUCKET_SECS: u64 = 300;
+const PING_WINDOW_SECS: u64 = 86400;
+const PING_MAX_SAMPLES: usize = 600;
+pub const PING_TARGETS: [&str; 3] = ["cu", "ct", "cm"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Point {
+    pub ts: u64,
+    pub cpu: f64,
+    pub memory: f64,
+    pub hdd: f64,
+    pub network_rx: u64,
+    pub network_tx: u64,
+}
+
+// 迷你趋势图, 按列存储以减小 json 体积, 每 SAMPLE_SECS 一个点
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Sparkline {
+    pub ts: Vec<u64>,
+    pub cpu: Vec<f64>,
+    pub memory: Vec<f64>,
+    pub network_rx: Vec<u64>,
+    pub network_tx: Vec<u64>,
+}
+
+static G_HISTORY: Lazy<Mutex<HashMap<String, VecDeque<Point>>>> = Lazy::new(Default::default);
+
+// 每 PING_BUCKET_SECS 一个桶, 延迟 ms, loss %
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PingBucket {
+    pub ts: u64,
+    pub min: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub max: f64,
+    pub loss: f64,
+}
+
+#[derive(Debug, Default)]
+struct PingSeries {
+    buckets: VecDeque<PingBucket>,
+    cur_ts: u64,
+    cur_time: Vec<f64>,
+    cur_loss: Vec<f64>,
+}
+
+impl PingSeries {
+    fn push(&mut self, ts: u64, time: f64, loss: f64) {
+        let bucket_ts = ts - ts % PING_BUCKET_SECS;
+        if bucket_ts != self.cur_ts {
+            if let Some(o) = self.summary() {
+                self.buckets.push_back(o);
+            }
+            self.cur_ts = bucket_ts;
+            self.cur_time.clear();
+            self.cur_loss.clear();
+            while self
+                .buckets
+                .front()
+                .map(|o| o.ts + PING_WINDOW_SECS < bucket_ts)
+                .unwrap_or(false)
+            {
+                self.buckets.pop_front();
+            }
+        }
+        if self.cur_time.len() < PING_MAX_SAMPLES {
+            self.cur_time.push(time);
+            self.cur_loss.push(loss);
+        }
+    }
+
+    fn summary(&self) -> Option<PingBucket> {
+        if self.cur_time.is_empty() {
+            return None;
+        }
+        let mut sorted = self.cur_time.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let pct = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Some(PingBucket {
+            ts: self.cur_ts,
+            min: pct(0.0),
+            p25: pct(0.25),
+            median: pct(0.5),
+            p75: pct(0.75),
+            max: pct(1.0),
+            loss: self.cur_loss.iter().sum::<f64>() / self.cur_loss.len() as f64,
+        })
+    }
+}
+
+static G_PING_HISTORY: Lazy<Mutex<HashMap<(String, &'static str), PingSeries>>> = Lazy::new(Default::default);
+
+fn record_ping(stat: &HostStat) {
+    let mut history = G_PING_HISTORY.lock().unwrap();
+    for (target, time, loss) in [
+        (PING_TARGETS[0], stat.time_10010, stat.ping_10010),
+        (PING_TARGETS[1], stat.time_189, stat.ping_189),
+        (PING_TARGETS[2], stat.time_10086, stat.ping_10086),
+    ] {
+        // 延迟与丢包均为 0 为客户端未启用该探测(--disable-ping), 不记录
+        if time <= 0.0 && loss <= 0.0 {
+            continue;
+        }
+        history
+            .entry((stat.name.to_string(), target))
+            .or_default()
+            .push(stat.latest_ts, time, loss);
+    }
+}
+
+// 每 PING_BUCKET_SECS 一个桶, counts 为桶内各延迟区间的成功探测次数, 区间见 RTT_HISTOGRAM_BOUNDS
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatBucket {
+    pub ts: u64,
+    pub counts: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+struct HeatSeries {
+    label: String,
+    buckets: VecDeque<HeatBucket>,
+    // 上次上报的累计值
+    last: Vec<u64>,
+}
+
+impl HeatSeries {
+    fn push(&mut self, ts: u64, histogram: &[u64]) {
+        // 客户端重启或桶数变化时累计值重置
+        let reset = self.last.len() != histogram.len() || self.last.iter().zip(histogram).any(|(a, b)| a > b);
+        let delta = if reset {
+            histogram.to_vec()
+        } else {
+            histogram.iter().zip(&self.last).map(|(a, b)| a - b).collect()
+        };
+        self.last = histogram.to_vec();
+        // 首次上报只记录基准, 避免把启动以来的累计值计入当前桶
+        if reset && self.buckets.is_empty() {
+            return;
+        }
+
+        let bucket_ts = ts - ts % PING_BUCKET_SECS;
+        match self.buckets.back_mut() {
+            Some(o) if o.ts == bucket_ts && o.counts.len() == delta.len() => {
+                o.counts.iter_mut().zip(&delta).for_each(|(a, b)| *a += b);
+            }
+            _ => self.buckets.push_back(HeatBucket {
+                ts: bucket_ts,
+                counts: delta,
+            }),
+        }
+        while self
+            .buckets
+            .front()
+            .map(|o| o.ts + PING_WINDOW_SECS < bucket_ts)
+            .unwrap_or(false)
+        {
+            self.buckets.pop_front();
+        }
+    }
+}
+
+// (主机, 探测名)
+static G_HEAT_HISTORY: Lazy<Mutex<HashMap<(String, String), HeatSeries>>> = Lazy::new(Default::default);
+
+fn record_heatmap(stat: &HostStat) {
+    let mut history = G_HEAT_HISTORY.lock().unwrap();
+    for o in stat.carrier_probes.iter().chain(stat.probes.iter()) {
+        if o.histogram.is_empty() {
+            continue;
+        }
+        let series = history.entry((stat.name.to_string(), o.name.to_string())).or_default();
+        series.label = if o.label.is_empty() {
+            o.name.to_string()
+        } else {
+            o.label.to_string()
+        };
+        series.push(stat.latest_ts, &o.histogram);
+    }
+    // 客户端已移除的探测
+    history.retain(|(host, target), _| {
+        !host.eq(&stat.name)
+            || stat
+                .carrier_probes
+                .iter()
+                .chain(stat.probes.iter())
+                .any(|o| o.name.eq(target) && !o.histogram.is_empty())
+    });
+}
+
+// (探测名, 展示名, 桶), 三网在前
+pub fn heatmap_series(name: &str) -> Vec<(String, String, Vec<HeatBucket>)> {
+    let history = G_HEAT_HISTORY.lock().unwrap();
+    let mut list = history
+        .iter()
+        .filter(|((host, _), _)| host.eq(name))
+        .map(|((_, target), o)| {
+            (
+                target.to_string(),
+                o.label.to_string(),
+                o.buckets.iter().cloned().collect(),
+            )
+        })
+        .collect::<Vec<_>>();
+    list.sort_by_key(|(target, _, _)| {
+        let idx = PING_TARGETS
+            .iter()
+            .position(|o| *o == target.as_str())
+            .unwrap_or(PING_TARGETS.len());
+        (idx, target.to_string())
+    });
+    list
+}
+
+// 兼容 10010/189/10086 及运营商英文名
+pub fn ping_target(target: &str) -> Option<&'static str> {
+    match target.to_lowercase().as_str() {
+        "cu" | "10010" | "unicom" => Some(PING_TARGETS[0]),
+        "ct" | "189" | "telecom" => Some(PING_TARGETS[1]),
+        "cm" | "10086" | "mobile" => Some(PING_TARGETS[2]),
+        _ => None,
+    }
+}
+
+// 含当前未结束的桶
+pub fn ping_series(name: &str, target: &str) -> Vec<PingBucket> {
+    let target = match ping_target(target) {
+        Some(o) => o,
+        None => return Vec::new(),
+    };
+    match G_PING_HISTORY.lock().unwrap().get(&(name.to_string(), target)) {
+        Some(o) => o.buckets.iter().copied().chain(o.summary()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    if total > 0 {
+        100.0 * used as f64 / total as f64
+    } else {
+        0.0
+    }
+}
+
+fn point(stat: &HostStat) -> Point {
+    Point {
+        ts: stat.latest_ts,
+        cpu: stat.cpu,
+        memory: percent(stat.memory_used, stat.memory_total),
+        hdd: percent(stat.hdd_used, stat.hdd_total),
+        network_rx: stat.network_rx,
+        network_tx: stat.network_tx,
+    }
+}
+
+// 清除已不存在的主机(如分组主机被回收)
+pub fn prune(hosts: &HashSet<String>) {
+    G_HISTORY.lock().unwrap().retain(|name, _| hosts.contains(name));
+    G_PING_HISTORY
+        .lock()
+        .unwrap()
+        .retain(|(name, _), _| hosts.contains(name));
+    G_HEAT_HISTORY
+        .lock()
+        .unwrap()
+        .retain(|(name, _), _| hosts.contains(name));
+}
+
+pub fn record(stat: &HostStat) {
+    record_ping(stat);
+    record_heatmap(stat);
+
+    let mut history = G_HISTORY.lock().unwrap();
+    let points = history.entry(stat.name.to_string()).or_default();
+    if points
+        .back()
+        .map(|o| o.ts + SAMPLE_SECS > stat.latest_ts)
+        .unwrap_or(false)
+    {
+        return;
+    }
+    points.push_back(point(stat));
+    while points
+        .front()
+        .map(|o| o.ts + WINDOW_SECS < stat.latest_ts)
+        .unwrap_or(false)
+    {
+        points.pop_front();
+    }
+}
+
+// 客户端离线期间的补发数据, 按时间插入; 超出窗口或附近已有采样点的丢弃, ping 延迟分布不补
+pub fn record_replay(stat: &HostStat) {
+    // 采集时间由客户端给出, 时钟错误时可能在未来或很久以前, 未来的点会使后续实时采样被当作重复丢弃
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    if stat.latest_ts > now + MAX_REPLAY_SKEW || stat.latest_ts + WINDOW_SECS < now {
+        warn!("drop replay of {} at {}, now {}", stat.name, stat.latest_ts, now);
+        return;
+    }
+    let mut history = G_HISTORY.lock().unwrap();
+    let points = history.entry(stat.name.to_string()).or_default();
+    let latest_ts = points.back().map(|o| o.ts).unwrap_or(stat.latest_ts);
+    if stat.latest_ts + WINDOW_SECS < latest_ts {
+        return;
+    }
+    let idx = points.partition_point(|o| o.ts < stat.latest_ts);
+    let near = |i: usize| {
+        points
+            .get(i)
+            .map(|o| o.ts.abs_diff(stat.latest_ts) < SAMPLE_SECS)
+            .unwrap_or(false)
+    };
+    if near(idx) || (idx > 0 && near(idx - 1)) {
+        return;
+    }
+    points.insert(idx, point(stat));
+}
+
+pub fn get(name: &str) -> Vec<Point> {
+    G_HISTORY
+        .lock()
+        .unwrap()
+        .get(name)
+        .map(|points| points.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+// 最近 minutes 分钟
+pub fn sparkline(name: &str, minutes: u64) -> Option<Sparkline> {
+    let history = G_HISTORY.lock().unwrap();
+    let points = history.get(name)?;
+    let since = points.back()?.ts.saturating_sub(minutes * 60);
+    let mut o = Sparkline::default();
+    for p in points.iter().filter(|p| p.ts > since) {
+        o.ts.push(p.ts);
+        o.cpu.push((p.cpu * 10.0).round() / 10.0);
+        o.memory.push((p.memory * 10.0).round() / 10.0);
+        o.network_rx.push(p.network_rx);
+        o.network_tx.push(p.network_tx);
+    }
+    Some(o)
+}
@@ -1,14 +1,24 @@
 // #![allow(unused)]
 use http_auth_basic::Credentials;
-use hyper::{header, Body, Request, Response, StatusCode};
+use hyper::{header, Body, Method, Request, Response, StatusCode};
 use minijinja::context;
 use prettytable::Table;
+use serde::Serialize;
+use stat_common::server_status::StatRequest;
 use std::collections::HashMap;
 use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::alert::G_ALERT_MGR;
+use crate::checks::{Ping, G_CHECK_MGR};
+use crate::incident::{IncidentFilter, IncidentView, G_INCIDENT_MGR};
 use crate::jinja;
+use crate::maintenance::G_MAINTENANCE_MGR;
+use crate::notifier::retry::G_RETRY_MGR;
+use crate::rotation::G_ROTATION_MGR;
 use crate::Asset;
 use crate::G_CONFIG;
+use crate::G_CONFIG_PATH;
 use crate::G_STATS_MGR;
 
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
@@ -31,6 +41,735 @@ fn is_admin(req: &Request<Body>) -> bool {
     false
 }
 
+fn query_params(req: &Request<Body>) -> HashMap<String, String> {
+    req.uri()
+        .query()
+        .map(|v| url::form_urlencoded::parse(v.as_bytes()).into_owned().collect())
+        .unwrap_or_else(HashMap::new)
+}
+
+fn unauthorized() -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .header(header::WWW_AUTHENTICATE, "Basic realm=\"Restricted\"")
+        .status(StatusCode::UNAUTHORIZED)
+        .body(UNAUTHORIZED.into())?)
+}
+
+fn json_resp<T: Serialize>(status: StatusCode, data: &T) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(data)?))?)
+}
+
+// GET /json/stats.json?version=<v>, version=outdated 为低于 min_client_version 的主机
+pub async fn get_stats_filtered(req: Request<Body>, tenant: &str) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let resp = G_STATS_MGR.get().unwrap().get_stats();
+    let o = resp.lock().unwrap();
+    let servers = o
+        .servers
+        .iter()
+        .filter(|s| s.tenant.eq(tenant))
+        .filter(|s| match params.get("version").map(String::as_str) {
+            None | Some("") => true,
+            Some("outdated") => s.outdated,
+            Some(v) => s.version.trim_start_matches('v').eq(v.trim_start_matches('v')),
+        })
+        .collect::<Vec<_>>();
+    json_resp(
+        StatusCode::OK,
+        &serde_json::json!({"updated": o.updated, "servers": servers}),
+    )
+}
+
+// 租户面板 /t/<id>/, 租户用户 basic auth / 令牌 / 管理员可访问
+pub async fn tenant_dispatch(req: Request<Body>) -> Result<Response<Body>> {
+    let path = req.uri().path().trim_start_matches("/t/").to_string();
+    let (id, sub) = path.split_once('/').unwrap_or((&path, ""));
+    let tenant = match G_CONFIG.get().and_then(|cfg| cfg.get_tenant(id)) {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?)
+        }
+    };
+    // 相对路径资源需以 / 结尾
+    if !path.contains('/') {
+        return Ok(Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(header::LOCATION, format!("/t/{}/", id))
+            .body(Body::empty())?);
+    }
+
+    let cookie_name = format!("ssr_tenant_{}", id);
+    let query_token = query_params(&req).get("token").cloned().unwrap_or_default();
+    let cookie_token = req
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            v.split(';')
+                .filter_map(|kv| kv.trim().split_once('='))
+                .find(|(k, _)| cookie_name.eq(k))
+                .map(|(_, v)| v.to_string())
+        })
+        .unwrap_or_default();
+    let user_ok = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| Credentials::from_header(v.to_str().ok()?.to_string()).ok())
+        .map(|o| tenant.auth(&o.user_id, &o.password))
+        .unwrap_or(false);
+    let token_ok = tenant.auth_token(&query_token) || tenant.auth_token(&cookie_token);
+    if !(user_ok || token_ok || is_admin(&req)) {
+        return unauthorized();
+    }
+
+    match sub {
+        "" | "index.html" => {
+            let mut builder = Response::builder().header(header::CONTENT_TYPE, "text/html; charset=utf-8");
+            if tenant.auth_token(&query_token) {
+                builder = builder.header(
+                    header::SET_COOKIE,
+                    format!(
+                        "{}={}; Path=/t/{}/; HttpOnly; SameSite=Lax",
+                        cookie_name, query_token, id
+                    ),
+                );
+            }
+            Ok(builder.body(Body::from(Asset::get("/index.html").unwrap().data))?)
+        }
+        "json/stats.json" => get_stats_filtered(req, &tenant.id).await,
+        "api/checks" => json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": []})),
+        "smokeping" => get_smokeping_page(req, &tenant.id).await,
+        _ if sub.starts_with("js/") || sub.starts_with("css/") || sub.starts_with("img/") || sub.eq("favicon.ico") => {
+            let asset_path = format!("/{}", sub);
+            match Asset::get(&asset_path) {
+                Some(data) => Ok(Response::builder()
+                    .header(
+                        header::CONTENT_TYPE,
+                        mime_guess::from_path(&asset_path)
+                            .first_raw()
+                            .unwrap_or("application/octet-stream"),
+                    )
+                    .body(Body::from(data.data))?),
+                None => Ok(Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?),
+            }
+        }
+        _ => Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?),
+    }
+}
+
+// 根据主机当前状态推断待确认的告警
+fn current_alert_event(host: &str) -> Option<&'static str> {
+    let resp = G_STATS_MGR.get()?.get_stats();
+    let o = resp.lock().ok()?;
+    let event = o.servers.iter().find(|s| s.name.eq(host)).map(|s| {
+        if s.flapping {
+            "Flapping"
+        } else if !s.online4 && !s.online6 {
+            "NodeDown"
+        } else {
+            "Custom"
+        }
+    });
+    event
+}
+
+pub async fn ack_alert(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+
+    let params = query_params(&req);
+    let host = params.get("host").map(|s| s.trim()).unwrap_or_default();
+    let event = params.get("event").map(|s| s.trim()).filter(|s| !s.is_empty());
+    if host.is_empty() {
+        return json_resp(
+            StatusCode::BAD_REQUEST,
+            &serde_json::json!({"code": 1, "message": "invalid host"}),
+        );
+    }
+
+    let mut alert_mgr = G_ALERT_MGR.lock().unwrap();
+    if req.method() == Method::DELETE {
+        // 未指定 event 时清除该主机全部确认
+        let acks = match event {
+            Some(event) => alert_mgr.unack(host, event).into_iter().collect(),
+            None => alert_mgr.unack_all(host),
+        };
+        for o in acks.iter() {
+            G_INCIDENT_MGR.lock().unwrap().record(host, "Unack", &o.event);
+        }
+        return json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": acks}));
+    }
+
+    let event = match event.or_else(|| current_alert_event(host)) {
+        Some(e) => e,
+        None => {
+            return json_resp(
+                StatusCode::BAD_REQUEST,
+                &serde_json::json!({"code": 1, "message": "invalid host"}),
+            )
+        }
+    };
+
+    let by = params
+        .get("by")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("admin");
+    let ack = alert_mgr.ack(host, event, by);
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": ack}))
+}
+
+// 维护窗口, CI/CD 部署前后调用, 期间不发送告警, 令牌仅接受 Authorization: Bearer 头
+// GET 列表; POST ?host=|gid=&duration=3600&reason=&by= 开始; DELETE 或 POST &action=stop 结束
+pub async fn maintenance(req: Request<Body>) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let cfg = G_CONFIG.get().unwrap();
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let token_ok = !cfg.maintenance_token.is_empty() && stat_common::auth::ct_eq(&cfg.maintenance_token, token);
+    if !token_ok && !is_admin(&req) {
+        return unauthorized();
+    }
+
+    let mut maintenance_mgr = G_MAINTENANCE_MGR.lock().unwrap();
+    if req.method() == Method::GET {
+        let list = maintenance_mgr.list();
+        return json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": list}));
+    }
+
+    // 组模式主机动态注册, 按当前上报的主机校验
+    let host_exists = |name: &str| {
+        cfg.hosts_map.contains_key(name)
+            || G_STATS_MGR
+                .get()
+                .map(|mgr| mgr.get_stats().lock().unwrap().servers.iter().any(|o| o.name.eq(name)))
+                .unwrap_or(false)
+    };
+    let (id, group) = match (params.get("host"), params.get("gid")) {
+        (Some(host), _) if host_exists(host) => (host, false),
+        (_, Some(gid)) if cfg.hosts_group_map.contains_key(gid) => (gid, true),
+        _ => {
+            return json_resp(
+                StatusCode::NOT_FOUND,
+                &serde_json::json!({"code": 1, "msg": "host/gid not found"}),
+            );
+        }
+    };
+
+    if req.method() == Method::DELETE || params.get("action").map(|s| s.eq("stop")).unwrap_or(false) {
+        let ok = maintenance_mgr.stop(id, group);
+        return json_resp(StatusCode::OK, &serde_json::json!({"code": if ok { 0 } else { 1 }}));
+    }
+    let duration = params
+        .get("duration")
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(3600);
+    let reason = params.get("reason").map(|s| s.trim()).unwrap_or_default();
+    let by = params
+        .get("by")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(if token_ok { "webhook" } else { "admin" });
+    let o = maintenance_mgr.start(id, group, duration, reason, by);
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": o}))
+}
+
+// 下载备份, 恢复使用 stat_server restore <file>
+pub async fn get_backup(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let config = G_CONFIG_PATH.get().map(String::as_str).unwrap_or_default();
+    let archive = crate::backup::create(config)?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(r#"attachment; filename="{}""#, crate::backup::default_name()),
+        )
+        .body(Body::from(serde_json::to_string_pretty(&archive)?))?)
+}
+
+// GET /api/diag 列表, GET /api/diag/<host> 诊断包内容
+pub async fn get_diag(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let host = req.uri().path().trim_start_matches("/api/diag").trim_matches('/');
+    if host.is_empty() {
+        return json_resp(
+            StatusCode::OK,
+            &serde_json::json!({"code": 0, "data": crate::diag::list()}),
+        );
+    }
+    match crate::diag::get(host) {
+        Some(bundle) => json_resp(StatusCode::OK, &bundle),
+        None => json_resp(
+            StatusCode::NOT_FOUND,
+            &serde_json::json!({"code": 1, "msg": format!("no diag bundle for `{}`", host)}),
+        ),
+    }
+}
+
+// GET /api/logs?level=warn&q=&limit=200
+pub async fn get_logs(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let params = query_params(&req);
+    let level = params
+        .get("level")
+        .and_then(|s| s.parse::<log::LevelFilter>().ok())
+        .unwrap_or(log::LevelFilter::Trace);
+    let q = params.get("q").map(|s| s.trim()).unwrap_or_default();
+    let limit = params.get("limit").and_then(|s| s.parse::<usize>().ok()).unwrap_or(500);
+    let lines = crate::logbuf::query(level, q, limit);
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": lines}))
+}
+
+pub async fn get_logs_page(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let params = query_params(&req);
+    let level = params.get("level").map(String::as_str).unwrap_or("info");
+    let q = params.get("q").map(String::as_str).unwrap_or_default();
+    Ok(
+        jinja::render_template(KIND, "logs", context!(level => level, q => q), false)
+            .map(|contents| {
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+                    .body(Body::from(contents))
+            })?
+            .unwrap_or(
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(INTERNAL_SERVER_ERROR.into())?,
+            ),
+    )
+}
+
+pub async fn get_acks(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let acks = G_ALERT_MGR.lock().unwrap().list_acks();
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": acks}))
+}
+
+// Uptime Kuma push 兼容, /api/push/<token>?status=up&msg=OK&ping=
+pub async fn push_heartbeat(req: Request<Body>) -> Result<Response<Body>> {
+    let token = req.uri().path().trim_start_matches("/api/push/").trim_end_matches('/');
+    let host = match G_CONFIG.get().and_then(|cfg| cfg.get_push_host(token)) {
+        Some(host) => host,
+        None => {
+            return json_resp(
+                StatusCode::NOT_FOUND,
+                &serde_json::json!({"ok": false, "msg": "Monitor not found or not active."}),
+            )
+        }
+    };
+
+    let params = query_params(&req);
+    let up = !params.get("status").map(|s| s.eq("down")).unwrap_or(false);
+    let mut stat = StatRequest {
+        name: host.name.to_string(),
+        frame: "data".to_string(),
+        version: "push".to_string(),
+        notify: true,
+        online4: up,
+        latest_ts: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        ..Default::default()
+    };
+    for (k, key) in [("msg", "push_msg"), ("ping", "push_ping")] {
+        if let Some(v) = params.get(k).filter(|v| !v.is_empty()) {
+            stat.extra.insert(key.to_string(), v.to_string());
+        }
+    }
+    if let Some(mgr) = G_STATS_MGR.get() {
+        mgr.report(serde_json::to_value(stat)?)?;
+    }
+
+    json_resp(StatusCode::OK, &serde_json::json!({"ok": true}))
+}
+
+// /ping/<token>[/start|/fail]
+pub async fn ping_check(req: Request<Body>) -> Result<Response<Body>> {
+    let path = req.uri().path().trim_start_matches("/ping/").trim_end_matches('/');
+    let (token, ping) = match path.rsplit_once('/') {
+        Some((token, "start")) => (token, Ping::Start),
+        Some((token, "fail")) => (token, Ping::Fail),
+        _ => (path, Ping::Success),
+    };
+    match G_CHECK_MGR.lock().unwrap().ping(token, ping) {
+        Some(name) => {
+            trace!("check {} ping {:?}", name, ping);
+            Ok(Response::new(Body::from("OK")))
+        }
+        None => Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("not found"))?),
+    }
+}
+
+pub async fn get_checks() -> Result<Response<Body>> {
+    let checks = G_CHECK_MGR.lock().unwrap().list();
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": checks}))
+}
+
+// 安装命令生成, 含组密码, 仅管理员
+pub async fn get_groups(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let groups = G_CONFIG
+        .get()
+        .map(|cfg| {
+            cfg.hosts_group
+                .iter()
+                .map(|o| {
+                    serde_json::json!({
+                        "gid": o.gid,
+                        // hash 无法还原, 由前端提示手动填写
+                        "password": if crate::config::is_hashed(&o.password) { "" } else { o.password.as_str() },
+                        "hashed": crate::config::is_hashed(&o.password),
+                        "install_token": o.install_token,
+                        "location": o.location,
+                        "type": o.r#type,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    json_resp(
+        StatusCode::OK,
+        &serde_json::json!({"code": 0, "data": groups, "version": env!("CARGO_PKG_VERSION")}),
+    )
+}
+
+// POST /api/notify-test?channel=tgbot, 空为全部
+pub async fn notify_test(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let params = query_params(&req);
+    let channel = params.get("channel").map(String::as_str).unwrap_or_default();
+    let notifies = G_STATS_MGR.get().unwrap().get_notifies();
+    let result = crate::notifier::test_channels(&notifies, channel).await;
+    if result.is_empty() {
+        return json_resp(
+            StatusCode::NOT_FOUND,
+            &serde_json::json!({"code": 1, "msg": format!("channel `{}` not enabled", channel)}),
+        );
+    }
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": result}))
+}
+
+// GET 轮换列表; POST ?host=|gid=&grace=秒 开始轮换, 进行中返回 409; DELETE ?host=|gid= 取消
+pub async fn rotate_credential(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    if req.method() == Method::GET {
+        let list = G_ROTATION_MGR.lock().unwrap().list();
+        return json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": list}));
+    }
+
+    let params = query_params(&req);
+    let cfg = G_CONFIG.get().unwrap();
+    let (id, group, stored) = match (params.get("host"), params.get("gid")) {
+        (Some(host), _) if cfg.hosts_map.contains_key(host) => (host, false, &cfg.hosts_map[host].password),
+        (_, Some(gid)) if cfg.hosts_group_map.contains_key(gid) => (gid, true, &cfg.hosts_group_map[gid].password),
+        _ => {
+            return json_resp(
+                StatusCode::NOT_FOUND,
+                &serde_json::json!({"code": 1, "msg": "host/gid not found"}),
+            );
+        }
+    };
+
+    let mut rotation_mgr = G_ROTATION_MGR.lock().unwrap();
+    if req.method() == Method::DELETE {
+        let ok = rotation_mgr.cancel(id, group);
+        return json_resp(StatusCode::OK, &serde_json::json!({"code": if ok { 0 } else { 1 }}));
+    }
+    let grace = params.get("grace").and_then(|s| s.parse::<u64>().ok()).unwrap_or(86400);
+    match rotation_mgr.rotate(id, group, stored, grace)? {
+        Some(o) => {
+            // 新密码仅在此返回一次, 不写入 rotations.json
+            let mut data = serde_json::to_value(&o)?;
+            data["new_pass"] = o.new_pass.into();
+            json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": data}))
+        }
+        None => json_resp(
+            StatusCode::CONFLICT,
+            &serde_json::json!({"code": 1, "msg": "rotation in progress, cancel it first"}),
+        ),
+    }
+}
+
+// GET 重试队列及死信; POST ?id= 死信重新入队; DELETE ?id= 删除死信
+pub async fn notify_queue(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let mut retry_mgr = G_RETRY_MGR.lock().unwrap();
+    if req.method() == Method::GET {
+        return json_resp(
+            StatusCode::OK,
+            &serde_json::json!({"code": 0, "data": {"queue": retry_mgr.queue(), "dead": retry_mgr.dead()}}),
+        );
+    }
+    let id = match query_params(&req).get("id").and_then(|s| s.parse::<u64>().ok()) {
+        Some(id) => id,
+        None => {
+            return json_resp(
+                StatusCode::BAD_REQUEST,
+                &serde_json::json!({"code": 1, "msg": "invalid id"}),
+            );
+        }
+    };
+    let ok = if req.method() == Method::DELETE {
+        retry_mgr.remove_dead(id)
+    } else {
+        retry_mgr.requeue(id)
+    };
+    drop(retry_mgr);
+    crate::notifier::retry::save();
+    if ok {
+        json_resp(StatusCode::OK, &serde_json::json!({"code": 0}))
+    } else {
+        json_resp(
+            StatusCode::NOT_FOUND,
+            &serde_json::json!({"code": 1, "msg": "not found"}),
+        )
+    }
+}
+
+// 版本及能力协商, 无需鉴权
+pub async fn get_capabilities() -> Result<Response<Body>> {
+    json_resp(StatusCode::OK, &crate::grpc::server_capabilities())
+}
+
+fn incident_filter(params: &HashMap<String, String>) -> IncidentFilter {
+    IncidentFilter {
+        host: params.get("host").filter(|s| !s.is_empty()).cloned(),
+        kind: params.get("kind").filter(|s| !s.is_empty()).cloned(),
+        since: params.get("since").and_then(|s| s.parse::<u64>().ok()),
+        limit: params.get("limit").and_then(|s| s.parse::<usize>().ok()),
+    }
+}
+
+pub async fn get_incidents(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let filter = incident_filter(&query_params(&req));
+    let list = G_INCIDENT_MGR.lock().unwrap().query(&filter);
+    let data = list.iter().map(IncidentView::from).collect::<Vec<_>>();
+    json_resp(StatusCode::OK, &serde_json::json!({"code": 0, "data": data}))
+}
+
+// 直接嵌入 <script> 的 json, 转义 <>& 避免探测名等客户端上报的字符串中的 </script> 提前结束脚本
+fn script_json<T: Serialize>(v: &T) -> Result<String> {
+    Ok(serde_json::to_string(v)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026"))
+}
+
+// GET /smokeping?host=<name>, 最近 24 小时 ping 延迟分布及丢包
+pub async fn get_smokeping_page(req: Request<Body>, tenant: &str) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let name = params.get("host").map(String::as_str).unwrap_or_default();
+    let alias = G_STATS_MGR.get().and_then(|mgr| {
+        mgr.get_stats()
+            .lock()
+            .unwrap()
+            .servers
+            .iter()
+            .find(|o| o.name.eq(name) && o.tenant.eq(tenant))
+            .map(|o| o.alias.to_string())
+    });
+    let alias = match alias {
+        Some(alias) => alias,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?)
+        }
+    };
+
+    let series = crate::history::PING_TARGETS
+        .iter()
+        .zip(["联通", "电信", "移动"])
+        .map(|(target, label)| {
+            serde_json::json!({
+                "target": target,
+                "label": label,
+                "buckets": crate::history::ping_series(name, target),
+            })
+        })
+        .collect::<Vec<_>>();
+    // 延迟分布热力图, 需客户端上报直方图
+    let heatmaps = crate::history::heatmap_series(name)
+        .into_iter()
+        .map(|(target, label, buckets)| {
+            serde_json::json!({
+                "target": target,
+                "label": label,
+                "buckets": buckets,
+            })
+        })
+        .collect::<Vec<_>>();
+    let heatmap_json = script_json(&serde_json::json!({
+        "bounds": stat_common::RTT_HISTOGRAM_BOUNDS,
+        "series": heatmaps,
+    }))?;
+
+    Ok(jinja::render_template(
+        KIND,
+        "smokeping",
+        context!(
+            alias => alias,
+            series => series,
+            series_json => script_json(&series)?,
+            heatmaps => heatmaps,
+            heatmap_json => heatmap_json,
+        ),
+        false,
+    )
+    .map(|contents| {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(contents))
+    })?
+    .unwrap_or(
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(INTERNAL_SERVER_ERROR.into())?,
+    ))
+}
+
+// GET /api/series/<host>/ping/<target>?since=<ts>, target: cu/ct/cm
+pub async fn get_ping_series(req: Request<Body>) -> Result<Response<Body>> {
+    let path = req
+        .uri()
+        .path()
+        .trim_start_matches("/api/series/")
+        .trim_end_matches('/');
+    let (name, target) = match path.rsplit_once("/ping/") {
+        Some((name, target)) => (name, crate::history::ping_target(target)),
+        None => ("", None),
+    };
+    let exists = G_STATS_MGR
+        .get()
+        .map(|mgr| {
+            mgr.get_stats()
+                .lock()
+                .unwrap()
+                .servers
+                .iter()
+                .any(|o| o.name.eq(name) && o.tenant.is_empty())
+        })
+        .unwrap_or(false);
+    let target = match target.filter(|_| exists) {
+        Some(target) => target,
+        None => {
+            return json_resp(
+                StatusCode::NOT_FOUND,
+                &serde_json::json!({"ok": false, "msg": "host or target not found"}),
+            )
+        }
+    };
+
+    let since = query_params(&req)
+        .get("since")
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(0);
+    let buckets = crate::history::ping_series(name, target)
+        .into_iter()
+        .filter(|o| o.ts >= since)
+        .collect::<Vec<_>>();
+    json_resp(
+        StatusCode::OK,
+        &serde_json::json!({
+            "host": name,
+            "target": target,
+            "bucket_secs": crate::history::PING_BUCKET_SECS,
+            "buckets": buckets,
+        }),
+    )
+}
+
+pub async fn get_incidents_page(req: Request<Body>) -> Result<Response<Body>> {
+    if !is_admin(&req) {
+        return unauthorized();
+    }
+    let mut filter = incident_filter(&query_params(&req));
+    if filter.limit.is_none() {
+        filter.limit = Some(500);
+    }
+    let (list, kinds) = {
+        let incident_mgr = G_INCIDENT_MGR.lock().unwrap();
+        (incident_mgr.query(&filter), incident_mgr.kinds())
+    };
+    let data = list.iter().map(IncidentView::from).collect::<Vec<_>>();
+    let dead_letters = G_RETRY_MGR
+        .lock()
+        .unwrap()
+        .dead()
+        .iter()
+        .rev()
+        .map(|o| {
+            serde_json::json!({
+                "id": o.id,
+                "kind": o.kind,
+                "attempts": o.attempts,
+                "created_str": crate::incident::fmt_ts(o.created),
+                "last_error": o.last_error,
+                "payload": o.payload.chars().take(200).collect::<String>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(jinja::render_template(
+        KIND,
+        "incidents",
+        context!(
+            incidents => data, kinds => kinds, dead_letters => dead_letters,
+            host => filter.host.unwrap_or_default(),
+            cur_kind => filter.kind.unwrap_or_default(),
+            limit => filter.limit,
+        ),
+        false,
+    )
+    .map(|contents| {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(contents))
+    })?
+    .unwrap_or(
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(INTERNAL_SERVER_ERROR.into())?,
+    ))
+}
+
 pub async fn init_client(req: Request<Body>) -> Result<Response<Body>> {
     // dbg!(&req);
     let params: HashMap<String, String> = req
@@ -67,6 +806,56 @@ pub async fn init_client(req: Request<Body>) -> Result<Response<Body>> {
             .body(UNAUTHORIZED.into())?);
     }
 
+    client_init_script(&req, &params, pass, uid, gid, alias)
+}
+
+// GET /i/<gid>?token=xxx, 组安装脚本, curl -sSL "http://<server>/i/<gid>?token=xxx" | bash
+pub async fn init_group_client(req: Request<Body>) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let gid = req.uri().path().trim_start_matches("/i/").trim_end_matches('/');
+    let group = match G_CONFIG.get().and_then(|cfg| cfg.hosts_group_map.get(gid)) {
+        Some(group) => group,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?)
+        }
+    };
+    let token_ok = !group.install_token.is_empty()
+        && params
+            .get("token")
+            .map(|t| stat_common::auth::ct_eq(&group.install_token, t))
+            .unwrap_or(false);
+    if !token_ok && !is_admin(&req) {
+        return unauthorized();
+    }
+
+    // 密码为 hash 时需通过 &pass= 传入明文
+    let pass = if crate::config::is_hashed(&group.password) {
+        match params
+            .get("pass")
+            .filter(|p| crate::config::verify_pass(&group.password, p))
+        {
+            Some(pass) => pass.to_string(),
+            None => return unauthorized(),
+        }
+    } else {
+        group.password.to_string()
+    };
+
+    // 安装时展开为主机名
+    client_init_script(&req, &params, &pass, "", &group.gid, "$(hostname)")
+}
+
+fn client_init_script(
+    req: &Request<Body>,
+    params: &HashMap<String, String>,
+    pass: &str,
+    uid: &str,
+    gid: &str,
+    alias: &str,
+) -> Result<Response<Body>> {
+    let invalid = "".to_string();
     let mut domain = "localhost".to_string();
     let mut scheme = "http".to_string();
     let mut server_url = "".to_string();
@@ -106,6 +895,14 @@ pub async fn init_client(req: Request<Body>) -> Result<Response<Body>> {
         });
         server_url = format!("{}://{}/report", scheme, domain);
     }
+    // 服务端托管的客户端
+    let mut download_url = "".to_string();
+    if G_CONFIG.get().map(|cfg| !cfg.client_dir.is_empty()).unwrap_or(false) {
+        download_url = match server_url.rfind('/') {
+            Some(idx) => format!("{}/dl", &server_url[..idx]),
+            None => format!("{}/dl", server_url),
+        };
+    }
 
     let vnstat = params.get("vnstat").map(|p| p.eq("1")).unwrap_or(false);
     let disable_ping = params.get("ping").map(|p| p.eq("0")).unwrap_or(false);
@@ -162,6 +959,7 @@ pub async fn init_client(req: Request<Body>) -> Result<Response<Body>> {
             vnstat => vnstat, weight => weight, cn => cn,
             domain => domain, scheme => scheme,
             server_url => server_url, workspace => workspace,
+            download_url => download_url,
             client_opts => client_opts,
             pkg_version => env!("CARGO_PKG_VERSION"),
         ),
@@ -183,6 +981,32 @@ pub async fn init_client(req: Request<Body>) -> Result<Response<Body>> {
     ))
 }
 
+// GET /dl/<file>, client_dir 下的客户端文件
+pub async fn download_client(req: Request<Body>) -> Result<Response<Body>> {
+    let name = req.uri().path().trim_start_matches("/dl/");
+    let client_dir = G_CONFIG.get().map(|cfg| cfg.client_dir.as_str()).unwrap_or_default();
+    if client_dir.is_empty() || name.is_empty() || name.contains('/') || name.contains("..") {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?);
+    }
+    match tokio::fs::read(std::path::Path::new(client_dir).join(name)).await {
+        Ok(data) => Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!(r#"attachment; filename="{}""#, name),
+            )
+            .body(Body::from(data))?),
+        Err(err) => {
+            warn!("download client {} err => {:?}", name, err);
+            Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(StatusCode::NOT_FOUND.canonical_reason().unwrap().into())?)
+        }
+    }
+}
+
 pub fn init_jinja_tpl() -> Result<()> {
     let detail_data = Asset::get("/jinja/detail.jinja.html").expect("detail.jinja.html not found");
     let detail_html: String = String::from_utf8(detail_data.data.try_into()?).unwrap();
@@ -199,6 +1023,18 @@ pub fn init_jinja_tpl() -> Result<()> {
     let client_init_sh = Asset::get("/jinja/client-init.jinja.sh").expect("client-init.jinja.sh not found");
     let client_init_sh_s: String = String::from_utf8(client_init_sh.data.try_into()?).unwrap();
     jinja::add_template(KIND, "client-init", client_init_sh_s);
+
+    let incidents_data = Asset::get("/jinja/incidents.jinja.html").expect("incidents.jinja.html not found");
+    let incidents_html: String = String::from_utf8(incidents_data.data.try_into()?).unwrap();
+    jinja::add_template(KIND, "incidents", incidents_html);
+
+    let logs_data = Asset::get("/jinja/logs.jinja.html").expect("logs.jinja.html not found");
+    let logs_html: String = String::from_utf8(logs_data.data.try_into()?).unwrap();
+    jinja::add_template(KIND, "logs", logs_html);
+
+    let smokeping_data = Asset::get("/jinja/smokeping.jinja.html").expect("smokeping.jinja.html not found");
+    let smokeping_html: String = String::from_utf8(smokeping_data.data.try_into()?).unwrap();
+    jinja::add_template(KIND, "smokeping", smokeping_html);
     Ok(())
 }
 
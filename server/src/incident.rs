@@ -0,0 +1,289 @@
+#![deny(warnings)]
+use chrono::{Local, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const INCIDENTS_FILE: &str = "incidents.json";
+
+pub static G_INCIDENT_MGR: Lazy<Mutex<IncidentMgr>> = Lazy::new(Default::default);
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+pub fn fmt_ts(ts: u64) -> String {
+    Local
+        .timestamp_opt(ts as i64, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+pub fn fmt_duration(secs: u64) -> String {
+    if secs >= 86400 {
+        format!("{}天{}时", secs / 86400, secs % 86400 / 3600)
+    } else if secs >= 3600 {
+        format!("{}时{}分", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}分{}秒", secs / 60, secs % 60)
+    } else {
+        format!("{}秒", secs)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: u64,
+    pub host: String,
+    // NodeDown / Flapping / Ack / Unack ...
+    pub kind: String,
+    pub start: u64,
+    // None => ongoing
+    pub end: Option<u64>,
+    #[serde(default = "Default::default")]
+    pub detail: String,
+}
+
+impl Incident {
+    pub fn duration(&self) -> u64 {
+        self.end.unwrap_or_else(now_ts).saturating_sub(self.start)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct IncidentView<'a> {
+    #[serde(flatten)]
+    pub incident: &'a Incident,
+    pub ongoing: bool,
+    pub duration: u64,
+    pub start_str: String,
+    pub end_str: String,
+    pub duration_str: String,
+}
+
+impl<'a> From<&'a Incident> for IncidentView<'a> {
+    fn from(o: &'a Incident) -> Self {
+        Self {
+            incident: o,
+            ongoing: o.end.is_none(),
+            duration: o.duration(),
+            start_str: fmt_ts(o.start),
+            end_str: o.end.map(fmt_ts).unwrap_or_default(),
+            duration_str: fmt_duration(o.duration()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IncidentFilter {
+    pub host: Option<String>,
+    pub kind: Option<String>,
+    pub since: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct IncidentMgr {
+    max_size: usize,
+    next_id: u64,
+    incidents: Vec<Incident>,
+}
+
+impl IncidentMgr {
+    pub fn init(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        let contents = fs::read_to_string(INCIDENTS_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<Vec<Incident>>(&contents) {
+            Ok(incidents) => {
+                self.next_id = incidents.iter().map(|o| o.id).max().unwrap_or(0) + 1;
+                self.incidents = incidents;
+                trace!("load {} succ!", INCIDENTS_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", INCIDENTS_FILE, err);
+            }
+        }
+    }
+
+    fn save(&self) {
+        // 单元测试不落盘
+        if cfg!(test) {
+            return;
+        }
+        match serde_json::to_string(&self.incidents) {
+            Ok(s) => {
+                if let Err(err) = fs::write(INCIDENTS_FILE, s) {
+                    error!("save {} fail => {:?}", INCIDENTS_FILE, err);
+                }
+            }
+            Err(err) => error!("save {} fail => {:?}", INCIDENTS_FILE, err),
+        }
+    }
+
+    fn push(&mut self, o: Incident) {
+        self.incidents.push(o);
+        self.trim();
+        self.save();
+    }
+
+    // 超出 max_size 时从旧到新淘汰已结束的事件, 未结束的事件是告警的触发状态, 不能淘汰
+    fn trim(&mut self) {
+        if self.max_size == 0 || self.incidents.len() <= self.max_size {
+            return;
+        }
+        let mut n = self.incidents.len() - self.max_size;
+        self.incidents.retain(|o| {
+            if n > 0 && o.end.is_some() {
+                n -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    // 开始一个持续性事件, 已存在未结束的同类事件则忽略
+    pub fn open(&mut self, host: &str, kind: &str, detail: &str) {
+        if self.is_open(host, kind) {
+            return;
+        }
+        let o = Incident {
+            id: self.next_id,
+            host: host.to_string(),
+            kind: kind.to_string(),
+            start: now_ts(),
+            end: None,
+            detail: detail.to_string(),
+        };
+        self.next_id += 1;
+        self.push(o);
+    }
+
+    pub fn close(&mut self, host: &str, kind: &str) {
+        let now = now_ts();
+        let mut changed = false;
+        for o in self.incidents.iter_mut() {
+            if o.end.is_none() && o.host.eq(host) && o.kind.eq(kind) {
+                o.end = Some(now);
+                changed = true;
+            }
+        }
+        if changed {
+            self.save();
+        }
+    }
+
+    pub fn is_open(&self, host: &str, kind: &str) -> bool {
+        self.incidents
+            .iter()
+            .any(|o| o.end.is_none() && o.host.eq(host) && o.kind.eq(kind))
+    }
+
+    // 未结束事件的 id, 用于 tg 按钮回调, callback_data 限制 64 字节放不下主机名
+    pub fn open_id(&self, host: &str, kind: &str) -> Option<u64> {
+        self.incidents
+            .iter()
+            .find(|o| o.end.is_none() && o.host.eq(host) && o.kind.eq(kind))
+            .map(|o| o.id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Incident> {
+        self.incidents.iter().find(|o| o.id == id)
+    }
+
+    // 主机未结束的事件中以 prefix 开头的 kind
+    pub fn open_kinds(&self, host: &str, prefix: &str) -> Vec<String> {
+        self.incidents
+            .iter()
+            .filter(|o| o.end.is_none() && o.host.eq(host) && o.kind.starts_with(prefix))
+            .map(|o| o.kind.to_string())
+            .collect()
+    }
+
+    // 瞬时事件
+    pub fn record(&mut self, host: &str, kind: &str, detail: &str) {
+        let now = now_ts();
+        let o = Incident {
+            id: self.next_id,
+            host: host.to_string(),
+            kind: kind.to_string(),
+            start: now,
+            end: Some(now),
+            detail: detail.to_string(),
+        };
+        self.next_id += 1;
+        self.push(o);
+    }
+
+    pub fn query(&self, filter: &IncidentFilter) -> Vec<Incident> {
+        let mut list = self
+            .incidents
+            .iter()
+            .rev()
+            .filter(|o| filter.host.as_ref().map(|h| o.host.eq(h)).unwrap_or(true))
+            .filter(|o| filter.kind.as_ref().map(|k| o.kind.eq(k)).unwrap_or(true))
+            .filter(|o| filter.since.map(|ts| o.end.unwrap_or(u64::MAX) >= ts).unwrap_or(true))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(limit) = filter.limit {
+            list.truncate(limit);
+        }
+        list
+    }
+
+    pub fn kinds(&self) -> Vec<String> {
+        let mut kinds = self.incidents.iter().map(|o| o.kind.to_string()).collect::<Vec<_>>();
+        kinds.sort();
+        kinds.dedup();
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_keep_open() {
+        let mut mgr = IncidentMgr {
+            max_size: 3,
+            ..Default::default()
+        };
+        mgr.open("h1", "NodeDown", "");
+        for i in 0..5 {
+            mgr.record("h2", "Ack", &i.to_string());
+        }
+        assert_eq!(mgr.incidents.len(), 3);
+        assert!(mgr.is_open("h1", "NodeDown"));
+        // 淘汰的是最旧的已结束事件
+        let details = mgr.incidents.iter().map(|o| o.detail.as_str()).collect::<Vec<_>>();
+        assert_eq!(details, vec!["", "3", "4"]);
+
+        mgr.close("h1", "NodeDown");
+        mgr.record("h2", "Ack", "5");
+        assert_eq!(mgr.incidents.len(), 3);
+        assert!(mgr.open_kinds("h1", "").is_empty());
+    }
+
+    #[test]
+    fn test_trim_all_open() {
+        let mut mgr = IncidentMgr {
+            max_size: 2,
+            ..Default::default()
+        };
+        for host in ["h1", "h2", "h3"] {
+            mgr.open(host, "NodeDown", "");
+        }
+        // 全部未结束时允许暂时超出 max_size
+        assert_eq!(mgr.incidents.len(), 3);
+        mgr.close("h2", "NodeDown");
+        mgr.record("h4", "Ack", "");
+        assert_eq!(mgr.incidents.len(), 2);
+        assert!(["h1", "h3"].iter().all(|h| mgr.is_open(h, "NodeDown")));
+    }
+}
@@ -0,0 +1,116 @@
+#![deny(warnings)]
+// 兼容原版 ServerStatus / ServerStatus-Hotaru 客户端的 tcp json 协议
+// server => "Authentication required"
+// client => "user:pass"
+// server => "Authentication successful. Access granted." / "You are connecting via: IPv4"
+// client => "update {json}" 每秒一行
+use anyhow::Result;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+
+use stat_common::server_status::StatRequest;
+
+use crate::G_CONFIG;
+use crate::G_STATS_MGR;
+
+const AUTH_TIMEOUT: u64 = 10;
+
+// 旧协议字段合并到 StatRequest 默认值上, 缺失字段置 0
+fn to_stat_value(user: &str, peer: &SocketAddr, legacy: Value) -> Result<Value> {
+    let mut stat = serde_json::to_value(StatRequest {
+        name: user.to_string(),
+        frame: "data".to_string(),
+        version: "legacy".to_string(),
+        notify: true,
+        online4: peer.is_ipv4(),
+        online6: peer.is_ipv6(),
+        ..Default::default()
+    })?;
+
+    if let (Some(o), Value::Object(legacy)) = (stat.as_object_mut(), legacy) {
+        for (k, v) in legacy {
+            match k.as_str() {
+                // Hotaru / 旧版只有 load
+                "load" => {
+                    o.insert("load_1".to_string(), v);
+                }
+                _ if o.contains_key(&k) && !v.is_null() => {
+                    o.insert(k, v);
+                }
+                _ => {}
+            }
+        }
+        o.insert("name".to_string(), Value::from(user));
+        o.insert(
+            "latest_ts".to_string(),
+            Value::from(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
+        );
+    }
+
+    Ok(stat)
+}
+
+async fn handle_conn(stream: TcpStream, peer: SocketAddr) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer.write_all(b"Authentication required\n").await?;
+    let auth_line = timeout(Duration::from_secs(AUTH_TIMEOUT), lines.next_line())
+        .await??
+        .unwrap_or_default();
+    let (user, pass) = auth_line.trim().split_once(':').unwrap_or_default();
+    if !G_CONFIG.get().map(|cfg| cfg.auth(user, pass)).unwrap_or(false) {
+        writer.write_all(b"Wrong username and/or password.\n").await?;
+        warn!("legacy client {} auth fail => {}", peer, user);
+        return Ok(());
+    }
+    let user = user.to_string();
+    writer
+        .write_all(b"Authentication successful. Access granted.\n")
+        .await?;
+    let via = if peer.is_ipv4() { "IPv4" } else { "IPv6" };
+    writer
+        .write_all(format!("You are connecting via: {}\n", via).as_bytes())
+        .await?;
+    info!("legacy client {} connected => {}", peer, user);
+
+    while let Some(line) = lines.next_line().await? {
+        let data = line.trim().trim_start_matches("update").trim();
+        if data.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(data)
+            .map_err(anyhow::Error::new)
+            .and_then(|v| to_stat_value(&user, &peer, v))
+        {
+            Ok(v) => {
+                if let Some(mgr) = G_STATS_MGR.get() {
+                    let _ = mgr.report(v);
+                }
+            }
+            Err(err) => {
+                error!("legacy client {} invalid data => {:?}", peer, err);
+            }
+        }
+    }
+
+    info!("legacy client {} disconnected => {}", peer, user);
+    Ok(())
+}
+
+pub async fn serv_legacy(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("🚀 listening on legacy tcp://{}", addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(err) = handle_conn(stream, peer).await {
+                error!("legacy client {} error => {:?}", peer, err);
+            }
+        });
+    }
+}
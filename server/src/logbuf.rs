@@ -0,0 +1,83 @@
+#![deny(warnings)]
+// 最近的日志保存在内存环形缓冲区, 用于 /logs 及 /api/logs
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::env;
+use std::sync::Mutex;
+
+const CAPACITY: usize = 2000;
+
+static G_LOG_BUF: Lazy<Mutex<VecDeque<Line>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    pub time: String,
+    pub level: String,
+    pub target: String,
+    pub msg: String,
+}
+
+struct BufLogger {
+    inner: Box<dyn Log>,
+}
+
+impl Log for BufLogger {
+    // 即使 RUST_LOG 未开启, info 及以上也写入缓冲区
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+        let mut buf = G_LOG_BUF.lock().unwrap();
+        if buf.len() >= CAPACITY {
+            buf.pop_front();
+        }
+        buf.push_back(Line {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            msg: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// 替代 pretty_env_logger::init, 终端输出不变
+pub fn init() {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(s) = env::var("RUST_LOG") {
+        builder.parse_filters(&s);
+    }
+    let inner = builder.build();
+    log::set_max_level(inner.filter().max(LevelFilter::Info));
+    if let Err(err) = log::set_boxed_logger(Box::new(BufLogger { inner: Box::new(inner) })) {
+        eprintln!("init logger error => {:?}", err);
+    }
+}
+
+// level 为最低级别, q 为关键词, 返回最近 limit 条
+pub fn query(level: LevelFilter, q: &str, limit: usize) -> Vec<Line> {
+    let buf = G_LOG_BUF.lock().unwrap();
+    let mut list = buf
+        .iter()
+        .rev()
+        .filter(|o| o.level.parse::<Level>().map(|l| l <= level).unwrap_or(true))
+        .filter(|o| q.is_empty() || o.msg.contains(q) || o.target.contains(q))
+        .take(limit)
+        .cloned()
+        .collect::<Vec<_>>();
+    list.reverse();
+    list
+}
@@ -12,21 +12,41 @@ use once_cell::sync::OnceCell;
 use prost::Message;
 use rust_embed::RustEmbed;
 use stat_common::server_status::StatRequest;
+use stat_common::{auth, codec};
 use std::collections::HashMap;
 use std::process;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::thread;
-use std::time::Duration;
 use tokio::runtime::Handle;
 
+mod alert;
+mod backup;
+mod chart;
+mod checks;
 mod config;
+mod delta;
+mod diag;
+mod digest;
+mod diskfill;
+mod flapping;
 mod grpc;
+mod history;
 mod http;
+mod incident;
 mod jinja;
+mod legacy;
+mod logbuf;
+mod maintenance;
+mod mqtt;
 mod notifier;
 mod payload;
+mod rotation;
+mod secret;
+mod sign;
 mod stats;
+mod telegraf;
+mod tenant;
+mod tls;
 
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
@@ -35,9 +55,14 @@ type Result<T> = std::result::Result<T, GenericError>;
 
 static NOTFOUND: &[u8] = b"Not Found";
 static UNAUTHORIZED: &[u8] = b"Unauthorized";
+static APPLICATION_MSGPACK: &str = "application/msgpack";
+// 上报 body 上限, 与解压后上限一致
+const MAX_REPORT_SIZE: usize = 4 << 20;
 
 static G_CONFIG: OnceCell<crate::config::Config> = OnceCell::new();
 static G_STATS_MGR: OnceCell<crate::stats::StatsMgr> = OnceCell::new();
+// 配置文件路径, 用于备份
+static G_CONFIG_PATH: OnceCell<String> = OnceCell::new();
 
 #[derive(RustEmbed)]
 #[folder = "../web"]
@@ -55,40 +80,193 @@ struct Args {
     notify_test: bool,
     #[clap(long = "cloud", value_parser, help = "cloud mode, load cfg from env var: SRV_CONF")]
     cloud: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-// stat report
-async fn stats_report(req: Request<Body>) -> Result<Response<Body>> {
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// send a test message through notifiers and report delivery result
+    NotifyTest {
+        #[clap(
+            long,
+            value_parser,
+            default_value = "all",
+            help = "tgbot|telegram|wechat|email|log|webhook|pagerduty|opsgenie|all"
+        )]
+        channel: String,
+    },
+    /// generate argon2 hash for admin_pass / hosts / hosts_group password, read from stdin if omitted
+    HashPassword {
+        #[clap(value_parser)]
+        password: Option<String>,
+    },
+    /// pack config and state files into a single json archive, `-` for stdout
+    Backup {
+        #[clap(short, long, value_parser)]
+        output: Option<String>,
+    },
+    /// restore config and state files from a backup archive, stop the server first
+    Restore {
+        #[clap(value_parser)]
+        file: String,
+        #[clap(long, value_parser, help = "overwrite existing files, old ones are renamed to *.bak")]
+        force: bool,
+    },
+}
+
+fn is_group_auth(req_header: &header::HeaderMap) -> bool {
+    req_header
+        .get(auth::SSR_AUTH)
+        .map(|v| auth::GROUP.eq(v))
+        .unwrap_or(false)
+}
+
+// mTLS, 证书 CN 已配置为主机/组时返回 (CN, group_auth)
+fn cert_user<T>(req: &Request<T>) -> Option<(String, bool)> {
+    let group_auth = is_group_auth(req.headers());
+    let tls::PeerCn(cn) = req.extensions().get::<tls::PeerCn>()?;
+    if G_CONFIG.get()?.cert_auth(cn, group_auth) {
+        return Some((cn.to_string(), group_auth));
+    }
+    None
+}
+
+// report auth => (user/gid, group_auth)
+fn report_auth<T>(req: &Request<T>) -> Option<(String, bool)> {
     let req_header = req.headers();
-    // auth
-    let mut auth_ok = false;
-    let mut group_auth = false;
-    if let Some(ssr_auth) = req_header.get("ssr-auth") {
-        group_auth = "group".eq(ssr_auth);
+    let group_auth = is_group_auth(req_header);
+
+    // mTLS, 证书 CN 即 user/gid
+    if let Some(o) = cert_user(req) {
+        return Some(o);
+    }
+    if G_CONFIG.get().map(|cfg| cfg.tls.require_client_cert).unwrap_or(false) {
+        return None;
     }
 
     if let Some(auth) = req_header.get(hyper::header::AUTHORIZATION) {
-        let auth_header_value = auth.to_str()?.to_string();
+        let auth_header_value = auth.to_str().ok()?.to_string();
         if let Ok(credentials) = Credentials::from_header(auth_header_value) {
             if let Some(cfg) = G_CONFIG.get() {
-                if group_auth {
-                    auth_ok = cfg.group_auth(&credentials.user_id, &credentials.password);
+                let auth_ok = if group_auth {
+                    cfg.group_auth(&credentials.user_id, &credentials.password)
                 } else {
-                    auth_ok = cfg.auth(&credentials.user_id, &credentials.password);
+                    cfg.auth(&credentials.user_id, &credentials.password)
+                };
+                if auth_ok {
+                    return Some((credentials.user_id, group_auth));
                 }
             }
         }
     }
-    if !auth_ok {
+    None
+}
+
+// 密码轮换中仍使用旧密码, 返回新密码
+fn rotate_hint(req_header: &header::HeaderMap) -> Option<String> {
+    let group_auth = is_group_auth(req_header);
+    let auth_header_value = req_header.get(hyper::header::AUTHORIZATION)?.to_str().ok()?.to_string();
+    let credentials = Credentials::from_header(auth_header_value).ok()?;
+    rotation::G_ROTATION_MGR
+        .lock()
+        .unwrap()
+        .hint(&credentials.user_id, group_auth, &credentials.password)
+}
+
+// hmac 签名上报 => (user/gid, group_auth, 新密码), Authorization 中不带密码
+fn signed_auth(req_header: &header::HeaderMap, body: &[u8]) -> Option<(String, bool, Option<String>)> {
+    let group_auth = is_group_auth(req_header);
+    let auth_header_value = req_header.get(hyper::header::AUTHORIZATION)?.to_str().ok()?.to_string();
+    let credentials = Credentials::from_header(auth_header_value).ok()?;
+    if G_CONFIG.get()?.tls.require_client_cert {
+        return None;
+    }
+    let key = sign::verify(&credentials.user_id, group_auth, req_header, body)?;
+    let new_password = rotation::G_ROTATION_MGR
+        .lock()
+        .unwrap()
+        .hint(&credentials.user_id, group_auth, &key);
+    Some((credentials.user_id, group_auth, new_password))
+}
+
+// 签名上报读取 body 前的检查 => (user/gid, group_auth)
+fn signed_precheck(req_header: &header::HeaderMap) -> Option<(String, bool)> {
+    let group_auth = is_group_auth(req_header);
+    let auth_header_value = req_header.get(hyper::header::AUTHORIZATION)?.to_str().ok()?.to_string();
+    let credentials = Credentials::from_header(auth_header_value).ok()?;
+    if G_CONFIG.get()?.tls.require_client_cert {
+        return None;
+    }
+    if !sign::precheck(&credentials.user_id, group_auth, req_header) {
+        return None;
+    }
+    Some((credentials.user_id, group_auth))
+}
+
+// stat report
+async fn stats_report(req: Request<Body>) -> Result<Response<Body>> {
+    let (parts, body) = req.into_parts();
+    let req = Request::from_parts(parts, ());
+    let req_header = req.headers();
+    // 先按 header 鉴权再读取 body; 签名需读取 body 后才能校验, 此前只检查用户及时间戳
+    let signed = req_header.contains_key(auth::SSR_SIGNATURE);
+    let header_user = if signed {
+        signed_precheck(req_header)
+    } else {
+        report_auth(&req)
+    };
+    if header_user.is_none() {
         return Ok(Response::builder()
             .status(StatusCode::UNAUTHORIZED)
             .body(UNAUTHORIZED.into())?);
     }
-    // auth end
+    let mut whole_body = match read_body(body, MAX_REPORT_SIZE).await? {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())?)
+        }
+    };
+    let (report_user, new_password) = if signed {
+        match signed_auth(req_header, &whole_body) {
+            Some((user, group_auth, new_password)) => (Some((user, group_auth)), new_password),
+            None => (None, None),
+        }
+    } else {
+        (header_user, rotate_hint(req_header))
+    };
+    let report_interval = match report_user {
+        Some((user, group_auth)) => G_CONFIG.get().unwrap().report_interval(&user, group_auth),
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(UNAUTHORIZED.into())?)
+        }
+    };
+
+    // Content-Encoding: gzip/zstd
+    let content_encoding = req_header
+        .get(header::CONTENT_ENCODING)
+        .map(|v| v.to_str().unwrap_or_default());
+    let compression = match content_encoding {
+        None | Some("identity") => None,
+        Some(o) => match codec::Compression::from_content_encoding(o) {
+            Some(c) => Some(c),
+            None => {
+                return Ok(Response::builder()
+                    .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    .body(Body::empty())?)
+            }
+        },
+    };
 
     let mut json_data: Option<serde_json::Value> = None;
     if let Ok(content_type) = req_header.get(hyper::header::CONTENT_TYPE).unwrap().clone().to_str() {
-        let whole_body = hyper::body::aggregate(req).await?;
+        if let Some(compression) = compression {
+            whole_body = codec::decompress(&whole_body, compression)?.into();
+        }
         // dbg!(content_type);
         if content_type.eq(&mime::APPLICATION_JSON.to_string()) {
             // json
@@ -97,16 +275,48 @@ async fn stats_report(req: Request<Body>) -> Result<Response<Body>> {
             // protobuf
             let stat = StatRequest::decode(whole_body)?;
             json_data = Some(serde_json::to_value(stat)?);
+        } else if content_type.eq(APPLICATION_MSGPACK) || content_type.eq("application/x-msgpack") {
+            // msgpack, 字段同 json
+            json_data = Some(rmp_serde::from_read(whole_body.reader())?);
+        }
+    }
+
+    if json_data.is_none() {
+        return Ok(Response::builder()
+            .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            .body(Body::empty())?);
+    }
+
+    // 证书鉴权时不允许冒用其他主机/组上报
+    if let Some((cn, group_auth)) = cert_user(&req) {
+        let data = json_data.as_ref().unwrap();
+        let (name, gid) = (
+            data["name"].as_str().unwrap_or_default(),
+            data["gid"].as_str().unwrap_or_default(),
+        );
+        if !tls::stat_matches_cn(&cn, group_auth, name, gid) {
+            warn!("report {}/{} mismatch client cert `{}`", gid, name, cn);
+            return Ok(Response::builder().status(StatusCode::FORBIDDEN).body(Body::empty())?);
         }
     }
 
     // report
+    let mut need_full = false;
     if let Some(mgr) = G_STATS_MGR.get() {
-        mgr.report(json_data.unwrap())?;
+        need_full = !mgr.report(json_data.unwrap())?;
     }
 
     let mut resp = HashMap::new();
     resp.insert(&"code", serde_json::Value::from(0_i32));
+    if need_full {
+        resp.insert(&"need_full", serde_json::Value::from(true));
+    }
+    if let Some(new_password) = new_password {
+        resp.insert(&"new_password", serde_json::Value::from(new_password));
+    }
+    if report_interval > 0 {
+        resp.insert(&"report_interval", serde_json::Value::from(report_interval));
+    }
     let resp_str = serde_json::to_string(&resp)?;
 
     Ok(Response::builder()
@@ -115,8 +325,128 @@ async fn stats_report(req: Request<Body>) -> Result<Response<Body>> {
         .body(Body::from(resp_str))?)
 }
 
+// telegraf outputs.http json, 组模式下以 tags.host 作为主机名
+async fn telegraf_report(req: Request<Body>) -> Result<Response<Body>> {
+    let (user, group_auth) = match report_auth(&req) {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(UNAUTHORIZED.into())?)
+        }
+    };
+
+    let cert = cert_user(&req);
+    let whole_body = match read_body(req.into_body(), MAX_REPORT_SIZE).await? {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())?)
+        }
+    };
+    let body: serde_json::Value = serde_json::from_reader(whole_body.reader())?;
+    let default_host = if group_auth { None } else { Some(user.as_str()) };
+    let stats = telegraf::to_stat_requests(&body, default_host)
+        .into_iter()
+        .map(|mut stat| {
+            if group_auth {
+                stat.gid = user.to_string();
+                stat.alias = stat.name.to_string();
+            }
+            stat
+        })
+        .collect::<Vec<_>>();
+    // 证书鉴权时不允许冒用其他主机上报
+    if let Some((cn, group_auth)) = cert {
+        if let Some(stat) = stats
+            .iter()
+            .find(|o| !tls::stat_matches_cn(&cn, group_auth, &o.name, &o.gid))
+        {
+            warn!(
+                "telegraf report {}/{} mismatch client cert `{}`",
+                stat.gid, stat.name, cn
+            );
+            return Ok(Response::builder().status(StatusCode::FORBIDDEN).body(Body::empty())?);
+        }
+    }
+    for stat in stats {
+        if let Some(mgr) = G_STATS_MGR.get() {
+            mgr.report(serde_json::to_value(stat)?)?;
+        }
+    }
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"code":0}"#))?)
+}
+
+// 读取 body, 超过 limit 返回 None, 不再继续读取
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<bytes::Bytes>> {
+    use hyper::body::HttpBody;
+    let mut buf = bytes::BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf.freeze()))
+}
+
+// 客户端诊断包, 组模式下以包内 name 作为主机名, 需为该组当前在线上报的主机
+async fn diag_upload(req: Request<Body>) -> Result<Response<Body>> {
+    let (user, group_auth) = match report_auth(&req) {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(UNAUTHORIZED.into())?)
+        }
+    };
+
+    let whole_body = match read_body(req.into_body(), diag::MAX_SIZE).await? {
+        Some(o) => o,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())?)
+        }
+    };
+    let bundle: serde_json::Value = serde_json::from_slice(&whole_body)?;
+    let host = if group_auth {
+        let name = bundle["name"].as_str().unwrap_or_default();
+        let registered = G_STATS_MGR
+            .get()
+            .map(|mgr| {
+                mgr.get_stats()
+                    .lock()
+                    .unwrap()
+                    .servers
+                    .iter()
+                    .any(|o| o.gid.eq(&user) && o.name.eq(name))
+            })
+            .unwrap_or(false);
+        if !registered {
+            return Ok(Response::builder().status(StatusCode::FORBIDDEN).body(Body::empty())?);
+        }
+        format!("{}/{}", user, name)
+    } else {
+        user
+    };
+    diag::put(&host, whole_body.len(), bundle);
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"code":0}"#))?)
+}
+
 // get json data
-async fn get_stats_json() -> Result<Response<Body>> {
+async fn get_stats_json(req: Request<Body>) -> Result<Response<Body>> {
+    if req.uri().query().is_some() {
+        return http::get_stats_filtered(req, "").await;
+    }
     Ok(Response::builder()
         .header(header::CONTENT_TYPE, "application/json")
         .body(Body::from(G_STATS_MGR.get().unwrap().get_stats_json()))?)
@@ -126,17 +456,51 @@ async fn main_service_func(req: Request<Body>) -> Result<Response<Body>> {
     let req_path = req.uri().path();
     match (req.method(), req_path) {
         (&Method::POST, "/report") => stats_report(req).await,
-        (&Method::GET, "/json/stats.json") => get_stats_json().await,
+        (&Method::POST, "/api/telegraf") => telegraf_report(req).await,
+        (&Method::GET, "/json/stats.json") => get_stats_json(req).await,
         (&Method::GET, "/detail") => http::get_detail(req).await,
         (&Method::GET, "/detail_ht") => http::render_jinja_ht_tpl("detail_ht", req).await,
         (&Method::GET, "/map") => http::render_jinja_ht_tpl("map", req).await,
         (&Method::GET, "/i") => http::init_client(req).await,
+        (&Method::POST, "/api/ack") | (&Method::DELETE, "/api/ack") => http::ack_alert(req).await,
+        (&Method::GET, "/api/acks") => http::get_acks(req).await,
+        (&Method::GET, "/api/backup") => http::get_backup(req).await,
+        (&Method::POST, "/api/diag") => diag_upload(req).await,
+        (&Method::GET, "/logs") => http::get_logs_page(req).await,
+        (&Method::GET, "/api/logs") => http::get_logs(req).await,
+        (&Method::GET, "/api/maintenance")
+        | (&Method::POST, "/api/maintenance")
+        | (&Method::DELETE, "/api/maintenance") => http::maintenance(req).await,
+        (&Method::GET, "/incidents") => http::get_incidents_page(req).await,
+        (&Method::GET, "/api/incidents") => http::get_incidents(req).await,
+        (&Method::GET, "/smokeping") => http::get_smokeping_page(req, "").await,
+        (&Method::GET, "/api/capabilities") => http::get_capabilities().await,
         (&Method::GET, "/") | (&Method::GET, "/index.html") => {
             let body = Body::from(Asset::get("/index.html").unwrap().data);
             Ok(Response::builder()
                 .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
                 .body(body)?)
         }
+        (&Method::GET, "/api/checks") => http::get_checks().await,
+        (&Method::GET, "/api/groups") => http::get_groups(req).await,
+        (&Method::POST, "/api/notify-test") => http::notify_test(req).await,
+        (&Method::GET, "/api/rotate") | (&Method::POST, "/api/rotate") | (&Method::DELETE, "/api/rotate") => {
+            http::rotate_credential(req).await
+        }
+        (&Method::GET, "/api/notify-queue")
+        | (&Method::POST, "/api/notify-queue")
+        | (&Method::DELETE, "/api/notify-queue") => http::notify_queue(req).await,
+        (&Method::GET, _) if req_path.starts_with("/i/") => http::init_group_client(req).await,
+        (&Method::GET, _) if req_path.starts_with("/api/series/") => http::get_ping_series(req).await,
+        (&Method::GET, _) if req_path.eq("/api/diag") || req_path.starts_with("/api/diag/") => {
+            http::get_diag(req).await
+        }
+        (&Method::GET, _) if req_path.starts_with("/t/") => http::tenant_dispatch(req).await,
+        (&Method::GET, _) if req_path.starts_with("/dl/") => http::download_client(req).await,
+        (&Method::GET, _) | (&Method::POST, _) | (&Method::HEAD, _) if req_path.starts_with("/ping/") => {
+            http::ping_check(req).await
+        }
+        (&Method::GET, _) | (&Method::POST, _) if req_path.starts_with("/api/push/") => http::push_heartbeat(req).await,
         _ => {
             if req.method() == Method::GET
                 && (req_path.starts_with("/js/")
@@ -171,11 +535,62 @@ async fn shutdown_signal() {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    pretty_env_logger::init();
+    logbuf::init();
+    // workspace release 为 unwind (客户端采集线程需要), 服务端线程 panic 后状态不可信, 保持直接退出
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        process::abort();
+    }));
     let args = Args::parse();
 
     eprintln!("✨ {} {}", env!("CARGO_BIN_NAME"), env!("APP_VERSION"));
 
+    // hash password
+    if let Some(Command::HashPassword { password }) = &args.command {
+        let password = match password {
+            Some(o) => o.to_string(),
+            None => {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                line.trim_end_matches(&['\r', '\n'][..]).to_string()
+            }
+        };
+        println!("{}", config::hash_pass(&password)?);
+        process::exit(0);
+    }
+
+    // backup & restore
+    match &args.command {
+        Some(Command::Backup { output }) => {
+            let archive = serde_json::to_string_pretty(&backup::create(&args.config)?)?;
+            let output = output.clone().unwrap_or_else(backup::default_name);
+            if output.eq("-") {
+                println!("{}", archive);
+            } else {
+                std::fs::write(&output, archive)?;
+                eprintln!("✨ backup to `{}`", output);
+            }
+            process::exit(0);
+        }
+        Some(Command::Restore { file, force }) => {
+            let archive = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            match backup::restore(&archive, &args.config, *force) {
+                Ok(restored) => {
+                    for o in restored {
+                        eprintln!("✅ restore `{}`", o);
+                    }
+                    process::exit(0);
+                }
+                Err(err) => {
+                    eprintln!("❌ restore error => {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => {}
+    }
+
     // config test
     if args.config_test {
         config::test_from_file(&args.config).unwrap();
@@ -196,6 +611,7 @@ async fn main() -> Result<()> {
     } {
         debug!("{}", serde_json::to_string_pretty(&cfg).unwrap());
         G_CONFIG.set(cfg).unwrap();
+        G_CONFIG_PATH.set(args.config.to_string()).unwrap();
     } else {
         error!("can't parse config");
         process::exit(1);
@@ -209,15 +625,15 @@ async fn main() -> Result<()> {
     let cfg = G_CONFIG.get().unwrap();
     let notifies: Arc<Mutex<Vec<Box<dyn notifier::Notifier + Send>>>> = Arc::new(Mutex::new(Vec::new()));
     if cfg.tgbot.enabled {
-        let o = Box::new(notifier::tgbot::TGBot::new(&cfg.tgbot));
+        let o = Box::new(notifier::tgbot::TGBot::new(&cfg.tgbot, ""));
         notifies.lock().unwrap().push(o);
     }
     if cfg.wechat.enabled {
-        let o = Box::new(notifier::wechat::WeChat::new(&cfg.wechat));
+        let o = Box::new(notifier::wechat::WeChat::new(&cfg.wechat, ""));
         notifies.lock().unwrap().push(o);
     }
     if cfg.email.enabled {
-        let o = Box::new(notifier::email::Email::new(&cfg.email));
+        let o = Box::new(notifier::email::Email::new(&cfg.email, ""));
         notifies.lock().unwrap().push(o);
     }
     if cfg.log.enabled {
@@ -225,20 +641,46 @@ async fn main() -> Result<()> {
         notifies.lock().unwrap().push(o);
     }
     if cfg.webhook.enabled {
-        let o = Box::new(notifier::webhook::Webhook::new(&cfg.webhook));
+        let o = Box::new(notifier::webhook::Webhook::new(&cfg.webhook, ""));
         notifies.lock().unwrap().push(o);
     }
+    if cfg.pagerduty.enabled {
+        let o = Box::new(notifier::pagerduty::PagerDuty::new(&cfg.pagerduty, ""));
+        notifies.lock().unwrap().push(o);
+    }
+    if cfg.opsgenie.enabled {
+        let o = Box::new(notifier::opsgenie::Opsgenie::new(&cfg.opsgenie, ""));
+        notifies.lock().unwrap().push(o);
+    }
+    for o in cfg.tenants.iter() {
+        notifies.lock().unwrap().extend(o.notifiers());
+    }
     // init notifier end
 
     // notify test
-    if args.notify_test {
-        for notifier in &*notifies.lock().unwrap() {
-            eprintln!("send test message to {}", notifier.kind());
-            notifier.notify_test().unwrap();
+    let channel = match &args.command {
+        Some(Command::NotifyTest { channel }) => Some(channel.as_str()),
+        None if args.notify_test => Some("all"),
+        None => None,
+    };
+    if let Some(channel) = channel {
+        eprintln!("✨ send test message to `{}", channel);
+        let result = notifier::test_channels(&notifies, channel).await;
+        if result.is_empty() {
+            eprintln!("❌ channel `{}` not enabled", channel);
+            process::exit(1);
         }
-        thread::sleep(Duration::from_millis(7000)); // TODO: wait
-        eprintln!("Please check for notifications");
-        process::exit(0);
+        let mut failed = false;
+        for o in result.iter() {
+            let kind = notifier::tpl_kind(o.kind, &o.tenant);
+            if o.ok {
+                eprintln!("✅ {} => {}", kind, o.detail);
+            } else {
+                failed = true;
+                eprintln!("❌ {} => {}", kind, o.detail);
+            }
+        }
+        process::exit(if failed { 1 } else { 0 });
     }
 
     // init mgr
@@ -255,10 +697,47 @@ async fn main() -> Result<()> {
         grpc::serv_grpc(addr).await
     });
 
+    // serv legacy tcp
+    if !G_CONFIG.get().unwrap().legacy_addr.is_empty() {
+        tokio::spawn(async move {
+            let addr = &*G_CONFIG.get().unwrap().legacy_addr;
+            if let Err(err) = legacy::serv_legacy(addr).await {
+                error!("serv legacy error => {:?}", err);
+            }
+        });
+    }
+
+    // mqtt publish
+    if G_CONFIG.get().unwrap().mqtt.enabled {
+        tokio::spawn(async move {
+            if let Err(err) = mqtt::serv_mqtt(&G_CONFIG.get().unwrap().mqtt).await {
+                error!("serv mqtt error => {:?}", err);
+            }
+        });
+        if G_CONFIG.get().unwrap().mqtt.subscribe {
+            tokio::spawn(async move {
+                if let Err(err) = mqtt::sub_mqtt(&G_CONFIG.get().unwrap().mqtt).await {
+                    error!("sub mqtt error => {:?}", err);
+                }
+            });
+        }
+    }
+
     // serv http
     let http_service = make_service_fn(|_| async { Ok::<_, GenericError>(service_fn(main_service_func)) });
 
     let http_addr = G_CONFIG.get().unwrap().http_addr.parse()?;
+    if G_CONFIG.get().unwrap().tls.enabled() {
+        tokio::select! {
+            result = tls::serv_https(http_addr, &G_CONFIG.get().unwrap().tls) => {
+                if let Err(e) = result {
+                    eprintln!("server error: {}", e);
+                }
+            },
+            _ = shutdown_signal() => {},
+        }
+        return Ok(());
+    }
     eprintln!("🚀 listening on http://{}", http_addr);
     let server = Server::bind(&http_addr).serve(http_service);
     let graceful = server.with_graceful_shutdown(shutdown_signal());
@@ -0,0 +1,194 @@
+#![deny(warnings)]
+// 维护窗口, 期间不发送该主机/组的告警, 供 CI/CD 部署时调用
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::incident::G_INCIDENT_MGR;
+
+const MAINTENANCE_FILE: &str = "maintenance.json";
+
+pub static G_MAINTENANCE_MGR: Lazy<Mutex<MaintenanceMgr>> = Lazy::new(Default::default);
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Window {
+    // host name 或 gid
+    pub id: String,
+    pub group: bool,
+    pub start: u64,
+    // 0 为手动结束
+    pub end: u64,
+    pub reason: String,
+    pub by: String,
+}
+
+impl Window {
+    fn expired(&self, now: u64) -> bool {
+        self.end > 0 && self.end <= now
+    }
+
+    pub fn matches(&self, name: &str, gid: &str) -> bool {
+        match self.group {
+            true => !gid.is_empty() && self.id.eq(gid),
+            false => self.id.eq(name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MaintenanceMgr {
+    windows: Vec<Window>,
+    // 已结束(到期或手动)待重新检查主机状态的窗口
+    ended: Vec<Window>,
+}
+
+impl MaintenanceMgr {
+    pub fn init(&mut self) {
+        let contents = fs::read_to_string(MAINTENANCE_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<Vec<Window>>(&contents) {
+            Ok(windows) => {
+                self.windows = windows;
+                trace!("load {} succ!", MAINTENANCE_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", MAINTENANCE_FILE, err);
+            }
+        }
+    }
+
+    fn save(&self) {
+        // 单元测试不落盘
+        if cfg!(test) {
+            return;
+        }
+        match serde_json::to_string(&self.windows) {
+            Ok(s) => {
+                if let Err(err) = fs::write(MAINTENANCE_FILE, s) {
+                    error!("save {} fail => {:?}", MAINTENANCE_FILE, err);
+                }
+            }
+            Err(err) => error!("save {} fail => {:?}", MAINTENANCE_FILE, err),
+        }
+    }
+
+    // 到期的窗口移入 ended
+    fn expire(&mut self, now: u64) {
+        let (expired, windows): (Vec<_>, Vec<_>) = self.windows.drain(..).partition(|w| w.expired(now));
+        self.windows = windows;
+        if expired.is_empty() {
+            return;
+        }
+        self.save();
+        for w in expired {
+            info!("maintenance {} expired", w.id);
+            G_INCIDENT_MGR.lock().unwrap().record(&w.id, "Maintenance", "expired");
+            self.ended.push(w);
+        }
+    }
+
+    // 同一目标重复开始时覆盖
+    pub fn start(&mut self, id: &str, group: bool, duration: u64, reason: &str, by: &str) -> Window {
+        let now = now_ts();
+        self.expire(now);
+        let o = Window {
+            id: id.to_string(),
+            group,
+            start: now,
+            end: if duration > 0 { now + duration } else { 0 },
+            reason: reason.to_string(),
+            by: by.to_string(),
+        };
+        self.windows.retain(|w| !(w.id.eq(id) && w.group == group));
+        self.windows.push(o.clone());
+        self.save();
+        info!("maintenance {} start by {}, {}, end => {}", id, by, reason, o.end);
+        G_INCIDENT_MGR
+            .lock()
+            .unwrap()
+            .record(id, "Maintenance", &format!("start by {}, {}", by, reason));
+        o
+    }
+
+    pub fn stop(&mut self, id: &str, group: bool) -> bool {
+        let (stopped, windows): (Vec<_>, Vec<_>) =
+            self.windows.drain(..).partition(|w| w.id.eq(id) && w.group == group);
+        self.windows = windows;
+        if stopped.is_empty() {
+            return false;
+        }
+        self.save();
+        info!("maintenance {} stop", id);
+        G_INCIDENT_MGR.lock().unwrap().record(id, "Maintenance", "stop");
+        self.ended.extend(stopped);
+        true
+    }
+
+    pub fn list(&mut self) -> Vec<Window> {
+        self.expire(now_ts());
+        self.windows.clone()
+    }
+
+    // 取出已结束的窗口, 由统计线程重新检查其中主机的当前状态
+    pub fn take_ended(&mut self) -> Vec<Window> {
+        self.expire(now_ts());
+        std::mem::take(&mut self.ended)
+    }
+
+    pub fn is_active(&self, name: &str, gid: &str) -> bool {
+        let now = now_ts();
+        self.windows
+            .iter()
+            .filter(|w| !w.expired(now))
+            .any(|w| w.matches(name, gid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_stop() {
+        let mut mgr = MaintenanceMgr::default();
+        mgr.start("h1", false, 0, "deploy", "ci");
+        mgr.start("g1", true, 3600, "deploy", "ci");
+        assert!(mgr.is_active("h1", ""));
+        assert!(mgr.is_active("h2", "g1"));
+        assert!(!mgr.is_active("h2", ""));
+        // gid 与主机名相同不匹配
+        assert!(!mgr.is_active("g1", ""));
+
+        // 重复开始覆盖
+        mgr.start("h1", false, 60, "again", "ci");
+        assert_eq!(mgr.list().len(), 2);
+
+        assert!(mgr.stop("h1", false));
+        assert!(!mgr.stop("h1", false));
+        assert!(!mgr.is_active("h1", ""));
+        let ended = mgr.take_ended();
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].reason, "again");
+        assert!(mgr.take_ended().is_empty());
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut mgr = MaintenanceMgr::default();
+        mgr.start("h1", false, 60, "deploy", "ci");
+        mgr.windows[0].end = now_ts() - 1;
+        assert!(!mgr.is_active("h1", ""));
+        assert!(mgr.list().is_empty());
+        let ended = mgr.take_ended();
+        assert_eq!(ended.len(), 1);
+        assert!(ended[0].matches("h1", ""));
+    }
+}
@@ -0,0 +1,253 @@
+#![deny(warnings)]
+// 定时发布主机状态到 mqtt, 可选 Home Assistant MQTT discovery; 可选订阅客户端 mqtt:// 上报
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::time::{self, Duration};
+
+use stat_common::auth;
+use stat_common::codec;
+
+use crate::payload::HostStat;
+use crate::sign;
+use crate::G_STATS_MGR;
+
+fn default_port() -> u16 {
+    1883
+}
+fn default_client_id() -> String {
+    "stat_server".to_string()
+}
+fn default_topic_prefix() -> String {
+    "serverstatus".to_string()
+}
+fn default_ha_prefix() -> String {
+    "homeassistant".to_string()
+}
+fn default_interval() -> u64 {
+    10
+}
+fn default_report_topic() -> String {
+    "serverstatus/report/#".to_string()
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "Default::default")]
+    pub username: String,
+    #[serde(default = "Default::default")]
+    pub password: String,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    // 状态 topic: {topic_prefix}/{host.name}/state
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    // 发布间隔(s)
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    // Home Assistant MQTT discovery
+    #[serde(default = "Default::default")]
+    pub ha_discovery: bool,
+    #[serde(default = "default_ha_prefix")]
+    pub ha_prefix: String,
+    // 订阅客户端上报(--addr mqtt://...)
+    #[serde(default = "Default::default")]
+    pub subscribe: bool,
+    #[serde(default = "default_report_topic")]
+    pub report_topic: String,
+}
+
+fn object_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn state_payload(o: &HostStat) -> serde_json::Value {
+    let percent = |used: u64, total: u64| {
+        if total > 0 {
+            (1000.0 * used as f64 / total as f64).round() / 10.0
+        } else {
+            0.0
+        }
+    };
+    serde_json::json!({
+        "online": o.online4 || o.online6,
+        "cpu": o.cpu,
+        "memory": percent(o.memory_used, o.memory_total),
+        "swap": percent(o.swap_used, o.swap_total),
+        "hdd": percent(o.hdd_used, o.hdd_total),
+        "load_1": o.load_1,
+        "load_5": o.load_5,
+        "load_15": o.load_15,
+        "load_per_core": o.load_per_core,
+        "network_rx": o.network_rx,
+        "network_tx": o.network_tx,
+        "network_in": o.network_in,
+        "network_out": o.network_out,
+        "disk_read": o.disk_read,
+        "disk_write": o.disk_write,
+        "uptime": o.uptime_str,
+        "latest_ts": o.latest_ts,
+    })
+}
+
+// (component, key, name, unit, device_class)
+const HA_SENSORS: &[(&str, &str, &str, &str, &str)] = &[
+    ("binary_sensor", "online", "Online", "", "connectivity"),
+    ("sensor", "cpu", "CPU", "%", ""),
+    ("sensor", "memory", "Memory", "%", ""),
+    ("sensor", "swap", "Swap", "%", ""),
+    ("sensor", "hdd", "Disk", "%", ""),
+    ("sensor", "load_1", "Load", "", ""),
+    ("sensor", "network_rx", "Network RX", "B/s", "data_rate"),
+    ("sensor", "network_tx", "Network TX", "B/s", "data_rate"),
+    ("sensor", "network_in", "Traffic In", "B", "data_size"),
+    ("sensor", "network_out", "Traffic Out", "B", "data_size"),
+    ("sensor", "disk_read", "Disk Read", "B/s", "data_rate"),
+    ("sensor", "disk_write", "Disk Write", "B/s", "data_rate"),
+];
+
+async fn publish_discovery(client: &AsyncClient, cfg: &Config, o: &HostStat) -> anyhow::Result<()> {
+    let id = object_id(&o.name);
+    let device = serde_json::json!({
+        "identifiers": [format!("ssr_{}", id)],
+        "name": if o.alias.is_empty() { &o.name } else { &o.alias },
+        "manufacturer": "ServerStatus-Rust",
+        "model": o.host_type,
+        "suggested_area": o.location,
+    });
+    let state_topic = format!("{}/{}/state", cfg.topic_prefix, o.name);
+    for (component, key, name, unit, device_class) in HA_SENSORS {
+        let mut data = serde_json::json!({
+            "name": name,
+            "unique_id": format!("ssr_{}_{}", id, key),
+            "object_id": format!("ssr_{}_{}", id, key),
+            "state_topic": state_topic,
+            "device": device,
+        });
+        if component.eq(&"binary_sensor") {
+            data["value_template"] = format!("{{{{ 'ON' if value_json.{} else 'OFF' }}}}", key).into();
+        } else {
+            data["value_template"] = format!("{{{{ value_json.{} }}}}", key).into();
+            data["state_class"] = if device_class.eq(&"data_size") {
+                "total_increasing"
+            } else {
+                "measurement"
+            }
+            .into();
+        }
+        if !unit.is_empty() {
+            data["unit_of_measurement"] = (*unit).into();
+        }
+        if !device_class.is_empty() {
+            data["device_class"] = (*device_class).into();
+        }
+        let topic = format!("{}/{}/ssr_{}/{}/config", cfg.ha_prefix, component, id, key);
+        client
+            .publish(topic, QoS::AtLeastOnce, true, serde_json::to_vec(&data)?)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn serv_mqtt(cfg: &'static Config) -> anyhow::Result<()> {
+    let mut opts = MqttOptions::new(&cfg.client_id, &cfg.host, cfg.port);
+    opts.set_keep_alive(Duration::from_secs(30));
+    if !cfg.username.is_empty() {
+        opts.set_credentials(&cfg.username, &cfg.password);
+    }
+    let (client, mut eventloop) = AsyncClient::new(opts, 64);
+    eprintln!("🚀 publish to mqtt://{}:{}", cfg.host, cfg.port);
+
+    // eventloop, 断线自动重连
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = eventloop.poll().await {
+                error!("mqtt eventloop error => {:?}", err);
+                time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+
+    let mut discovered: HashSet<String> = HashSet::new();
+    let mut interval = time::interval(Duration::from_secs(cfg.interval.max(1)));
+    loop {
+        interval.tick().await;
+
+        let servers = match G_STATS_MGR.get() {
+            Some(mgr) => mgr.get_stats().lock().unwrap().servers.clone(),
+            None => continue,
+        };
+        for o in servers.iter() {
+            if cfg.ha_discovery && !discovered.contains(&o.name) {
+                match publish_discovery(&client, cfg, o).await {
+                    Ok(_) => {
+                        discovered.insert(o.name.to_string());
+                    }
+                    Err(err) => error!("mqtt publish discovery error => {:?}", err),
+                }
+            }
+            let topic = format!("{}/{}/state", cfg.topic_prefix, o.name);
+            let payload = serde_json::to_vec(&state_payload(o))?;
+            if let Err(err) = client.publish(topic, QoS::AtMostOnce, true, payload).await {
+                error!("mqtt publish error => {:?}", err);
+            }
+        }
+    }
+}
+
+// 鉴权同 http 签名上报(时间窗口及防重放), 组模式校验 gid; 服务端密码为 argon2 hash 时无法校验
+fn handle_report(payload: &[u8]) -> anyhow::Result<()> {
+    let (ssr_auth, user, ts, sig, body) =
+        auth::parse_mqtt_payload(payload).ok_or_else(|| anyhow::anyhow!("invalid payload"))?;
+    let group_auth = auth::GROUP.eq(ssr_auth);
+    if sign::verify_sig(user, group_auth, ts, sig, body).is_none() {
+        anyhow::bail!("invalid user/group or signature => {}", user);
+    }
+
+    let stat = codec::decode(body, codec::sniff(body))?;
+    if let Some(mgr) = G_STATS_MGR.get() {
+        mgr.report(serde_json::to_value(stat)?)?;
+    }
+    Ok(())
+}
+
+pub async fn sub_mqtt(cfg: &'static Config) -> anyhow::Result<()> {
+    let mut opts = MqttOptions::new(format!("{}_sub", cfg.client_id), &cfg.host, cfg.port);
+    opts.set_keep_alive(Duration::from_secs(30));
+    if !cfg.username.is_empty() {
+        opts.set_credentials(&cfg.username, &cfg.password);
+    }
+    let (client, mut eventloop) = AsyncClient::new(opts, 64);
+    eprintln!("🚀 subscribe mqtt://{}:{}/{}", cfg.host, cfg.port, cfg.report_topic);
+
+    loop {
+        match eventloop.poll().await {
+            // clean session, 每次连上重新订阅
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                client.subscribe(&cfg.report_topic, QoS::AtLeastOnce).await?;
+            }
+            Ok(Event::Incoming(Packet::Publish(msg))) => {
+                if let Err(err) = handle_report(&msg.payload) {
+                    error!("mqtt report {} error => {:?}", msg.topic, err);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                error!("mqtt subscribe eventloop error => {:?}", err);
+                time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
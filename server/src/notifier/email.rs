@@ -10,7 +10,9 @@ use minijinja::context;
 use serde::{Deserialize, Serialize};
 
 use crate::jinja::{add_template, render_template};
-use crate::notifier::{get_tag, Event, HostStat, NOTIFIER_HANDLE};
+use crate::notifier::{
+    get_tag, record_delivery, tpl_kind, Event, HostStat, QuietHours, Severity, NOTIFIER_HANDLE, TEST_MSG,
+};
 
 const KIND: &str = "email";
 
@@ -26,28 +28,38 @@ pub struct Config {
     pub online_tpl: String,
     pub offline_tpl: String,
     pub custom_tpl: String,
+    #[serde(default = "crate::notifier::default_flapping_tpl")]
+    pub flapping_tpl: String,
+    #[serde(default = "Default::default")]
+    pub quiet_hours: String,
+    #[serde(default = "Default::default")]
+    pub min_severity: Severity,
 }
 
 pub struct Email {
     config: &'static Config,
+    tenant: String,
+    tpl: String,
+    quiet: QuietHours,
 }
 
 impl Email {
-    pub fn new(cfg: &'static Config) -> Self {
-        let o = Self { config: cfg };
-        add_template(KIND, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
-        add_template(KIND, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
-        add_template(KIND, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
+        let o = Self {
+            config: cfg,
+            tenant: tenant.to_string(),
+            tpl: tpl_kind(KIND, tenant),
+            quiet: QuietHours::new(&cfg.quiet_hours),
+        };
+        add_template(&o.tpl, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Flapping), o.config.flapping_tpl.to_string());
         o
     }
-}
-
-impl crate::notifier::Notifier for Email {
-    fn kind(&self) -> &'static str {
-        KIND
-    }
 
-    fn send_notify(&self, html_content: String) -> Result<()> {
+    // payload 见 record_delivery, 为空时不重试
+    fn send(&self, html_content: String, payload: String) -> Result<()> {
         let email = Message::builder()
             .from(format!("ServerStatus <{}>", self.config.username).parse().unwrap())
             .to(self.config.to.parse().unwrap())
@@ -56,7 +68,7 @@ impl crate::notifier::Notifier for Email {
                 MultiPart::alternative().singlepart(
                     SinglePart::builder()
                         .header(header::ContentType::TEXT_HTML)
-                        .body(html_content),
+                        .body(html_content.to_string()),
                 ),
             )
             .unwrap();
@@ -64,6 +76,7 @@ impl crate::notifier::Notifier for Email {
         let creds = Credentials::new(self.config.username.to_string(), self.config.password.to_string());
 
         let smtp_server = self.config.server.to_string();
+        let tenant = self.tenant.to_string();
         let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
         handle.spawn(async move {
             // Open a remote connection to gmail
@@ -76,9 +89,11 @@ impl crate::notifier::Notifier for Email {
             // Send the email
             match mailer.send(email).await {
                 Ok(_) => {
+                    record_delivery(KIND, &tenant, &payload, true, "sent".to_string());
                     info!("Email sent successfully!");
                 }
                 Err(err) => {
+                    record_delivery(KIND, &tenant, &payload, false, err.to_string());
                     error!("Could not send email: {:?}", err);
                 }
             }
@@ -86,23 +101,57 @@ impl crate::notifier::Notifier for Email {
 
         Ok(())
     }
+}
+
+impl crate::notifier::Notifier for Email {
+    fn kind(&self) -> &'static str {
+        KIND
+    }
+
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn quiet_hours(&self) -> Option<&QuietHours> {
+        Some(&self.quiet)
+    }
+
+    fn send_notify(&self, html_content: String) -> Result<()> {
+        self.send(html_content.to_string(), html_content)
+    }
+
+    fn notify_test(&self) -> Result<()> {
+        self.send(TEST_MSG.to_string(), String::new())
+    }
 
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
         render_template(
-            self.kind(),
+            &self.tpl,
             get_tag(e),
             context!(host => stat, config => self.config, ip_info => stat.ip_info, sys_info => stat.sys_info),
             true,
         )
-        .map(|content| match *e {
-            Event::NodeUp | Event::NodeDown => self.send_notify(content).unwrap(),
-            Event::Custom => {
-                info!("render.custom.tpl => {}", content);
-                if !content.is_empty() {
-                    self.send_notify(format!("{}\n{}", self.config.title, content))
-                        .unwrap_or_else(|err| {
-                            error!("send_msg err => {:?}", err);
-                        });
+        .map(|content| {
+            if self.quiet.hold(e, &content) {
+                info!("{} quiet hours, hold {:?}", self.kind(), e);
+                return;
+            }
+            match *e {
+                Event::NodeUp | Event::NodeDown | Event::Flapping | Event::Resolved => {
+                    self.send_notify(content).unwrap()
+                }
+                Event::Custom => {
+                    info!("render.custom.tpl => {}", content);
+                    if !content.is_empty() {
+                        self.send_notify(format!("{}\n{}", self.config.title, content))
+                            .unwrap_or_else(|err| {
+                                error!("send_msg err => {:?}", err);
+                            });
+                    }
                 }
             }
         })
@@ -9,7 +9,7 @@ use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
 
 use crate::jinja::{add_template, render_template};
-use crate::notifier::{Event, HostStat, NOTIFIER_HANDLE};
+use crate::notifier::{record_delivery, Event, HostStat, Severity, NOTIFIER_HANDLE, TEST_MSG};
 
 const KIND: &str = "log";
 
@@ -18,6 +18,8 @@ pub struct Config {
     pub enabled: bool,
     pub log_dir: String,
     pub tpl: String,
+    #[serde(default = "Default::default")]
+    pub min_severity: Severity,
 }
 
 pub struct Log {
@@ -41,6 +43,10 @@ impl crate::notifier::Notifier for Log {
         KIND
     }
 
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
     fn send_notify(&self, content: String) -> Result<()> {
         if content.is_empty() {
             return Ok(());
@@ -78,10 +84,15 @@ impl crate::notifier::Notifier for Log {
             file.flush()
                 .await
                 .unwrap_or_else(|_| panic!("can't flush log `{}", log_file));
+            record_delivery(KIND, "", &content, true, log_file);
         });
         Ok(())
     }
 
+    fn notify_test(&self) -> Result<()> {
+        self.send_notify(TEST_MSG.to_string())
+    }
+
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
         render_template(
             self.kind(),
@@ -1,40 +1,333 @@
 use anyhow::Result;
+use chrono::{Local, Timelike};
 use once_cell::sync::Lazy;
-use serde::Serialize;
-use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::runtime::Handle;
+use tokio::time::{self, Duration, Instant};
 
 use crate::payload::HostStat;
 
 pub mod email;
 pub mod log;
+pub mod opsgenie;
+pub mod pagerduty;
+pub mod paging;
+pub mod retry;
 pub mod tgbot;
 pub mod webhook;
 pub mod wechat;
 
 pub static NOTIFIER_HANDLE: Lazy<Mutex<Option<Handle>>> = Lazy::new(Default::default);
 
+// proxy 如 http://127.0.0.1:7890, socks5h://127.0.0.1:1080, 空为使用全局 notify_proxy, direct 为直连
+pub fn http_client(proxy: &str) -> reqwest::Client {
+    let global = crate::G_CONFIG
+        .get()
+        .map(|o| o.notify_proxy.as_str())
+        .unwrap_or_default();
+    let proxy = if proxy.is_empty() { global } else { proxy };
+    let mut builder = reqwest::Client::builder();
+    if !proxy.is_empty() && !proxy.eq("direct") {
+        match reqwest::Proxy::all(proxy) {
+            Ok(o) => builder = builder.proxy(o),
+            Err(err) => error!("invalid proxy `{}` => {:?}", proxy, err),
+        }
+    }
+    builder.build().unwrap_or_else(|err| {
+        error!("build http client error => {:?}", err);
+        reqwest::Client::new()
+    })
+}
+
+// 租户通知与全局通知相互独立, 模板按 kind@tenant 注册
+pub fn tpl_kind(kind: &str, tenant: &str) -> String {
+    if tenant.is_empty() {
+        kind.to_string()
+    } else {
+        format!("{}@{}", kind, tenant)
+    }
+}
+
+// 各通道最近一次发送结果, 按 (tenant, kind) 区分
+#[derive(Debug, Serialize, Clone)]
+pub struct Delivery {
+    pub kind: &'static str,
+    pub tenant: String,
+    pub ts: u128,
+    pub ok: bool,
+    pub detail: String,
+}
+
+static G_DELIVERY: Lazy<Mutex<HashMap<(String, &'static str), Delivery>>> = Lazy::new(Default::default);
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+// payload 为 Notifier::resend 可重发的内容, 不含密钥及接口地址, 失败进入重试队列; 为空时不重试(如测试消息)
+pub fn record_delivery(kind: &'static str, tenant: &str, payload: &str, ok: bool, detail: String) {
+    deliver(kind, tenant, payload, ok, false, detail)
+}
+
+// permanent: 不可恢复的失败, 不再重试
+fn deliver(kind: &'static str, tenant: &str, payload: &str, ok: bool, permanent: bool, detail: String) {
+    if !payload.is_empty() {
+        {
+            let mut retry_mgr = retry::G_RETRY_MGR.lock().unwrap();
+            if ok {
+                retry_mgr.succeeded(tenant, kind, payload);
+            } else {
+                retry_mgr.failed(tenant, kind, payload, &detail, permanent);
+            }
+        }
+        retry::save();
+    }
+    let o = Delivery {
+        kind,
+        tenant: tenant.to_string(),
+        ts: now_ms(),
+        ok,
+        detail,
+    };
+    G_DELIVERY.lock().unwrap().insert((tenant.to_string(), kind), o);
+}
+
+// 4xx 除 408/429 外为请求本身错误, 重试无意义
+pub fn record_resp(kind: &'static str, tenant: &str, payload: &str, resp: &reqwest::Result<reqwest::Response>) {
+    match resp {
+        Ok(resp) => {
+            let status = resp.status();
+            let permanent = status.is_client_error()
+                && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                && status != reqwest::StatusCode::TOO_MANY_REQUESTS;
+            deliver(
+                kind,
+                tenant,
+                payload,
+                status.is_success(),
+                permanent,
+                status.to_string(),
+            )
+        }
+        Err(err) => deliver(kind, tenant, payload, false, false, err.to_string()),
+    }
+}
+
+// telegram => tgbot, 空或 all 为全部
+pub fn channel_kind(channel: &str) -> &str {
+    match channel.trim() {
+        "" | "all" => "",
+        "telegram" | "tg" => "tgbot",
+        "mail" => "email",
+        o => o,
+    }
+}
+
+// 通过指定通道发送测试消息, 等待并返回各通道发送结果
+pub async fn test_channels(notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>, channel: &str) -> Vec<Delivery> {
+    let kind = channel_kind(channel);
+    let start = now_ms();
+    let mut pending = Vec::new();
+    let mut result = Vec::new();
+    for notifier in &*notifies.lock().unwrap() {
+        if !kind.is_empty() && !kind.eq(notifier.kind()) {
+            continue;
+        }
+        match notifier.notify_test() {
+            Ok(_) => pending.push((notifier.tenant().to_string(), notifier.kind())),
+            Err(err) => result.push(Delivery {
+                kind: notifier.kind(),
+                tenant: notifier.tenant().to_string(),
+                ts: now_ms(),
+                ok: false,
+                detail: err.to_string(),
+            }),
+        }
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(15);
+    while !pending.is_empty() {
+        {
+            let deliveries = G_DELIVERY.lock().unwrap();
+            pending.retain(|k| match deliveries.get(k).filter(|o| o.ts >= start) {
+                Some(o) => {
+                    result.push(o.clone());
+                    false
+                }
+                None => true,
+            });
+        }
+        if pending.is_empty() || Instant::now() >= deadline {
+            break;
+        }
+        time::sleep(Duration::from_millis(200)).await;
+    }
+    for (tenant, kind) in pending {
+        result.push(Delivery {
+            kind,
+            tenant,
+            ts: now_ms(),
+            ok: false,
+            detail: "timeout".to_string(),
+        });
+    }
+    result
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub enum Event {
     NodeUp,
     NodeDown,
     Custom,
+    Flapping,
+    // 持续性告警恢复(服务/端口/证书等), 与主机上线区分
+    Resolved,
 }
 
-fn get_tag(e: &Event) -> &'static str {
+pub fn get_tag(e: &Event) -> &'static str {
     match *e {
         Event::NodeUp => "NodeUp",
         Event::NodeDown => "NodeDown",
         Event::Custom => "Custom",
+        Event::Flapping => "Flapping",
+        Event::Resolved => "Resolved",
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity::Info
+    }
+}
+
+pub fn get_severity(e: &Event) -> Severity {
+    match *e {
+        Event::NodeUp | Event::Resolved => Severity::Info,
+        Event::Custom | Event::Flapping => Severity::Warning,
+        Event::NodeDown => Severity::Critical,
+    }
+}
+
+pub const TEST_MSG: &str = "❗ServerStatus test msg";
+
+pub fn default_flapping_tpl() -> String {
+    "{{config.title}} \n🔁 {{host.location}} {{host.name}} 主机频繁上下线({{host.flap_count}}次), 已暂停上下线通知"
+        .to_string()
+}
+
+fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+    if h > 23 || m > 59 {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+// 静默时段, 如 "23:00-07:00", 期间非 critical 通知暂存, 结束后合并发送
+#[derive(Debug, Default)]
+pub struct QuietHours {
+    range: Option<(u32, u32)>,
+    held: Mutex<Vec<String>>,
+}
+
+impl QuietHours {
+    pub fn new(spec: &str) -> Self {
+        let range = spec
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_hhmm(start)?, parse_hhmm(end)?)));
+        if range.is_none() && !spec.trim().is_empty() {
+            error!("invalid quiet_hours `{}", spec);
+        }
+        Self {
+            range,
+            held: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        if let Some((start, end)) = self.range {
+            let now = Local::now();
+            let cur = now.hour() * 60 + now.minute();
+            if start <= end {
+                return start <= cur && cur < end;
+            }
+            return cur >= start || cur < end;
+        }
+        false
+    }
+
+    // 返回 true 表示已暂存, 不再立即发送
+    pub fn hold(&self, e: &Event, content: &str) -> bool {
+        if get_severity(e) >= Severity::Critical {
+            return false;
+        }
+        if content.is_empty() || !self.is_quiet() {
+            return false;
+        }
+        self.held.lock().unwrap().push(content.to_string());
+        true
+    }
+
+    pub fn take_batch(&self) -> Option<String> {
+        if self.is_quiet() {
+            return None;
+        }
+        let mut held = self.held.lock().unwrap();
+        if held.is_empty() {
+            return None;
+        }
+        let content = format!("🌙 静默时段内 {} 条通知\n\n{}", held.len(), held.join("\n\n"));
+        held.clear();
+        Some(content)
     }
 }
 
 pub trait Notifier {
     fn kind(&self) -> &'static str;
+    // 所属租户, 空为全局, 只接收本租户主机的事件
+    fn tenant(&self) -> &str {
+        ""
+    }
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()>;
     // send notify impl
     fn send_notify(&self, content: String) -> Result<()>;
-    fn notify_test(&self) -> Result<()> {
-        self.send_notify("❗ServerStatus test msg".to_string())
+    // 带事件的文本通知, 事件型通知方式据此确定级别; key 非空时为持续性告警, 可被同 key 的 Resolved 关闭
+    fn send_event(&self, _e: &Event, _key: &str, content: String) -> Result<()> {
+        self.send_notify(content)
+    }
+    // 测试消息不进入重试队列
+    fn notify_test(&self) -> Result<()>;
+    // 重试队列重发, payload 见 record_delivery
+    fn resend(&self, payload: String) -> Result<()> {
+        self.send_notify(payload)
+    }
+    fn quiet_hours(&self) -> Option<&QuietHours> {
+        None
+    }
+    // 低于该级别的事件不发送
+    fn min_severity(&self) -> Severity {
+        Severity::Info
+    }
+    // 静默时段结束后补发
+    fn flush_quiet(&self) -> Result<()> {
+        if let Some(content) = self.quiet_hours().and_then(|o| o.take_batch()) {
+            return self.send_notify(content);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,239 @@
+#![deny(warnings)]
+use anyhow::Result;
+use log::info;
+use minijinja::context;
+use serde::{Deserialize, Serialize};
+
+use crate::jinja::{add_template, render_template};
+use crate::notifier::paging::{alert_key, details, Paging};
+use crate::notifier::{get_severity, get_tag, tpl_kind, Event, HostStat, Severity, TEST_MSG};
+
+// https://docs.opsgenie.com/docs/alert-api
+const KIND: &str = "opsgenie";
+
+fn default_api_url() -> String {
+    "https://api.opsgenie.com".to_string()
+}
+fn default_min_severity() -> Severity {
+    Severity::Warning
+}
+fn default_priority_critical() -> String {
+    "P1".to_string()
+}
+fn default_priority_warning() -> String {
+    "P3".to_string()
+}
+fn default_priority_info() -> String {
+    "P5".to_string()
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    pub enabled: bool,
+    // 代理, 空为使用全局 notify_proxy, direct 为直连
+    #[serde(default = "Default::default")]
+    pub proxy: String,
+    pub api_key: String,
+    // EU 区域 https://api.eu.opsgenie.com
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    // 低于该级别不创建告警, 恢复事件总是关闭告警
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    #[serde(default = "default_priority_critical")]
+    pub priority_critical: String,
+    #[serde(default = "default_priority_warning")]
+    pub priority_warning: String,
+    #[serde(default = "default_priority_info")]
+    pub priority_info: String,
+    // 额外 tags, 另会附带主机 type/location/gid 及 extra 标签
+    #[serde(default = "Default::default")]
+    pub tags: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub custom_tpl: String,
+}
+
+pub struct Opsgenie {
+    config: &'static Config,
+    tenant: String,
+    tpl: String,
+    paging: Paging,
+}
+
+impl Opsgenie {
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
+        let tpl = tpl_kind(KIND, tenant);
+        add_template(&tpl, get_tag(&Event::Custom), cfg.custom_tpl.to_string());
+        Self {
+            config: cfg,
+            tenant: tenant.to_string(),
+            tpl,
+            paging: Paging::new(KIND, tenant, &cfg.proxy),
+        }
+    }
+
+    fn priority(&self, s: Severity) -> &str {
+        match s {
+            Severity::Critical => &self.config.priority_critical,
+            Severity::Warning => &self.config.priority_warning,
+            Severity::Info => &self.config.priority_info,
+        }
+    }
+
+    fn tags(&self, stat: &HostStat) -> Vec<String> {
+        let mut tags = self.config.tags.clone();
+        for v in [&stat.host_type, &stat.location, &stat.gid] {
+            if !v.is_empty() {
+                tags.push(v.to_string());
+            }
+        }
+        for (k, v) in stat.extra.iter() {
+            tags.push(format!("{}:{}", k, v));
+        }
+        tags
+    }
+
+    // 重试保存 path 及请求内容, 不含 api_key; retry 为 false 时不重试(测试消息)
+    fn post(&self, path: String, data: serde_json::Value, retry: bool) -> Result<()> {
+        let payload = if retry {
+            serde_json::json!({"path": path, "data": data}).to_string()
+        } else {
+            String::new()
+        };
+        let auth = format!("GenieKey {}", self.config.api_key);
+        self.paging
+            .post(format!("{}{}", self.config.api_url, path), Some(auth), data, payload)
+    }
+
+    fn create(&self, e: &Event, stat: &HostStat, message: String) -> Result<()> {
+        let severity = get_severity(e);
+        if severity < self.config.min_severity {
+            return Ok(());
+        }
+        let alias = alert_key(&stat.name, get_tag(e));
+        self.paging.mark(&alias);
+        self.post(
+            "/v2/alerts".to_string(),
+            serde_json::json!({
+                "message": message.chars().take(130).collect::<String>(),
+                "alias": alias,
+                "description": message,
+                "priority": self.priority(severity),
+                "tags": self.tags(stat),
+                "entity": stat.name,
+                "source": "ServerStatus",
+                "details": details(e, stat),
+            }),
+            true,
+        )
+    }
+
+    // 文本通知, key 非空时作为 alias, 可被关闭
+    fn send_text(&self, severity: Severity, event: &str, key: &str, content: String) -> Result<()> {
+        if content.is_empty() || severity < self.config.min_severity {
+            info!("{} ignore text notify => {}", KIND, content);
+            return Ok(());
+        }
+        let mut data = serde_json::json!({
+            "message": content.chars().take(130).collect::<String>(),
+            "description": content,
+            "priority": self.priority(severity),
+            "tags": self.config.tags,
+            "source": "ServerStatus",
+            "details": {"event": event},
+        });
+        if !key.is_empty() {
+            self.paging.mark(key);
+            data["alias"] = key.into();
+        }
+        self.post("/v2/alerts".to_string(), data, true)
+    }
+
+    // 仅关闭已创建的告警, 状态持久化, 重启后不丢失
+    fn close(&self, alias: &str) -> Result<()> {
+        if !self.paging.unmark(alias) {
+            return Ok(());
+        }
+        let url = url::form_urlencoded::byte_serialize(alias.as_bytes()).collect::<String>();
+        self.post(
+            format!("/v2/alerts/{}/close?identifierType=alias", url),
+            serde_json::json!({"source": "ServerStatus", "note": "recovered"}),
+            true,
+        )
+    }
+}
+
+impl crate::notifier::Notifier for Opsgenie {
+    fn kind(&self) -> &'static str {
+        KIND
+    }
+
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn send_notify(&self, content: String) -> Result<()> {
+        self.send_text(Severity::Info, "Info", "", content)
+    }
+
+    fn send_event(&self, e: &Event, key: &str, content: String) -> Result<()> {
+        match *e {
+            Event::Resolved if !key.is_empty() => self.close(key),
+            _ => self.send_text(get_severity(e), get_tag(e), key, content),
+        }
+    }
+
+    fn resend(&self, payload: String) -> Result<()> {
+        let o: serde_json::Value = serde_json::from_str(&payload)?;
+        self.post(
+            o["path"].as_str().unwrap_or_default().to_string(),
+            o["data"].clone(),
+            true,
+        )
+    }
+
+    fn notify_test(&self) -> Result<()> {
+        self.post(
+            "/v2/alerts".to_string(),
+            serde_json::json!({
+                "message": TEST_MSG,
+                "priority": self.config.priority_info,
+                "source": "ServerStatus",
+            }),
+            false,
+        )
+    }
+
+    fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
+        let name = if stat.alias.is_empty() { &stat.name } else { &stat.alias };
+        match *e {
+            Event::NodeUp => {
+                self.close(&alert_key(&stat.name, get_tag(&Event::NodeDown)))?;
+                self.close(&alert_key(&stat.name, get_tag(&Event::Flapping)))
+            }
+            Event::Resolved => Ok(()),
+            Event::NodeDown => self.create(e, stat, format!("{} {} is down", stat.location, name)),
+            Event::Flapping => self.create(
+                e,
+                stat,
+                format!(
+                    "{} {} is flapping ({} transitions)",
+                    stat.location, name, stat.flap_count
+                ),
+            ),
+            Event::Custom => {
+                let content = render_template(
+                    &self.tpl,
+                    get_tag(e),
+                    context!(host => stat, config => self.config, ip_info => stat.ip_info, sys_info => stat.sys_info),
+                    true,
+                )?;
+                if content.is_empty() {
+                    self.close(&alert_key(&stat.name, get_tag(e)))
+                } else {
+                    self.create(e, stat, content)
+                }
+            }
+        }
+    }
+}
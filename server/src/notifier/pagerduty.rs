@@ -0,0 +1,200 @@
+#![deny(warnings)]
+use anyhow::Result;
+use log::info;
+use minijinja::context;
+use serde::{Deserialize, Serialize};
+
+use crate::jinja::{add_template, render_template};
+use crate::notifier::paging::{alert_key, details, Paging};
+use crate::notifier::{get_severity, get_tag, tpl_kind, Event, HostStat, Severity, TEST_MSG};
+
+// https://developer.pagerduty.com/docs/ZG9jOjExMDI5NTgw-events-api-v2-overview
+const KIND: &str = "pagerduty";
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+fn default_min_severity() -> Severity {
+    Severity::Critical
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    pub enabled: bool,
+    // 代理, 空为使用全局 notify_proxy, direct 为直连
+    #[serde(default = "Default::default")]
+    pub proxy: String,
+    // Events API v2 integration key
+    pub routing_key: String,
+    // 低于该级别不触发, 恢复事件总是发送
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    #[serde(default = "Default::default")]
+    pub custom_tpl: String,
+}
+
+pub struct PagerDuty {
+    config: &'static Config,
+    tenant: String,
+    tpl: String,
+    paging: Paging,
+}
+
+fn pd_severity(s: Severity) -> &'static str {
+    match s {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+impl PagerDuty {
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
+        let tpl = tpl_kind(KIND, tenant);
+        add_template(&tpl, get_tag(&Event::Custom), cfg.custom_tpl.to_string());
+        Self {
+            config: cfg,
+            tenant: tenant.to_string(),
+            tpl,
+            paging: Paging::new(KIND, tenant, &cfg.proxy),
+        }
+    }
+
+    // 重试保存的事件不含 routing_key, 发送时补上; retry 为 false 时不重试(测试消息)
+    fn enqueue(&self, mut data: serde_json::Value, retry: bool) -> Result<()> {
+        let payload = if retry { data.to_string() } else { String::new() };
+        data["routing_key"] = self.config.routing_key.to_string().into();
+        self.paging.post(EVENTS_URL.to_string(), None, data, payload)
+    }
+
+    fn trigger(&self, e: &Event, stat: &HostStat, summary: String) -> Result<()> {
+        let severity = get_severity(e);
+        if severity < self.config.min_severity {
+            return Ok(());
+        }
+        let key = alert_key(&stat.name, get_tag(e));
+        self.paging.mark(&key);
+        self.enqueue(
+            serde_json::json!({
+                "event_action": "trigger",
+                "dedup_key": key,
+                "payload": {
+                    "summary": summary,
+                    "source": stat.name,
+                    "severity": pd_severity(severity),
+                    "component": stat.alias,
+                    "group": stat.location,
+                    "class": get_tag(e),
+                    "custom_details": details(e, stat),
+                },
+            }),
+            true,
+        )
+    }
+
+    // 文本通知, key 非空时作为 dedup_key, 可被恢复
+    fn send_text(&self, severity: Severity, class: &str, key: &str, content: String) -> Result<()> {
+        if content.is_empty() || severity < self.config.min_severity {
+            info!("{} ignore text notify => {}", KIND, content);
+            return Ok(());
+        }
+        let mut data = serde_json::json!({
+            "event_action": "trigger",
+            "payload": {
+                "summary": content.chars().take(1024).collect::<String>(),
+                "source": "ServerStatus",
+                "severity": pd_severity(severity),
+                "class": class,
+            },
+        });
+        if !key.is_empty() {
+            self.paging.mark(key);
+            data["dedup_key"] = key.into();
+        }
+        self.enqueue(data, true)
+    }
+
+    // 仅恢复已触发的事件, 状态持久化, 重启后不丢失
+    fn resolve(&self, key: &str) -> Result<()> {
+        if !self.paging.unmark(key) {
+            return Ok(());
+        }
+        self.enqueue(
+            serde_json::json!({
+                "event_action": "resolve",
+                "dedup_key": key,
+            }),
+            true,
+        )
+    }
+}
+
+impl crate::notifier::Notifier for PagerDuty {
+    fn kind(&self) -> &'static str {
+        KIND
+    }
+
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn send_notify(&self, content: String) -> Result<()> {
+        self.send_text(Severity::Info, "Info", "", content)
+    }
+
+    fn send_event(&self, e: &Event, key: &str, content: String) -> Result<()> {
+        match *e {
+            Event::Resolved if !key.is_empty() => self.resolve(key),
+            _ => self.send_text(get_severity(e), get_tag(e), key, content),
+        }
+    }
+
+    fn resend(&self, payload: String) -> Result<()> {
+        self.enqueue(serde_json::from_str(&payload)?, true)
+    }
+
+    fn notify_test(&self) -> Result<()> {
+        self.enqueue(
+            serde_json::json!({
+                "event_action": "trigger",
+                "payload": {
+                    "summary": TEST_MSG,
+                    "source": "ServerStatus",
+                    "severity": "info",
+                },
+            }),
+            false,
+        )
+    }
+
+    fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
+        let name = if stat.alias.is_empty() { &stat.name } else { &stat.alias };
+        match *e {
+            Event::NodeUp => {
+                self.resolve(&alert_key(&stat.name, get_tag(&Event::NodeDown)))?;
+                self.resolve(&alert_key(&stat.name, get_tag(&Event::Flapping)))
+            }
+            Event::Resolved => Ok(()),
+            Event::NodeDown => self.trigger(e, stat, format!("{} {} is down", stat.location, name)),
+            Event::Flapping => self.trigger(
+                e,
+                stat,
+                format!(
+                    "{} {} is flapping ({} transitions)",
+                    stat.location, name, stat.flap_count
+                ),
+            ),
+            Event::Custom => {
+                let content = render_template(
+                    &self.tpl,
+                    get_tag(e),
+                    context!(host => stat, config => self.config, ip_info => stat.ip_info, sys_info => stat.sys_info),
+                    true,
+                )?;
+                if content.is_empty() {
+                    self.resolve(&alert_key(&stat.name, get_tag(e)))
+                } else {
+                    self.trigger(e, stat, content)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+#![deny(warnings)]
+// PagerDuty / Opsgenie 等事件型通知的公共部分, 按 key 触发与恢复, 已触发状态持久化, 重启后仍能恢复
+use anyhow::Result;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Mutex;
+use tokio::time::Duration;
+
+use crate::notifier::{get_tag, http_client, record_resp, Event, HostStat, NOTIFIER_HANDLE};
+
+const PAGING_FILE: &str = "paging.json";
+
+static G_TRIGGERED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| {
+    let contents = fs::read_to_string(PAGING_FILE).unwrap_or_default();
+    if contents.is_empty() {
+        return Default::default();
+    }
+    Mutex::new(serde_json::from_str(&contents).unwrap_or_else(|err| {
+        warn!("ignore invalid {} => {:?}", PAGING_FILE, err);
+        Default::default()
+    }))
+});
+
+fn save(data: String) {
+    if let Err(err) = fs::write(PAGING_FILE, data) {
+        error!("save {} fail => {:?}", PAGING_FILE, err);
+    }
+}
+
+pub fn alert_key(host: &str, tag: &str) -> String {
+    format!("ssr/{}/{}", host, tag)
+}
+
+// 附带的主机概要, 不含完整 HostStat
+pub fn details(e: &Event, stat: &HostStat) -> serde_json::Value {
+    serde_json::json!({
+        "event": get_tag(e),
+        "host": stat.name,
+        "alias": stat.alias,
+        "type": stat.host_type,
+        "location": stat.location,
+        "gid": stat.gid,
+        "online4": stat.online4,
+        "online6": stat.online6,
+        "uptime": stat.uptime_str,
+        "load_1": stat.load_1,
+        "cpu": stat.cpu,
+        "memory_used": stat.memory_used,
+        "memory_total": stat.memory_total,
+        "hdd_used": stat.hdd_used,
+        "hdd_total": stat.hdd_total,
+        "latest_ts": stat.latest_ts,
+    })
+}
+
+pub struct Paging {
+    kind: &'static str,
+    tenant: String,
+    http_client: reqwest::Client,
+}
+
+impl Paging {
+    pub fn new(kind: &'static str, tenant: &str, proxy: &str) -> Self {
+        Self {
+            kind,
+            tenant: tenant.to_string(),
+            http_client: http_client(proxy),
+        }
+    }
+
+    fn state_key(&self, key: &str) -> String {
+        format!("{}/{}", self.kind, key)
+    }
+
+    fn update(&self, key: &str, triggered: bool) -> bool {
+        let data = {
+            let mut set = G_TRIGGERED.lock().unwrap();
+            let changed = if triggered {
+                set.insert(self.state_key(key))
+            } else {
+                set.remove(&self.state_key(key))
+            };
+            if !changed {
+                return false;
+            }
+            serde_json::to_string(&*set).unwrap_or_default()
+        };
+        save(data);
+        true
+    }
+
+    pub fn mark(&self, key: &str) {
+        self.update(key, true);
+    }
+
+    // 返回 true 表示之前已触发, 需要发送恢复
+    pub fn unmark(&self, key: &str) -> bool {
+        self.update(key, false)
+    }
+
+    // payload 见 record_delivery
+    pub fn post(&self, url: String, auth: Option<String>, data: serde_json::Value, payload: String) -> Result<()> {
+        let (kind, tenant) = (self.kind, self.tenant.to_string());
+        let http_client = self.http_client.clone();
+        let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
+        handle.spawn(async move {
+            let mut req = http_client.post(&url).timeout(Duration::from_secs(5)).json(&data);
+            if let Some(auth) = auth {
+                req = req.header(reqwest::header::AUTHORIZATION, auth);
+            }
+            let resp = req.send().await;
+            record_resp(kind, &tenant, &payload, &resp);
+            match resp {
+                Ok(resp) => {
+                    info!("{} resp => {:?}", kind, resp);
+                }
+                Err(err) => {
+                    error!("{} error => {:?}", kind, err);
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+#![deny(warnings)]
+// 发送失败的通知持久化重试, 超过最大次数移入死信列表
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RETRY_FILE: &str = "notify_queue.json";
+const BACKOFF_MIN: u64 = 30;
+const BACKOFF_MAX: u64 = 3600;
+// 重试发出后等待结果, 超时未回报则再次重试
+const INFLIGHT_SECS: u64 = 120;
+
+pub static G_RETRY_MGR: Lazy<Mutex<RetryMgr>> = Lazy::new(Default::default);
+// 写文件时不持有 G_RETRY_MGR, 保证快照按顺序写入
+static SAVE_LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn backoff(attempts: u32) -> u64 {
+    BACKOFF_MIN
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(BACKOFF_MAX)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub kind: String,
+    // 只由原租户的同类通知方式重发
+    #[serde(default = "Default::default")]
+    pub tenant: String,
+    // 各通知方式自行解析, 见 Notifier::resend
+    pub payload: String,
+    pub attempts: u32,
+    pub created: u64,
+    pub next_ts: u64,
+    #[serde(default = "Default::default")]
+    pub last_error: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    queue: Vec<Job>,
+    dead: Vec<Job>,
+}
+
+#[derive(Debug, Default)]
+pub struct RetryMgr {
+    max_attempts: u32,
+    max_dead: usize,
+    next_id: u64,
+    dirty: bool,
+    store: Store,
+}
+
+// 修改后调用, 在锁外写文件
+pub fn save() {
+    let _guard = SAVE_LOCK.lock().unwrap();
+    let data = {
+        let mut mgr = G_RETRY_MGR.lock().unwrap();
+        if !mgr.dirty {
+            return;
+        }
+        mgr.dirty = false;
+        serde_json::to_string(&mgr.store)
+    };
+    match data {
+        Ok(s) => {
+            if let Err(err) = fs::write(RETRY_FILE, s) {
+                error!("save {} fail => {:?}", RETRY_FILE, err);
+            }
+        }
+        Err(err) => error!("save {} fail => {:?}", RETRY_FILE, err),
+    }
+}
+
+impl RetryMgr {
+    pub fn init(&mut self, max_attempts: u32, max_dead: usize) {
+        self.max_attempts = max_attempts;
+        self.max_dead = max_dead;
+        let contents = fs::read_to_string(RETRY_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<Store>(&contents) {
+            Ok(store) => {
+                self.next_id = store
+                    .queue
+                    .iter()
+                    .chain(store.dead.iter())
+                    .map(|o| o.id)
+                    .max()
+                    .unwrap_or(0);
+                self.store = store;
+                trace!("load {} succ!", RETRY_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", RETRY_FILE, err);
+            }
+        }
+    }
+
+    // permanent: 不可恢复的错误(如 4xx), 直接移入死信
+    pub fn failed(&mut self, tenant: &str, kind: &str, payload: &str, err: &str, permanent: bool) {
+        if self.max_attempts == 0 {
+            return;
+        }
+        let now = now_ts();
+        let pos = self
+            .store
+            .queue
+            .iter()
+            .position(|o| o.tenant.eq(tenant) && o.kind.eq(kind) && o.payload.eq(payload));
+        let mut job = match pos {
+            Some(pos) => self.store.queue.remove(pos),
+            None => {
+                self.next_id += 1;
+                Job {
+                    id: self.next_id,
+                    kind: kind.to_string(),
+                    tenant: tenant.to_string(),
+                    payload: payload.to_string(),
+                    created: now,
+                    ..Default::default()
+                }
+            }
+        };
+        job.attempts += 1;
+        job.last_error = err.to_string();
+        if permanent || job.attempts > self.max_attempts {
+            warn!("{} notify dead after {} attempts => {}", kind, job.attempts, err);
+            self.store.dead.push(job);
+            if self.max_dead > 0 && self.store.dead.len() > self.max_dead {
+                let n = self.store.dead.len() - self.max_dead;
+                self.store.dead.drain(..n);
+            }
+        } else {
+            job.next_ts = now + backoff(job.attempts);
+            self.store.queue.push(job);
+        }
+        self.dirty = true;
+    }
+
+    pub fn succeeded(&mut self, tenant: &str, kind: &str, payload: &str) {
+        let len = self.store.queue.len();
+        self.store
+            .queue
+            .retain(|o| !(o.tenant.eq(tenant) && o.kind.eq(kind) && o.payload.eq(payload)));
+        if len != self.store.queue.len() {
+            self.dirty = true;
+        }
+    }
+
+    // 取出到期任务 (tenant, kind, payload)
+    pub fn take_due(&mut self) -> Vec<(String, String, String)> {
+        let now = now_ts();
+        let mut due = Vec::new();
+        for o in self.store.queue.iter_mut().filter(|o| o.next_ts <= now) {
+            o.next_ts = now + INFLIGHT_SECS;
+            due.push((o.tenant.to_string(), o.kind.to_string(), o.payload.to_string()));
+        }
+        due
+    }
+
+    pub fn queue(&self) -> &Vec<Job> {
+        &self.store.queue
+    }
+
+    pub fn dead(&self) -> &Vec<Job> {
+        &self.store.dead
+    }
+
+    // 死信重新入队
+    pub fn requeue(&mut self, id: u64) -> bool {
+        match self.store.dead.iter().position(|o| o.id == id) {
+            Some(pos) => {
+                let mut job = self.store.dead.remove(pos);
+                job.attempts = 0;
+                job.next_ts = 0;
+                self.store.queue.push(job);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_dead(&mut self, id: u64) -> bool {
+        let len = self.store.dead.len();
+        self.store.dead.retain(|o| o.id != id);
+        if len != self.store.dead.len() {
+            self.dirty = true;
+            return true;
+        }
+        false
+    }
+}
@@ -4,69 +4,116 @@ use log::{error, info};
 use minijinja::context;
 use reqwest;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use tokio::time::Duration;
 
+use crate::alert::G_ALERT_MGR;
+use crate::incident::G_INCIDENT_MGR;
 use crate::jinja::{add_template, render_template};
-use crate::notifier::{get_tag, Event, HostStat, NOTIFIER_HANDLE};
+use crate::notifier::{
+    get_tag, http_client, record_resp, tpl_kind, Event, HostStat, QuietHours, Severity, NOTIFIER_HANDLE, TEST_MSG,
+};
 
 const KIND: &str = "tgbot";
+const ACK_PREFIX: &str = "ack:";
 
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     pub enabled: bool,
+    // 代理, 空为使用全局 notify_proxy, direct 为直连
+    #[serde(default = "Default::default")]
+    pub proxy: String,
     pub bot_token: String,
     pub chat_id: String,
     pub title: String,
     pub online_tpl: String,
     pub offline_tpl: String,
     pub custom_tpl: String,
+    #[serde(default = "crate::notifier::default_flapping_tpl")]
+    pub flapping_tpl: String,
+    #[serde(default = "Default::default")]
+    pub quiet_hours: String,
+    #[serde(default = "Default::default")]
+    pub min_severity: Severity,
+    #[serde(default = "Default::default")]
+    pub ack_button: bool,
+    // 掉线告警附带最近一小时 cpu/内存/硬盘 趋势图, 自定义告警只画占用最高的一项, 仅 tgbot 支持
+    #[serde(default = "Default::default")]
+    pub chart: bool,
 }
 
 pub struct TGBot {
     config: &'static Config,
-    tg_url: String,
+    tenant: String,
+    tpl: String,
+    quiet: QuietHours,
+    api_url: String,
     http_client: reqwest::Client,
 }
 
 impl TGBot {
-    pub fn new(cfg: &'static Config) -> Self {
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
         let o = Self {
             config: cfg,
-            tg_url: format!("https://api.telegram.org/bot{}/sendMessage", &cfg.bot_token),
-            http_client: reqwest::Client::new(),
+            tenant: tenant.to_string(),
+            tpl: tpl_kind(KIND, tenant),
+            quiet: QuietHours::new(&cfg.quiet_hours),
+            api_url: format!("https://api.telegram.org/bot{}", &cfg.bot_token),
+            http_client: http_client(&cfg.proxy),
         };
 
-        add_template(KIND, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
-        add_template(KIND, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
-        add_template(KIND, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Flapping), o.config.flapping_tpl.to_string());
+
+        if o.config.ack_button {
+            let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
+            handle.spawn(poll_ack_callback(
+                o.http_client.clone(),
+                o.api_url.to_string(),
+                o.config.chat_id.to_string(),
+            ));
+        }
 
         o
     }
-}
 
-impl crate::notifier::Notifier for TGBot {
-    fn kind(&self) -> &'static str {
-        KIND
+    fn msg_data(&self, html_content: String, reply_markup: Option<serde_json::Value>) -> serde_json::Value {
+        let mut data = serde_json::json!({
+            "chat_id": self.config.chat_id,
+            "parse_mode": "HTML",
+            "text": html_content,
+        });
+        if let Some(markup) = reply_markup {
+            data["reply_markup"] = markup;
+        }
+        data
     }
 
-    fn send_notify(&self, html_content: String) -> Result<()> {
-        let mut data = HashMap::new();
-        data.insert("chat_id", self.config.chat_id.to_string());
-        data.insert("parse_mode", "HTML".to_string());
-        data.insert("text", html_content);
+    // 重试只保存消息内容, 不含 chat_id
+    fn retry_payload(html_content: &str, reply_markup: &Option<serde_json::Value>) -> String {
+        serde_json::json!({"text": html_content, "reply_markup": reply_markup}).to_string()
+    }
 
-        let tg_url = self.tg_url.to_string();
+    fn send_msg(&self, html_content: String, reply_markup: Option<serde_json::Value>) -> Result<()> {
+        let payload = Self::retry_payload(&html_content, &reply_markup);
+        self.post_msg(self.msg_data(html_content, reply_markup), payload)
+    }
+
+    fn post_msg(&self, data: serde_json::Value, payload: String) -> Result<()> {
+        let tg_url = format!("{}/sendMessage", self.api_url);
+        let tenant = self.tenant.to_string();
         let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
         let http_client = self.http_client.clone();
         handle.spawn(async move {
-            match http_client
+            let resp = http_client
                 .post(&tg_url)
                 .timeout(Duration::from_secs(5))
                 .json(&data)
                 .send()
-                .await
-            {
+                .await;
+            record_resp(KIND, &tenant, &payload, &resp);
+            match resp {
                 Ok(resp) => {
                     info!("tg send msg resp => {:?}", resp);
                 }
@@ -79,22 +126,193 @@ impl crate::notifier::Notifier for TGBot {
         Ok(())
     }
 
+    // caption 最长 1024, 超出或无历史数据时退回文本消息
+    fn send_alert(&self, html_content: String, e: &Event, stat: &HostStat) -> Result<()> {
+        let reply_markup = self.ack_markup(e, stat);
+        if !self.config.chart || html_content.chars().count() > 1024 {
+            return self.send_msg(html_content, reply_markup);
+        }
+        let png_data = match crate::chart::render_png(&stat.name, !matches!(e, Event::Custom)) {
+            Ok(Some(png_data)) => png_data,
+            Ok(None) => return self.send_msg(html_content, reply_markup),
+            Err(err) => {
+                error!("render chart error => {:?}", err);
+                return self.send_msg(html_content, reply_markup);
+            }
+        };
+
+        // 失败后以文本消息重试
+        let payload = Self::retry_payload(&html_content, &reply_markup);
+        let mut form = reqwest::multipart::Form::new()
+            .text("chat_id", self.config.chat_id.to_string())
+            .text("parse_mode", "HTML")
+            .text("caption", html_content)
+            .part(
+                "photo",
+                reqwest::multipart::Part::bytes(png_data)
+                    .file_name("chart.png")
+                    .mime_str("image/png")?,
+            );
+        if let Some(markup) = reply_markup {
+            form = form.text("reply_markup", markup.to_string());
+        }
+
+        let tg_url = format!("{}/sendPhoto", self.api_url);
+        let tenant = self.tenant.to_string();
+        let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
+        let http_client = self.http_client.clone();
+        handle.spawn(async move {
+            let resp = http_client
+                .post(&tg_url)
+                .timeout(Duration::from_secs(10))
+                .multipart(form)
+                .send()
+                .await;
+            record_resp(KIND, &tenant, &payload, &resp);
+            match resp {
+                Ok(resp) => {
+                    info!("tg send photo resp => {:?}", resp);
+                }
+                Err(err) => {
+                    error!("tg send photo error => {:?}", err);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn ack_markup(&self, e: &Event, stat: &HostStat) -> Option<serde_json::Value> {
+        if !self.config.ack_button {
+            return None;
+        }
+        let id = G_INCIDENT_MGR.lock().unwrap().open_id(&stat.name, get_tag(e))?;
+        Some(serde_json::json!({
+            "inline_keyboard": [[{
+                "text": "✅ 确认",
+                "callback_data": format!("{}{}", ACK_PREFIX, id),
+            }]]
+        }))
+    }
+}
+
+// 轮询 inline button 回调, 与 setWebhook 互斥
+async fn poll_ack_callback(http_client: reqwest::Client, api_url: String, chat_id: String) {
+    let mut offset = 0_i64;
+    loop {
+        let resp = http_client
+            .get(format!("{}/getUpdates", api_url))
+            .query(&[
+                ("offset", offset.to_string()),
+                ("timeout", "30".to_string()),
+                ("allowed_updates", r#"["callback_query"]"#.to_string()),
+            ])
+            .timeout(Duration::from_secs(40))
+            .send()
+            .await;
+        let updates = match resp {
+            Ok(resp) => resp.json::<serde_json::Value>().await.unwrap_or_default(),
+            Err(err) => {
+                error!("tg get updates error => {:?}", err);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        for update in updates["result"].as_array().cloned().unwrap_or_default() {
+            offset = update["update_id"].as_i64().unwrap_or(offset) + 1;
+
+            let cb = &update["callback_query"];
+            let msg_chat_id = cb["message"]["chat"]["id"].to_string();
+            let data = cb["data"].as_str().unwrap_or_default();
+            if !msg_chat_id.eq(&chat_id) || !data.starts_with(ACK_PREFIX) {
+                continue;
+            }
+
+            let mut text = "invalid ack".to_string();
+            let incident = data[ACK_PREFIX.len()..]
+                .parse::<u64>()
+                .ok()
+                .and_then(|id| G_INCIDENT_MGR.lock().unwrap().get(id).cloned());
+            if let Some(o) = incident.as_ref().filter(|o| o.end.is_some()) {
+                text = format!("{} {} already resolved", o.host, o.kind);
+            } else if let Some(o) = incident {
+                let (host, event) = (o.host.as_str(), o.kind.as_str());
+                let by = cb["from"]["username"]
+                    .as_str()
+                    .or_else(|| cb["from"]["first_name"].as_str())
+                    .unwrap_or("telegram");
+                G_ALERT_MGR.lock().unwrap().ack(host, event, by);
+                text = format!("{} {} acked by {}", host, event, by);
+            }
+
+            let _ = http_client
+                .post(format!("{}/answerCallbackQuery", api_url))
+                .timeout(Duration::from_secs(5))
+                .json(&serde_json::json!({"callback_query_id": cb["id"], "text": text}))
+                .send()
+                .await;
+        }
+    }
+}
+
+impl crate::notifier::Notifier for TGBot {
+    fn kind(&self) -> &'static str {
+        KIND
+    }
+
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn quiet_hours(&self) -> Option<&QuietHours> {
+        Some(&self.quiet)
+    }
+
+    fn send_notify(&self, html_content: String) -> Result<()> {
+        self.send_msg(html_content, None)
+    }
+
+    fn notify_test(&self) -> Result<()> {
+        self.post_msg(self.msg_data(TEST_MSG.to_string(), None), String::new())
+    }
+
+    fn resend(&self, payload: String) -> Result<()> {
+        let o: serde_json::Value = serde_json::from_str(&payload)?;
+        let reply_markup = Some(o["reply_markup"].clone()).filter(|v| !v.is_null());
+        self.send_msg(o["text"].as_str().unwrap_or_default().to_string(), reply_markup)
+    }
+
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
         render_template(
-            self.kind(),
+            &self.tpl,
             get_tag(e),
             context!(host => stat, config => self.config, ip_info => stat.ip_info, sys_info => stat.sys_info),
             true,
         )
-        .map(|content| match *e {
-            Event::NodeUp | Event::NodeDown => self.send_notify(content).unwrap(),
-            Event::Custom => {
-                info!("render.custom.tpl => {}", content);
-                if !content.is_empty() {
-                    self.send_notify(format!("{}\n{}", self.config.title, content))
-                        .unwrap_or_else(|err| {
-                            error!("send_msg err => {:?}", err);
-                        });
+        .map(|content| {
+            if self.quiet.hold(e, &content) {
+                info!("{} quiet hours, hold {:?}", self.kind(), e);
+                return;
+            }
+            match *e {
+                Event::NodeUp | Event::Resolved => self.send_notify(content).unwrap(),
+                Event::Flapping => self.send_msg(content, self.ack_markup(e, stat)).unwrap(),
+                Event::NodeDown => self.send_alert(content, e, stat).unwrap_or_else(|err| {
+                    error!("send_alert err => {:?}", err);
+                }),
+                Event::Custom => {
+                    info!("render.custom.tpl => {}", content);
+                    if !content.is_empty() {
+                        self.send_alert(format!("{}\n{}", self.config.title, content), e, stat)
+                            .unwrap_or_else(|err| {
+                                error!("send_alert err => {:?}", err);
+                            });
+                    }
                 }
             }
         })
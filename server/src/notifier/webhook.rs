@@ -10,7 +10,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::time::Duration;
 
-use crate::notifier::{get_tag, Event, HostStat, NOTIFIER_HANDLE};
+use crate::notifier::{
+    get_severity, get_tag, http_client, record_resp, Event, HostStat, Severity, NOTIFIER_HANDLE, TEST_MSG,
+};
 
 const KIND: &str = "webhook";
 
@@ -23,16 +25,22 @@ pub struct Receiver {
     pub password: Option<String>,
     pub timeout: u32,
     pub script: String,
+    #[serde(default = "Default::default")]
+    pub min_severity: Severity,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub enabled: bool,
+    // 代理, 空为使用全局 notify_proxy, direct 为直连
+    #[serde(default = "Default::default")]
+    pub proxy: String,
     pub receiver: Vec<Receiver>,
 }
 
 pub struct Webhook {
     config: &'static Config,
+    tenant: String,
     http_client: reqwest::Client,
     engine: Engine,
     ast_list: Vec<Option<AST>>,
@@ -55,10 +63,11 @@ fn to_json(o: Dynamic) -> ImmutableString {
 }
 
 impl Webhook {
-    pub fn new(cfg: &'static Config) -> Self {
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
         let mut o = Self {
             config: cfg,
-            http_client: reqwest::Client::new(),
+            tenant: tenant.to_string(),
+            http_client: http_client(&cfg.proxy),
             engine: Engine::new(),
             ast_list: Vec::new(),
         };
@@ -79,11 +88,19 @@ impl Webhook {
 
         o
     }
-    fn call_webhook(&self, r: &'static Receiver, content: String) -> Result<()> {
+    // 重试按 receiver 序号保存, 不含 url 及认证信息; retry 为 false 时不重试(测试消息)
+    fn call_webhook(&self, idx: usize, content: String, retry: bool) -> Result<()> {
         if content.is_empty() {
             return Ok(());
         }
 
+        let r: &'static Receiver = &self.config.receiver[idx];
+        let payload = if retry {
+            serde_json::json!({"receiver": idx, "content": content}).to_string()
+        } else {
+            String::new()
+        };
+        let tenant = self.tenant.to_string();
         let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
         let http_client = self.http_client.clone();
         handle.spawn(async move {
@@ -105,7 +122,9 @@ impl Webhook {
             }
 
             //
-            match http_client_builder.send().await {
+            let resp = http_client_builder.send().await;
+            record_resp(KIND, &tenant, &payload, &resp);
+            match resp {
                 Ok(resp) => {
                     info!("webhook send msg resp => {:?}", resp);
                 }
@@ -122,29 +141,43 @@ impl crate::notifier::Notifier for Webhook {
         KIND
     }
 
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
     fn send_notify(&self, content: String) -> Result<()> {
         info!("{}", content);
         Ok(())
     }
 
+    fn resend(&self, payload: String) -> Result<()> {
+        let o: serde_json::Value = serde_json::from_str(&payload)?;
+        let idx = o["receiver"].as_u64().unwrap_or(u64::MAX) as usize;
+        match self.config.receiver.get(idx).filter(|r| r.enabled) {
+            Some(_) => self.call_webhook(idx, o["content"].as_str().unwrap_or_default().to_string(), true),
+            None => Err(anyhow::anyhow!("webhook receiver `{}` not found", idx)),
+        }
+    }
+
     fn notify_test(&self) -> Result<()> {
-        for r in self.config.receiver.iter() {
+        for (idx, r) in self.config.receiver.iter().enumerate() {
             if !r.enabled {
                 continue;
             }
-            self.call_webhook(r, "❗ServerStatus test msg".into())?;
+            self.call_webhook(idx, TEST_MSG.into(), false)?;
         }
         Ok(())
     }
 
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
         for (idx, r) in self.config.receiver.iter().enumerate() {
-            if !r.enabled {
+            if !r.enabled || get_severity(e) < r.min_severity {
                 continue;
             }
 
             let mut scope = Scope::new();
             scope.push("event", get_tag(e));
+            scope.push("severity", to_dynamic(get_severity(e))?);
             scope.push("host", to_dynamic(stat)?);
             scope.push("config", to_dynamic(r)?);
             scope.push("ip_info", to_dynamic(stat.ip_info.as_ref())?);
@@ -157,7 +190,7 @@ impl crate::notifier::Notifier for Webhook {
             // [notify, json_body/content]
             if let Ok(v) = from_dynamic::<Array>(&res) {
                 if v.len() >= 2 && from_dynamic::<bool>(&v[0]).unwrap_or_default() {
-                    self.call_webhook(r, serde_json::to_string(&v[1]).unwrap_or_default())?
+                    self.call_webhook(idx, serde_json::to_string(&v[1]).unwrap_or_default(), true)?
                 }
             }
         }
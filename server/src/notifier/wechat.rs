@@ -9,7 +9,10 @@ use std::collections::HashMap;
 use tokio::time::Duration;
 
 use crate::jinja::{add_template, render_template};
-use crate::notifier::{get_tag, Event, HostStat, NOTIFIER_HANDLE};
+use crate::notifier::{
+    get_tag, http_client, record_delivery, record_resp, tpl_kind, Event, HostStat, QuietHours, Severity,
+    NOTIFIER_HANDLE, TEST_MSG,
+};
 
 // https://qydev.weixin.qq.com/wiki/index.php?title=%E4%B8%BB%E5%8A%A8%E8%B0%83%E7%94%A8
 // https://qydev.weixin.qq.com/wiki/index.php?title=%E5%8F%91%E9%80%81%E6%8E%A5%E5%8F%A3%E8%AF%B4%E6%98%8E
@@ -19,6 +22,9 @@ const KIND: &str = "wechat";
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     pub enabled: bool,
+    // 代理, 空为使用全局 notify_proxy, direct 为直连
+    #[serde(default = "Default::default")]
+    pub proxy: String,
     pub corp_id: String,
     pub corp_secret: String,
     pub agent_id: String,
@@ -26,33 +32,41 @@ pub struct Config {
     pub online_tpl: String,
     pub offline_tpl: String,
     pub custom_tpl: String,
+    #[serde(default = "crate::notifier::default_flapping_tpl")]
+    pub flapping_tpl: String,
+    #[serde(default = "Default::default")]
+    pub quiet_hours: String,
+    #[serde(default = "Default::default")]
+    pub min_severity: Severity,
 }
 
 pub struct WeChat {
     config: &'static Config,
+    tenant: String,
+    tpl: String,
+    quiet: QuietHours,
     http_client: reqwest::Client,
 }
 
 impl WeChat {
-    pub fn new(cfg: &'static Config) -> Self {
+    pub fn new(cfg: &'static Config, tenant: &str) -> Self {
         let o = Self {
             config: cfg,
-            http_client: reqwest::Client::new(),
+            tenant: tenant.to_string(),
+            tpl: tpl_kind(KIND, tenant),
+            quiet: QuietHours::new(&cfg.quiet_hours),
+            http_client: http_client(&cfg.proxy),
         };
-        add_template(KIND, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
-        add_template(KIND, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
-        add_template(KIND, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::NodeUp), o.config.online_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::NodeDown), o.config.offline_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Custom), o.config.custom_tpl.to_string());
+        add_template(&o.tpl, get_tag(&Event::Flapping), o.config.flapping_tpl.to_string());
 
         o
     }
-}
 
-impl crate::notifier::Notifier for WeChat {
-    fn kind(&self) -> &'static str {
-        KIND
-    }
-
-    fn send_notify(&self, text_content: String) -> Result<()> {
+    // payload 见 record_delivery, 为空时不重试
+    fn send(&self, text_content: String, payload: String) -> Result<()> {
         // get access_token
         let mut data = HashMap::new();
         data.insert("corpid", self.config.corp_id.to_string());
@@ -61,6 +75,7 @@ impl crate::notifier::Notifier for WeChat {
         let http_client = self.http_client.clone();
         let handle = NOTIFIER_HANDLE.lock().unwrap().as_ref().unwrap().clone();
         let agent_id = self.config.agent_id.to_string();
+        let tenant = self.tenant.to_string();
         handle.spawn(async move {
             match http_client
                 .post(TOKEN_URL)
@@ -89,13 +104,14 @@ impl crate::notifier::Notifier for WeChat {
                                     "safe": 0
                                 });
 
-                                match http_client
+                                let resp = http_client
                                     .post(&req_url)
                                     .timeout(Duration::from_secs(5))
                                     .json(&req_data)
                                     .send()
-                                    .await
-                                {
+                                    .await;
+                                record_resp(KIND, &tenant, &payload, &resp);
+                                match resp {
                                     Ok(resp) => {
                                         info!("wechat send msg resp => {:?}", resp);
                                     }
@@ -104,10 +120,13 @@ impl crate::notifier::Notifier for WeChat {
                                     }
                                 }
                             }
+                        } else {
+                            record_delivery(KIND, &tenant, &payload, false, format!("{:?}", json_data));
                         }
                     }
                 }
                 Err(err) => {
+                    record_delivery(KIND, &tenant, &payload, false, err.to_string());
                     error!("wechat get access_token error => {:?}", err);
                 }
             }
@@ -115,23 +134,57 @@ impl crate::notifier::Notifier for WeChat {
 
         Ok(())
     }
+}
+
+impl crate::notifier::Notifier for WeChat {
+    fn kind(&self) -> &'static str {
+        KIND
+    }
+
+    fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn quiet_hours(&self) -> Option<&QuietHours> {
+        Some(&self.quiet)
+    }
+
+    fn send_notify(&self, text_content: String) -> Result<()> {
+        self.send(text_content.to_string(), text_content)
+    }
+
+    fn notify_test(&self) -> Result<()> {
+        self.send(TEST_MSG.to_string(), String::new())
+    }
 
     fn notify(&self, e: &Event, stat: &HostStat) -> Result<()> {
         render_template(
-            self.kind(),
+            &self.tpl,
             get_tag(e),
             context!(host => stat, config => self.config, ip_info => stat.ip_info, sys_info => stat.sys_info),
             true,
         )
-        .map(|content| match *e {
-            Event::NodeUp | Event::NodeDown => self.send_notify(content).unwrap(),
-            Event::Custom => {
-                info!("render.custom.tpl => {}", content);
-                if !content.is_empty() {
-                    self.send_notify(format!("{}\n{}", self.config.title, content))
-                        .unwrap_or_else(|err| {
-                            error!("send_msg err => {:?}", err);
-                        });
+        .map(|content| {
+            if self.quiet.hold(e, &content) {
+                info!("{} quiet hours, hold {:?}", self.kind(), e);
+                return;
+            }
+            match *e {
+                Event::NodeUp | Event::NodeDown | Event::Flapping | Event::Resolved => {
+                    self.send_notify(content).unwrap()
+                }
+                Event::Custom => {
+                    info!("render.custom.tpl => {}", content);
+                    if !content.is_empty() {
+                        self.send_notify(format!("{}\n{}", self.config.title, content))
+                            .unwrap_or_else(|err| {
+                                error!("send_msg err => {:?}", err);
+                            });
+                    }
                 }
             }
         })
@@ -1,6 +1,10 @@
 #![deny(warnings)]
 use serde::{Deserialize, Serialize};
-use stat_common::server_status::{IpInfo, SysInfo};
+use stat_common::server_status::{
+    CertInfo, DiskUsage, DnsProbe, DockerInfo, Gpu, HttpCheck, IpInfo, IpmiSensor, MemoryDetail, NvmeDrive, PortState,
+    Pressure, ProbeResult, Process, RaidArray, SysInfo, Temperature, TraceReport, UnitState, ZfsPool,
+};
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn default_as_true() -> bool {
@@ -20,6 +24,9 @@ pub struct HostStat {
     pub notify: bool,
     #[serde(default = "bool::default")]
     pub vnstat: bool,
+    // 客户端 --traffic-reset-day, 0 为使用 hosts 配置的 monthstart
+    #[serde(default = "Default::default")]
+    pub traffic_reset_day: u32,
 
     #[serde(default = "default_as_true")]
     pub online4: bool,
@@ -34,6 +41,9 @@ pub struct HostStat {
     pub load_1: f64,
     pub load_5: f64,
     pub load_15: f64,
+    // load_1 / 逻辑核数, 旧版本客户端为 0
+    #[serde(default = "Default::default")]
+    pub load_per_core: f64,
 
     pub ping_10010: f64,
     pub ping_189: f64,
@@ -50,6 +60,61 @@ pub struct HostStat {
     pub process_count: u32,
     #[serde(rename(deserialize = "thread"))]
     pub thread_count: u32,
+    #[serde(default = "Default::default")]
+    pub tcp_established: u32,
+    #[serde(default = "Default::default")]
+    pub tcp_time_wait: u32,
+    #[serde(default = "Default::default")]
+    pub udp_sockets: u32,
+    // 连接跟踪表, 满后新连接会被丢弃
+    #[serde(default = "Default::default")]
+    pub conntrack_count: u64,
+    #[serde(default = "Default::default")]
+    pub conntrack_max: u64,
+    // 系统 fd 已分配/上限
+    #[serde(default = "Default::default")]
+    pub fd_used: u64,
+    #[serde(default = "Default::default")]
+    pub fd_max: u64,
+    // 客户端自身 fd 已打开/soft limit
+    #[serde(default = "Default::default")]
+    pub client_fds: u64,
+    #[serde(default = "Default::default")]
+    pub client_fd_limit: u64,
+    // 按协议族的 ip 层流量, 累计字节及字节/秒
+    #[serde(default = "Default::default")]
+    pub net_in_v4: u64,
+    #[serde(default = "Default::default")]
+    pub net_out_v4: u64,
+    #[serde(default = "Default::default")]
+    pub net_in_v6: u64,
+    #[serde(default = "Default::default")]
+    pub net_out_v6: u64,
+    #[serde(default = "Default::default")]
+    pub net_rx_v4: u64,
+    #[serde(default = "Default::default")]
+    pub net_tx_v4: u64,
+    #[serde(default = "Default::default")]
+    pub net_rx_v6: u64,
+    #[serde(default = "Default::default")]
+    pub net_tx_v6: u64,
+    // cpu 封装功耗 W, 客户端未开启时为 None
+    #[serde(default = "Default::default")]
+    pub power: Option<f64>,
+    // PSI 资源压力 %, 旧内核为 None
+    #[serde(default = "Default::default")]
+    pub psi_cpu: Option<Pressure>,
+    #[serde(default = "Default::default")]
+    pub psi_memory: Option<Pressure>,
+    #[serde(default = "Default::default")]
+    pub psi_io: Option<Pressure>,
+    // 运行中的内核版本, 是否需要重启
+    #[serde(default = "Default::default")]
+    pub kernel: String,
+    #[serde(default = "Default::default")]
+    pub reboot_required: bool,
+    #[serde(default = "Default::default")]
+    pub reboot_reason: String,
 
     pub network_rx: u64,
     pub network_tx: u64,
@@ -62,12 +127,82 @@ pub struct HostStat {
     pub last_network_out: u64,
 
     pub cpu: f32,
+    // cpu steal %, 用于判断 VPS 超售, 旧版本客户端及 sysinfo 采集器为 0
+    #[serde(default = "Default::default")]
+    pub cpu_steal: f64,
+    // 各核心使用率, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub cpu_cores: Vec<f64>,
     pub memory_total: u64,
     pub memory_used: u64,
     pub swap_total: u64,
     pub swap_used: u64,
+    // 内存明细, 旧版本客户端为 None
+    #[serde(default = "Default::default")]
+    pub memory_detail: Option<MemoryDetail>,
     pub hdd_total: u64,
     pub hdd_used: u64,
+    // 各挂载点用量, 旧版本客户端为空
+    #[serde(default = "Default::default")]
+    pub disks: Vec<DiskUsage>,
+    // 硬盘读写速度 bytes/s
+    #[serde(default = "Default::default")]
+    pub disk_read: u64,
+    #[serde(default = "Default::default")]
+    pub disk_write: u64,
+    // 温度, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub temps: Vec<Temperature>,
+    // GPU, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub gpus: Vec<Gpu>,
+    // cpu/内存占用前 N 的进程, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub top_cpu: Vec<Process>,
+    #[serde(default = "Default::default")]
+    pub top_memory: Vec<Process>,
+    // systemd 服务状态, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub units: Vec<UnitState>,
+    // zfs 存储池, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub zfs_pools: Vec<ZfsPool>,
+    // mdadm 软 raid
+    #[serde(default = "Default::default")]
+    pub raid: Vec<RaidArray>,
+    // tls 证书到期检查, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub certs: Vec<CertInfo>,
+    // 端口存活检查, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub ports: Vec<PortState>,
+    // http(s) 健康检查, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub http_checks: Vec<HttpCheck>,
+    // nvme 盘温度及寿命, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub nvme: Vec<NvmeDrive>,
+    // ipmi 传感器, 客户端未开启时为空
+    #[serde(default = "Default::default")]
+    pub ipmi: Vec<IpmiSensor>,
+    // 自定义探测目标
+    #[serde(default = "Default::default")]
+    pub probes: Vec<ProbeResult>,
+    // 三网探测的抖动及分位数
+    #[serde(default = "Default::default")]
+    pub carrier_probes: Vec<ProbeResult>,
+    // dns 解析探测
+    #[serde(default = "Default::default")]
+    pub dns_probes: Vec<DnsProbe>,
+    // 最近的路由跟踪, 客户端只上报一次, 服务端保留最近几条
+    #[serde(default = "Default::default")]
+    pub traces: Vec<TraceReport>,
+    // 客户端离线期间缓存的补发数据, 只写入历史
+    #[serde(default = "Default::default")]
+    pub replay: bool,
+    // docker 容器, 客户端未开启时为 None
+    #[serde(default = "Default::default")]
+    pub docker: Option<DockerInfo>,
 
     #[serde(skip_deserializing)]
     pub custom: String,
@@ -82,15 +217,63 @@ pub struct HostStat {
     pub gid: String,
     #[serde(default = "Default::default")]
     pub weight: u64,
+    #[serde(default = "Default::default")]
+    pub proto_version: u32,
+    // 客户端版本
+    #[serde(default = "Default::default")]
+    pub version: String,
+    // 自定义指标脚本的输出, 模板中 {{host.metrics["name"]}}
+    #[serde(default = "Default::default")]
+    pub metrics: HashMap<String, f64>,
+    // 扩展字段, 模板中 {{host.extra.xxx}}
+    #[serde(default = "Default::default")]
+    pub extra: HashMap<String, String>,
+    // 有过重启的采集线程, eg: cpu:2,ping_ct:1
+    #[serde(default = "Default::default")]
+    pub collector_restarts: String,
 
     // user data
     #[serde(skip_deserializing)]
     pub latest_ts: u64,
+    #[serde(skip_deserializing)]
+    pub flapping: bool,
+    #[serde(skip_deserializing)]
+    pub flap_count: u32,
+    #[serde(skip_deserializing)]
+    pub acked_by: String,
+    #[serde(skip_deserializing)]
+    pub maintenance: bool,
+    // 客户端时间 - 服务端接收时间(s)
+    #[serde(skip_deserializing)]
+    pub clock_skew: i64,
+    // 客户端 --ntp 测得的本机时钟偏差 ms, 比 clock_skew 精确, 不受网络延迟影响
+    #[serde(default = "Default::default")]
+    pub ntp_offset: Option<i64>,
+    // 偏差超过 clock_skew_threshold
+    #[serde(skip_deserializing)]
+    pub clock_skewed: bool,
+    // 交换分区使用率超过 swap_threshold
+    #[serde(skip_deserializing)]
+    pub swap_heavy: bool,
+    // 最近 sparkline_minutes 分钟趋势
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub series: Option<crate::history::Sparkline>,
+    #[serde(skip_deserializing)]
+    pub expire: String,
+    #[serde(skip_deserializing)]
+    pub traffic_quota: u64,
+    // 客户端版本低于 min_client_version
+    #[serde(skip_deserializing)]
+    pub outdated: bool,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub pos: usize,
     #[serde(skip_serializing, skip_deserializing)]
     pub disabled: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub offline_threshold: u64,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub tenant: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
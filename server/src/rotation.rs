@@ -0,0 +1,169 @@
+#![deny(warnings)]
+// 主机/组密码轮换, 宽限期内新旧密码均可用, 上报响应中下发新密码
+// rotations.json 不保存新密码明文: 新密码由配置中的旧密码与随机 seed 派生, 仅保存 seed 及新密码的 argon2 hash
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use stat_common::auth;
+
+use crate::config::{hash_pass, plain_pass, verify_pass};
+
+const ROTATIONS_FILE: &str = "rotations.json";
+
+pub static G_ROTATION_MGR: Lazy<Mutex<RotationMgr>> = Lazy::new(Default::default);
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn rotation_key(id: &str, group: bool) -> String {
+    format!("{}:{}", if group { "group" } else { "host" }, id)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rotation {
+    // host name 或 gid
+    pub id: String,
+    pub group: bool,
+    // 仅在内存中, 重启后由 seed 重新派生
+    #[serde(skip)]
+    pub new_pass: String,
+    pub seed: String,
+    pub new_hash: String,
+    pub start: u64,
+    // 之后仅接受新密码
+    pub grace_until: u64,
+}
+
+fn derive_pass(stored: &str, seed: &str, id: &str) -> String {
+    auth::sign(stored, seed, id.as_bytes())[..32].to_string()
+}
+
+fn stored_pass<'a>(cfg: &'a crate::config::Config, id: &str, group: bool) -> Option<&'a str> {
+    if group {
+        cfg.hosts_group_map.get(id).map(|g| g.password.as_str())
+    } else {
+        cfg.hosts_map.get(id).map(|h| h.password.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RotationMgr {
+    rotations: HashMap<String, Rotation>,
+}
+
+impl RotationMgr {
+    // 配置中的密码已变更(更新为新密码)的轮换视为完成
+    pub fn init(&mut self, cfg: &crate::config::Config) {
+        let contents = fs::read_to_string(ROTATIONS_FILE).unwrap_or_default();
+        if contents.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<HashMap<String, Rotation>>(&contents) {
+            Ok(mut rotations) => {
+                rotations.retain(|key, o| {
+                    let new_pass = stored_pass(cfg, &o.id, o.group)
+                        .map(|stored| derive_pass(stored, &o.seed, &o.id))
+                        .filter(|new_pass| verify_pass(&o.new_hash, new_pass));
+                    match new_pass {
+                        Some(new_pass) => {
+                            o.new_pass = new_pass;
+                            true
+                        }
+                        None => {
+                            info!("rotation `{}` done", key);
+                            false
+                        }
+                    }
+                });
+                self.rotations = rotations;
+                self.save();
+                trace!("load {} succ!", ROTATIONS_FILE);
+            }
+            Err(err) => {
+                warn!("ignore invalid {} => {:?}", ROTATIONS_FILE, err);
+            }
+        }
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.rotations) {
+            Ok(s) => {
+                if let Err(err) = fs::write(ROTATIONS_FILE, s) {
+                    error!("save {} fail => {:?}", ROTATIONS_FILE, err);
+                }
+            }
+            Err(err) => error!("save {} fail => {:?}", ROTATIONS_FILE, err),
+        }
+    }
+
+    // 同一主机/组已有进行中的轮换时返回 None, 需先取消
+    pub fn rotate(&mut self, id: &str, group: bool, stored: &str, grace: u64) -> Result<Option<Rotation>> {
+        let key = rotation_key(id, group);
+        if self.rotations.contains_key(&key) {
+            return Ok(None);
+        }
+        let now = now_ts();
+        let seed = Uuid::new_v4().to_string();
+        let new_pass = derive_pass(stored, &seed, id);
+        let o = Rotation {
+            id: id.to_string(),
+            group,
+            new_hash: hash_pass(&new_pass)?,
+            new_pass,
+            seed,
+            start: now,
+            grace_until: now + grace,
+        };
+        self.rotations.insert(key, o.clone());
+        self.save();
+        Ok(Some(o))
+    }
+
+    pub fn cancel(&mut self, id: &str, group: bool) -> bool {
+        let ok = self.rotations.remove(&rotation_key(id, group)).is_some();
+        if ok {
+            self.save();
+        }
+        ok
+    }
+
+    pub fn list(&self) -> Vec<Rotation> {
+        self.rotations.values().cloned().collect()
+    }
+
+    // stored 为配置中的密码
+    pub fn verify(&self, id: &str, group: bool, stored: &str, pass: &str) -> bool {
+        match self.rotations.get(&rotation_key(id, group)) {
+            Some(o) => auth::ct_eq(pass, &o.new_pass) || (now_ts() < o.grace_until && verify_pass(stored, pass)),
+            None => verify_pass(stored, pass),
+        }
+    }
+
+    // hmac 签名可用的密码明文, 旧密码为 argon2 hash 时不可用于签名
+    pub fn sign_keys(&self, id: &str, group: bool, stored: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        let rotation = self.rotations.get(&rotation_key(id, group));
+        if let Some(o) = rotation {
+            keys.push(o.new_pass.to_string());
+        }
+        if rotation.map(|o| now_ts() < o.grace_until).unwrap_or(true) {
+            keys.extend(plain_pass(stored));
+        }
+        keys
+    }
+
+    // 仍在使用旧密码时返回新密码
+    pub fn hint(&self, id: &str, group: bool, pass: &str) -> Option<String> {
+        self.rotations
+            .get(&rotation_key(id, group))
+            .filter(|o| !o.new_pass.eq(pass))
+            .map(|o| o.new_pass.to_string())
+    }
+}
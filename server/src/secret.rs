@@ -0,0 +1,110 @@
+#![deny(warnings)]
+// 配置中的密钥引用, 启动时解析, 整个字符串需为引用:
+// ${env:NAME} / ${file:/path/to/secret} / ${exec:command args} / ${vault:secret/data/ssr#key}
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fs;
+use std::process::Command;
+use tokio::runtime::Handle;
+use tokio::time::Duration;
+
+const VAULT_ADDR: &str = "http://127.0.0.1:8200";
+
+pub fn resolve(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::String(s) => {
+            if let Some(secret) = resolve_ref(s)? {
+                *s = secret;
+            }
+        }
+        toml::Value::Array(arr) => {
+            for o in arr.iter_mut() {
+                resolve(o)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, o) in table.iter_mut() {
+                resolve(o)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn resolve_ref(s: &str) -> Result<Option<String>> {
+    let (kind, arg) = match s
+        .strip_prefix("${")
+        .and_then(|o| o.strip_suffix('}'))
+        .and_then(|o| o.split_once(':'))
+    {
+        Some(o) => o,
+        None => return Ok(None),
+    };
+    let secret = match kind {
+        "env" => env::var(arg).map_err(|_| anyhow!("env `{}` not found", arg))?,
+        "file" => fs::read_to_string(arg)
+            .map_err(|err| anyhow!("read `{}` => {}", arg, err))?
+            .trim_end_matches(&['\r', '\n'][..])
+            .to_string(),
+        "exec" => exec(arg)?,
+        "vault" => vault(arg)?,
+        _ => return Ok(None),
+    };
+    trace!("resolve secret ${{{}:{}}}", kind, arg);
+    Ok(Some(secret))
+}
+
+fn exec(cmd: &str) -> Result<String> {
+    let output = Command::new("sh").arg("-c").arg(cmd).output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "exec `{}` {} => {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .trim_end_matches(&['\r', '\n'][..])
+        .to_string())
+}
+
+// VAULT_ADDR / VAULT_TOKEN 环境变量, 兼容 kv v1/v2
+fn vault(arg: &str) -> Result<String> {
+    let (path, key) = arg
+        .split_once('#')
+        .ok_or_else(|| anyhow!("vault ref `{}` missing #key", arg))?;
+    let addr = env::var("VAULT_ADDR").unwrap_or_else(|_| VAULT_ADDR.to_string());
+    let token = match env::var("VAULT_TOKEN") {
+        Ok(token) => token,
+        Err(_) => fs::read_to_string(format!("{}/.vault-token", env::var("HOME").unwrap_or_default()))
+            .map_err(|_| anyhow!("VAULT_TOKEN not set"))?
+            .trim()
+            .to_string(),
+    };
+    let url = format!("{}/v1/{}", addr.trim_end_matches('/'), path.trim_start_matches('/'));
+
+    // 在 tokio 运行时内同步等待
+    let resp = tokio::task::block_in_place(|| {
+        Handle::current().block_on(async {
+            reqwest::Client::new()
+                .get(&url)
+                .header("X-Vault-Token", token)
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await
+        })
+    })
+    .map_err(|err| anyhow!("vault `{}` => {}", path, err))?;
+
+    let data = &resp["data"];
+    data["data"][key]
+        .as_str()
+        .or_else(|| data[key].as_str())
+        .map(|o| o.to_string())
+        .ok_or_else(|| anyhow!("vault `{}` key `{}` not found", path, key))
+}
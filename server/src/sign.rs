@@ -0,0 +1,137 @@
+#![deny(warnings)]
+// hmac 签名上报, 防重放: 时间戳与服务端偏差超过 WINDOW_SECS 拒绝, 窗口内同一签名只接受一次
+use hyper::header::HeaderMap;
+use once_cell::sync::Lazy;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use stat_common::auth;
+
+use crate::rotation::G_ROTATION_MGR;
+use crate::G_CONFIG;
+
+const WINDOW_SECS: u64 = 300;
+
+#[derive(Default)]
+struct Seen {
+    sigs: HashSet<String>,
+    // (接收时间, 签名)
+    queue: VecDeque<(u64, String)>,
+}
+
+static G_SEEN: Lazy<Mutex<Seen>> = Lazy::new(Default::default);
+
+// 时间戳与服务端偏差在 WINDOW_SECS 内
+fn in_window(ts: &str, now: u64) -> bool {
+    ts.parse::<u64>()
+        .map(|ts| now.abs_diff(ts) <= WINDOW_SECS)
+        .unwrap_or(false)
+}
+
+// 可用于校验签名的密码, 服务端为 argon2 hash 时没有明文, 无法校验
+fn sign_keys(user: &str, group_auth: bool) -> Vec<String> {
+    let cfg = match G_CONFIG.get() {
+        Some(o) => o,
+        None => return Vec::new(),
+    };
+    let stored = if group_auth {
+        cfg.hosts_group_map.get(user).map(|o| &o.password)
+    } else {
+        cfg.hosts_map.get(user).map(|o| &o.password)
+    };
+    match stored {
+        Some(stored) => G_ROTATION_MGR.lock().unwrap().sign_keys(user, group_auth, stored),
+        None => Vec::new(),
+    }
+}
+
+// 窗口内同一签名只接受一次, 记录保留 2 * WINDOW_SECS
+fn check_replay(seen: &mut Seen, sig: &str, now: u64) -> bool {
+    while let Some((recv_ts, _)) = seen.queue.front() {
+        if recv_ts + 2 * WINDOW_SECS > now {
+            break;
+        }
+        if let Some((_, o)) = seen.queue.pop_front() {
+            seen.sigs.remove(&o);
+        }
+    }
+    if !seen.sigs.insert(sig.to_string()) {
+        return false;
+    }
+    seen.queue.push_back((now, sig.to_string()));
+    true
+}
+
+// 读取 body 前的检查, 用户存在且可签名, 时间戳在窗口内
+pub fn precheck(user: &str, group_auth: bool, req_header: &HeaderMap) -> bool {
+    let ts = match req_header.get(auth::SSR_TIMESTAMP).and_then(|v| v.to_str().ok()) {
+        Some(o) => o,
+        None => return false,
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    if !in_window(ts, now) {
+        warn!("signed report from {} out of window, ts => {}", user, ts);
+        return false;
+    }
+    if sign_keys(user, group_auth).is_empty() {
+        warn!("signed report from {} rejected, no plaintext password to verify", user);
+        return false;
+    }
+    true
+}
+
+// 校验通过返回所用密码
+pub fn verify(user: &str, group_auth: bool, req_header: &HeaderMap, body: &[u8]) -> Option<String> {
+    let ts = req_header.get(auth::SSR_TIMESTAMP)?.to_str().ok()?;
+    let sig = req_header.get(auth::SSR_SIGNATURE)?.to_str().ok()?;
+    verify_sig(user, group_auth, ts, sig, body)
+}
+
+// mqtt 等无 header 的通道, 时间戳与签名由调用方解析
+pub fn verify_sig(user: &str, group_auth: bool, ts: &str, sig: &str, body: &[u8]) -> Option<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    if !in_window(ts, now) {
+        warn!("signed report from {} out of window, ts => {}", user, ts);
+        return None;
+    }
+    let key = sign_keys(user, group_auth)
+        .into_iter()
+        .find(|k| auth::verify_sign(k, ts, body, sig))?;
+    if !check_replay(&mut G_SEEN.lock().unwrap(), sig, now) {
+        warn!("replayed report from {}", user);
+        return None;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_window() {
+        let now = 1_700_000_000;
+        assert!(in_window(&now.to_string(), now));
+        assert!(in_window(&(now - WINDOW_SECS).to_string(), now));
+        assert!(in_window(&(now + WINDOW_SECS).to_string(), now));
+        assert!(!in_window(&(now - WINDOW_SECS - 1).to_string(), now));
+        assert!(!in_window(&(now + WINDOW_SECS + 1).to_string(), now));
+        assert!(!in_window("", now));
+        assert!(!in_window("-1", now));
+        assert!(!in_window("abc", now));
+    }
+
+    #[test]
+    fn test_check_replay() {
+        let mut seen = Seen::default();
+        let now = 1_700_000_000;
+        assert!(check_replay(&mut seen, "a", now));
+        assert!(!check_replay(&mut seen, "a", now + 1));
+        assert!(check_replay(&mut seen, "b", now + 1));
+        // 超出窗口的时间戳已被拒绝, 此后记录可以清除
+        assert!(!check_replay(&mut seen, "a", now + 2 * WINDOW_SECS - 1));
+        assert!(check_replay(&mut seen, "a", now + 2 * WINDOW_SECS));
+        assert_eq!(seen.sigs.len(), seen.queue.len());
+    }
+}
@@ -1,16 +1,18 @@
 #![allow(unused)]
 use anyhow::Result;
-use chrono::{Datelike, Local, Timelike};
+use chrono::{Datelike, Local, NaiveDate, Timelike};
 use lazy_static::lazy_static;
 use once_cell::sync::OnceCell;
 use std::borrow::Borrow;
 use std::borrow::BorrowMut;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::SyncSender;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -18,17 +20,168 @@ use std::thread;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::alert::G_ALERT_MGR;
+use crate::checks::G_CHECK_MGR;
 use crate::config::Host;
-use crate::notifier::{Event, Notifier};
+use crate::digest::{expire_days, month_traffic};
+use crate::diskfill::G_DISKFILL_MGR;
+use crate::flapping::{FlapAction, FlapDetector};
+use crate::incident::G_INCIDENT_MGR;
+use crate::maintenance::G_MAINTENANCE_MGR;
+use crate::notifier::paging::alert_key;
+use crate::notifier::retry::G_RETRY_MGR;
+use crate::notifier::{get_severity, get_tag, Event, Notifier};
 use crate::payload::{HostStat, StatsResp};
+use crate::rotation::G_ROTATION_MGR;
 
 const SAVE_INTERVAL: u64 = 60;
+// 每台主机保留的路由跟踪条数
+const MAX_TRACES: usize = 5;
 
 static STAT_SENDER: OnceCell<SyncSender<Cow<HostStat>>> = OnceCell::new();
 
+pub const SORT_MODES: [&str; 5] = ["weight", "group", "name", "expire", "traffic"];
+
+// None 排在最后
+fn cmp_some_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// 当月天数, 重置日 29/30/31 在小月按月末处理
+fn month_days(year: i32, month: u32) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|&day| NaiveDate::from_ymd_opt(year, month, day).is_some())
+        .unwrap_or(28)
+}
+
+// 剩余月流量, 未配置配额返回 None
+fn traffic_left(o: &HostStat) -> Option<i128> {
+    (o.traffic_quota > 0).then(|| (o.traffic_quota as i128) * 1024 * 1024 * 1024 - month_traffic(o) as i128)
+}
+
+// 相同时按 weight 排序
+fn sort_servers(servers: &mut [HostStat], sort_by: &str) {
+    servers.sort_by(|a, b| {
+        let ord = match sort_by {
+            "group" => cmp_some_first(
+                Some(a.gid.as_str()).filter(|s| !s.is_empty()),
+                Some(b.gid.as_str()).filter(|s| !s.is_empty()),
+            ),
+            "name" => a.alias.to_lowercase().cmp(&b.alias.to_lowercase()),
+            "expire" => cmp_some_first(expire_days(a), expire_days(b)),
+            "traffic" => cmp_some_first(traffic_left(a), traffic_left(b)),
+            _ => Ordering::Equal,
+        };
+        ord.then_with(|| b.weight.cmp(&a.weight))
+            .then_with(|| a.pos.cmp(&b.pos))
+            // same group
+            .then_with(|| a.alias.cmp(&b.alias))
+    });
+}
+
+fn dispatch_notify(notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>, e: &Event, stat: &HostStat) {
+    if G_MAINTENANCE_MGR.lock().unwrap().is_active(&stat.name, &stat.gid) {
+        trace!("{} in maintenance, suppress {:?}", stat.name, e);
+        return;
+    }
+    let notifiers = &*notifies.lock().unwrap();
+    trace!("recv notify => {:?}, {:?}", e, stat);
+    for notifier in notifiers {
+        if get_severity(e) < notifier.min_severity() || !notifier.tenant().eq(&stat.tenant) {
+            continue;
+        }
+        trace!("{} notify {:?} => {:?}", notifier.kind(), e, stat);
+        notifier.notify(e, stat);
+    }
+}
+
+// 非主机事件, 直接发送文本, key 见 Notifier::send_event
+fn broadcast_notify(notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>, e: &Event, key: &str, content: &str) {
+    for notifier in &*notifies.lock().unwrap() {
+        if get_severity(e) < notifier.min_severity() || !notifier.tenant().is_empty() {
+            continue;
+        }
+        if notifier.quiet_hours().map(|o| o.hold(e, content)).unwrap_or(false) {
+            continue;
+        }
+        if let Err(err) = notifier.send_event(e, key, content.to_string()) {
+            error!("{} send notify err => {:?}", notifier.kind(), err);
+        }
+    }
+}
+
+// 主机相关的文本通知, 发送给主机所属租户的通知方式
+fn host_notify(
+    notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>,
+    e: &Event,
+    key: &str,
+    stat: &HostStat,
+    content: &str,
+) {
+    if G_MAINTENANCE_MGR.lock().unwrap().is_active(&stat.name, &stat.gid) {
+        trace!("{} in maintenance, suppress {:?}", stat.name, e);
+        return;
+    }
+    for notifier in &*notifies.lock().unwrap() {
+        if get_severity(e) < notifier.min_severity() || !notifier.tenant().eq(&stat.tenant) {
+            continue;
+        }
+        if notifier.quiet_hours().map(|o| o.hold(e, content)).unwrap_or(false) {
+            continue;
+        }
+        if let Err(err) = notifier.send_event(e, key, content.to_string()) {
+            error!("{} send notify err => {:?}", notifier.kind(), err);
+        }
+    }
+}
+
+// 持续性告警, 状态见 alert::edge, 触发时发送 Custom, 恢复时发送 Resolved; content 参数为是否触发
+fn edge_alert(
+    notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>,
+    stat: &HostStat,
+    kind: &str,
+    firing: bool,
+    detail: &str,
+    content: impl FnOnce(bool) -> String,
+) {
+    if let Some(fired) = crate::alert::edge(&stat.name, kind, firing, detail) {
+        let e = if fired { Event::Custom } else { Event::Resolved };
+        host_notify(notifies, &e, &alert_key(&stat.name, kind), stat, &content(fired));
+    }
+}
+
+// 监控项已移除(如配置中删除的服务), 关闭其告警
+fn sweep_alerts(
+    notifies: &Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>,
+    stat: &HostStat,
+    prefix: &str,
+    seen: &HashSet<String>,
+) {
+    for kind in crate::alert::sweep(&stat.name, prefix, seen) {
+        let content = format!(
+            "❗ServerStatus\n✅ {} {} {} 已移除, 告警关闭",
+            stat.location, stat.alias, kind
+        );
+        host_notify(
+            notifies,
+            &Event::Resolved,
+            &alert_key(&stat.name, &kind),
+            stat,
+            &content,
+        );
+    }
+}
+
 pub struct StatsMgr {
     resp_json: Arc<Mutex<String>>,
     stats_data: Arc<Mutex<StatsResp>>,
+    notifies: Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>,
 }
 
 impl StatsMgr {
@@ -36,6 +189,7 @@ impl StatsMgr {
         Self {
             resp_json: Arc::new(Mutex::new("{}".to_string())),
             stats_data: Arc::new(Mutex::new(StatsResp::new())),
+            notifies: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -75,7 +229,15 @@ impl StatsMgr {
         cfg: &'static crate::config::Config,
         notifies: Arc<Mutex<Vec<Box<dyn Notifier + Send>>>>,
     ) -> Result<()> {
+        self.notifies = notifies.clone();
         let hosts_map_base = Arc::new(Mutex::new(cfg.hosts_map.clone()));
+        G_ALERT_MGR.lock().unwrap().init(cfg.ack_expire);
+        G_INCIDENT_MGR.lock().unwrap().init(cfg.max_incidents);
+        G_ROTATION_MGR.lock().unwrap().init(cfg);
+        G_RETRY_MGR.lock().unwrap().init(cfg.notify_retry, cfg.max_dead_letters);
+        G_CHECK_MGR.lock().unwrap().init(&cfg.checks);
+        G_MAINTENANCE_MGR.lock().unwrap().init();
+        G_DISKFILL_MGR.lock().unwrap().init();
 
         // load last_network_in/out
         if let Ok(mut hosts_map) = hosts_map_base.lock() {
@@ -87,6 +249,7 @@ impl StatsMgr {
         let (notifier_tx, notifier_rx) = sync_channel(512);
 
         let stat_map: Arc<Mutex<HashMap<String, Cow<HostStat>>>> = Arc::new(Mutex::new(HashMap::new()));
+        let flap_detector = Arc::new(Mutex::new(FlapDetector::new(cfg.flap_window, cfg.flap_count)));
 
         // stat_rx thread
         let hosts_group_map = cfg.hosts_group_map.clone();
@@ -136,6 +299,11 @@ impl StatsMgr {
                     if info.disabled {
                         continue;
                     }
+                    // 补发数据保留采集时间
+                    if stat_t.replay {
+                        crate::history::record_replay(&stat_t);
+                        continue;
+                    }
 
                     // 补齐
                     if stat_t.location.is_empty() {
@@ -148,6 +316,20 @@ impl StatsMgr {
                     stat_t.pos = info.pos;
                     stat_t.disabled = info.disabled;
                     stat_t.weight += info.weight;
+                    stat_t.expire = info.expire.to_owned();
+                    stat_t.traffic_quota = info.traffic_quota;
+                    stat_t.tenant = info.tenant.to_owned();
+                    stat_t.offline_threshold = cfg
+                        .offline_threshold
+                        .max(info.push_interval * 2)
+                        .max(info.report_interval * 2);
+                    stat_t.outdated = cfg.is_outdated(&stat_t.version);
+                    stat_t.clock_skewed = cfg.clock_skew_threshold > 0
+                        && (stat_t.clock_skew.unsigned_abs() >= cfg.clock_skew_threshold
+                            || stat_t.ntp_offset.unwrap_or(0).unsigned_abs() >= cfg.clock_skew_threshold * 1000);
+                    stat_t.swap_heavy = cfg.swap_threshold > 0
+                        && stat_t.swap_total > 0
+                        && stat_t.swap_used * 100 >= stat_t.swap_total * cfg.swap_threshold;
 
                     // !group
                     if !info.alias.is_empty() {
@@ -160,9 +342,14 @@ impl StatsMgr {
                     // last_network_in/out
                     if !stat_t.vnstat {
                         let local_now = Local::now();
+                        let monthstart = match stat_t.traffic_reset_day {
+                            0 => info.monthstart,
+                            day => day,
+                        }
+                        .min(month_days(local_now.year(), local_now.month()));
                         if info.last_network_in == 0
                             || (stat_t.network_in != 0 && info.last_network_in > stat_t.network_in)
-                            || (local_now.day() == info.monthstart && local_now.hour() == 0 && local_now.minute() < 5)
+                            || (local_now.day() == monthstart && local_now.hour() == 0 && local_now.minute() < 5)
                         {
                             info.last_network_in = stat_t.network_in;
                             info.last_network_out = stat_t.network_out;
@@ -186,16 +373,33 @@ impl StatsMgr {
                     }
 
                     info!("update stat `{:?}", stat_t);
+                    crate::history::record(&stat_t);
                     if let Ok(mut host_stat_map) = stat_map_1.lock() {
                         if let Some(pre_stat) = host_stat_map.get(&stat_t.name) {
                             if stat_t.ip_info.is_none() {
                                 stat_t.ip_info = pre_stat.ip_info.to_owned();
                             }
+                            // 新的路由跟踪在前, 客户端在上报成功前会重发, 按 (target, ts) 去重
+                            stat_t
+                                .traces
+                                .retain(|o| !pre_stat.traces.iter().any(|t| t.target == o.target && t.ts == o.ts));
+                            for o in stat_t.traces.iter() {
+                                info!("{} trace {} ({}) =>\n{}", stat_t.name, o.target, o.reason, o.report);
+                            }
+                            stat_t.traces.extend(pre_stat.traces.iter().cloned());
+                            stat_t.traces.truncate(MAX_TRACES);
 
-                            if stat_t.notify && (pre_stat.latest_ts + cfg.offline_threshold < stat_t.latest_ts) {
+                            // 超时后重新上报, 或 push 模式由 down 恢复
+                            let recovered = pre_stat.latest_ts + stat_t.offline_threshold < stat_t.latest_ts
+                                || (pre_stat.disabled && (stat_t.online4 || stat_t.online6));
+                            if stat_t.notify && recovered {
                                 // node up notify
                                 notifier_tx_1.send((Event::NodeUp, stat_c.to_owned()));
                             }
+                            // push 模式持续 down, 不重复发送掉线通知
+                            if pre_stat.disabled && !stat_c.online4 && !stat_c.online6 {
+                                stat_c.to_mut().disabled = true;
+                            }
                         }
                         host_stat_map.insert(stat_c.name.to_string(), stat_c);
                         //trace!("{:?}", host_stat_map);
@@ -210,6 +414,7 @@ impl StatsMgr {
         let hosts_map_2 = hosts_map_base.clone();
         let stat_map_2 = stat_map.clone();
         let notifier_tx_2 = notifier_tx.clone();
+        let flap_detector_2 = flap_detector.clone();
         let mut latest_notify_ts = 0_u64;
         let mut latest_save_ts = 0_u64;
         let mut latest_group_gc = 0_u64;
@@ -226,23 +431,54 @@ impl StatsMgr {
                 //
                 if let Ok(mut hosts_map) = hosts_map_2.lock() {
                     hosts_map.retain(|_, o| o.gid.is_empty() || o.latest_ts + cfg.group_gc >= now);
+                    crate::delta::prune(
+                        &hosts_map
+                            .iter()
+                            .map(|(name, o)| crate::delta::key(&o.gid, name))
+                            .collect(),
+                    );
                 }
                 //
                 if let Ok(mut stat_map) = stat_map_2.lock() {
                     stat_map.retain(|_, o| o.gid.is_empty() || o.latest_ts + cfg.group_gc >= now);
+                    crate::history::prune(&stat_map.keys().cloned().collect());
                 }
             }
 
+            // 维护结束时仍离线的主机补发掉线告警, 期间的掉线已被抑制
+            let maintenance_ended = G_MAINTENANCE_MGR.lock().unwrap().take_ended();
             if let Ok(mut host_stat_map) = stat_map_2.lock() {
                 for (_, stat) in host_stat_map.iter_mut() {
+                    if let Ok(flap) = flap_detector_2.lock() {
+                        let flapping = flap.is_flapping(&stat.name);
+                        stat.to_mut().flapping = flapping;
+                    }
+                    if let Ok(alert_mgr) = G_ALERT_MGR.lock() {
+                        let acked_by = alert_mgr
+                            .acked_by(&stat.name)
+                            .map(|o| o.by.to_string())
+                            .unwrap_or_default();
+                        stat.to_mut().acked_by = acked_by;
+                    }
+                    let maintenance = G_MAINTENANCE_MGR.lock().unwrap().is_active(&stat.name, &stat.gid);
+                    stat.to_mut().maintenance = maintenance;
+                    if cfg.sparkline_minutes > 0 {
+                        stat.to_mut().series = crate::history::sparkline(&stat.name, cfg.sparkline_minutes);
+                    }
                     if stat.disabled {
+                        if stat.notify
+                            && !maintenance
+                            && maintenance_ended.iter().any(|w| w.matches(&stat.name, &stat.gid))
+                        {
+                            notifier_tx_2.send((Event::NodeDown, stat.to_owned()));
+                        }
                         resp.servers.push(stat.to_owned().into_owned());
                         continue;
                     }
                     let stat_c = stat.borrow_mut();
                     let o = stat_c.to_mut();
                     // 30s 下线
-                    if o.latest_ts + cfg.offline_threshold < now {
+                    if o.latest_ts + o.offline_threshold.max(cfg.offline_threshold) < now {
                         o.online4 = false;
                         o.online6 = false;
                     }
@@ -268,16 +504,7 @@ impl StatsMgr {
                 }
             }
 
-            resp.servers.sort_by(|a, b| {
-                if a.weight != b.weight {
-                    return a.weight.cmp(&b.weight).reverse();
-                }
-                if a.pos != b.pos {
-                    return a.pos.cmp(&b.pos);
-                }
-                // same group
-                a.alias.cmp(&b.alias)
-            });
+            sort_servers(&mut resp.servers, &cfg.sort_by);
 
             // last_network_in/out save /60s
             if latest_save_ts + SAVE_INTERVAL < now {
@@ -293,23 +520,569 @@ impl StatsMgr {
                 }
             }
             //
+            // 租户主机仅在租户面板展示
             if let Ok(mut o) = resp_json.lock() {
-                *o = serde_json::to_string(&resp).unwrap();
+                let servers = resp.servers.iter().filter(|o| o.tenant.is_empty()).collect::<Vec<_>>();
+                *o = serde_json::json!({"updated": resp.updated, "servers": servers}).to_string();
             }
             if let Ok(mut o) = stats_data.lock() {
                 *o = resp;
             }
         });
 
+        // digest thread
+        if let Some(digest_at) = cfg.digest_at {
+            crate::digest::init(&cfg.digest_tpl);
+            let stats_data_3 = self.stats_data.clone();
+            let notifies_3 = notifies.clone();
+            let mut latest_digest_day: Option<u32> = None;
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(30));
+
+                let local_now = Local::now();
+                if latest_digest_day == Some(local_now.ordinal0()) || local_now.time() < digest_at {
+                    continue;
+                }
+                latest_digest_day = Some(local_now.ordinal0());
+
+                let content = match stats_data_3.lock() {
+                    Ok(resp) => crate::digest::render(&resp, cfg.digest_top),
+                    Err(_) => continue,
+                };
+                match content {
+                    // 日报按 Info 级别发送, 不带告警 key
+                    Ok(content) if !content.is_empty() => broadcast_notify(&notifies_3, &Event::Resolved, "", &content),
+                    Ok(_) => {}
+                    Err(err) => error!("render digest err => {:?}", err),
+                }
+            });
+        }
+
+        // outdated thread
+        if cfg.outdated_notify_interval > 0 {
+            let stats_data_5 = self.stats_data.clone();
+            let notifies_5 = notifies.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(cfg.outdated_notify_interval * 3600));
+
+                let hosts = match stats_data_5.lock() {
+                    Ok(resp) => resp
+                        .servers
+                        .iter()
+                        .filter(|o| o.outdated)
+                        // 维护中的主机可能正在升级, 不提醒
+                        .filter(|o| !G_MAINTENANCE_MGR.lock().unwrap().is_active(&o.name, &o.gid))
+                        .map(|o| format!("{} {} (v{})", o.location, o.alias, o.version))
+                        .collect::<Vec<_>>(),
+                    Err(_) => continue,
+                };
+                if hosts.is_empty() {
+                    continue;
+                }
+                let content = format!(
+                    "⬆️ {} 台主机客户端版本低于 v{}, 请升级:\n{}",
+                    hosts.len(),
+                    cfg.min_client_version,
+                    hosts.join("\n")
+                );
+                broadcast_notify(&notifies_5, &Event::Custom, "", &content);
+            });
+        }
+
+        // disk full forecast thread
+        if cfg.disk_full_days > 0 {
+            let stats_data_7 = self.stats_data.clone();
+            let notifies_7 = notifies.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(crate::diskfill::SAMPLE_SECS));
+
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                let servers = match stats_data_7.lock() {
+                    Ok(resp) => resp
+                        .servers
+                        .iter()
+                        .filter(|o| (o.online4 || o.online6) && o.hdd_total > 0)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    Err(_) => continue,
+                };
+                let forecasts = G_DISKFILL_MGR.lock().unwrap().update(&servers, cfg.disk_full_days, now);
+                let mut seen: HashMap<String, HashSet<String>> = HashMap::new();
+                for (stat, mount, forecast) in forecasts.iter() {
+                    let kind = if mount.is_empty() {
+                        "DiskFull".to_string()
+                    } else {
+                        format!("DiskFull {}", mount)
+                    };
+                    let detail = forecast
+                        .map(|o| format!("{:.1} 天后写满, 每天增长 {:.2} GB", o.days, o.per_day / 1024.0))
+                        .unwrap_or_default();
+                    edge_alert(&notifies_7, stat, &kind, forecast.is_some(), &detail, |firing| {
+                        if firing {
+                            format!(
+                                "❗ServerStatus\n💾 {} {} 硬盘 {} 预计 {}",
+                                stat.location, stat.alias, mount, detail
+                            )
+                        } else {
+                            format!(
+                                "❗ServerStatus\n✅ {} {} 硬盘 {} 用量已不再快速增长",
+                                stat.location, stat.alias, mount
+                            )
+                        }
+                    });
+                    seen.entry(stat.name.to_string()).or_default().insert(kind);
+                }
+                // 已移除的挂载点, 前缀同时匹配旧版本按主机的 DiskFull
+                for stat in servers.iter() {
+                    sweep_alerts(
+                        &notifies_7,
+                        stat,
+                        "DiskFull",
+                        &seen.remove(&stat.name).unwrap_or_default(),
+                    );
+                }
+            });
+        }
+
+        // clock skew / swap / inode / ipv6 thread
+        if cfg.clock_skew_threshold > 0
+            || cfg.swap_threshold > 0
+            || cfg.inode_threshold > 0
+            || cfg.ipv6_idle_minutes > 0
+        {
+            let stats_data_8 = self.stats_data.clone();
+            let notifies_8 = notifies.clone();
+            // host => (net_in_v6, 最近一次增长时间)
+            let mut v6_seen: HashMap<String, (u64, u64)> = HashMap::new();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(30));
+
+                let servers = match stats_data_8.lock() {
+                    Ok(resp) => resp
+                        .servers
+                        .iter()
+                        .filter(|o| o.online4 || o.online6)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    Err(_) => continue,
+                };
+                for stat in servers {
+                    let detail = match stat.ntp_offset {
+                        Some(ms) => format!("时钟偏差 {}s, NTP 偏差 {}ms", stat.clock_skew, ms),
+                        None => format!("时钟偏差 {}s", stat.clock_skew),
+                    };
+                    edge_alert(&notifies_8, &stat, "ClockSkew", stat.clock_skewed, &detail, |firing| {
+                        if firing {
+                            format!(
+                                "❗ServerStatus\n⏱ {} {} {}, 流量统计及在线时长可能不准确, 请检查 NTP 同步",
+                                stat.location, stat.alias, detail
+                            )
+                        } else {
+                            format!("❗ServerStatus\n✅ {} {} 时钟已同步", stat.location, stat.alias)
+                        }
+                    });
+
+                    let detail = format!(
+                        "交换分区使用率 {}%",
+                        (100 * stat.swap_used).checked_div(stat.swap_total).unwrap_or(0)
+                    );
+                    edge_alert(&notifies_8, &stat, "Swap", stat.swap_heavy, &detail, |firing| {
+                        if firing {
+                            format!(
+                                "❗ServerStatus\n🐢 {} {} {}, 内存不足可能导致响应变慢",
+                                stat.location, stat.alias, detail
+                            )
+                        } else {
+                            format!(
+                                "❗ServerStatus\n✅ {} {} 交换分区使用率已恢复",
+                                stat.location, stat.alias
+                            )
+                        }
+                    });
+
+                    if cfg.ipv6_idle_minutes > 0 && stat.net_in_v6 > 0 {
+                        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                        let seen = v6_seen.entry(stat.name.to_string()).or_insert((stat.net_in_v6, now));
+                        if stat.net_in_v6 != seen.0 {
+                            *seen = (stat.net_in_v6, now);
+                        }
+                        let idle = seen.1 + cfg.ipv6_idle_minutes * 60 < now && stat.net_rx_v4 > 0;
+                        let detail = format!("{} 分钟未收到 IPv6 数据", cfg.ipv6_idle_minutes);
+                        edge_alert(&notifies_8, &stat, "IPv6", idle, &detail, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n🌐 {} {} {}, IPv4 正常, 请检查 IPv6 路由",
+                                    stat.location, stat.alias, detail
+                                )
+                            } else {
+                                format!("❗ServerStatus\n✅ {} {} IPv6 已恢复", stat.location, stat.alias)
+                            }
+                        });
+                    }
+
+                    if cfg.inode_threshold == 0 {
+                        continue;
+                    }
+                    let mut seen = HashSet::new();
+                    for disk in stat.disks.iter().filter(|o| o.inodes_total > 0) {
+                        let kind = format!("Inode {}", disk.mount);
+                        let usage = 100 * disk.inodes_used / disk.inodes_total;
+                        let detail = format!("{} inode 使用率 {}%", disk.mount, usage);
+                        edge_alert(
+                            &notifies_8,
+                            &stat,
+                            &kind,
+                            usage >= cfg.inode_threshold,
+                            &detail,
+                            |firing| {
+                                if firing {
+                                    format!(
+                                        "❗ServerStatus\n🗂 {} {} {}, 耗尽后即使有剩余空间也无法创建文件",
+                                        stat.location, stat.alias, detail
+                                    )
+                                } else {
+                                    format!(
+                                        "❗ServerStatus\n✅ {} {} {} inode 使用率已恢复",
+                                        stat.location, stat.alias, disk.mount
+                                    )
+                                }
+                            },
+                        );
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_8, &stat, "Inode ", &seen);
+                }
+            });
+        }
+
+        // systemd unit / zfs pool / raid / cert / port / http / ipmi thread
+        {
+            let stats_data_9 = self.stats_data.clone();
+            let notifies_9 = notifies.clone();
+            // (host, target) => 已告警的最小阈值, 用于证书临近过期时逐级提醒
+            let mut cert_tier: HashMap<(String, String), i64> = HashMap::new();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(10));
+
+                // 监控项为空的主机也需检查, 以关闭已移除监控项的告警
+                let servers = match stats_data_9.lock() {
+                    Ok(resp) => resp
+                        .servers
+                        .iter()
+                        .filter(|o| o.online4 || o.online6)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    Err(_) => continue,
+                };
+                for stat in servers {
+                    let mut seen = HashSet::new();
+                    for unit in stat.units.iter() {
+                        let kind = format!("Unit {}", unit.name);
+                        // reloading/activating 为正常的重载和启动过程
+                        let ok = matches!(unit.state.as_str(), "active" | "reloading" | "activating");
+                        edge_alert(&notifies_9, &stat, &kind, !ok, &unit.state, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n🛑 {} {} 服务 {} 状态 {}",
+                                    stat.location, stat.alias, unit.name, unit.state
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} 服务 {} 已恢复",
+                                    stat.location, stat.alias, unit.name
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "Unit ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for pool in stat.zfs_pools.iter() {
+                        let kind = format!("Zpool {}", pool.name);
+                        let ok = pool.health.eq("ONLINE");
+                        edge_alert(&notifies_9, &stat, &kind, !ok, &pool.health, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n💽 {} {} 存储池 {} 状态 {}",
+                                    stat.location, stat.alias, pool.name, pool.health
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} 存储池 {} 已恢复 ONLINE",
+                                    stat.location, stat.alias, pool.name
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "Zpool ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for md in stat.raid.iter() {
+                        let kind = format!("Raid {}", md.name);
+                        let detail = format!("{}/{} 块盘在线", md.active, md.devices);
+                        edge_alert(&notifies_9, &stat, &kind, md.degraded, &detail, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n💽 {} {} 阵列 {} ({}) 降级, {}",
+                                    stat.location, stat.alias, md.name, md.level, detail
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} 阵列 {} 已恢复",
+                                    stat.location, stat.alias, md.name
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "Raid ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for cert in stat.certs.iter() {
+                        let kind = format!("Cert {}", cert.target);
+                        seen.insert(kind.to_string());
+                        // 握手失败时保留原状态
+                        if !cert.error.is_empty() {
+                            continue;
+                        }
+                        let key = (stat.name.to_string(), cert.target.to_string());
+                        let tier = cfg.cert_notify_days.iter().rev().find(|&&d| cert.days <= d).copied();
+                        let detail = if cert.days < 0 {
+                            format!("证书已过期 {} 天", -cert.days)
+                        } else {
+                            format!("证书 {} 天后过期", cert.days)
+                        };
+                        let alert = format!(
+                            "❗ServerStatus\n🔒 {} {} {} {}",
+                            stat.location, stat.alias, cert.target, detail
+                        );
+                        edge_alert(&notifies_9, &stat, &kind, tier.is_some(), &detail, |firing| {
+                            if firing {
+                                alert.to_string()
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} {} 证书已续期, {} 天后过期",
+                                    stat.location, stat.alias, cert.target, cert.days
+                                )
+                            }
+                        });
+                        // 告警期间进入更小的阈值时再次提醒, 重启后从当前阈值开始
+                        match tier {
+                            Some(tier) => {
+                                if cert_tier.insert(key, tier).map_or(false, |last| tier < last) {
+                                    let key = alert_key(&stat.name, &kind);
+                                    host_notify(&notifies_9, &Event::Custom, &key, &stat, &alert);
+                                }
+                            }
+                            None => {
+                                cert_tier.remove(&key);
+                            }
+                        }
+                    }
+                    sweep_alerts(&notifies_9, &stat, "Cert ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for port in stat.ports.iter() {
+                        let kind = format!("Port {}", port.target);
+                        edge_alert(&notifies_9, &stat, &kind, !port.up, "down", |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n🔌 {} {} 端口 {} 无法连接",
+                                    stat.location, stat.alias, port.target
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} 端口 {} 已恢复",
+                                    stat.location, stat.alias, port.target
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "Port ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for check in stat.http_checks.iter() {
+                        let kind = format!("HTTP {}", check.url);
+                        let detail = if check.error.is_empty() {
+                            format!("状态码 {}", check.status)
+                        } else {
+                            check.error.to_string()
+                        };
+                        edge_alert(&notifies_9, &stat, &kind, !check.ok, &detail, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n🔗 {} {} {} 检查失败, {}",
+                                    stat.location, stat.alias, check.url, detail
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} {} 已恢复, {}ms",
+                                    stat.location, stat.alias, check.url, check.latency
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "HTTP ", &seen);
+
+                    let mut seen = HashSet::new();
+                    for sensor in stat.ipmi.iter() {
+                        let kind = format!("IPMI {}", sensor.name);
+                        // cr 超过临界阈值, nr 不可恢复
+                        let critical = sensor.status.eq("cr") || sensor.status.eq("nr");
+                        let detail = format!("{} {}", sensor.reading, sensor.status);
+                        edge_alert(&notifies_9, &stat, &kind, critical, &detail, |firing| {
+                            if firing {
+                                format!(
+                                    "❗ServerStatus\n🌡 {} {} 传感器 {} {}",
+                                    stat.location, stat.alias, sensor.name, detail
+                                )
+                            } else {
+                                format!(
+                                    "❗ServerStatus\n✅ {} {} 传感器 {} 已恢复 {}",
+                                    stat.location, stat.alias, sensor.name, sensor.reading
+                                )
+                            }
+                        });
+                        seen.insert(kind);
+                    }
+                    sweep_alerts(&notifies_9, &stat, "IPMI ", &seen);
+                }
+            });
+        }
+
+        // notify retry thread
+        if cfg.notify_retry > 0 {
+            let notifies_6 = notifies.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(10));
+
+                let jobs = G_RETRY_MGR.lock().unwrap().take_due();
+                for (tenant, kind, payload) in jobs {
+                    let notifiers = notifies_6.lock().unwrap();
+                    let notifier = notifiers.iter().find(|o| kind.eq(o.kind()) && tenant.eq(o.tenant()));
+                    let res = match notifier {
+                        Some(notifier) => {
+                            trace!("{} resend", kind);
+                            notifier.resend(payload.to_string())
+                        }
+                        None => Err(anyhow::anyhow!("notifier disabled")),
+                    };
+                    // 未发出的直接计入失败次数
+                    if let Err(err) = res {
+                        error!("{} resend err => {:?}", kind, err);
+                        G_RETRY_MGR
+                            .lock()
+                            .unwrap()
+                            .failed(&tenant, &kind, &payload, &err.to_string(), false);
+                        crate::notifier::retry::save();
+                    }
+                }
+            });
+        }
+
+        // checks thread
+        if G_CHECK_MGR.lock().unwrap().enabled() {
+            let notifies_4 = notifies.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(5));
+
+                let events = G_CHECK_MGR.lock().unwrap().check();
+                for o in events {
+                    let kind = "CronDown";
+                    let content = if o.down {
+                        G_INCIDENT_MGR.lock().unwrap().open(&o.name, kind, "");
+                        format!(
+                            "❗ServerStatus\n⏰ 定时任务 {} 未按时完成, 最后成功 {}",
+                            o.name,
+                            crate::incident::fmt_ts(o.last_ping)
+                        )
+                    } else {
+                        G_INCIDENT_MGR.lock().unwrap().close(&o.name, kind);
+                        format!("❗ServerStatus\n✅ 定时任务 {} 已恢复", o.name)
+                    };
+                    if o.notify {
+                        let e = if o.down { Event::NodeDown } else { Event::Resolved };
+                        broadcast_notify(&notifies_4, &e, &alert_key(&o.name, kind), &content);
+                    }
+                }
+            });
+        }
+
         // notify thread
         thread::spawn(move || loop {
-            while let Ok(msg) = notifier_rx.recv() {
-                let (e, stat) = msg;
-                let notifiers = &*notifies.lock().unwrap();
-                trace!("recv notify => {:?}, {:?}", e, stat);
-                for notifier in notifiers {
-                    trace!("{} notify {:?} => {:?}", notifier.kind(), e, stat);
-                    notifier.notify(&e, stat.borrow());
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            match notifier_rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(msg) => {
+                    let (e, stat): (Event, Cow<HostStat>) = msg;
+                    if let Ok(mut incident_mgr) = G_INCIDENT_MGR.lock() {
+                        match e {
+                            Event::NodeDown => incident_mgr.open(&stat.name, get_tag(&e), &stat.location),
+                            Event::NodeUp => incident_mgr.close(&stat.name, get_tag(&Event::NodeDown)),
+                            _ => {}
+                        }
+                    }
+                    // acked, suppress repeats until recovered
+                    if let Ok(mut alert_mgr) = G_ALERT_MGR.lock() {
+                        if let Event::NodeUp = e {
+                            alert_mgr.unack(&stat.name, get_tag(&Event::NodeDown));
+                        } else if alert_mgr.is_acked(&stat.name, get_tag(&e)) {
+                            trace!("{} is acked, suppress {:?}", stat.name, e);
+                            continue;
+                        }
+                    }
+                    match e {
+                        Event::NodeUp | Event::NodeDown => {
+                            let action = flap_detector.lock().unwrap().on_transition(&e, stat.borrow(), now);
+                            match action {
+                                FlapAction::Pass => dispatch_notify(&notifies, &e, stat.borrow()),
+                                FlapAction::Suppress => {
+                                    trace!("{} is flapping, suppress {:?}", stat.name, e);
+                                }
+                                FlapAction::Start(count) => {
+                                    let mut stat_c = stat;
+                                    let o = stat_c.to_mut();
+                                    o.flapping = true;
+                                    o.flap_count = count;
+                                    G_INCIDENT_MGR.lock().unwrap().open(
+                                        &o.name,
+                                        get_tag(&Event::Flapping),
+                                        &format!("{} transitions", count),
+                                    );
+                                    dispatch_notify(&notifies, &Event::Flapping, stat_c.borrow());
+                                }
+                            }
+                        }
+                        _ => dispatch_notify(&notifies, &e, stat.borrow()),
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            // flapping end, resend latest state
+            let resumed = flap_detector.lock().unwrap().check_stable(now);
+            for (e, stat) in resumed {
+                G_INCIDENT_MGR
+                    .lock()
+                    .unwrap()
+                    .close(&stat.name, get_tag(&Event::Flapping));
+                if let Ok(mut alert_mgr) = G_ALERT_MGR.lock() {
+                    alert_mgr.unack(&stat.name, get_tag(&Event::Flapping));
+                    if let Event::NodeUp = e {
+                        alert_mgr.unack(&stat.name, get_tag(&Event::NodeDown));
+                    }
+                }
+                dispatch_notify(&notifies, &e, &stat);
+            }
+
+            // quiet hours end, send held notify
+            for notifier in &*notifies.lock().unwrap() {
+                if let Err(err) = notifier.flush_quiet() {
+                    error!("{} flush quiet notify err => {:?}", notifier.kind(), err);
                 }
             }
         });
@@ -321,17 +1094,31 @@ impl StatsMgr {
         self.stats_data.clone()
     }
 
+    pub fn get_notifies(&self) -> Arc<Mutex<Vec<Box<dyn Notifier + Send>>>> {
+        self.notifies.clone()
+    }
+
     pub fn get_stats_json(&self) -> String {
         self.resp_json.lock().unwrap().to_string()
     }
 
-    pub fn report(&self, data: serde_json::Value) -> Result<()> {
+    // 返回 false 为增量缺少完整数据被丢弃, 客户端需完整上报
+    pub fn report(&self, data: serde_json::Value) -> Result<bool> {
         lazy_static! {
             static ref SENDER: SyncSender<Cow<'static, HostStat>> = STAT_SENDER.get().unwrap().clone();
         }
 
-        match serde_json::from_value(data) {
-            Ok(stat) => {
+        let data = match crate::delta::apply(data) {
+            Some(o) => o,
+            None => return Ok(false),
+        };
+        let client_ts = data["latest_ts"].as_u64().unwrap_or(0);
+        match serde_json::from_value::<HostStat>(data) {
+            Ok(mut stat) => {
+                if client_ts > 0 && !stat.replay {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                    stat.clock_skew = client_ts as i64 - now as i64;
+                }
                 trace!("send stat => {:?} ", stat);
                 SENDER.send(Cow::Owned(stat));
             }
@@ -339,6 +1126,6 @@ impl StatsMgr {
                 error!("report error => {:?}", err);
             }
         };
-        Ok(())
+        Ok(true)
     }
 }
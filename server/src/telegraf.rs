@@ -0,0 +1,136 @@
+#![deny(warnings)]
+// telegraf outputs.http, data_format = "json"
+// {"metrics": [{"name": "cpu", "tags": {"host": "h1", "cpu": "cpu-total"}, "fields": {...}, "timestamp": 0}]}
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use stat_common::server_status::StatRequest;
+
+// host => (ts, network_in, network_out), 用于计算网速
+static NET_STATE: Lazy<Mutex<HashMap<String, (u64, u64, u64)>>> = Lazy::new(Default::default);
+// telegraf 每次 flush 不一定包含全部 metric, 缺失的沿用上次的值
+static LAST_STATS: Lazy<Mutex<HashMap<String, StatRequest>>> = Lazy::new(Default::default);
+
+fn field_u64(fields: &Value, key: &str) -> u64 {
+    fields[key]
+        .as_u64()
+        .or_else(|| fields[key].as_f64().map(|v| v as u64))
+        .unwrap_or(0)
+}
+
+fn field_f64(fields: &Value, key: &str) -> f64 {
+    fields[key].as_f64().unwrap_or(0.0)
+}
+
+// default_host 非空时所有 metric 归属该主机, 否则取 tags.host
+pub fn to_stat_requests(body: &Value, default_host: Option<&str>) -> Vec<StatRequest> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let metrics = match body["metrics"].as_array() {
+        Some(list) => list.iter().collect::<Vec<_>>(),
+        None => vec![body],
+    };
+
+    let mut last_stats = LAST_STATS.lock().unwrap();
+    let mut stats: HashMap<String, StatRequest> = HashMap::new();
+    // 累加型字段, 本批次出现时重新统计
+    let mut reset: Vec<(String, &str)> = Vec::new();
+    for metric in metrics {
+        let tags = &metric["tags"];
+        let fields = &metric["fields"];
+        let host = match default_host.or_else(|| tags["host"].as_str()) {
+            Some(host) => host,
+            None => continue,
+        };
+        let stat = stats.entry(host.to_string()).or_insert_with(|| {
+            let mut stat = last_stats.get(host).cloned().unwrap_or_else(|| StatRequest {
+                name: host.to_string(),
+                frame: "data".to_string(),
+                version: "telegraf".to_string(),
+                notify: true,
+                online4: true,
+                ..Default::default()
+            });
+            stat.latest_ts = now;
+            stat
+        });
+
+        let name = metric["name"].as_str().unwrap_or_default();
+        if (name.eq("disk") || name.eq("net")) && !reset.iter().any(|(h, n)| h.eq(host) && name.eq(*n)) {
+            reset.push((host.to_string(), name));
+            if name.eq("disk") {
+                stat.hdd_total = 0;
+                stat.hdd_used = 0;
+            } else {
+                stat.network_in = 0;
+                stat.network_out = 0;
+            }
+        }
+
+        match name {
+            "cpu" => {
+                if tags["cpu"].as_str().unwrap_or("cpu-total").eq("cpu-total") {
+                    stat.cpu = (100.0 - field_f64(fields, "usage_idle")).max(0.0).round();
+                }
+            }
+            "system" => {
+                stat.load_1 = field_f64(fields, "load1");
+                stat.load_5 = field_f64(fields, "load5");
+                stat.load_15 = field_f64(fields, "load15");
+                if fields.get("uptime").is_some() {
+                    stat.uptime = field_u64(fields, "uptime");
+                }
+            }
+            "mem" => {
+                stat.memory_total = field_u64(fields, "total") / 1024;
+                stat.memory_used = field_u64(fields, "used") / 1024;
+            }
+            "swap" => {
+                stat.swap_total = field_u64(fields, "total") / 1024;
+                stat.swap_used = field_u64(fields, "used") / 1024;
+            }
+            "disk" => {
+                stat.hdd_total += field_u64(fields, "total") / 1024 / 1024;
+                stat.hdd_used += field_u64(fields, "used") / 1024 / 1024;
+            }
+            "net" => {
+                let iface = tags["interface"].as_str().unwrap_or_default();
+                if !iface.is_empty() && !iface.eq("all") && !iface.eq("lo") {
+                    stat.network_in += field_u64(fields, "bytes_recv");
+                    stat.network_out += field_u64(fields, "bytes_sent");
+                }
+            }
+            "processes" => {
+                stat.process = field_u64(fields, "total") as u32;
+                stat.thread = field_u64(fields, "total_threads") as u32;
+            }
+            "netstat" => {
+                stat.tcp = field_u64(fields, "tcp_established") as u32;
+                stat.udp = field_u64(fields, "udp_socket") as u32;
+            }
+            _ => {}
+        }
+    }
+
+    if let Ok(mut net_state) = NET_STATE.lock() {
+        for stat in stats.values_mut() {
+            if !reset.iter().any(|(h, n)| h.eq(&stat.name) && "net".eq(*n)) {
+                continue;
+            }
+            if let Some((ts, net_in, net_out)) = net_state.get(&stat.name) {
+                if now > *ts && stat.network_in >= *net_in && stat.network_out >= *net_out {
+                    stat.network_rx = (stat.network_in - net_in) / (now - ts);
+                    stat.network_tx = (stat.network_out - net_out) / (now - ts);
+                }
+            }
+            net_state.insert(stat.name.to_string(), (now, stat.network_in, stat.network_out));
+        }
+    }
+
+    for stat in stats.values() {
+        last_stats.insert(stat.name.to_string(), stat.clone());
+    }
+    stats.into_values().collect()
+}
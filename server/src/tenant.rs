@@ -0,0 +1,105 @@
+#![deny(warnings)]
+// 多租户, 租户间主机/用户/令牌/通知相互隔离, 租户面板 /t/<id>/
+use serde::{Deserialize, Serialize};
+use stat_common::auth::ct_eq;
+
+use crate::config::verify_pass;
+use crate::notifier::{self, Notifier};
+
+const NOTIFIERS: [&str; 6] = ["tgbot", "wechat", "email", "webhook", "pagerduty", "opsgenie"];
+// 不继承, 同一 bot_token 只能有一个 getUpdates 轮询
+const NO_INHERIT_KEYS: [&str; 1] = ["ack_button"];
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct User {
+    pub user: String,
+    pub pass: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Tenant {
+    pub id: String,
+    #[serde(default = "Default::default")]
+    pub name: String,
+    // 租户面板 basic auth 用户, 密码支持 argon2 hash
+    #[serde(default = "Default::default")]
+    pub users: Vec<User>,
+    // 只读令牌, /t/<id>/?token=xxx, users 与 token 至少配置一项
+    #[serde(default = "Default::default", skip_serializing)]
+    pub token: String,
+
+    #[serde(default = "Default::default")]
+    pub tgbot: Option<notifier::tgbot::Config>,
+    #[serde(default = "Default::default")]
+    pub wechat: Option<notifier::wechat::Config>,
+    #[serde(default = "Default::default")]
+    pub email: Option<notifier::email::Config>,
+    #[serde(default = "Default::default")]
+    pub webhook: Option<notifier::webhook::Config>,
+    #[serde(default = "Default::default")]
+    pub pagerduty: Option<notifier::pagerduty::Config>,
+    #[serde(default = "Default::default")]
+    pub opsgenie: Option<notifier::opsgenie::Config>,
+}
+
+impl Tenant {
+    pub fn auth(&self, user: &str, pass: &str) -> bool {
+        self.users.iter().any(|o| o.user.eq(user) && verify_pass(&o.pass, pass))
+    }
+
+    pub fn auth_token(&self, token: &str) -> bool {
+        !self.token.is_empty() && ct_eq(&self.token, token)
+    }
+
+    pub fn notifiers(&'static self) -> Vec<Box<dyn Notifier + Send>> {
+        let mut list: Vec<Box<dyn Notifier + Send>> = Vec::new();
+        if let Some(cfg) = self.tgbot.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::tgbot::TGBot::new(cfg, &self.id)));
+        }
+        if let Some(cfg) = self.wechat.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::wechat::WeChat::new(cfg, &self.id)));
+        }
+        if let Some(cfg) = self.email.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::email::Email::new(cfg, &self.id)));
+        }
+        if let Some(cfg) = self.webhook.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::webhook::Webhook::new(cfg, &self.id)));
+        }
+        if let Some(cfg) = self.pagerduty.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::pagerduty::PagerDuty::new(cfg, &self.id)));
+        }
+        if let Some(cfg) = self.opsgenie.as_ref().filter(|o| o.enabled) {
+            list.push(Box::new(notifier::opsgenie::Opsgenie::new(cfg, &self.id)));
+        }
+        list
+    }
+}
+
+// 租户通知配置只需填写差异字段, 其余沿用全局同名配置, 存在即启用
+pub fn merge_notify_config(value: &mut toml::Value) {
+    let globals = NOTIFIERS
+        .iter()
+        .map(|kind| (*kind, value.get(*kind).and_then(|o| o.as_table()).cloned()))
+        .collect::<Vec<_>>();
+    let tenants = match value.get_mut("tenants").and_then(|o| o.as_array_mut()) {
+        Some(o) => o,
+        None => return,
+    };
+    for tenant in tenants.iter_mut().filter_map(|o| o.as_table_mut()) {
+        for (kind, global) in globals.iter() {
+            let overlay = match tenant.get(*kind).and_then(|o| o.as_table()) {
+                Some(o) => o.clone(),
+                None => continue,
+            };
+            let mut merged = global.clone().unwrap_or_default();
+            for k in NO_INHERIT_KEYS {
+                merged.remove(k);
+            }
+            merged.insert("enabled".to_string(), toml::Value::Boolean(true));
+            for (k, v) in overlay {
+                merged.insert(k, v);
+            }
+            tenant.insert(kind.to_string(), toml::Value::Table(merged));
+        }
+    }
+}
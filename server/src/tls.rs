@@ -0,0 +1,154 @@
+#![deny(warnings)]
+// tls/mTLS, 配置 cert/key 后 http/grpc 均启用 tls, 配置 client_ca 时校验客户端证书
+// 客户端证书 CN 对应 hosts name(单机) 或 hosts_group gid(组模式, ssr-auth: group), 通过后不再检查密码
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Request};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::time::{self, Duration};
+use tokio_rustls::rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, NoClientAuth};
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, RootCertStore};
+use tokio_rustls::TlsAcceptor;
+
+const HANDSHAKE_TIMEOUT: u64 = 10; //s
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default = "Default::default")]
+    pub cert: String,
+    #[serde(default = "Default::default")]
+    pub key: String,
+    // 签发客户端证书的 CA, 留空不校验客户端证书
+    #[serde(default = "Default::default")]
+    pub client_ca: String,
+    // 上报必须携带客户端证书, 不再接受密码鉴权
+    #[serde(default = "Default::default")]
+    pub require_client_cert: bool,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        !self.cert.is_empty() && !self.key.is_empty()
+    }
+}
+
+// 已校验的客户端证书 CN, 放在 http request extensions 中
+#[derive(Debug, Clone)]
+pub struct PeerCn(pub String);
+
+// 客户端证书(DER) => CN
+pub fn peer_cn(der: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let cn = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    Some(cn.to_string())
+}
+
+// 证书鉴权的上报内容须与证书一致, 单机模式 name 即 CN 且不带 gid, 组模式 gid 即 CN
+pub fn stat_matches_cn(cn: &str, group_auth: bool, name: &str, gid: &str) -> bool {
+    if group_auth {
+        gid.eq(cn)
+    } else {
+        name.eq(cn) && gid.is_empty()
+    }
+}
+
+fn load_certs(path: &str) -> anyhow::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate found in {}", path);
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &str) -> anyhow::Result<PrivateKey> {
+    // pkcs8, 其次 rsa(pkcs1) 及 ec(sec1)
+    let mut keys = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(path)?))?;
+    if keys.is_empty() {
+        keys = rustls_pemfile::rsa_private_keys(&mut BufReader::new(File::open(path)?))?;
+    }
+    if keys.is_empty() {
+        keys = rustls_pemfile::ec_private_keys(&mut BufReader::new(File::open(path)?))?;
+    }
+    match keys.into_iter().next() {
+        Some(key) => Ok(PrivateKey(key)),
+        None => anyhow::bail!("no private key found in {}", path),
+    }
+}
+
+// http 端口同时服务面板, 客户端证书可选, 未携带时按密码鉴权
+fn acceptor(cfg: &Config) -> anyhow::Result<TlsAcceptor> {
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = if cfg.client_ca.is_empty() {
+        builder.with_client_cert_verifier(NoClientAuth::new())
+    } else {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&cfg.client_ca)? {
+            roots.add(&cert)?;
+        }
+        builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots))
+    };
+    let mut server_cfg = builder.with_single_cert(load_certs(&cfg.cert)?, load_key(&cfg.key)?)?;
+    server_cfg.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server_cfg)))
+}
+
+pub fn grpc_config(cfg: &Config) -> anyhow::Result<tonic::transport::ServerTlsConfig> {
+    let identity = tonic::transport::Identity::from_pem(std::fs::read(&cfg.cert)?, std::fs::read(&cfg.key)?);
+    let mut tls = tonic::transport::ServerTlsConfig::new().identity(identity);
+    // grpc 端口仅供客户端上报, 配置 client_ca 后强制客户端证书
+    if !cfg.client_ca.is_empty() {
+        tls = tls.client_ca_root(tonic::transport::Certificate::from_pem(std::fs::read(&cfg.client_ca)?));
+    }
+    Ok(tls)
+}
+
+pub async fn serv_https(addr: SocketAddr, cfg: &Config) -> anyhow::Result<()> {
+    let acceptor = acceptor(cfg)?;
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("🚀 listening on https://{}", addr);
+
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(o) => o,
+            Err(err) => {
+                error!("accept error => {:?}", err);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            // 握手超时, 避免只建连不握手的连接一直占用
+            let stream = match time::timeout(Duration::from_secs(HANDSHAKE_TIMEOUT), acceptor.accept(stream)).await {
+                Ok(Ok(o)) => o,
+                Ok(Err(err)) => {
+                    info!("tls handshake {} error => {:?}", peer_addr, err);
+                    return;
+                }
+                Err(_) => {
+                    info!("tls handshake {} timeout", peer_addr);
+                    return;
+                }
+            };
+            let cn = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| peer_cn(&cert.0));
+            let svc = service_fn(move |mut req: Request<Body>| {
+                if let Some(cn) = cn.as_ref() {
+                    req.extensions_mut().insert(PeerCn(cn.to_string()));
+                }
+                crate::main_service_func(req)
+            });
+            if let Err(err) = Http::new().serve_connection(stream, svc).await {
+                info!("serve {} error => {:?}", peer_addr, err);
+            }
+        });
+    }
+}
@@ -112,7 +112,10 @@ function download_client() {
 
     say "start downloading the stat_client"
 
-    if [ "${SSR_CN}" = true ]; then
+    if [ -n "{{download_url}}" ]; then
+        say "using server: {{download_url}}"
+        wget --no-check-certificate -qO "client-${arch}-unknown-linux-musl.zip" "{{download_url}}/client-${arch}-unknown-linux-musl.zip"
+    elif [ "${SSR_CN}" = true ]; then
         say "using cn mirror: coding.net"
         wget --no-check-certificate -qO "client-${arch}-unknown-linux-musl.zip" "https://d0ge-generic.pkg.coding.net/ServerStatus-Rust/releases/client-${arch}-unknown-linux-musl-v{{pkg_version}}.zip?version=v{{pkg_version}}"
     else
@@ -2,6 +2,8 @@
 var error = 0;
 var d = 0;
 var server_status = new Array();
+// 租户面板 /t/<id>/, 不显示全局页面入口及告警确认
+var tenant_mode = location.pathname.indexOf("/t/") == 0;
 
 function timeSince(date) {
 	if(date == 0)
@@ -48,24 +50,230 @@ function bytesToSize(bytes, precision, si)
 	}
 }
 
+// search & filter, 状态保存在 url 中, 便于收藏
+var last_result = null;
+var version_options = "";
+var filter = (function() {
+	var params = new URLSearchParams(location.search);
+	return {
+		q: params.get("q") || "",
+		hide_offline: params.get("hide_offline") == "1",
+		version: params.get("version") || "",
+		collapsed: (params.get("collapsed") || "").split(",").filter(function(s) { return s; })
+	};
+})();
+
+function saveFilter() {
+	var params = new URLSearchParams(location.search);
+	var set = function(k, v) { if (v) params.set(k, v); else params.delete(k); };
+	set("q", filter.q.trim());
+	set("hide_offline", filter.hide_offline ? "1" : "");
+	set("version", filter.version);
+	set("collapsed", filter.collapsed.join(","));
+	var qs = params.toString();
+	history.replaceState(null, "", location.pathname + (qs ? "?" + qs : "") + location.hash);
+}
+
+// 空格分隔的关键词均需匹配 name/alias/location/type/gid/版本/标签
+function matchHost(o) {
+	if (filter.hide_offline && !o.online4 && !o.online6)
+		return false;
+	if (filter.version && (filter.version == "outdated" ? !o.outdated : o.version != filter.version))
+		return false;
+	var words = filter.q.trim().toLowerCase().split(/\s+/).filter(function(s) { return s; });
+	var fields = [o.name, o.alias, o.location, o.type, o.gid, o.version];
+	for (var k in o.extra || {})
+		fields.push(k + "=" + o.extra[k]);
+	return words.every(function(w) {
+		return fields.some(function(f) { return String(f || "").toLowerCase().indexOf(w) != -1; });
+	});
+}
+
+function applyFilter() {
+	if (!last_result)
+		return;
+	var groups = {};
+	for (var i = 0; i < last_result.servers.length; i++) {
+		var o = last_result.servers[i];
+		var match = matchHost(o);
+		var show = match && !(o.gid && filter.collapsed.indexOf(o.gid) != -1);
+		$("#servers tr#r" + i).toggle(show);
+		$("#servers #rt" + i).closest("tr").toggle(show);
+		if (o.gid) {
+			var g = groups[o.gid] = groups[o.gid] || {match: 0, total: 0};
+			g.total++;
+			if (match)
+				g.match++;
+		}
+	}
+	$("#servers tr.group-row").each(function() {
+		var gid = $(this).attr("data-group");
+		var g = groups[gid] || {match: 0, total: 0};
+		var collapsed = filter.collapsed.indexOf(gid) != -1;
+		$(this).toggle(g.match > 0);
+		$(this).find(".group-toggle").text((collapsed ? "▸ " : "▾ ") + gid);
+		$(this).find(".group-count").text("(" + g.match + "/" + g.total + ")");
+	});
+	// 版本下拉框, 含各版本主机数
+	var versions = {};
+	for (var i = 0; i < last_result.servers.length; i++) {
+		var v = last_result.servers[i].version;
+		if (v)
+			versions[v] = (versions[v] || 0) + 1;
+	}
+	if (filter.version && filter.version != "outdated" && !versions[filter.version])
+		versions[filter.version] = 0;
+	var html = "<option value=\"\">全部版本</option><option value=\"outdated\">过旧版本</option>";
+	Object.keys(versions).sort().reverse().forEach(function(v) {
+		html += "<option value=\"" + $("<div>").text(v).html() + "\">" + $("<div>").text(v + " (" + versions[v] + ")").html() + "</option>";
+	});
+	if (version_options != html) {
+		version_options = html;
+		$("#filter-version").html(html);
+	}
+	$("#filter-version").val(filter.version);
+
+	var gids = Object.keys(groups);
+	$("#filter-collapse").prop("checked", gids.length > 0 && gids.every(function(gid) { return filter.collapsed.indexOf(gid) != -1; }));
+}
+
+$("#filter-q").val(filter.q);
+$("#filter-offline").prop("checked", filter.hide_offline);
+$("#filter-q").on("input", function() {
+	filter.q = $(this).val();
+	saveFilter();
+	applyFilter();
+});
+$("#filter-offline").change(function() {
+	filter.hide_offline = this.checked;
+	saveFilter();
+	applyFilter();
+});
+$("#filter-version").change(function() {
+	filter.version = $(this).val();
+	saveFilter();
+	applyFilter();
+});
+$("#show-version").prop("checked", readCookie("show_version") == "1");
+$("#servers-table").toggleClass("no-version", !$("#show-version").prop("checked"));
+$("#show-version").change(function() {
+	createCookie("show_version", this.checked ? "1" : "0", 365);
+	$("#servers-table").toggleClass("no-version", !this.checked);
+});
+$("#filter-collapse").change(function() {
+	filter.collapsed = [];
+	if (this.checked && last_result) {
+		for (var i = 0; i < last_result.servers.length; i++) {
+			var gid = last_result.servers[i].gid;
+			if (gid && filter.collapsed.indexOf(gid) == -1)
+				filter.collapsed.push(gid);
+		}
+	}
+	saveFilter();
+	applyFilter();
+});
+$(document).on("click", "#servers tr.group-row", function(e) {
+	e.preventDefault();
+	var gid = $(this).attr("data-group");
+	var pos = filter.collapsed.indexOf(gid);
+	if (pos == -1)
+		filter.collapsed.push(gid);
+	else
+		filter.collapsed.splice(pos, 1);
+	saveFilter();
+	applyFilter();
+});
+
+// sort, 空为服务端 sort_by 顺序, 保存在 cookie 中
+var sort_mode = readCookie("sort") || "";
+var row_layout = "";
+
+function sortServers(servers) {
+	// 无值排在最后
+	var cmp = function(a, b) {
+		if (a === null || b === null)
+			return (a === null) - (b === null);
+		return a < b ? -1 : (a > b ? 1 : 0);
+	};
+	var key = function(o) {
+		switch (sort_mode) {
+		case "group": return o.gid || null;
+		case "name": return String(o.alias).toLowerCase();
+		case "expire": return o.expire ? Date.parse(o.expire) || null : null;
+		case "traffic": return o.traffic_quota > 0 ? o.traffic_quota * 1024 * 1024 * 1024
+			- (o.network_in - o.last_network_in) - (o.network_out - o.last_network_out) : null;
+		}
+		return null;
+	};
+	var items = servers.map(function(o, i) { return {o: o, i: i, k: key(o)}; });
+	items.sort(function(a, b) { return cmp(a.k, b.k) || a.i - b.i; });
+	return items.map(function(o) { return o.o; });
+}
+
+$("#sort-mode").val(sort_mode);
+$("#sort-mode").change(function() {
+	sort_mode = $(this).val();
+	createCookie("sort", sort_mode, 365);
+	uptime();
+});
+
+// 迷你趋势图 svg
+function sparkline(values, max, color) {
+	var w = 120, h = 20;
+	max = max || 1;
+	var points = values.map(function(v, i) {
+		return (i * w / (values.length - 1)).toFixed(1) + "," + (h - Math.min(v, max) / max * h).toFixed(1);
+	}).join(" ");
+	return "<svg width=\"" + w + "\" height=\"" + h + "\" style=\"vertical-align: middle;\"><polyline fill=\"none\" stroke=\"" + color + "\" stroke-width=\"1\" points=\"" + points + "\"/></svg>";
+}
+
+// 探测展示名, 旧版客户端无 label
+var carrier_names = {"cu": "联通", "ct": "电信", "cm": "移动"};
+function probeLabel(o) {
+	return o.label || carrier_names[o.name] || o.name;
+}
+
+// 检查地址的路径/参数/账号可能带 token, 只展示 scheme://host
+function urlOrigin(url) {
+	var m = /^([a-z][a-z0-9+.-]*:\/\/)(?:[^\/?#@]*@)?([^\/?#]*)/i.exec(url || "");
+	return m ? m[1] + m[2] : "";
+}
+
 function uptime() {
 	$.getJSON("json/stats.json", function(result) {
 		$("#loading-notice").remove();
 		if(result.reload)
 			setTimeout(function() { location.reload() }, 1000);
 
+		// 顺序变化时重建表格
+		result.servers = sortServers(result.servers);
+		var layout = result.servers.map(function(o) { return o.name; }).join("\n");
+		if (layout != row_layout) {
+			$("#servers").empty();
+			server_status = new Array();
+			row_layout = layout;
+		}
+
+		var ping_labels = [];
 		for (var i = 0, rlen=result.servers.length; i < rlen; i++) {
 			var TableRow = $("#servers tr#r" + i);
 			var ExpandRow = $("#servers #rt" + i);
 			var hack; // fuck CSS for making me do this
 			if(i%2) hack="odd"; else hack="even";
 			if (!TableRow.length) {
+				// 组模式主机, 同组连续的主机前插入分组行
+				var gid = result.servers[i].gid;
+				if (gid && (i == 0 || result.servers[i - 1].gid != gid)) {
+					$("#servers").append($("<tr class=\"group-row\" style=\"cursor: pointer;\">").attr("data-group", gid).append(
+						$("<td colspan=\"14\">").append($("<strong class=\"group-toggle\">"), " ", $("<small class=\"group-count\">"))));
+				}
 				$("#servers").append(
 					"<tr id=\"r" + i + "\" data-toggle=\"collapse\" data-target=\"#rt" + i + "\" class=\"accordion-toggle " + hack + "\">" +
 						"<td id=\"online_status\"><div class=\"progress\"><div style=\"width: 100%;\" class=\"progress-bar progress-bar-warning\"><small>加载中</small></div></div></td>" +
 						"<td id=\"month_traffic\"><div class=\"progress\"><div style=\"width: 100%;\" class=\"progress-bar progress-bar-warning\"><small>加载中</small></div></div></td>" +
 						"<td id=\"name\">加载中</td>" +
 						"<td id=\"type\">加载中</td>" +
+						"<td id=\"version\">加载中</td>" +
 						"<td id=\"location\">加载中</td>" +
 						"<td id=\"uptime\">加载中</td>" +
 						"<td id=\"load\">加载中</td>" +
@@ -77,11 +285,19 @@ function uptime() {
 						"<td id=\"ping\"><div class=\"progress\"><div style=\"width: 100%;\" class=\"progress-bar progress-bar-warning\"><small>加载中</small></div></div></td>" +
 					"</tr>" +
 					"<tr class=\"expandRow " + hack + "\"><td colspan=\"16\"><div class=\"accordian-body collapse\" id=\"rt" + i + "\">" +
+						"<div id=\"expand_cores\"></div>" +
 						"<div id=\"expand_mem\">加载中</div>" +
 						"<div id=\"expand_swap\">加载中</div>" +
 						"<div id=\"expand_hdd\">加载中</div>" +
 						"<div id=\"expand_tupd\">加载中</div>" +
+						"<div id=\"expand_temp\"></div>" +
+						"<div id=\"expand_gpu\"></div>" +
+						"<div id=\"expand_docker\"></div>" +
+						"<div id=\"expand_procs\"></div>" +
+						"<div id=\"expand_units\"></div>" +
+						"<div id=\"expand_zfs\"></div>" +
 						"<div id=\"expand_ping\">加载中</div>" +
+						"<div id=\"expand_trend\"></div>" +
 						"<div id=\"expand_custom\">加载中</div>" +
 					"</div></td></tr>"
 				);
@@ -112,10 +328,32 @@ function uptime() {
 
 			// Name
 			TableRow.children["name"].innerHTML = result.servers[i].alias;
+			if (result.servers[i].flapping)
+				TableRow.children["name"].innerHTML += " 🔁";
+			if (result.servers[i].maintenance)
+				TableRow.children["name"].innerHTML += " <span title=\"维护中\">🔧</span>";
+			if (result.servers[i].clock_skewed)
+				TableRow.children["name"].innerHTML += " <span title=\"时钟偏差 " + result.servers[i].clock_skew + "s" +
+					(result.servers[i].ntp_offset != null ? ", NTP 偏差 " + result.servers[i].ntp_offset + "ms" : "") + "\">⏱</span>";
+			if (result.servers[i].collector_restarts)
+				TableRow.children["name"].innerHTML += " <span title=\"采集线程重启 " + $("<div>").text(result.servers[i].collector_restarts).html() + "\">🩹</span>";
+			if (result.servers[i].reboot_required)
+				TableRow.children["name"].innerHTML += " " + $("<span>").text("🔄").attr("title", "需要重启, 内核 " + result.servers[i].kernel +
+					(result.servers[i].reboot_reason ? ", " + result.servers[i].reboot_reason : "")).prop("outerHTML");
+			if (result.servers[i].outdated)
+				TableRow.children["name"].innerHTML += " " + $("<span>").text("⬆️").attr("title", "客户端 v" + result.servers[i].version + " 版本过旧, 请升级").prop("outerHTML");
+			if (!tenant_mode && result.servers[i].acked_by)
+				TableRow.children["name"].innerHTML += " <a href=\"#\" class=\"ack\" data-host=\"" + result.servers[i].name + "\" data-acked=\"1\" title=\"已确认: " + result.servers[i].acked_by + "\">✅</a>";
+			else if (!tenant_mode && (result.servers[i].flapping || (!result.servers[i].online4 && !result.servers[i].online6)))
+				TableRow.children["name"].innerHTML += " <a href=\"#\" class=\"ack\" data-host=\"" + result.servers[i].name + "\" title=\"确认告警\">🔔</a>";
 
 			// Type
 			TableRow.children["type"].innerHTML = result.servers[i].type;
 
+			// Version
+			TableRow.children["version"].innerHTML = result.servers[i].version ? $("<div>").text(result.servers[i].version).html() : "–";
+			TableRow.children["version"].className = result.servers[i].outdated ? "text-warning" : "";
+
 			// Location
 			TableRow.children["location"].innerHTML = result.servers[i].location;
 			if (!result.servers[i].online4 && !result.servers[i].online6) {
@@ -174,6 +412,10 @@ function uptime() {
 				    TableRow.children["load"].innerHTML = "–";
 				} else {
 				    TableRow.children["load"].innerHTML = result.servers[i].load_1.toFixed(2);
+				    // 按核数归一化后着色, 1 核与多核主机使用同一阈值
+				    var lpc = result.servers[i].load_per_core;
+				    TableRow.children["load"].title = lpc ? "每核负载 " + lpc.toFixed(2) : "";
+				    TableRow.children["load"].style.color = lpc >= 1 ? "#d9534f" : (lpc >= 0.7 ? "#f0ad4e" : "");
 				}
 
 				// Network
@@ -211,6 +453,16 @@ function uptime() {
 					TableRow.children["cpu"].children[0].children[0].className = "progress-bar progress-bar-success";
 				TableRow.children["cpu"].children[0].children[0].style.width = result.servers[i].cpu + "%";
 				TableRow.children["cpu"].children[0].children[0].innerHTML = result.servers[i].cpu + "%";
+				// steal 超过 10% 时提示, 宿主机可能超售
+				var steal = result.servers[i].cpu_steal;
+				TableRow.children["cpu"].title = steal ? "steal " + steal.toFixed(1) + "%" : "";
+				if (steal >= 10)
+					TableRow.children["cpu"].children[0].children[0].innerHTML += " <small>st " + steal.toFixed(0) + "%</small>";
+				// 各核心, 单核打满时标红
+				var cores = result.servers[i].cpu_cores || [];
+				ExpandRow[0].children["expand_cores"].innerHTML = cores.length == 0 ? "" : "各核心: " + cores.map(function(v) {
+					return v >= 90 ? "<span style=\"color:#d9534f\">" + v + "%</span>" : v + "%";
+				}).join(" ");
 
 				// Memory
 				var Mem = ((result.servers[i].memory_used/result.servers[i].memory_total)*100.0).toFixed(0);
@@ -223,8 +475,26 @@ function uptime() {
 				TableRow.children["memory"].children[0].children[0].style.width = Mem + "%";
 				TableRow.children["memory"].children[0].children[0].innerHTML = Mem + "%";
 				ExpandRow[0].children["expand_mem"].innerHTML = "内存: " + bytesToSize(result.servers[i].memory_used*1024, 2) + " / " + bytesToSize(result.servers[i].memory_total*1024, 2);
-				// Swap
+				// 内存明细, used 不含 page cache
+				var md = result.servers[i].memory_detail;
+				var mem_title = "";
+				if (md) {
+					mem_title = "可用: " + bytesToSize(md.available*1024, 2) + "\n缓存: " + bytesToSize(md.cached*1024, 2) +
+						"\n缓冲: " + bytesToSize(md.buffers*1024, 2) + "\n共享: " + bytesToSize(md.shared*1024, 2);
+					if (md.hugepages_total)
+						mem_title += "\n大页: " + (md.hugepages_total - md.hugepages_free) + " / " + md.hugepages_total + " × " + bytesToSize(md.hugepage_size*1024, 0);
+					ExpandRow[0].children["expand_mem"].innerHTML += " (可用 " + bytesToSize(md.available*1024, 2) + ", 缓存 " + bytesToSize((md.cached + md.buffers)*1024, 2) + ")";
+				}
+				TableRow.children["memory"].title = mem_title;
+				// Swap, 超过 swap_threshold 时单独标记
 				ExpandRow[0].children["expand_swap"].innerHTML = "交换分区: " + bytesToSize(result.servers[i].swap_used*1024, 2) + " / " + bytesToSize(result.servers[i].swap_total*1024, 2);
+				ExpandRow[0].children["expand_swap"].style.color = result.servers[i].swap_heavy ? "#d9534f" : "";
+				if (result.servers[i].swap_heavy) {
+					var Swap = ((result.servers[i].swap_used/result.servers[i].swap_total)*100.0).toFixed(0);
+					TableRow.children["memory"].children[0].children[0].className = "progress-bar progress-bar-danger";
+					TableRow.children["memory"].children[0].children[0].innerHTML = Mem + "% S" + Swap + "%";
+					TableRow.children["memory"].title = "交换分区: " + Swap + "%\n" + mem_title;
+				}
 
 				// HDD
 				var HDD = ((result.servers[i].hdd_used/result.servers[i].hdd_total)*100.0).toFixed(0);
@@ -237,22 +507,207 @@ function uptime() {
 				TableRow.children["hdd"].children[0].children[0].style.width = HDD + "%";
 				TableRow.children["hdd"].children[0].children[0].innerHTML = HDD + "%";
 				ExpandRow[0].children["expand_hdd"].innerHTML = "硬盘: " + bytesToSize(result.servers[i].hdd_used*1024*1024, 2) + " / " + bytesToSize(result.servers[i].hdd_total*1024*1024, 2);
+				// 挂载点
+				var disks = result.servers[i].disks || [];
+				if (disks.length > 1 || (disks.length == 1 && disks[0].inodes_total)) {
+					ExpandRow[0].children["expand_hdd"].innerHTML += disks.map(function(o) {
+						var s = "<br/>&nbsp;&nbsp;" + $("<div>").text(o.mount).html() + " (" + o.fs_type + "): " + bytesToSize(o.used*1024*1024, 2) + " / " + bytesToSize(o.total*1024*1024, 2);
+						if (o.inodes_total)
+							s += ", inode " + (100 * o.inodes_used / o.inodes_total).toFixed(0) + "%";
+						return s;
+					}).join("");
+				}
+				if (result.servers[i].disk_read || result.servers[i].disk_write)
+					ExpandRow[0].children["expand_hdd"].innerHTML += "<br/>读写: " + bytesToSize(result.servers[i].disk_read, 1) + "/s | " + bytesToSize(result.servers[i].disk_write, 1) + "/s";
+				// nvme 温度/寿命, 有告警位或寿命将尽时标红
+				(result.servers[i].nvme || []).forEach(function(o) {
+					var s = o.name + " " + $("<div>").text(o.model).html() + ": " + o.temperature.toFixed(0) + "℃, 已用寿命 " + o.percent_used + "%";
+					if (o.throttle_time)
+						s += ", 降速 " + o.throttle_time + "s";
+					ExpandRow[0].children["expand_hdd"].innerHTML += "<br/>" + (o.critical_warning || o.percent_used >= 90 ? "<span style=\"color:#d9534f\">" + s + "</span>" : s);
+				});
 
                 // delay time
 
 				// tcp, udp, process, thread count
 				ExpandRow[0].children["expand_tupd"].innerHTML = "TCP/UDP/进/线: " + result.servers[i].tcp_count + " / " + result.servers[i].udp_count + " / " + result.servers[i].process_count+ " / " + result.servers[i].thread_count;
-				ExpandRow[0].children["expand_ping"].innerHTML = "联通/电信/移动: " + result.servers[i].time_10010 + "ms / " + result.servers[i].time_189 + "ms / " + result.servers[i].time_10086 + "ms"
-
-                // ping
-                var PING_10010 = result.servers[i].ping_10010.toFixed(0);
-                var PING_189 = result.servers[i].ping_189.toFixed(0);
-                var PING_10086 = result.servers[i].ping_10086.toFixed(0);
-                if (PING_10010 >= 20 || PING_189 >= 20 || PING_10086 >= 20)
+				if (result.servers[i].tcp_established || result.servers[i].tcp_time_wait || result.servers[i].udp_sockets)
+					ExpandRow[0].children["expand_tupd"].innerHTML += "<br/>连接: ESTAB " + result.servers[i].tcp_established + " / TIME_WAIT " + result.servers[i].tcp_time_wait + " / UDP " + result.servers[i].udp_sockets;
+				if (result.servers[i].fd_max) {
+					var fd = (100 * result.servers[i].fd_used / result.servers[i].fd_max).toFixed(0);
+					var fd_str = "fd: " + result.servers[i].fd_used + " / " + result.servers[i].fd_max + " (" + fd + "%)";
+					if (result.servers[i].client_fd_limit)
+						fd_str += ", 客户端 " + result.servers[i].client_fds + " / " + result.servers[i].client_fd_limit;
+					ExpandRow[0].children["expand_tupd"].innerHTML += "<br/>" + (fd >= 80 ? "<span style=\"color:#d9534f\">" + fd_str + "</span>" : fd_str);
+				}
+				if (result.servers[i].conntrack_max) {
+					var ct = (100 * result.servers[i].conntrack_count / result.servers[i].conntrack_max).toFixed(0);
+					var ct_str = "conntrack: " + result.servers[i].conntrack_count + " / " + result.servers[i].conntrack_max + " (" + ct + "%)";
+					ExpandRow[0].children["expand_tupd"].innerHTML += "<br/>" + (ct >= 80 ? "<span style=\"color:#d9534f\">" + ct_str + "</span>" : ct_str);
+				}
+				// PSI, avg10 超过 20% 标红
+				var psi = [["CPU", result.servers[i].psi_cpu], ["内存", result.servers[i].psi_memory], ["IO", result.servers[i].psi_io]].filter(function(o) { return o[1]; });
+				if (psi.length)
+					ExpandRow[0].children["expand_tupd"].innerHTML += "<br/>PSI(avg10/avg60): " + psi.map(function(o) {
+						var s = o[0] + " " + o[1].some_avg10.toFixed(1) + "% / " + o[1].some_avg60.toFixed(1) + "%";
+						return o[1].some_avg10 >= 20 ? "<span style=\"color:#d9534f\">" + s + "</span>" : s;
+					}).join(" | ");
+				// 按协议族的流量
+				if (result.servers[i].net_in_v4 || result.servers[i].net_in_v6)
+					ExpandRow[0].children["expand_tupd"].innerHTML += "<br/>IPv4: " + bytesToSize(result.servers[i].net_rx_v4, 1) + "/s↓ " + bytesToSize(result.servers[i].net_tx_v4, 1) + "/s↑ (" + bytesToSize(result.servers[i].net_in_v4, 1) + " / " + bytesToSize(result.servers[i].net_out_v4, 1) + ")" +
+						" | IPv6: " + bytesToSize(result.servers[i].net_rx_v6, 1) + "/s↓ " + bytesToSize(result.servers[i].net_tx_v6, 1) + "/s↑ (" + bytesToSize(result.servers[i].net_in_v6, 1) + " / " + bytesToSize(result.servers[i].net_out_v6, 1) + ")";
+				// 温度, 接近临界值时标红
+				var temps = result.servers[i].temps || [];
+				ExpandRow[0].children["expand_temp"].innerHTML = temps.length == 0 ? "" : "温度: " + temps.map(function(o) {
+					var s = $("<div>").text(o.label).html() + " " + o.current.toFixed(0) + "℃";
+					var limit = o.critical || o.max;
+					return (limit && o.current >= limit - 5) ? "<span style=\"color:#d9534f\">" + s + "</span>" : s;
+				}).join(" / ");
+				// ipmi 传感器, 非 ok 标红
+				var sensors = result.servers[i].ipmi || [];
+				if (sensors.length)
+					ExpandRow[0].children["expand_temp"].innerHTML += (ExpandRow[0].children["expand_temp"].innerHTML ? "<br/>" : "") + "IPMI: " + sensors.map(function(o) {
+						var s = $("<div>").text(o.name + " " + o.reading).html();
+						return o.status == "ok" ? s : "<span style=\"color:#d9534f\">" + s + " (" + $("<div>").text(o.status).html() + ")</span>";
+					}).join(" / ");
+				// cpu 封装功耗
+				if (result.servers[i].power != null)
+					ExpandRow[0].children["expand_temp"].innerHTML += (ExpandRow[0].children["expand_temp"].innerHTML ? "<br/>" : "") + "功耗: " + result.servers[i].power.toFixed(1) + " W";
+				// GPU
+				var gpus = result.servers[i].gpus || [];
+				ExpandRow[0].children["expand_gpu"].innerHTML = gpus.map(function(o) {
+					return "GPU" + o.index + " " + $("<div>").text(o.name).html() + ": " + o.utilization.toFixed(0) + "%, 显存 " +
+						bytesToSize(o.memory_used*1024*1024, 1) + " / " + bytesToSize(o.memory_total*1024*1024, 1) +
+						(o.temperature ? ", " + o.temperature.toFixed(0) + "℃" : "");
+				}).join("<br/>");
+				// top 进程
+				var procs_str = "";
+				var fmt_proc = function(o) {
+					return "<br/>&nbsp;&nbsp;" + $("<div>").text(o.name).html() + " (" + o.pid + "): CPU " + o.cpu.toFixed(1) + "%, 内存 " + bytesToSize(o.memory*1024, 1);
+				};
+				if ((result.servers[i].top_cpu || []).length)
+					procs_str += "CPU 占用前 " + result.servers[i].top_cpu.length + ":" + result.servers[i].top_cpu.map(fmt_proc).join("");
+				if ((result.servers[i].top_memory || []).length)
+					procs_str += (procs_str ? "<br/>" : "") + "内存占用前 " + result.servers[i].top_memory.length + ":" + result.servers[i].top_memory.map(fmt_proc).join("");
+				ExpandRow[0].children["expand_procs"].innerHTML = procs_str;
+				// systemd 服务
+				var units = result.servers[i].units || [];
+				ExpandRow[0].children["expand_units"].innerHTML = units.length == 0 ? "" : "服务: " + units.map(function(o) {
+					var s = $("<div>").text(o.name + " " + o.state).html();
+					return o.state == "active" ? s : "<span style=\"color:#d9534f\">" + s + "</span>";
+				}).join(" / ");
+				// tls 证书剩余天数
+				var certs = result.servers[i].certs || [];
+				ExpandRow[0].children["expand_units"].innerHTML += (units.length && certs.length ? "<br/>" : "") + (certs.length == 0 ? "" : "证书: " + certs.map(function(o) {
+					var target = $("<div>").text(o.target).html();
+					if (o.error)
+						return $("<span>").css("color", "#d9534f").attr("title", o.error).text(o.target + " 检查失败").prop("outerHTML");
+					var s = target + " " + o.days + "天";
+					return o.days > 7 ? "<span title=\"" + new Date(o.expire_at * 1000).toLocaleString() + "\">" + s + "</span>" : "<span style=\"color:#d9534f\">" + s + "</span>";
+				}).join(" / "));
+				// http 健康检查
+				var http_checks = result.servers[i].http_checks || [];
+				if (http_checks.length)
+					ExpandRow[0].children["expand_units"].innerHTML += (ExpandRow[0].children["expand_units"].innerHTML ? "<br/>" : "") + "HTTP: " + http_checks.map(function(o) {
+						var s = urlOrigin(o.url) + " " + (o.status || "-") + " " + o.latency + "ms";
+						var span = $("<span>").text(s);
+						if (!o.ok)
+							span.css("color", "#d9534f").attr("title", o.error);
+						return span.prop("outerHTML");
+					}).join(" / ");
+				// 端口存活
+				var ports = result.servers[i].ports || [];
+				ExpandRow[0].children["expand_units"].innerHTML += (ExpandRow[0].children["expand_units"].innerHTML && ports.length ? "<br/>" : "") + (ports.length == 0 ? "" : "端口: " + ports.map(function(o) {
+					var s = $("<div>").text(o.target).html();
+					return o.up ? s : "<span style=\"color:#d9534f\">" + s + " down</span>";
+				}).join(" / "));
+				// zfs 存储池
+				var pools = result.servers[i].zfs_pools || [];
+				ExpandRow[0].children["expand_zfs"].innerHTML = pools.map(function(o) {
+					var s = "ZFS " + $("<div>").text(o.name).html() + ": " + (o.health == "ONLINE" ? o.health : "<span style=\"color:#d9534f\">" + $("<div>").text(o.health).html() + "</span>") +
+						", " + bytesToSize(o.alloc, 1) + " / " + bytesToSize(o.size, 1);
+					if (o.scan)
+						s += "<br/>&nbsp;&nbsp;" + $("<div>").text(o.scan).html();
+					return s;
+				}).join("<br/>");
+				// mdadm 软 raid
+				var raid = result.servers[i].raid || [];
+				ExpandRow[0].children["expand_zfs"].innerHTML += (pools.length && raid.length ? "<br/>" : "") + raid.map(function(o) {
+					var s = o.name + " " + o.level + ": [" + o.active + "/" + o.devices + "]";
+					if (o.degraded)
+						s = "<span style=\"color:#d9534f\">" + s + " 降级</span>";
+					if (o.action)
+						s += ", " + o.action + (o.progress ? " " + o.progress.toFixed(1) + "%" : "");
+					return s;
+				}).join("<br/>");
+				// 自定义指标
+				var metrics = result.servers[i].metrics || {};
+				var metric_keys = Object.keys(metrics).sort();
+				ExpandRow[0].children["expand_procs"].innerHTML += (metric_keys.length && ExpandRow[0].children["expand_procs"].innerHTML ? "<br/>" : "") +
+					(metric_keys.length ? "指标: " + metric_keys.map(function(k) { return $("<div>").text(k).html() + "=" + metrics[k]; }).join(", ") : "");
+				// docker
+				var docker = result.servers[i].docker;
+				if (docker) {
+					ExpandRow[0].children["expand_docker"].innerHTML = "容器: " + docker.running + " / " + docker.total + (docker.containers || []).map(function(o) {
+						var s = "<br/>&nbsp;&nbsp;" + $("<div>").text(o.name + " (" + o.image + ")").html() + ": " + o.state;
+						if (o.state == "running")
+							s += ", CPU " + o.cpu.toFixed(1) + "%, 内存 " + bytesToSize(o.memory, 1);
+						return s;
+					}).join("");
+				} else {
+					ExpandRow[0].children["expand_docker"].innerHTML = "";
+				}
+				ExpandRow[0].children["expand_ping"].innerHTML = "联通/电信/移动: " + result.servers[i].time_10010 + "ms / " + result.servers[i].time_189 + "ms / " + result.servers[i].time_10086 + "ms" +
+					" <a href=\"smokeping?host=" + encodeURIComponent(result.servers[i].name) + "\" target=\"_blank\" title=\"延迟历史\">📈</a>";
+				// 三网抖动/分位数
+				var carriers = (result.servers[i].carrier_probes || []).filter(function(o) { return o.p50; });
+				if (carriers.length)
+					ExpandRow[0].children["expand_ping"].innerHTML += "<br/>抖动 p50/p95/p99: " + carriers.map(function(o) {
+						return $("<div>").text(probeLabel(o)).html() + " ±" + o.jitter.toFixed(1) + " " + o.p50 + "/" + o.p95 + "/" + o.p99 + "ms";
+					}).join(" | ");
+				// 路由跟踪, 悬停查看
+				(result.servers[i].traces || []).forEach(function(o) {
+					ExpandRow[0].children["expand_ping"].innerHTML += "<br/>" + $("<span>").css("color", "#d9534f").attr("title", o.report)
+						.text("路由跟踪 " + o.target + " (" + o.reason + ") " + new Date(o.ts * 1000).toLocaleString()).prop("outerHTML");
+				});
+				// dns 解析探测
+				var dns_probes = result.servers[i].dns_probes || [];
+				if (dns_probes.length)
+					ExpandRow[0].children["expand_ping"].innerHTML += "<br/>DNS: " + dns_probes.map(function(o) {
+						var s = $("<div>").text(o.name + (o.resolver ? "@" + o.resolver : "")).html() + " " + (o.ok ? o.time + "ms" : "失败") + ", 失败率 " + o.fail_rate + "%";
+						return o.ok && o.fail_rate < 10 ? s : "<span style=\"color:#d9534f\">" + s + "</span>";
+					}).join(" / ");
+				// 自定义探测
+				(result.servers[i].probes || []).forEach(function(o) {
+					var s = $("<div>").text(probeLabel(o)).html() + ": " + o.ping_time + "ms, 丢包 " + o.lost_rate + "%";
+					if (o.p50)
+						s += ", ±" + o.jitter.toFixed(1) + " p50/p95/p99 " + o.p50 + "/" + o.p95 + "/" + o.p99 + "ms";
+					ExpandRow[0].children["expand_ping"].innerHTML += "<br/>" + (o.lost_rate >= 10 ? "<span style=\"color:#d9534f\">" + s + "</span>" : s);
+				});
+
+                // ping, 三网及自定义探测的丢包率, 悬停查看探测名
+                var ping_probes = (result.servers[i].carrier_probes || []).concat(result.servers[i].probes || []);
+                if (!ping_probes.length)
+                    ping_probes = [{name: "cu", lost_rate: result.servers[i].ping_10010}, {name: "ct", lost_rate: result.servers[i].ping_189}, {name: "cm", lost_rate: result.servers[i].ping_10086}];
+                ping_labels.push(ping_probes.map(probeLabel).join(" | "));
+                if (ping_probes.some(function(o) { return o.lost_rate >= 20; }))
                     TableRow.children["ping"].children[0].children[0].className = "progress-bar progress-bar-warning";
                 else
                     TableRow.children["ping"].children[0].children[0].className = "progress-bar progress-bar-success";
-	            TableRow.children["ping"].children[0].children[0].innerHTML = PING_10010 + '% ⚡ ' + PING_189 + '% ⚡ ' + PING_10086 + '%';
+	            TableRow.children["ping"].children[0].children[0].innerHTML = ping_probes.map(function(o) {
+					return $("<span>").attr("title", probeLabel(o)).text(o.lost_rate.toFixed(0) + "%").prop("outerHTML");
+				}).join(" ⚡ ");
+
+				// sparkline
+				if (result.servers[i].series && result.servers[i].series.ts.length > 1) {
+					var series = result.servers[i].series;
+					var net = series.network_rx.map(function(v, j) { return v + series.network_tx[j]; });
+					ExpandRow[0].children["expand_trend"].innerHTML = "趋势: CPU " + sparkline(series.cpu, 100, "#f0ad4e") +
+						" 内存 " + sparkline(series.memory, 100, "#5bc0de") +
+						" 网络 " + sparkline(net, Math.max.apply(null, net), "#5cb85c");
+				} else {
+					ExpandRow[0].children["expand_trend"].innerHTML = "";
+				}
 
 				// Custom
 				if (result.servers[i].custom) {
@@ -262,6 +717,12 @@ function uptime() {
 				}
 			}
 		};
+		// 各节点探测目标一致时表头显示探测名
+		if (ping_labels.length)
+			$("th#ping").text(ping_labels.every(function(s) { return s == ping_labels[0]; }) ? ping_labels[0] : "丢包");
+
+		last_result = result;
+		applyFilter();
 
 		d = new Date(result.updated*1000);
 		error = 0;
@@ -306,6 +767,145 @@ function uptime() {
 	});
 }
 
+// ack
+$(document).on("click", "a.ack", function(e) {
+	e.preventDefault();
+	e.stopPropagation();
+	var host = $(this).data("host");
+	if ($(this).data("acked")) {
+		if (confirm("取消确认 " + host + " ?"))
+			$.ajax({url: "api/ack?host=" + encodeURIComponent(host), type: "DELETE"});
+		return;
+	}
+	var by = prompt("确认 " + host + " 的告警, 确认人:", readCookie("ack_by") || "");
+	if (by === null)
+		return;
+	createCookie("ack_by", by, 365);
+	$.post("api/ack?host=" + encodeURIComponent(host) + "&by=" + encodeURIComponent(by));
+});
+
+// cron checks
+function updateChecks() {
+	$.getJSON("api/checks", function(result) {
+		var checks = result.data || [];
+		$("#checks-table").toggle(checks.length > 0);
+		var html = "";
+		for (var i = 0; i < checks.length; i++) {
+			var o = checks[i];
+			var label = {"up": "label-success", "down": "label-danger", "started": "label-info"}[o.status] || "label-default";
+			html += "<tr><td>" + $("<div>").text(o.name).html() + "</td>"
+				+ "<td><span class=\"label " + label + "\">" + o.status + "</span></td>"
+				+ "<td>" + (o.last_ping ? timeSince(new Date(o.last_ping * 1000)) : "-") + "</td>"
+				+ "<td>" + o.interval + "s</td>"
+				+ "<td>" + (o.last_duration ? o.last_duration + "s" : "-") + "</td></tr>";
+		}
+		$("#checks").html(html);
+	});
+}
+
+// install command generator
+var install_groups = [];
+var install_version = "";
+
+function shellQuote(s) {
+	return "'" + String(s).replace(/'/g, "'\\''") + "'";
+}
+
+function renderInstall() {
+	var g = install_groups[$("#install-gid").val()];
+	if (!g) {
+		$("#install-cmds").html("<p>未配置 hosts_group</p>");
+		return;
+	}
+	var os = $("#install-os").val();
+	var alias = $("#install-alias").val().trim();
+	var origin = location.protocol + "//" + location.host;
+	var report = origin + "/report";
+	var alias_opt = alias ? shellQuote(alias) : "\"$(hostname)\"";
+	// 密码以 argon2 hash 配置时无法还原, 需手动替换 <password>
+	var password = g.hashed ? "<password>" : g.password;
+	var opts = "-a " + shellQuote(report) + " -g " + shellQuote(g.gid) + " -p " + shellQuote(password) + " --alias " + alias_opt;
+	var cmds = [];
+	if (g.install_token) {
+		cmds.push(["一键安装", "curl -sSL " + shellQuote(origin + "/i/" + encodeURIComponent(g.gid) + "?token=" + encodeURIComponent(g.install_token)
+			+ (g.hashed ? "&pass=" + password : "")) + " | bash"]);
+	}
+	// 未指定 alias 时双引号包裹, 安装时展开主机名
+	cmds.push(["一键安装 (systemd)", "curl -sSL \"" + origin + "/i?pass=" + (g.hashed ? password : encodeURIComponent(password))
+		+ "&gid=" + encodeURIComponent(g.gid) + "&alias=" + (alias ? encodeURIComponent(alias) : "$(hostname)") + "\" | bash"]);
+	var zip = "client-" + os + "-unknown-linux-musl.zip";
+	cmds.push(["手动安装", "mkdir -p /opt/ServerStatus && cd /opt/ServerStatus\n"
+		+ "wget -O " + zip + " https://github.com/zdz/ServerStatus-Rust/releases/download/v" + install_version + "/" + zip + "\n"
+		+ "unzip -o " + zip + " && chmod +x stat_client\n"
+		+ "./stat_client " + opts]);
+	cmds.push(["systemd", "cat > /etc/systemd/system/stat_client.service <<EOF\n"
+		+ "[Unit]\nDescription=ServerStatus-Rust Client\nAfter=network.target\n\n"
+		+ "[Service]\nUser=root\nGroup=root\nWorkingDirectory=/opt/ServerStatus\n"
+		+ "ExecStart=/opt/ServerStatus/stat_client " + opts + "\n"
+		+ "ExecReload=/bin/kill -HUP \\$MAINPID\nRestart=on-failure\n\n"
+		+ "[Install]\nWantedBy=multi-user.target\nEOF\n"
+		+ "systemctl daemon-reload && systemctl enable --now stat_client"]);
+	var html = "";
+	for (var i = 0; i < cmds.length; i++) {
+		html += "<p><strong>" + cmds[i][0] + "</strong> <button type=\"button\" class=\"btn btn-default btn-xs install-copy\">复制</button></p>"
+			+ "<pre style=\"white-space: pre-wrap;\">" + $("<div>").text(cmds[i][1]).html() + "</pre>";
+	}
+	$("#install-cmds").html(html);
+}
+
+$("#install-link").click(function(e) {
+	e.preventDefault();
+	$.getJSON("api/groups", function(result) {
+		install_groups = result.data || [];
+		install_version = result.version;
+		var html = "";
+		for (var i = 0; i < install_groups.length; i++)
+			html += "<option value=\"" + i + "\">" + $("<div>").text(install_groups[i].gid + " " + install_groups[i].location).html() + "</option>";
+		$("#install-gid").html(html);
+		renderInstall();
+		$("#install-modal").modal("show");
+	}).fail(function() {
+		alert("需要管理员登录");
+	});
+});
+$(document).on("change keyup", "#install-gid, #install-os, #install-alias", renderInstall);
+$("#install-rotate").click(function() {
+	var g = install_groups[$("#install-gid").val()];
+	if (!g || !confirm("轮换组 " + g.gid + " 的密码?"))
+		return;
+	$.post("api/rotate?gid=" + encodeURIComponent(g.gid) + "&grace=86400", function(result) {
+		prompt("新密码(24 小时内旧密码仍可用, 请同步更新 config.toml):", result.data.new_pass);
+	}).fail(function() {
+		alert("轮换失败");
+	});
+});
+$(document).on("click", "button.install-copy", function() {
+	var text = $(this).parent().next("pre").text();
+	var btn = $(this);
+	navigator.clipboard.writeText(text).then(function() {
+		btn.text("已复制");
+		setTimeout(function() { btn.text("复制"); }, 1500);
+	});
+});
+
+// notify test
+$("#notify-test-link").click(function(e) {
+	e.preventDefault();
+	var channel = prompt("通知测试, 通道 (tgbot/wechat/email/log/webhook/pagerduty/opsgenie/all):", "all");
+	if (channel === null)
+		return;
+	$.post("api/notify-test?channel=" + encodeURIComponent(channel.trim()), function(result) {
+		var lines = [];
+		for (var i = 0; i < result.data.length; i++) {
+			var o = result.data[i];
+			lines.push((o.ok ? "✅ " : "❌ ") + o.kind + " => " + o.detail);
+		}
+		alert(lines.join("\n"));
+	}).fail(function(xhr) {
+		alert(xhr.status == 401 ? "需要管理员登录" : ((xhr.responseJSON || {}).msg || "发送失败"));
+	});
+});
+
 function updateTime() {
 	if (!error)
 		$("#updated").html("最后更新: " + timeSince(d));
@@ -315,6 +915,8 @@ uptime();
 updateTime();
 setInterval(uptime, 2000);
 setInterval(updateTime, 2000);
+updateChecks();
+setInterval(updateChecks, 10000);
 
 
 // styleswitcher.js
@@ -365,6 +967,8 @@ function readCookie(name) {
 
 window.onload = function(e) {
 	setActiveStyleSheet("dark");
+	if (tenant_mode)
+		$(".global-link").hide();
 
 	// var cookie = readCookie("style");
 	// if (cookie && cookie != 'null' ) {